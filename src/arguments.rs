@@ -1,16 +1,114 @@
-use color_eyre::Result;
+use chrono::{DateTime, NaiveDate, NaiveTime, Utc};
+use color_eyre::{eyre::eyre, Result};
 use itertools::Itertools;
 use once_cell::sync::OnceCell;
-use std::env;
+use regex::{Regex, RegexBuilder};
+use std::{env, path::PathBuf, str::FromStr};
+use tracing::level_filters::LevelFilter;
 
-use crate::SortingOption;
+use crate::{
+    daemon, logging,
+    output::OutputFormat,
+    schedule::{Cron, Schedule},
+    shared::{Action, MediaScope},
+    title_filter,
+    utils::{parse_age, parse_file_size},
+    SortingOption,
+};
 
 static INSTANCE: OnceCell<Arguments> = OnceCell::new();
 
 #[derive(Debug)]
 pub struct Arguments {
     pub sorting: Option<SortingOption>,
+    /// The action for all chosen items, otherwise it is asked for each item.
+    pub action: Option<Action>,
     pub all_media: bool,
+    pub ignore_active: bool,
+    pub no_cache: bool,
+    pub check_connections: bool,
+    pub skip_health_check: bool,
+    pub show_excluded: bool,
+    pub discover: bool,
+    /// Shows every change that would be made instead of making it.
+    pub dry_run: bool,
+    /// Handles whatever the rules allow without asking, with --auto or --yes.
+    pub auto: bool,
+    /// The most items shown, or handled with --auto, from --limit or --max-items.
+    pub limit: Option<usize>,
+    /// Leaves out the items after the first ones that would free this many bytes.
+    pub max_bytes: Option<i64>,
+    /// Where --auto writes what it did, instead of next to the config.
+    pub report_file: Option<PathBuf>,
+    /// How the candidates are written, with --output.
+    pub output: OutputFormat,
+    /// Logs a line every so often instead of drawing progress bars.
+    pub quiet: bool,
+    /// What is logged to stderr, overriding RUST_LOG.
+    pub log_level: Option<LevelFilter>,
+    /// Where every log line down to debug is written as JSON.
+    pub log_file: Option<PathBuf>,
+    /// Connects the Trakt accounts in the config, instead of cleaning up.
+    pub trakt_login: bool,
+    /// Prints the config in use, instead of cleaning up.
+    pub show_config: bool,
+    /// The config to use, instead of looking for one.
+    pub config_path: Option<PathBuf>,
+    /// The profile in the config whose settings are used.
+    pub profile: Option<String>,
+    /// Writes a starter config with `config init`, instead of cleaning up.
+    pub config_init: Option<ConfigInit>,
+    /// Updates the config to the current version with `config migrate`, instead of cleaning up.
+    pub migrate_config: bool,
+    /// Moves the media with this title back out of the trash with `restore`, instead of cleaning up.
+    pub restore: Option<String>,
+    /// Only lists the candidates with `list`, changing nothing and not needing Radarr or Sonarr.
+    pub list: bool,
+    /// Cleans up with `clean`, which is also what happens without a command.
+    pub clean: bool,
+    /// Shows the details and whole watch history of the candidate with this title or rating key
+    /// with `show`, instead of cleaning up.
+    pub show: Option<String>,
+    /// Deletes what was moved to the trash longer ago than this with `purge-trash`.
+    pub purge_trash: Option<chrono::Duration>,
+    /// Runs the cleanup on a schedule with `daemon`, instead of once.
+    pub daemon: Option<Daemon>,
+    pub export_history: Option<String>,
+    pub export_file: Option<PathBuf>,
+    pub min_days_since_watch: Option<i64>,
+    pub min_request_age_days: Option<i64>,
+    /// Overrides `rules.keep_days_after_watch`.
+    pub keep_days_after_watch: Option<u32>,
+    /// Overrides `rules.keep_days_after_added`.
+    pub keep_days_after_added: Option<u32>,
+    pub watched_since: Option<DateTime<Utc>>,
+    /// Overrides `rules.media_type`.
+    pub media_type: Option<MediaScope>,
+    /// Items whose title matches any of these are left out, along with `rules.exclude_titles`.
+    pub exclude: Vec<Regex>,
+    /// Only items whose title matches any of these are shown, if there are any.
+    pub include: Vec<Regex>,
+    /// Only items whose file quality matches are shown.
+    pub quality: Option<Regex>,
+}
+
+/// How `config init` writes the starter config.
+#[derive(Debug)]
+pub struct ConfigInit {
+    /// Where to write it, instead of the config directory of the platform.
+    pub path: Option<PathBuf>,
+    /// Overwrites an existing config.
+    pub force: bool,
+    /// Asks for the services instead of writing the commented template.
+    pub interactive: bool,
+}
+
+/// When `daemon` runs the cleanup.
+#[derive(Debug)]
+pub struct Daemon {
+    pub schedule: Schedule,
+    /// The file that starts a run right away when it is touched, instead of the one next to the config.
+    pub run_now_file: Option<PathBuf>,
 }
 
 impl Arguments {
@@ -18,27 +116,266 @@ impl Arguments {
         INSTANCE.get().expect("Arguments have not been initialised")
     }
 
+    /// The --output format, which is text until the arguments are read.
+    pub fn output_format() -> OutputFormat {
+        INSTANCE
+            .get()
+            .map_or(OutputFormat::Text, |args| args.output)
+    }
+
     pub fn read_args() -> Result<()> {
-        if let Some(_) = INSTANCE.get() {
+        if INSTANCE.get().is_some() {
             return Ok(());
         }
 
-        let mut args = env::args().collect_vec();
-
-        let args = Arguments {
-            sorting: Self::read_sort(&mut args),
-            all_media: Self::read_all_media(&mut args),
-        };
-
+        let arguments = Self::parse(env::args().collect_vec())?;
         INSTANCE
-            .set(args)
+            .set(arguments)
             .expect("Arguments have already been initialized...");
         Ok(())
     }
 
+    /// Sets the arguments to the ones of running without any.
+    #[cfg(test)]
+    pub fn init_for_tests() {
+        INSTANCE.get_or_init(|| Self::parse(vec!["media-cleaner".to_string()]).unwrap());
+    }
+
+    /// Reads the arguments, the first of which is the program itself.
+    pub(crate) fn parse(mut args: Vec<String>) -> Result<Arguments> {
+        // Options with values have to be read first, so their values aren't mistaken for flags.
+        let min_days_since_watch = Self::read_number(&mut args, "--min-days-since-watch")?;
+        let min_request_age_days = Self::read_number(&mut args, "--min-request-age-days")?;
+        let keep_days_after_watch = Self::read_number(&mut args, "--keep-days-after-watch")?;
+        let keep_days_after_added = Self::read_number(&mut args, "--keep-days-after-added")?;
+        let watched_since = Self::read_date(&mut args, "--watched-since")?;
+        let export_history = Self::read_value(&mut args, "--export-history")?;
+        let export_file = Self::read_value(&mut args, "--export-file")?.map(PathBuf::from);
+        let config_path = Self::read_value(&mut args, "--config")?.map(PathBuf::from);
+        let profile = Self::read_value(&mut args, "--profile")?;
+        // --max-items is the name --limit had when it was only for --auto.
+        let limit = match Self::read_number(&mut args, "--limit")? {
+            Some(limit) => Some(limit),
+            None => Self::read_number(&mut args, "--max-items")?,
+        };
+        let max_bytes = Self::read_size(&mut args, "--max-bytes")?;
+        let report_file = Self::read_value(&mut args, "--report")?.map(PathBuf::from);
+        let output = match Self::read_value(&mut args, "--output")? {
+            Some(output) => OutputFormat::from_str(&output)?,
+            None => OutputFormat::default(),
+        };
+        let log_level = match Self::read_value(&mut args, "--log-level")? {
+            Some(level) => Some(logging::parse_level(&level)?),
+            None => None,
+        };
+        let log_file = Self::read_value(&mut args, "--log-file")?.map(PathBuf::from);
+        let sort = match Self::read_value(&mut args, "--sort")? {
+            Some(sort) => Some(
+                SortingOption::from_str(&sort)
+                    .map_err(|_| eyre!("{} is not a valid sorting option for --sort.", sort))?,
+            ),
+            None => None,
+        };
+
+        let quality = match Self::read_value(&mut args, "--quality")? {
+            Some(quality) => Some(
+                RegexBuilder::new(&quality)
+                    .case_insensitive(true)
+                    .build()
+                    .map_err(|err| {
+                        eyre!("{} is not a valid regex for --quality: {}", quality, err)
+                    })?,
+            ),
+            None => None,
+        };
+
+        let media_type = match Self::read_value(&mut args, "--media-type")? {
+            Some(media_type) => Some(MediaScope::from_str(&media_type)?),
+            None => None,
+        };
+
+        let exclude = Self::read_title_patterns(&mut args, "--exclude")?;
+        let include = Self::read_title_patterns(&mut args, "--include")?;
+
+        let action = match Self::read_value(&mut args, "--action")? {
+            Some(action) => Some(Action::from_str(&action)?),
+            None => None,
+        };
+
+        // Commands are read once the options with values are gone, so a value is never taken for
+        // a command, and the command is the first argument left that isn't a flag.
+        let config_init = if Self::read_command(&mut args, &["config", "init"]) {
+            Some(ConfigInit {
+                path: Self::read_value(&mut args, "--path")?.map(PathBuf::from),
+                force: Self::read_flag(&mut args, "--force"),
+                interactive: Self::read_flag(&mut args, "--interactive"),
+            })
+        } else {
+            None
+        };
+        let migrate_config = Self::read_command(&mut args, &["config", "migrate"]);
+        let command_start = Self::command_start(&args);
+        if args.get(command_start).is_some_and(|arg| arg == "config") {
+            return Err(eyre!(
+                "Unknown config command {}, the config commands are init and migrate.",
+                args.get(command_start + 1)
+                    .map_or("(none)", |arg| arg.as_str())
+            ));
+        }
+
+        let list = Self::read_command(&mut args, &["list"]);
+        let clean = Self::read_command(&mut args, &["clean"]);
+        let restore = if Self::read_command(&mut args, &["restore"]) {
+            match Self::read_operand(&mut args) {
+                Some(title) => Some(title),
+                None => {
+                    return Err(eyre!(
+                    "restore requires the title of what to restore, like restore \"Movie Title\"."
+                ))
+                }
+            }
+        } else {
+            None
+        };
+        let show = if Self::read_command(&mut args, &["show"]) {
+            match Self::read_operand(&mut args) {
+                Some(query) => Some(query),
+                None => {
+                    return Err(eyre!(
+                    "show requires the title or rating key of what to show, like show \"Movie Title\"."
+                ))
+                }
+            }
+        } else {
+            None
+        };
+        let purge_trash = if Self::read_command(&mut args, &["purge-trash"]) {
+            match Self::read_value(&mut args, "--older-than")? {
+                Some(age) => Some(parse_age(&age).ok_or_else(|| {
+                    eyre!(
+                        "--older-than has to be an age like 30d, 12h or 2w, got {}.",
+                        age
+                    )
+                })?),
+                None => {
+                    return Err(eyre!(
+                        "purge-trash requires --older-than, like purge-trash --older-than 30d."
+                    ))
+                }
+            }
+        } else {
+            None
+        };
+
+        let daemon = if Self::read_command(&mut args, &["daemon"]) {
+            let cron = Self::read_value(&mut args, "--schedule")?;
+            let every = Self::read_value(&mut args, "--every")?;
+            let schedule = match (cron, every) {
+                (Some(cron), None) => Schedule::Cron(Cron::from_str(&cron)?),
+                (None, Some(every)) => Schedule::Every(
+                    parse_age(&every)
+                        .filter(|every| every.num_minutes() > 0)
+                        .ok_or_else(|| {
+                            eyre!(
+                                "--every has to be a time like 7d, 12h or 2w, got {}.",
+                                every
+                            )
+                        })?,
+                ),
+                _ => {
+                    return Err(eyre!(
+                        "daemon requires either --schedule with a cron expression, like --schedule \"0 3 * * Sun\", or --every, like --every 7d."
+                    ))
+                }
+            };
+            let run_now_file = Self::read_value(&mut args, "--run-now-file")?.map(PathBuf::from);
+            // A run started by the daemon is a single run.
+            match env::var_os(daemon::RUN_ENV) {
+                Some(_) => None,
+                None => Some(Daemon {
+                    schedule,
+                    run_now_file,
+                }),
+            }
+        } else {
+            None
+        };
+
+        // Colors are needed before the arguments are read, see `output::colors_enabled`.
+        Self::read_flag(&mut args, "--no-color");
+
+        let arguments = Arguments {
+            action,
+            media_type,
+            exclude,
+            include,
+            quality,
+            min_days_since_watch,
+            min_request_age_days,
+            keep_days_after_watch,
+            keep_days_after_added,
+            watched_since,
+            export_history,
+            export_file,
+            config_path,
+            profile,
+            config_init,
+            migrate_config,
+            list,
+            clean,
+            restore,
+            show,
+            purge_trash,
+            daemon,
+            limit,
+            max_bytes,
+            report_file,
+            output,
+            log_level,
+            log_file,
+            sorting: sort.or_else(|| Self::read_sort(&mut args)),
+            all_media: Self::read_flag(&mut args, "-C"),
+            ignore_active: Self::read_flag(&mut args, "--ignore-active"),
+            no_cache: Self::read_flag(&mut args, "--no-cache"),
+            check_connections: Self::read_flag(&mut args, "--check-connections"),
+            skip_health_check: Self::read_flag(&mut args, "--skip-health-check"),
+            show_excluded: Self::read_flag(&mut args, "--show-excluded"),
+            discover: Self::read_flag(&mut args, "--discover"),
+            dry_run: Self::read_flag(&mut args, "--dry-run"),
+            quiet: Self::read_flag(&mut args, "--quiet"),
+            // Both are read, so neither is left over when both are passed.
+            auto: Self::read_flag(&mut args, "--auto") | Self::read_flag(&mut args, "--yes"),
+            trakt_login: Self::read_flag(&mut args, "--trakt-login"),
+            show_config: Self::read_flag(&mut args, "--show-config"),
+        };
+        // A mistyped command or flag would otherwise start a normal cleanup.
+        if let Some(unknown) = args.get(1) {
+            return Err(eyre!(
+                "Unknown argument \"{}\", see the readme for the commands and arguments.",
+                unknown
+            ));
+        }
+        if arguments.list && (arguments.auto || arguments.action.is_some()) {
+            return Err(eyre!(
+                "list only lists the candidates, use clean to clean them up, like clean --auto."
+            ));
+        }
+        if arguments.clean && !arguments.auto && arguments.output != OutputFormat::Text {
+            return Err(eyre!(
+                "clean with --output json or csv requires --auto, as nobody is asked what to clean up. Use list to only list the candidates."
+            ));
+        }
+
+        Ok(arguments)
+    }
+
+    /// Reads a short sorting flag like `-wd`.
     fn read_sort(args: &mut Vec<String>) -> Option<SortingOption> {
-        for (i, arg) in args.iter_mut().enumerate() {
-            if let Ok(sort) = SortingOption::from_str(&arg[1..]) {
+        for (i, arg) in args.iter_mut().enumerate().skip(1) {
+            let sort = arg
+                .strip_prefix('-')
+                .and_then(|sort| SortingOption::from_str(sort).ok());
+            if let Some(sort) = sort {
                 args.swap_remove(i);
                 return Some(sort);
             }
@@ -47,14 +384,180 @@ impl Arguments {
         None
     }
 
-    fn read_all_media(args: &mut Vec<String>) -> bool {
+    /// Reads a command, like `config init`, before or after the flags.
+    fn read_command(args: &mut Vec<String>, command: &[&str]) -> bool {
+        let start = Self::command_start(args);
+        if !args
+            .iter()
+            .skip(start)
+            .take(command.len())
+            .eq(command.iter())
+        {
+            return false;
+        }
+
+        args.drain(start..start + command.len());
+        true
+    }
+
+    /// Reads what a command is about, like the title after `show`, the next argument that isn't a flag.
+    fn read_operand(args: &mut Vec<String>) -> Option<String> {
+        let start = Self::command_start(args);
+        (start < args.len()).then(|| args.remove(start))
+    }
+
+    /// Where the command starts, the first argument that isn't a flag, once the options with
+    /// values are read. The length of the arguments if there is none.
+    fn command_start(args: &[String]) -> usize {
+        args.iter()
+            .skip(1)
+            .position(|arg| !arg.starts_with('-'))
+            .map_or(args.len(), |position| position + 1)
+    }
+
+    fn read_value(args: &mut Vec<String>, option: &str) -> Result<Option<String>> {
+        let position = match args.iter().position(|arg| arg == option) {
+            Some(position) => position,
+            None => return Ok(None),
+        };
+
+        if position + 1 >= args.len() {
+            return Err(eyre!("{} requires a value.", option));
+        }
+
+        let value = args.remove(position + 1);
+        args.remove(position);
+
+        Ok(Some(value))
+    }
+
+    /// Reads every value of an option that can be passed more than once.
+    fn read_values(args: &mut Vec<String>, option: &str) -> Result<Vec<String>> {
+        let mut values = Vec::new();
+        while let Some(value) = Self::read_value(args, option)? {
+            values.push(value);
+        }
+
+        Ok(values)
+    }
+
+    fn read_title_patterns(args: &mut Vec<String>, option: &str) -> Result<Vec<Regex>> {
+        Self::read_values(args, option)?
+            .into_iter()
+            .map(|pattern| {
+                title_filter::compile(&pattern).map_err(|err| {
+                    eyre!(
+                        "\"{}\" is not a valid regex for {}: {}",
+                        pattern,
+                        option,
+                        err
+                    )
+                })
+            })
+            .collect()
+    }
+
+    fn read_number<T>(args: &mut Vec<String>, option: &str) -> Result<Option<T>>
+    where
+        T: FromStr,
+    {
+        match Self::read_value(args, option)? {
+            Some(value) => match value.parse() {
+                Ok(number) => Ok(Some(number)),
+                Err(_) => Err(eyre!("{} has to be a number, got {}.", option, value)),
+            },
+            None => Ok(None),
+        }
+    }
+
+    /// Reads a size like 500GB, see `parse_file_size`.
+    fn read_size(args: &mut Vec<String>, option: &str) -> Result<Option<i64>> {
+        match Self::read_value(args, option)? {
+            Some(value) => match parse_file_size(&value) {
+                Some(size) => Ok(Some(size)),
+                None => Err(eyre!(
+                    "{} has to be a size like 500GB or 1.5TiB, got {}.",
+                    option,
+                    value
+                )),
+            },
+            None => Ok(None),
+        }
+    }
+
+    fn read_date(args: &mut Vec<String>, option: &str) -> Result<Option<DateTime<Utc>>> {
+        match Self::read_value(args, option)? {
+            Some(value) => match NaiveDate::parse_from_str(&value, "%Y-%m-%d") {
+                Ok(date) => Ok(Some(date.and_time(NaiveTime::MIN).and_utc())),
+                Err(_) => Err(eyre!(
+                    "{} has to be a date formatted like 2023-01-31, got {}.",
+                    option,
+                    value
+                )),
+            },
+            None => Ok(None),
+        }
+    }
+
+    fn read_flag(args: &mut Vec<String>, flag: &str) -> bool {
         for (i, arg) in args.iter_mut().enumerate() {
-            if arg == "-C" {
+            if arg == flag {
                 args.swap_remove(i);
                 return true;
             }
         }
 
-        return false;
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Result<Arguments> {
+        Arguments::parse(
+            ["media-cleaner"]
+                .iter()
+                .chain(args)
+                .map(|arg| arg.to_string())
+                .collect(),
+        )
+    }
+
+    #[test]
+    fn reads_commands_before_or_after_the_flags() {
+        for args in [["list", "--dry-run"], ["--dry-run", "list"]] {
+            let arguments = parse(&args).unwrap();
+            assert!(arguments.list && arguments.dry_run);
+        }
+
+        let arguments = parse(&["--no-cache", "--sort", "size", "clean", "--auto"]).unwrap();
+        assert!(arguments.clean && arguments.auto && arguments.no_cache);
+
+        let arguments = parse(&["--force", "config", "init"]).unwrap();
+        assert!(arguments.config_init.is_some_and(|init| init.force));
+    }
+
+    #[test]
+    fn reads_what_a_command_is_about_after_the_flags() {
+        let arguments = parse(&["--no-cache", "show", "--no-color", "Some Movie"]).unwrap();
+        assert_eq!(arguments.show.as_deref(), Some("Some Movie"));
+        assert!(arguments.no_cache);
+
+        let err = parse(&["restore", "--dry-run"]).unwrap_err();
+        assert!(err.to_string().starts_with("restore requires the title"));
+    }
+
+    #[test]
+    fn rejects_unknown_commands() {
+        let err = parse(&["--dry-run", "lsit"]).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Unknown argument \"lsit\", see the readme for the commands and arguments."
+        );
+
+        let err = parse(&["--dry-run", "config", "edit"]).unwrap_err();
+        assert!(err.to_string().starts_with("Unknown config command edit"));
     }
 }
@@ -1,43 +1,426 @@
 mod radarr;
 mod sonarr;
 
-use std::fmt::Display;
+use std::{
+    collections::{hash_map::Entry, BTreeMap, HashMap, HashSet},
+    fmt::Display,
+    path::Path,
+};
 
 use chrono::{DateTime, Utc};
 use color_eyre::owo_colors::OwoColorize;
-use color_eyre::Result;
+use color_eyre::{eyre::eyre, Result};
+use itertools::Itertools;
+use once_cell::sync::{Lazy, OnceCell};
+use tokio::sync::Mutex;
 
 pub use self::radarr::MovieStatus;
 pub use self::sonarr::SeriesStatus;
-use crate::config::Config;
-use crate::shared::MediaType;
+use crate::config::{Config, SeriesWithoutFiles};
+use crate::http;
+use crate::shared::{MediaScope, MediaType};
+use crate::utils::{human_date, major_version};
 
-pub fn movie_manger_active() -> bool {
-    match Config::global().radarr {
-        Some(_) => true,
-        None => false,
+/// The oldest major version of Radarr and Sonarr that is supported, older ones don't have the v3 API.
+const MIN_VERSION: u32 = 3;
+/// The newest major versions that are known to work, newer ones are only warned about.
+const NEWEST_RADARR_VERSION: u32 = 5;
+const NEWEST_SONARR_VERSION: u32 = 4;
+
+/// Checks the version of every configured Radarr and Sonarr before anything is done with them,
+/// so an unsupported version fails right away instead of in the middle of removing items.
+pub async fn check_versions() -> Result<()> {
+    let config = Config::global();
+    let instances = [
+        (MediaType::Movie, false, config.radarr.is_some()),
+        (MediaType::Movie, true, config.radarr_4k.is_some()),
+        (MediaType::Tv, false, config.sonarr.is_some()),
+        (MediaType::Tv, true, config.sonarr_4k.is_some()),
+    ];
+
+    for (media_type, is_4k, _) in instances.into_iter().filter(|instance| instance.2) {
+        let version = get_version(media_type, is_4k).await?;
+        check_version(media_type, is_4k, &version)?;
     }
+
+    Ok(())
 }
 
-pub fn movie_4k_manager_active() -> bool {
-    match Config::global().radarr_4k {
-        Some(_) => true,
-        None => false,
+/// Fails for versions of Radarr or Sonarr that are too old, and warns about versions that
+/// are newer than the ones known to work.
+pub fn check_version(media_type: MediaType, is_4k: bool, version: &str) -> Result<()> {
+    let (service, newest) = match media_type {
+        MediaType::Movie => ("Radarr", NEWEST_RADARR_VERSION),
+        _ => ("Sonarr", NEWEST_SONARR_VERSION),
+    };
+    let major = major_version(version).ok_or_else(|| {
+        eyre!(
+            "{} answered with version {}, which could not be understood.",
+            instance_name(media_type, is_4k),
+            version
+        )
+    })?;
+
+    if major < MIN_VERSION {
+        return Err(eyre!(
+            "{} {}.x is not supported, at least version {} is needed.",
+            service,
+            major,
+            MIN_VERSION
+        ));
     }
+    if major > newest {
+        message!(
+            "{}",
+            format!(
+                "Warning: {} is on {} {}.x, which has not been tested with. Please report any problems on Github.",
+                instance_name(media_type, is_4k),
+                service,
+                major
+            )
+            .yellow()
+        );
+    }
+
+    Ok(())
 }
 
-pub fn tv_manager_active() -> bool {
-    match Config::global().sonarr {
-        Some(_) => true,
-        None => false,
+/// Gets the version of the Radarr (for movies) or Sonarr (for shows) instance.
+pub async fn get_version(media_type: MediaType, is_4k: bool) -> Result<String> {
+    match media_type {
+        MediaType::Movie => radarr::get_radarr_version(is_4k).await,
+        MediaType::Tv => sonarr::get_sonarr_version(is_4k).await,
+        MediaType::Music => Err(eyre!("Music is not managed by Radarr or Sonarr.")),
     }
 }
 
+/// The names of quality profiles by their id.
+type QualityProfiles = HashMap<i32, String>;
+
+/// The quality profiles of each instance, by the media type and whether it's the 4K instance.
+/// They are fetched from an instance the first time one of its items needs them.
+static QUALITY_PROFILES: Lazy<Mutex<HashMap<(MediaType, bool), QualityProfiles>>> =
+    Lazy::new(|| Mutex::new(HashMap::new()));
+
+/// Gets the name of a quality profile, `None` if the instance doesn't have it or can't be reached.
+async fn quality_profile_name(media_type: MediaType, is_4k: bool, id: i32) -> Option<String> {
+    let mut profiles = QUALITY_PROFILES.lock().await;
+    if let Entry::Vacant(entry) = profiles.entry((media_type, is_4k)) {
+        let fetched = match media_type {
+            MediaType::Movie => radarr::get_radarr_quality_profiles(is_4k).await,
+            _ => sonarr::get_sonarr_quality_profiles(is_4k).await,
+        };
+        entry.insert(fetched.unwrap_or_default().into_iter().collect());
+    }
+
+    profiles
+        .get(&(media_type, is_4k))
+        .and_then(|profiles| profiles.get(&id))
+        .cloned()
+}
+
+/// The quality of an item's files. For shows it's the quality most episode files have.
+#[derive(Debug, Clone)]
+pub struct FileQuality {
+    /// Like "Remux-2160p" or "WEBDL-1080p".
+    name: String,
+    resolution: Option<i32>,
+    codec: Option<String>,
+    /// How many of the files have this quality.
+    matching_files: usize,
+    files: usize,
+}
+
+impl FileQuality {
+    /// Finds the quality most of the files have, `None` if there are no files.
+    fn dominant(files: Vec<(String, Option<i32>, Option<String>)>) -> Option<Self> {
+        let file_count = files.len();
+        let counts = files.iter().counts_by(|(name, _, _)| name.clone());
+        let (name, matching_files) =
+            counts
+                .into_iter()
+                .max_by(|(name1, count1), (name2, count2)| {
+                    count1.cmp(count2).then_with(|| name2.cmp(name1))
+                })?;
+        let (_, resolution, codec) = files.into_iter().find(|(file, _, _)| *file == name)?;
+
+        Some(Self {
+            name,
+            resolution,
+            codec,
+            matching_files,
+            files: file_count,
+        })
+    }
+}
+
+impl Display for FileQuality {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.files > 1 {
+            write!(f, "mostly ")?;
+        }
+        write!(f, "{}", self.name.yellow())?;
+
+        let details = [
+            self.resolution.map(|resolution| format!("{}p", resolution)),
+            self.codec.clone(),
+            (self.files > 1).then(|| format!("{} of {} files", self.matching_files, self.files)),
+        ]
+        .into_iter()
+        .flatten()
+        .collect_vec();
+        if !details.is_empty() {
+            write!(f, " ({})", details.join(", "))?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Writes the quality of the files and the quality profile, as far as they are known.
+fn write_quality(
+    f: &mut std::fmt::Formatter,
+    quality: &Option<FileQuality>,
+    quality_profile: &Option<String>,
+) -> std::fmt::Result {
+    if let Some(ref quality) = quality {
+        write!(f, " Quality is {}", quality)?;
+        match quality_profile {
+            Some(ref profile) => write!(f, ", with the {} profile.", profile.blue())?,
+            None => write!(f, ".")?,
+        }
+    } else if let Some(ref profile) = quality_profile {
+        write!(f, " Quality profile is {}.", profile.blue())?;
+    }
+
+    Ok(())
+}
+
+/// The ids of the excluded tags in each instance, by the media type and whether it's the 4K instance.
+static EXCLUDED_TAGS: OnceCell<HashMap<(MediaType, bool), HashSet<i32>>> = OnceCell::new();
+
+/// Looks up the ids of the tags to exclude in each Radarr and Sonarr. Tags are matched by name
+/// without regard to case, and tags that don't exist are warned about.
+pub async fn resolve_exclude_tags() -> Result<()> {
+    let config = Config::global();
+    let instances = [
+        (
+            MediaType::Movie,
+            false,
+            config.radarr.as_ref().map(|radarr| &radarr.exclude_tags),
+        ),
+        (
+            MediaType::Movie,
+            true,
+            config.radarr_4k.as_ref().map(|radarr| &radarr.exclude_tags),
+        ),
+        (
+            MediaType::Tv,
+            false,
+            config.sonarr.as_ref().map(|sonarr| &sonarr.exclude_tags),
+        ),
+        (
+            MediaType::Tv,
+            true,
+            config.sonarr_4k.as_ref().map(|sonarr| &sonarr.exclude_tags),
+        ),
+    ];
+
+    let mut excluded_tags = HashMap::new();
+    for (media_type, is_4k, names) in instances {
+        let names = match names {
+            Some(names) if !names.is_empty() => names,
+            _ => continue,
+        };
+
+        let tags = match media_type {
+            MediaType::Movie => radarr::get_radarr_tags(is_4k).await?,
+            _ => sonarr::get_sonarr_tags(is_4k).await?,
+        };
+
+        let mut ids = HashSet::new();
+        for name in names {
+            match tags
+                .iter()
+                .find(|(_, label)| label.eq_ignore_ascii_case(name))
+            {
+                Some((id, _)) => {
+                    ids.insert(*id);
+                }
+                None => message!(
+                    "{}",
+                    format!(
+                        "Warning: {} has no tag named {}, so it doesn't exclude anything.",
+                        instance_name(media_type, is_4k),
+                        name
+                    )
+                    .yellow()
+                ),
+            }
+        }
+        excluded_tags.insert((media_type, is_4k), ids);
+    }
+
+    EXCLUDED_TAGS
+        .set(excluded_tags)
+        .map_err(|_| eyre!("The exclude tags have already been resolved."))
+}
+
+/// The name of a Radarr or Sonarr instance, as set in the config or else like "Radarr 4K".
+pub fn instance_name(media_type: MediaType, is_4k: bool) -> String {
+    let config = Config::global();
+    let name = match (media_type, is_4k) {
+        (MediaType::Movie, false) => config
+            .radarr
+            .as_ref()
+            .and_then(|radarr| radarr.name.clone()),
+        (MediaType::Movie, true) => config
+            .radarr_4k
+            .as_ref()
+            .and_then(|radarr| radarr.name.clone()),
+        (_, false) => config
+            .sonarr
+            .as_ref()
+            .and_then(|sonarr| sonarr.name.clone()),
+        (_, true) => config
+            .sonarr_4k
+            .as_ref()
+            .and_then(|sonarr| sonarr.name.clone()),
+    };
+
+    name.unwrap_or_else(|| {
+        let service = match media_type {
+            MediaType::Movie => "Radarr",
+            _ => "Sonarr",
+        };
+        if is_4k {
+            format!("{} 4K", service)
+        } else {
+            service.to_string()
+        }
+    })
+}
+
+fn is_excluded_tag(media_type: MediaType, is_4k: bool, tags: &[i32]) -> bool {
+    EXCLUDED_TAGS
+        .get()
+        .and_then(|excluded_tags| excluded_tags.get(&(media_type, is_4k)))
+        .is_some_and(|excluded| tags.iter().any(|tag| excluded.contains(tag)))
+}
+
+pub fn movie_manger_active() -> bool {
+    Config::global().radarr.is_some()
+}
+
+pub fn movie_4k_manager_active() -> bool {
+    Config::global().radarr_4k.is_some()
+}
+
+pub fn tv_manager_active() -> bool {
+    Config::global().sonarr.is_some()
+}
+
 pub fn tv_4k_manager_active() -> bool {
-    match Config::global().sonarr_4k {
-        Some(_) => true,
-        None => false,
+    Config::global().sonarr_4k.is_some()
+}
+
+/// A movie or series managed by Radarr or Sonarr, the candidates when there is no request manager.
+#[derive(Debug)]
+pub struct ManagedItem {
+    pub manager_id: i32,
+    /// Whether the item is in the 4K instance.
+    pub is_4k: bool,
+    pub media_type: MediaType,
+    pub tmdb_id: Option<u32>,
+    pub tvdb_id: Option<u32>,
+    pub has_files: bool,
+    /// When the item was added to Radarr or Sonarr.
+    pub added_at: Option<DateTime<Utc>>,
+}
+
+/// The titles Radarr and Sonarr have, like "Title (Year)", by whether the instance is the 4K one,
+/// the media type and the id in Radarr or Sonarr.
+pub type Titles = HashMap<(bool, MediaType, i32), String>;
+
+/// Gets the titles of every movie and series in the Radarr and Sonarr instances that are in scope,
+/// with a request per instance.
+pub async fn get_titles() -> Result<Titles> {
+    let scope = MediaScope::of_run();
+    let mut titles = Titles::new();
+
+    for (active, is_4k) in [
+        (movie_manger_active(), false),
+        (movie_4k_manager_active(), true),
+    ] {
+        if active && scope.includes(MediaType::Movie) {
+            for movie in radarr::get_radarr_movies(is_4k).await? {
+                let title = with_year(&movie.title, movie.year);
+                titles.insert((is_4k, MediaType::Movie, movie.id), title);
+            }
+        }
+    }
+    for (active, is_4k) in [(tv_manager_active(), false), (tv_4k_manager_active(), true)] {
+        if active && scope.includes(MediaType::Tv) {
+            for series in sonarr::get_all_sonarr_series(is_4k).await? {
+                let title = with_year(&series.title, series.year);
+                titles.insert((is_4k, MediaType::Tv, series.id), title);
+            }
+        }
+    }
+
+    Ok(titles)
+}
+
+fn with_year(title: &str, year: Option<i32>) -> String {
+    match year {
+        Some(year) => format!("{} ({})", title, year),
+        None => title.to_string(),
+    }
+}
+
+/// Gets every movie and series of all configured Radarr and Sonarr instances.
+pub async fn get_managed_items() -> Result<Vec<ManagedItem>> {
+    let mut items = Vec::new();
+    let scope = MediaScope::of_run();
+
+    for (active, is_4k) in [
+        (movie_manger_active(), false),
+        (movie_4k_manager_active(), true),
+    ] {
+        if !active || !scope.includes(MediaType::Movie) {
+            continue;
+        }
+        for movie in radarr::get_radarr_movies(is_4k).await? {
+            items.push(ManagedItem {
+                manager_id: movie.id,
+                is_4k,
+                media_type: MediaType::Movie,
+                tmdb_id: movie.tmdb_id,
+                tvdb_id: None,
+                has_files: movie.movie_file.is_some(),
+                added_at: get_potential_date_time(movie.added)?,
+            });
+        }
     }
+
+    for (active, is_4k) in [(tv_manager_active(), false), (tv_4k_manager_active(), true)] {
+        if !active || !scope.includes(MediaType::Tv) {
+            continue;
+        }
+        for series in sonarr::get_all_sonarr_series(is_4k).await? {
+            items.push(ManagedItem {
+                manager_id: series.id,
+                is_4k,
+                media_type: MediaType::Tv,
+                tmdb_id: None,
+                tvdb_id: series.tvdb_id,
+                has_files: series.statistics.size_on_disk > 0,
+                added_at: get_potential_date_time(series.added)?,
+            });
+        }
+    }
+
+    Ok(items)
 }
 
 #[derive(Debug)]
@@ -51,6 +434,7 @@ impl ArrData {
         match media_type {
             MediaType::Movie => Ok(Self::Movie(MovieData::get_data(id, false).await?)),
             MediaType::Tv => Ok(Self::Tv(TvData::get_data(id, false).await?)),
+            MediaType::Music => Err(eyre!("Music is not managed by Radarr or Sonarr.")),
         }
     }
 
@@ -58,13 +442,191 @@ impl ArrData {
         match media_type {
             MediaType::Movie => Ok(Self::Movie(MovieData::get_data(id, true).await?)),
             MediaType::Tv => Ok(Self::Tv(TvData::get_data(id, true).await?)),
+            MediaType::Music => Err(eyre!("Music is not managed by Radarr or Sonarr.")),
+        }
+    }
+
+    /// Finds a movie in Radarr by its TMDB id, `None` if that Radarr isn't set up or doesn't have it.
+    pub async fn find_movie(tmdb_id: u32, is_4k: bool) -> Result<Option<Self>> {
+        let active = if is_4k {
+            movie_4k_manager_active()
+        } else {
+            movie_manger_active()
+        };
+        if !active {
+            return Ok(None);
+        }
+
+        match radarr::find_radarr_movie(tmdb_id, is_4k).await? {
+            Some(movie) => Ok(Some(Self::Movie(
+                MovieData::from_resource(movie, is_4k).await?,
+            ))),
+            None => Ok(None),
+        }
+    }
+
+    /// Finds a series in Sonarr by its TVDB id, `None` if that Sonarr isn't set up or doesn't have it.
+    pub async fn find_series(tvdb_id: u32, is_4k: bool) -> Result<Option<Self>> {
+        let active = if is_4k {
+            tv_4k_manager_active()
+        } else {
+            tv_manager_active()
+        };
+        if !active {
+            return Ok(None);
+        }
+
+        match sonarr::find_sonarr_series(tvdb_id, is_4k).await? {
+            Some(series) => Ok(Some(Self::Tv(TvData::from_resource(series, is_4k).await?))),
+            None => Ok(None),
+        }
+    }
+
+    /// Removes the item, with `block` also keeping it from being added again. With `keep_files`
+    /// its files are left alone, as they were already moved to the trash.
+    pub async fn remove_data(&self, block: bool, keep_files: bool) -> Result<()> {
+        match self {
+            Self::Movie(movie) => movie.remove_data(block, keep_files).await,
+            Self::Tv(tv) => tv.remove_data(block, keep_files).await,
         }
     }
 
-    pub async fn remove_data(self) -> Result<()> {
+    /// Whether removing the item deletes its files, from `delete_files` of the instance.
+    pub fn deletes_files(&self) -> bool {
         match self {
-            Self::Movie(movie) => movie.remove_data().await,
-            Self::Tv(tv) => tv.remove_data().await,
+            Self::Movie(movie) => {
+                radarr::radarr_config(movie.is_4k).is_none_or(|radarr| radarr.delete_files)
+            }
+            Self::Tv(tv) => {
+                sonarr::sonarr_config(tv.is_4k).is_none_or(|sonarr| sonarr.delete_files)
+            }
+        }
+    }
+
+    /// The folder of the item, as Radarr or Sonarr knows it.
+    pub fn path(&self) -> Option<&str> {
+        match self {
+            Self::Movie(movie) => movie.path.as_deref(),
+            Self::Tv(tv) => tv.path.as_deref(),
+        }
+    }
+
+    /// Checks that a removal went through, by making sure Radarr or Sonarr no longer has the item,
+    /// or the episode files of the given seasons. Returns the paths that are still on disk,
+    /// which are only looked for if `check_deleted_paths` is set and the files were deleted.
+    pub async fn check_removed(&self, seasons: Option<&[u32]>) -> Result<Vec<String>> {
+        match (self, seasons) {
+            (Self::Movie(movie), _) => movie.check_removed().await,
+            (Self::Tv(tv), Some(seasons)) => tv.check_seasons_removed(seasons).await,
+            (Self::Tv(tv), None) => tv.check_removed().await,
+        }
+    }
+
+    /// Deletes the files of the item, or of the given seasons, and unmonitors it,
+    /// while keeping it in Radarr or Sonarr.
+    pub async fn remove_files(&self, seasons: Option<&[u32]>) -> Result<()> {
+        match (self, seasons) {
+            (Self::Movie(movie), _) => {
+                radarr::delete_radarr_movie_files(movie.id, movie.is_4k).await
+            }
+            (Self::Tv(tv), Some(seasons)) => {
+                sonarr::remove_sonarr_seasons(tv.id, seasons, tv.is_4k).await
+            }
+            (Self::Tv(tv), None) => sonarr::remove_sonarr_files(tv.id, tv.is_4k).await,
+        }
+    }
+
+    /// Checks that Radarr or Sonarr no longer has any files of the item, or of the given seasons.
+    pub async fn check_files_removed(&self, seasons: Option<&[u32]>) -> Result<()> {
+        let remaining = match (self, seasons) {
+            (Self::Movie(movie), _) => radarr::count_movie_files(movie.id, movie.is_4k).await,
+            (Self::Tv(tv), Some(seasons)) => {
+                sonarr::count_season_episode_files(tv.id, seasons, tv.is_4k).await
+            }
+            (Self::Tv(tv), None) => sonarr::get_episode_files(tv.id, tv.is_4k)
+                .await
+                .map(|files| files.len()),
+        }
+        .map_err(|err| eyre!("could not check the files are gone: {}", err))?;
+
+        if remaining > 0 {
+            return Err(eyre!("{} files are still there.", remaining));
+        }
+
+        Ok(())
+    }
+
+    /// Stops Radarr or Sonarr from monitoring the item, or only the given seasons of a show.
+    pub async fn unmonitor(&self, seasons: Option<&[u32]>) -> Result<()> {
+        match self {
+            Self::Movie(movie) => radarr::unmonitor_radarr_movie(movie.id, movie.is_4k).await,
+            Self::Tv(tv) => sonarr::unmonitor_sonarr_series(tv.id, seasons, tv.is_4k).await,
+        }
+    }
+
+    /// The name of the instance the item is in.
+    pub fn instance_name(&self) -> String {
+        match self {
+            Self::Movie(movie) => instance_name(MediaType::Movie, movie.is_4k),
+            Self::Tv(tv) => instance_name(MediaType::Tv, tv.is_4k),
+        }
+    }
+
+    /// The name of the quality of the item's files, for shows the quality most episode files have.
+    pub fn quality_name(&self) -> Option<&str> {
+        let quality = match self {
+            Self::Movie(movie) => movie.quality.as_ref(),
+            Self::Tv(tv) => tv.quality.as_ref(),
+        };
+        quality.map(|quality| quality.name.as_str())
+    }
+
+    /// Whether the item has one of the tags that are excluded in the config.
+    pub fn has_excluded_tag(&self) -> bool {
+        match self {
+            Self::Movie(movie) => is_excluded_tag(MediaType::Movie, movie.is_4k, &movie.tags),
+            Self::Tv(tv) => is_excluded_tag(MediaType::Tv, tv.is_4k, &tv.tags),
+        }
+    }
+
+    /// Removes only the given seasons, which only shows have.
+    pub async fn remove_seasons(&self, seasons: &[u32]) -> Result<()> {
+        match self {
+            Self::Movie(_) => Err(eyre!("Tried to remove seasons of a movie.")),
+            Self::Tv(tv) => sonarr::remove_sonarr_seasons(tv.id, seasons, tv.is_4k).await,
+        }
+    }
+
+    /// The size of the given seasons, for movies it is the size of the movie.
+    pub fn get_seasons_disk_size(&self, seasons: &[u32]) -> i64 {
+        match self {
+            Self::Movie(movie) => movie.size_on_disk,
+            Self::Tv(tv) => seasons
+                .iter()
+                .filter_map(|season| tv.seasons.get(season))
+                .map(|season| season.size_on_disk)
+                .sum(),
+        }
+    }
+
+    /// The seasons of a show that have files on disk, movies have none.
+    pub fn seasons_on_disk(&self) -> Vec<u32> {
+        match self {
+            Self::Movie(_) => Vec::new(),
+            Self::Tv(tv) => tv
+                .seasons
+                .iter()
+                .filter(|(_, season)| season.size_on_disk > 0)
+                .map(|(number, _)| *number)
+                .collect(),
+        }
+    }
+
+    /// The number of episodes in a season, if it is known.
+    pub fn season_episode_count(&self, season: u32) -> Option<i32> {
+        match self {
+            Self::Movie(_) => None,
+            Self::Tv(tv) => tv.seasons.get(&season).map(|season| season.episode_count),
         }
     }
 
@@ -74,6 +636,14 @@ impl ArrData {
             Self::Tv(tv) => tv.size_on_disk,
         }
     }
+
+    /// The title and release year the item has in Radarr or Sonarr.
+    pub fn title_and_year(&self) -> (&str, Option<i32>) {
+        match self {
+            Self::Movie(movie) => (&movie.title, movie.year),
+            Self::Tv(tv) => (&tv.title, tv.year),
+        }
+    }
 }
 
 impl Display for ArrData {
@@ -88,27 +658,74 @@ impl Display for ArrData {
 #[derive(Debug)]
 pub struct MovieData {
     id: i32,
+    /// Whether the movie is in the 4K instance of Radarr.
+    is_4k: bool,
+    title: String,
+    year: Option<i32>,
     status: MovieStatus,
     size_on_disk: i64,
     digital_release: Option<DateTime<Utc>>,
     physical_release: Option<DateTime<Utc>>,
+    tags: Vec<i32>,
+    path: Option<String>,
+    quality: Option<FileQuality>,
+    quality_profile: Option<String>,
 }
 
 impl MovieData {
     async fn get_data(id: i32, is_4k: bool) -> Result<Self> {
         let data = radarr::get_radarr_data(id, is_4k).await?;
 
+        Self::from_resource(data, is_4k).await
+    }
+
+    async fn from_resource(data: radarr::MovieResource, is_4k: bool) -> Result<Self> {
+        let quality_profile = match data.quality_profile_id {
+            Some(id) => quality_profile_name(MediaType::Movie, is_4k, id).await,
+            None => None,
+        };
+        let quality = data.movie_file.and_then(|file| {
+            FileQuality::dominant(vec![(
+                file.quality.quality.name,
+                file.quality.quality.resolution,
+                file.media_info
+                    .and_then(|media_info| media_info.video_codec),
+            )])
+        });
+
         Ok(Self {
+            quality,
+            quality_profile,
             id: data.id,
+            is_4k,
+            title: data.title,
+            year: data.year,
             status: data.status,
-            size_on_disk: data.size_on_disk,
+            size_on_disk: data
+                .size_on_disk
+                .or(data.statistics.map(|statistics| statistics.size_on_disk))
+                .unwrap_or_default(),
             digital_release: get_potential_date_time(data.digital_release)?,
             physical_release: get_potential_date_time(data.physical_release)?,
+            tags: data.tags,
+            path: data.path,
         })
     }
 
-    async fn remove_data(self) -> Result<()> {
-        radarr::delete_radarr_data_and_files(self.id).await
+    async fn remove_data(&self, block: bool, keep_files: bool) -> Result<()> {
+        radarr::delete_radarr_data_and_files(self.id, self.is_4k, block, keep_files).await
+    }
+
+    async fn check_removed(&self) -> Result<Vec<String>> {
+        match radarr::get_radarr_data(self.id, self.is_4k).await {
+            Ok(_) => Err(eyre!("the movie is still there after deleting it.")),
+            Err(err) if http::is_not_found(&err) => {
+                let delete_files =
+                    radarr::radarr_config(self.is_4k).is_none_or(|radarr| radarr.delete_files);
+                Ok(remaining_path(self.path.as_deref(), delete_files))
+            }
+            Err(err) => Err(eyre!("could not check the movie is gone: {}", err)),
+        }
     }
 }
 
@@ -124,13 +741,18 @@ impl Display for MovieData {
             digital_release.blue(),
             physical_release.blue(),
             self.status.green(),
-        )
+        )?;
+        write_quality(f, &self.quality, &self.quality_profile)
     }
 }
 
 #[derive(Debug)]
 pub struct TvData {
     id: i32,
+    /// Whether the show is in the 4K instance of Sonarr.
+    is_4k: bool,
+    title: String,
+    year: Option<i32>,
     status: SeriesStatus,
     last_airing: Option<DateTime<Utc>>,
     next_airing: Option<DateTime<Utc>>,
@@ -138,16 +760,97 @@ pub struct TvData {
     episodes_in_last_season: i32,
     percent_of_episodes_on_disk: f64,
     size_on_disk: i64,
+    seasons: BTreeMap<u32, SeasonData>,
+    tags: Vec<i32>,
+    path: Option<String>,
+    quality: Option<FileQuality>,
+    quality_profile: Option<String>,
+}
+
+#[derive(Debug)]
+struct SeasonData {
+    episode_count: i32,
+    size_on_disk: i64,
 }
 
 impl TvData {
-    async fn remove_data(self) -> Result<()> {
-        sonarr::remove_sonarr_data_and_files(self.id).await
+    async fn remove_data(&self, block: bool, keep_files: bool) -> Result<()> {
+        if self.is_kept_without_files() {
+            message!(
+                "{} has no files, so it was left in Sonarr.",
+                self.title.green()
+            );
+            return Ok(());
+        }
+
+        sonarr::remove_sonarr_data_and_files(self.id, self.is_4k, block, keep_files).await
+    }
+
+    /// Whether the series has no files and is left in Sonarr instead of being removed.
+    fn is_kept_without_files(&self) -> bool {
+        self.size_on_disk == 0
+            && sonarr::sonarr_config(self.is_4k)
+                .is_some_and(|sonarr| sonarr.series_without_files == SeriesWithoutFiles::Skip)
+    }
+
+    async fn check_removed(&self) -> Result<Vec<String>> {
+        if self.is_kept_without_files() {
+            return Ok(Vec::new());
+        }
+
+        match sonarr::get_sonarr_data(self.id, self.is_4k).await {
+            Ok(_) => Err(eyre!("the series is still there after deleting it.")),
+            Err(err) if http::is_not_found(&err) => {
+                let delete_files =
+                    sonarr::sonarr_config(self.is_4k).is_none_or(|sonarr| sonarr.delete_files);
+                Ok(remaining_path(self.path.as_deref(), delete_files))
+            }
+            Err(err) => Err(eyre!("could not check the series is gone: {}", err)),
+        }
+    }
+
+    async fn check_seasons_removed(&self, seasons: &[u32]) -> Result<Vec<String>> {
+        let remaining = sonarr::count_season_episode_files(self.id, seasons, self.is_4k)
+            .await
+            .map_err(|err| eyre!("could not check the episode files are gone: {}", err))?;
+        if remaining > 0 {
+            return Err(eyre!(
+                "{} episode files of the removed seasons are still there.",
+                remaining
+            ));
+        }
+
+        Ok(Vec::new())
     }
 
     async fn get_data(id: i32, is_4k: bool) -> Result<Self> {
         let data = sonarr::get_sonarr_data(id, is_4k).await?;
 
+        Self::from_resource(data, is_4k).await
+    }
+
+    async fn from_resource(data: sonarr::SeriesResource, is_4k: bool) -> Result<Self> {
+        let quality_profile = match data.quality_profile_id {
+            Some(id) => quality_profile_name(MediaType::Tv, is_4k, id).await,
+            None => None,
+        };
+        let files = match data.statistics.size_on_disk {
+            0 => Vec::new(),
+            _ => sonarr::get_episode_files(data.id, is_4k).await?,
+        };
+        let quality = FileQuality::dominant(
+            files
+                .into_iter()
+                .filter_map(|file| {
+                    let quality = file.quality?.quality;
+                    let codec = file
+                        .media_info
+                        .and_then(|media_info| media_info.video_codec);
+                    Some((quality.name, quality.resolution, codec))
+                })
+                .collect(),
+        );
+
         let episodes_in_last_season = data
             .seasons
             .iter()
@@ -155,17 +858,34 @@ impl TvData {
             .map(|s| s.statistics.episode_count);
 
         Ok(Self {
+            quality,
+            quality_profile,
             id: data.id,
+            is_4k,
+            title: data.title,
+            year: data.year,
             last_airing: get_potential_date_time(data.previous_airing)?,
             next_airing: get_potential_date_time(data.next_airing)?,
             status: data.status,
             season_count: data.statistics.season_count,
-            episodes_in_last_season: match episodes_in_last_season {
-                Some(count) => count,
-                None => 0,
-            },
+            episodes_in_last_season: episodes_in_last_season.unwrap_or_default(),
             percent_of_episodes_on_disk: data.statistics.percent_of_episodes,
             size_on_disk: data.statistics.size_on_disk,
+            tags: data.tags,
+            path: data.path,
+            seasons: data
+                .seasons
+                .iter()
+                .map(|season| {
+                    (
+                        season.season_number as u32,
+                        SeasonData {
+                            episode_count: season.statistics.episode_count,
+                            size_on_disk: season.statistics.size_on_disk,
+                        },
+                    )
+                })
+                .collect(),
         })
     }
 }
@@ -187,7 +907,8 @@ impl Display for TvData {
                 count => count.to_string()
             }.yellow(),
             &format!("{:.2}%", self.percent_of_episodes_on_disk).blue(),
-        )
+        )?;
+        write_quality(f, &self.quality, &self.quality_profile)
     }
 }
 
@@ -203,7 +924,146 @@ fn get_potential_date_time(potential_date: Option<String>) -> Result<Option<Date
 
 fn format_potential_date(potential_date: Option<DateTime<Utc>>) -> String {
     match potential_date {
-        Some(release) => release.format("%d-%m-%Y").to_string(),
+        Some(release) => human_date(release),
         None => "never(?)".into(),
     }
 }
+
+/// The path of removed media if it still exists on this machine. It is only looked for if
+/// `check_deleted_paths` is set, and the files were meant to be deleted.
+fn remaining_path(path: Option<&str>, files_deleted: bool) -> Vec<String> {
+    match path {
+        Some(path)
+            if files_deleted
+                && Config::global().check_deleted_paths
+                && Path::new(path).exists() =>
+        {
+            vec![path.to_string()]
+        }
+        _ => Vec::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{self, mock};
+
+    fn mock_quality_profiles() {
+        test_support::init();
+        let profiles = r#"[{"id": 2, "name": "HD-1080p"}, {"id": 4, "name": "HD - 720p/1080p"}]"#;
+        mock("/radarr/api/v3/qualityprofile?", &[(200, profiles)]);
+        mock("/sonarr/api/v3/qualityprofile?", &[(200, profiles)]);
+    }
+
+    #[tokio::test]
+    async fn reads_radarr_movies_of_each_version() {
+        mock_quality_profiles();
+        // Radarr 5 moved the size of the files into the statistics.
+        mock(
+            "/radarr/api/v3/movie/1010?",
+            &[(200, include_str!("../../fixtures/radarr/movie_v4.json"))],
+        );
+        mock(
+            "/radarr/api/v3/movie/1011?",
+            &[(200, include_str!("../../fixtures/radarr/movie_v5.json"))],
+        );
+
+        for id in [1010, 1011] {
+            let movie = MovieData::get_data(id, false).await.unwrap();
+
+            assert_eq!(movie.size_on_disk, 9_663_676_416);
+            assert_eq!(movie.quality_profile.as_deref(), Some("HD-1080p"));
+            let quality = movie.quality.unwrap();
+            assert_eq!(quality.name, "Bluray-1080p");
+            assert_eq!(quality.codec.as_deref(), Some("x264"));
+        }
+    }
+
+    #[tokio::test]
+    async fn reads_sonarr_3_series() {
+        mock_quality_profiles();
+        mock(
+            "/sonarr/api/v3/series/1012?",
+            &[(200, include_str!("../../fixtures/sonarr/series_v3.json"))],
+        );
+        mock(
+            "/sonarr/api/v3/episodefile?seriesId=1012",
+            &[(
+                200,
+                include_str!("../../fixtures/sonarr/episodefile_v3.json"),
+            )],
+        );
+
+        let series = TvData::get_data(1012, false).await.unwrap();
+
+        assert_eq!(series.size_on_disk, 21_474_836_480);
+        assert_eq!(series.season_count, 2);
+        assert_eq!(series.episodes_in_last_season, 10);
+        assert_eq!(series.seasons[&2].size_on_disk, 6_442_450_944);
+        assert_eq!(series.quality_profile.as_deref(), Some("HD - 720p/1080p"));
+        let quality = series.quality.unwrap();
+        assert_eq!(
+            (quality.name.as_str(), quality.matching_files),
+            ("WEBDL-1080p", 2)
+        );
+    }
+
+    #[tokio::test]
+    async fn reads_sonarr_4_series_without_statistics() {
+        mock_quality_profiles();
+        mock(
+            "/sonarr/api/v3/series/1013?",
+            &[(200, include_str!("../../fixtures/sonarr/series_v4.json"))],
+        );
+        let episode_files = mock("/sonarr/api/v3/episodefile?seriesId=1013", &[(200, "[]")]);
+
+        let series = TvData::get_data(1013, false).await.unwrap();
+
+        assert_eq!(series.size_on_disk, 0);
+        assert_eq!(series.seasons.len(), 2);
+        assert!(series
+            .seasons
+            .values()
+            .all(|season| season.episode_count == 0));
+        // Without anything on disk, there are no episode files to ask for.
+        assert_eq!(episode_files.hits(), 0);
+    }
+
+    #[tokio::test]
+    async fn gets_the_version_from_the_system_status() {
+        test_support::init();
+        mock(
+            "/sonarr/api/v3/system/status?",
+            &[(
+                200,
+                r#"{"appName": "Sonarr", "version": "4.0.1.929", "isProduction": true}"#,
+            )],
+        );
+
+        let version = get_version(MediaType::Tv, false).await.unwrap();
+
+        assert_eq!(version, "4.0.1.929");
+        assert!(check_version(MediaType::Tv, false, &version).is_ok());
+    }
+
+    #[test]
+    fn rejects_versions_without_the_v3_api() {
+        test_support::init();
+        for version in ["3.0.10.1567", "4.0.1.929"] {
+            assert!(check_version(MediaType::Tv, false, version).is_ok());
+        }
+        for version in ["3.2.2.5080", "4.7.5.7809", "v5.2.6.8376"] {
+            assert!(check_version(MediaType::Movie, false, version).is_ok());
+        }
+
+        let err = check_version(MediaType::Tv, false, "2.0.0.5344").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Sonarr 2.x is not supported, at least version 3 is needed."
+        );
+        let err = check_version(MediaType::Movie, false, "0.2.0.1504").unwrap_err();
+        assert!(err.to_string().starts_with("Radarr 0.x is not supported"));
+        assert!(check_version(MediaType::Movie, false, "nightly").is_err());
+    }
+}
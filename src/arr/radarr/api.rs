@@ -1,18 +1,20 @@
 use color_eyre::{eyre::eyre, Result};
+use reqwest::Method;
 use serde::de::DeserializeOwned;
+use serde_json::Value;
 
 use crate::{
     config::{Config, Radarr},
-    utils::{create_api_error_message, create_param_string},
+    http::{self, ApiError, SendLogged},
+    utils::create_param_string,
 };
 
 pub async fn get<T>(path: &str, params: Option<Vec<(&str, &str)>>, is_4k: bool) -> Result<T>
 where
     T: DeserializeOwned,
 {
-    let config: &Radarr;
-    if is_4k {
-        config = match &Config::global().radarr_4k {
+    let config: &Radarr = if is_4k {
+        match &Config::global().radarr_4k {
             Some(ref radarr) => radarr,
             None => {
                 return Err(eyre!(
@@ -21,15 +23,15 @@ where
             }
         }
     } else {
-        config = match &Config::global().radarr {
+        match &Config::global().radarr {
             Some(ref radarr) => radarr,
             None => {
                 return Err(eyre!(
                     "Tried to access radarr config, even though it is not defined."
                 ))
             }
-        };
-    }
+        }
+    };
 
     let client = reqwest::Client::new();
     let params = create_param_string(params);
@@ -37,12 +39,12 @@ where
     let response = client
         .get(format!("{}/api/v3{}?{}", config.url, path, params))
         .header("X-Api-Key", &config.api_key)
-        .send()
+        .send_logged("Radarr")
         .await?;
 
     if !(response.status().as_u16() >= 200 && response.status().as_u16() < 300) {
         let code = response.status().as_u16();
-        return Err(eyre!(create_api_error_message(code, path, "Radarr")));
+        return Err(ApiError::from_status("Radarr", path, code).into());
     }
 
     let response = response.json().await?;
@@ -50,9 +52,14 @@ where
     Ok(response)
 }
 
-pub async fn delete(path: &str, params: Option<Vec<(&str, &str)>>) -> Result<()> {
-    let config = match &Config::global().radarr {
-        Some(ref radarr) => radarr,
+pub async fn delete(path: &str, params: Option<Vec<(&str, &str)>>, is_4k: bool) -> Result<()> {
+    let instance = if is_4k {
+        &Config::global().radarr_4k
+    } else {
+        &Config::global().radarr
+    };
+    let config = match instance {
+        Some(radarr) => radarr,
         None => {
             return Err(eyre!(
                 "Tried to access radarr config, even though it is not defined."
@@ -62,11 +69,61 @@ pub async fn delete(path: &str, params: Option<Vec<(&str, &str)>>) -> Result<()>
     let client = reqwest::Client::new();
     let params = create_param_string(params);
 
-    client
-        .delete(format!("{}/api/v3{}?{}", &config.url, path, params))
-        .header("X-Api-Key", &config.api_key)
-        .send()
-        .await?;
+    let url = format!("{}/api/v3{}?{}", &config.url, path, params);
+
+    http::send_change(Method::DELETE, &url, None, || async {
+        let response = client
+            .delete(&url)
+            .header("X-Api-Key", &config.api_key)
+            .send_logged("Radarr")
+            .await?;
+
+        if !response.status().is_success() {
+            let code = response.status().as_u16();
+            return Err(ApiError::from_status("Radarr", path, code).into());
+        }
+
+        Ok(())
+    })
+    .await?;
+
+    Ok(())
+}
+
+pub async fn put(path: &str, body: &Value, is_4k: bool) -> Result<()> {
+    let instance = if is_4k {
+        &Config::global().radarr_4k
+    } else {
+        &Config::global().radarr
+    };
+    let config = match instance {
+        Some(radarr) => radarr,
+        None => {
+            return Err(eyre!(
+                "Tried to access radarr config, even though it is not defined."
+            ))
+        }
+    };
+    let client = reqwest::Client::new();
+
+    let url = format!("{}/api/v3{}", &config.url, path);
+
+    http::send_change(Method::PUT, &url, Some(body), || async {
+        let response = client
+            .put(&url)
+            .header("X-Api-Key", &config.api_key)
+            .json(body)
+            .send_logged("Radarr")
+            .await?;
+
+        if !response.status().is_success() {
+            let code = response.status().as_u16();
+            return Err(ApiError::from_status("Radarr", path, code).into());
+        }
+
+        Ok(())
+    })
+    .await?;
 
     Ok(())
 }
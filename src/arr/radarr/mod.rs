@@ -2,17 +2,110 @@ mod api;
 mod responses;
 
 use color_eyre::Result;
+use serde_json::Value;
 
-use self::responses::MovieResource;
-pub use self::responses::MovieStatus;
+use self::responses::{
+    MovieFileResource, QualityProfileResource, SystemStatusResource, TagResource,
+};
+pub use self::responses::{MovieResource, MovieStatus};
+use crate::config::{Config, Radarr};
 
 pub async fn get_radarr_data(id: i32, is_4k: bool) -> Result<MovieResource> {
-    let path = format!("/movie/{}", id.to_string());
+    let path = format!("/movie/{}", id);
     api::get(&path, None, is_4k).await
 }
 
-pub async fn delete_radarr_data_and_files(radarr_id: i32) -> Result<()> {
-    let path = format!("/movie/{}", radarr_id.to_string());
-    let params = vec![("deleteFiles", "true"), ("addImportExclusion", "false")];
-    api::delete(path.as_str(), Some(params)).await
+/// Gets every movie in Radarr.
+pub async fn get_radarr_movies(is_4k: bool) -> Result<Vec<MovieResource>> {
+    api::get("/movie", None, is_4k).await
+}
+
+/// Gets the version of Radarr, which also checks it can be reached with the API key.
+pub async fn get_radarr_version(is_4k: bool) -> Result<String> {
+    let status: SystemStatusResource = api::get("/system/status", None, is_4k).await?;
+    Ok(status.version)
+}
+
+/// Gets the ids and names of all quality profiles.
+pub async fn get_radarr_quality_profiles(is_4k: bool) -> Result<Vec<(i32, String)>> {
+    let profiles: Vec<QualityProfileResource> = api::get("/qualityprofile", None, is_4k).await?;
+    Ok(profiles
+        .into_iter()
+        .map(|profile| (profile.id, profile.name))
+        .collect())
+}
+
+/// Gets the ids and names of all tags.
+pub async fn get_radarr_tags(is_4k: bool) -> Result<Vec<(i32, String)>> {
+    let tags: Vec<TagResource> = api::get("/tag", None, is_4k).await?;
+    Ok(tags.into_iter().map(|tag| (tag.id, tag.label)).collect())
+}
+
+/// Looks a movie up by its TMDB id, for when Overseerr doesn't know its Radarr id.
+pub async fn find_radarr_movie(tmdb_id: u32, is_4k: bool) -> Result<Option<MovieResource>> {
+    let tmdb_id = tmdb_id.to_string();
+    let movies: Vec<MovieResource> =
+        api::get("/movie", Some(vec![("tmdbId", &tmdb_id)]), is_4k).await?;
+
+    Ok(movies.into_iter().next())
+}
+
+/// Deletes the movie, and its files and an import list exclusion as set in the config.
+/// With `block`, the exclusion is always added.
+pub async fn delete_radarr_data_and_files(
+    radarr_id: i32,
+    is_4k: bool,
+    block: bool,
+    keep_files: bool,
+) -> Result<()> {
+    let (delete_files, add_import_exclusion) = match radarr_config(is_4k) {
+        Some(radarr) => (radarr.delete_files, radarr.add_import_exclusion),
+        None => (true, false),
+    };
+
+    let path = format!("/movie/{}", radarr_id);
+    let delete_files = (delete_files && !keep_files).to_string();
+    let add_import_exclusion = (add_import_exclusion || block).to_string();
+    let params = vec![
+        ("deleteFiles", delete_files.as_str()),
+        ("addImportExclusion", add_import_exclusion.as_str()),
+    ];
+    api::delete(path.as_str(), Some(params), is_4k).await
+}
+
+pub fn radarr_config(is_4k: bool) -> Option<&'static Radarr> {
+    if is_4k {
+        Config::global().radarr_4k.as_ref()
+    } else {
+        Config::global().radarr.as_ref()
+    }
+}
+
+/// Deletes the files of the movie and stops monitoring it, keeping the movie in Radarr
+/// so it can be grabbed again later.
+pub async fn delete_radarr_movie_files(radarr_id: i32, is_4k: bool) -> Result<()> {
+    for movie_file in get_movie_files(radarr_id, is_4k).await? {
+        let path = format!("/moviefile/{}", movie_file.id);
+        api::delete(&path, None, is_4k).await?;
+    }
+
+    unmonitor_radarr_movie(radarr_id, is_4k).await
+}
+
+/// Counts the files Radarr still has of the movie.
+pub async fn count_movie_files(radarr_id: i32, is_4k: bool) -> Result<usize> {
+    Ok(get_movie_files(radarr_id, is_4k).await?.len())
+}
+
+async fn get_movie_files(radarr_id: i32, is_4k: bool) -> Result<Vec<MovieFileResource>> {
+    let movie_id = radarr_id.to_string();
+    api::get("/moviefile", Some(vec![("movieId", &movie_id)]), is_4k).await
+}
+
+/// Stops Radarr from monitoring the movie, so it isn't upgraded or grabbed again.
+pub async fn unmonitor_radarr_movie(radarr_id: i32, is_4k: bool) -> Result<()> {
+    let path = format!("/movie/{}", radarr_id);
+    let mut movie: Value = api::get(&path, None, is_4k).await?;
+    movie["monitored"] = Value::Bool(false);
+    api::put(&path, &movie, is_4k).await
 }
@@ -4,11 +4,65 @@ use serde::Deserialize;
 #[serde(rename_all = "camelCase")]
 pub struct MovieResource {
     pub id: i32,
-    pub title: Option<String>,
+    pub tmdb_id: Option<u32>,
+    pub title: String,
+    pub year: Option<i32>,
     pub status: MovieStatus,
-    pub size_on_disk: i64,
+    /// Radarr 5 moved the size into the statistics.
+    pub size_on_disk: Option<i64>,
+    pub statistics: Option<MovieStatisticsResource>,
     pub digital_release: Option<String>,
     pub physical_release: Option<String>,
+    #[serde(default)]
+    pub tags: Vec<i32>,
+    pub path: Option<String>,
+    pub quality_profile_id: Option<i32>,
+    pub movie_file: Option<MovieFileResource>,
+    /// When the movie was added to Radarr.
+    pub added: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MovieStatisticsResource {
+    pub size_on_disk: i64,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MovieFileResource {
+    pub id: i32,
+    pub quality: QualityModel,
+    pub media_info: Option<MediaInfoResource>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct QualityModel {
+    pub quality: QualityResource,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct QualityResource {
+    pub name: String,
+    pub resolution: Option<i32>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MediaInfoResource {
+    pub video_codec: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct QualityProfileResource {
+    pub id: i32,
+    pub name: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct TagResource {
+    pub id: i32,
+    pub label: String,
 }
 
 #[derive(Debug, Deserialize, Clone, Copy)]
@@ -21,3 +75,8 @@ pub enum MovieStatus {
     Released,
     Deleted,
 }
+
+#[derive(Debug, Deserialize)]
+pub struct SystemStatusResource {
+    pub version: String,
+}
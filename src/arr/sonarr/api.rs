@@ -1,49 +1,51 @@
 use std::fmt::Debug;
 
 use color_eyre::{eyre::eyre, Result};
+use reqwest::Method;
 use serde::de::DeserializeOwned;
+use serde_json::Value;
 
 use crate::{
     config::{Config, Sonarr},
-    utils::{create_api_error_message, create_param_string},
+    http::{self, ApiError, SendLogged},
+    utils::create_param_string,
 };
 
 pub async fn get<T>(path: &str, params: Option<Vec<(&str, &str)>>, is_4k: bool) -> Result<T>
 where
     T: DeserializeOwned + Debug,
 {
-    let config: &Sonarr;
-    if is_4k {
-        config = match &Config::global().sonarr_4k {
+    let config: &Sonarr = if is_4k {
+        match &Config::global().sonarr_4k {
             Some(sonarr) => sonarr,
             None => {
                 return Err(eyre!(
                     "Tried to access Sonarr config, even though it is not defined."
                 ))
             }
-        };
+        }
     } else {
-        config = match &Config::global().sonarr {
+        match &Config::global().sonarr {
             Some(sonarr) => sonarr,
             None => {
                 return Err(eyre!(
                     "Tried to access Sonarr config, even though it is not defined."
                 ))
             }
-        };
-    }
+        }
+    };
     let client = reqwest::Client::new();
     let params = create_param_string(params);
 
     let response = client
         .get(format!("{}/api/v3{}?{}", config.url, path, params))
         .header("X-Api-Key", &config.api_key)
-        .send()
+        .send_logged("Sonarr")
         .await?;
 
     if !(response.status().as_u16() >= 200 && response.status().as_u16() < 300) {
         let code = response.status().as_u16();
-        return Err(eyre!(create_api_error_message(code, path, "Sonarr")));
+        return Err(ApiError::from_status("Sonarr", path, code).into());
     }
 
     let response = response.json().await?;
@@ -51,8 +53,13 @@ where
     Ok(response)
 }
 
-pub async fn delete(path: &str, params: Option<Vec<(&str, &str)>>) -> Result<()> {
-    let config = match &Config::global().sonarr {
+pub async fn delete(path: &str, params: Option<Vec<(&str, &str)>>, is_4k: bool) -> Result<()> {
+    let instance = if is_4k {
+        &Config::global().sonarr_4k
+    } else {
+        &Config::global().sonarr
+    };
+    let config = match instance {
         Some(sonarr) => sonarr,
         None => {
             return Err(eyre!(
@@ -63,11 +70,61 @@ pub async fn delete(path: &str, params: Option<Vec<(&str, &str)>>) -> Result<()>
     let client = reqwest::Client::new();
     let params = create_param_string(params);
 
-    client
-        .delete(format!("{}/api/v3{}?{}", &config.url, path, params))
-        .header("X-Api-Key", &config.api_key)
-        .send()
-        .await?;
+    let url = format!("{}/api/v3{}?{}", &config.url, path, params);
+
+    http::send_change(Method::DELETE, &url, None, || async {
+        let response = client
+            .delete(&url)
+            .header("X-Api-Key", &config.api_key)
+            .send_logged("Sonarr")
+            .await?;
+
+        if !response.status().is_success() {
+            let code = response.status().as_u16();
+            return Err(ApiError::from_status("Sonarr", path, code).into());
+        }
+
+        Ok(())
+    })
+    .await?;
+
+    Ok(())
+}
+
+pub async fn put(path: &str, body: &Value, is_4k: bool) -> Result<()> {
+    let instance = if is_4k {
+        &Config::global().sonarr_4k
+    } else {
+        &Config::global().sonarr
+    };
+    let config = match instance {
+        Some(sonarr) => sonarr,
+        None => {
+            return Err(eyre!(
+                "Tried to access Sonarr config, even though it is not defined."
+            ))
+        }
+    };
+    let client = reqwest::Client::new();
+
+    let url = format!("{}/api/v3{}", &config.url, path);
+
+    http::send_change(Method::PUT, &url, Some(body), || async {
+        let response = client
+            .put(&url)
+            .header("X-Api-Key", &config.api_key)
+            .json(body)
+            .send_logged("Sonarr")
+            .await?;
+
+        if !response.status().is_success() {
+            let code = response.status().as_u16();
+            return Err(ApiError::from_status("Sonarr", path, code).into());
+        }
+
+        Ok(())
+    })
+    .await?;
 
     Ok(())
 }
@@ -2,17 +2,177 @@ mod api;
 mod responses;
 
 use color_eyre::Result;
+use serde_json::Value;
 
-use self::responses::SeriesResource;
-pub use self::responses::SeriesStatus;
+pub use self::responses::EpisodeFileResource;
+use self::responses::{QualityProfileResource, SystemStatusResource, TagResource};
+pub use self::responses::{SeriesResource, SeriesStatus};
+use crate::config::{Config, Sonarr};
 
 pub async fn get_sonarr_data(id: i32, is_4k: bool) -> Result<SeriesResource> {
-    let path = format!("/series/{}", id.to_string());
+    let path = format!("/series/{}", id);
     api::get(&path, None, is_4k).await
 }
 
-pub async fn remove_sonarr_data_and_files(sonarr_id: i32) -> Result<()> {
-    let path = format!("/series/{}", sonarr_id.to_string());
-    let params = vec![("deleteFiles", "true"), ("addImportListExclusion", "false")];
-    api::delete(path.as_str(), Some(params)).await
+/// Gets every series in Sonarr.
+pub async fn get_all_sonarr_series(is_4k: bool) -> Result<Vec<SeriesResource>> {
+    api::get("/series", None, is_4k).await
+}
+
+/// Gets the version of Sonarr, which also checks it can be reached with the API key.
+pub async fn get_sonarr_version(is_4k: bool) -> Result<String> {
+    let status: SystemStatusResource = api::get("/system/status", None, is_4k).await?;
+    Ok(status.version)
+}
+
+/// Gets the ids and names of all quality profiles.
+pub async fn get_sonarr_quality_profiles(is_4k: bool) -> Result<Vec<(i32, String)>> {
+    let profiles: Vec<QualityProfileResource> = api::get("/qualityprofile", None, is_4k).await?;
+    Ok(profiles
+        .into_iter()
+        .map(|profile| (profile.id, profile.name))
+        .collect())
+}
+
+/// Gets all episode files of a series.
+pub async fn get_episode_files(sonarr_id: i32, is_4k: bool) -> Result<Vec<EpisodeFileResource>> {
+    let series_id = sonarr_id.to_string();
+    api::get("/episodefile", Some(vec![("seriesId", &series_id)]), is_4k).await
+}
+
+/// Gets the ids and names of all tags.
+pub async fn get_sonarr_tags(is_4k: bool) -> Result<Vec<(i32, String)>> {
+    let tags: Vec<TagResource> = api::get("/tag", None, is_4k).await?;
+    Ok(tags.into_iter().map(|tag| (tag.id, tag.label)).collect())
+}
+
+/// Looks a series up by its TVDB id, for when Overseerr doesn't know its Sonarr id.
+pub async fn find_sonarr_series(tvdb_id: u32, is_4k: bool) -> Result<Option<SeriesResource>> {
+    let tvdb_id = tvdb_id.to_string();
+    let series: Vec<SeriesResource> =
+        api::get("/series", Some(vec![("tvdbId", &tvdb_id)]), is_4k).await?;
+
+    Ok(series.into_iter().next())
+}
+
+/// Deletes the series, and its files and an import list exclusion as set in the config.
+/// With `block`, the exclusion is always added.
+pub async fn remove_sonarr_data_and_files(
+    sonarr_id: i32,
+    is_4k: bool,
+    block: bool,
+    keep_files: bool,
+) -> Result<()> {
+    let (delete_files, add_import_list_exclusion) = match sonarr_config(is_4k) {
+        Some(sonarr) => (sonarr.delete_files, sonarr.add_import_list_exclusion),
+        None => (true, false),
+    };
+
+    let path = format!("/series/{}", sonarr_id);
+    let delete_files = (delete_files && !keep_files).to_string();
+    let add_import_list_exclusion = (add_import_list_exclusion || block).to_string();
+    let params = vec![
+        ("deleteFiles", delete_files.as_str()),
+        ("addImportListExclusion", add_import_list_exclusion.as_str()),
+    ];
+    api::delete(path.as_str(), Some(params), is_4k).await
+}
+
+/// Stops Sonarr from monitoring the series, so it isn't upgraded or grabbed again. The seasons
+/// are unmonitored as well if set in the config, or only the given seasons if there are any.
+pub async fn unmonitor_sonarr_series(
+    sonarr_id: i32,
+    seasons: Option<&[u32]>,
+    is_4k: bool,
+) -> Result<()> {
+    let unmonitor_seasons = sonarr_config(is_4k).is_none_or(|sonarr| sonarr.unmonitor_seasons);
+
+    let path = format!("/series/{}", sonarr_id);
+    let mut series: Value = api::get(&path, None, is_4k).await?;
+    if seasons.is_none() {
+        series["monitored"] = Value::Bool(false);
+    }
+    if let Some(series_seasons) = series.get_mut("seasons").and_then(Value::as_array_mut) {
+        for season in series_seasons.iter_mut() {
+            let season_number = season.get("seasonNumber").and_then(Value::as_u64);
+            let unmonitor = match seasons {
+                Some(seasons) => {
+                    season_number.is_some_and(|number| seasons.contains(&(number as u32)))
+                }
+                None => unmonitor_seasons,
+            };
+            if unmonitor {
+                season["monitored"] = Value::Bool(false);
+            }
+        }
+    }
+
+    api::put(&path, &series, is_4k).await
+}
+
+/// Deletes all episode files of the series and stops monitoring it, keeping the series
+/// in Sonarr so it can be grabbed again later.
+pub async fn remove_sonarr_files(sonarr_id: i32, is_4k: bool) -> Result<()> {
+    unmonitor_sonarr_series(sonarr_id, None, is_4k).await?;
+
+    for episode_file in get_episode_files(sonarr_id, is_4k).await? {
+        let path = format!("/episodefile/{}", episode_file.id);
+        api::delete(&path, None, is_4k).await?;
+    }
+
+    Ok(())
+}
+
+pub fn sonarr_config(is_4k: bool) -> Option<&'static Sonarr> {
+    if is_4k {
+        Config::global().sonarr_4k.as_ref()
+    } else {
+        Config::global().sonarr.as_ref()
+    }
+}
+
+/// Removes the files of some seasons of a series, and stops monitoring those seasons
+/// so Sonarr doesn't download them again. The rest of the series is left alone.
+pub async fn remove_sonarr_seasons(sonarr_id: i32, seasons: &[u32], is_4k: bool) -> Result<()> {
+    let path = format!("/series/{}", sonarr_id);
+    let mut series: Value = api::get(&path, None, is_4k).await?;
+    if let Some(series_seasons) = series.get_mut("seasons").and_then(Value::as_array_mut) {
+        for season in series_seasons.iter_mut() {
+            let season_number = season.get("seasonNumber").and_then(Value::as_u64);
+            if season_number.is_some_and(|number| seasons.contains(&(number as u32))) {
+                season["monitored"] = Value::Bool(false);
+            }
+        }
+    }
+    api::put(&path, &series, is_4k).await?;
+
+    for episode_file in get_season_episode_files(sonarr_id, seasons, is_4k).await? {
+        let path = format!("/episodefile/{}", episode_file.id);
+        api::delete(&path, None, is_4k).await?;
+    }
+
+    Ok(())
+}
+
+/// Counts the episode files Sonarr still has of the given seasons.
+pub async fn count_season_episode_files(
+    sonarr_id: i32,
+    seasons: &[u32],
+    is_4k: bool,
+) -> Result<usize> {
+    Ok(get_season_episode_files(sonarr_id, seasons, is_4k)
+        .await?
+        .len())
+}
+
+async fn get_season_episode_files(
+    sonarr_id: i32,
+    seasons: &[u32],
+    is_4k: bool,
+) -> Result<Vec<EpisodeFileResource>> {
+    Ok(get_episode_files(sonarr_id, is_4k)
+        .await?
+        .into_iter()
+        .filter(|file| seasons.contains(&file.season_number))
+        .collect())
 }
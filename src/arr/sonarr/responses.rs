@@ -4,12 +4,22 @@ use serde::Deserialize;
 #[serde(rename_all = "camelCase")]
 pub struct SeriesResource {
     pub id: i32,
-    pub title: Option<String>,
+    pub tvdb_id: Option<u32>,
+    pub title: String,
+    pub year: Option<i32>,
     pub status: SeriesStatus,
     pub previous_airing: Option<String>,
     pub next_airing: Option<String>,
+    /// Sonarr 4 leaves the statistics out in some responses.
+    #[serde(default)]
     pub statistics: SeriesStatisticsResource,
     pub seasons: Vec<SeasonResource>,
+    #[serde(default)]
+    pub tags: Vec<i32>,
+    pub path: Option<String>,
+    pub quality_profile_id: Option<i32>,
+    /// When the series was added to Sonarr.
+    pub added: Option<String>,
 }
 
 #[derive(Debug, Deserialize, Clone, Copy)]
@@ -21,11 +31,13 @@ pub enum SeriesStatus {
     Deleted,
 }
 
-#[derive(Debug, Deserialize, Clone)]
-#[serde(rename_all = "camelCase")]
+#[derive(Debug, Default, Deserialize, Clone)]
+#[serde(rename_all = "camelCase", default)]
 pub struct SeriesStatisticsResource {
     pub season_count: i32,
+    #[allow(dead_code)]
     pub episode_file_count: i32,
+    #[allow(dead_code)]
     pub episode_count: i32,
     pub size_on_disk: i64,
     pub percent_of_episodes: f64,
@@ -35,11 +47,59 @@ pub struct SeriesStatisticsResource {
 #[serde(rename_all = "camelCase")]
 pub struct SeasonResource {
     pub season_number: i32,
+    /// Left out for seasons without episodes.
+    #[serde(default)]
     pub statistics: SeasonStatisticsResource,
 }
 
-#[derive(Debug, Deserialize, Clone)]
+#[derive(Debug, Default, Deserialize, Clone)]
 #[serde(rename_all = "camelCase")]
 pub struct SeasonStatisticsResource {
+    #[serde(default)]
     pub episode_count: i32,
+    #[serde(default)]
+    pub size_on_disk: i64,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct EpisodeFileResource {
+    pub id: i32,
+    pub season_number: u32,
+    pub quality: Option<QualityModel>,
+    pub media_info: Option<MediaInfoResource>,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct QualityModel {
+    pub quality: QualityResource,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct QualityResource {
+    pub name: String,
+    pub resolution: Option<i32>,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct MediaInfoResource {
+    pub video_codec: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct QualityProfileResource {
+    pub id: i32,
+    pub name: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct TagResource {
+    pub id: i32,
+    pub label: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SystemStatusResource {
+    pub version: String,
 }
@@ -0,0 +1,261 @@
+use chrono::{DateTime, Utc};
+use color_eyre::{eyre::eyre, Result};
+use serde::Serialize;
+use std::fs;
+
+use crate::{
+    arguments::Arguments,
+    config::Config,
+    http,
+    media_item::CompleteMediaItem,
+    report::{HandledItem, RunReport},
+    shared::{Action, MediaScope, MediaType},
+    utils::human_file_size,
+};
+
+/// The file --auto writes what it did to, next to the config, unless --report is passed.
+const REPORT_FILE: &str = "auto_report.json";
+
+/// What --auto did, written for scripts and monitoring.
+#[derive(Debug, Serialize)]
+struct Report<'a> {
+    finished_at: DateTime<Utc>,
+    dry_run: bool,
+    /// The media types the run looked at, from --media-type or `rules.media_type`.
+    media_types: Vec<MediaType>,
+    action: Action,
+    /// How many items the rules allowed, before --limit and --max-bytes.
+    eligible: usize,
+    freed_bytes: i64,
+    items: &'a [HandledItem],
+    summary: &'a RunReport,
+}
+
+/// Makes sure something keeps media from being removed before anything is removed unattended,
+/// so a config with only the services in it can't empty the libraries.
+pub fn check_protective_rules() -> Result<()> {
+    if protective_rules().is_empty() {
+        return Err(eyre!(
+            "--auto removes media without asking, so at least one rule has to protect media first. \
+            Set rules.keep_days_after_watch, rules.keep_days_after_added, rules.protected_requesters, \
+            rules.protected_watchers, exclude_tags, protected_collections, protected_labels or a Plex watchlist, \
+            min_days_since_watch, or pass --min-days-since-watch."
+        ));
+    }
+
+    message!(
+        "Running without asking, protected by {}.",
+        protective_rules().join(", ")
+    );
+    Ok(())
+}
+
+/// The settings that keep media from being removed.
+fn protective_rules() -> Vec<&'static str> {
+    let config = Config::global();
+    let args = Arguments::get_args();
+    let plex = config.plex.as_ref();
+    let has_exclude_tags = [&config.radarr, &config.radarr_4k]
+        .into_iter()
+        .flatten()
+        .any(|radarr| !radarr.exclude_tags.is_empty())
+        || [&config.sonarr, &config.sonarr_4k]
+            .into_iter()
+            .flatten()
+            .any(|sonarr| !sonarr.exclude_tags.is_empty());
+
+    [
+        (
+            args.keep_days_after_watch
+                .or(config.rules.keep_days_after_watch)
+                .is_some(),
+            "keep_days_after_watch",
+        ),
+        (
+            args.keep_days_after_added
+                .or(config.rules.keep_days_after_added)
+                .is_some(),
+            "keep_days_after_added",
+        ),
+        (
+            args.min_days_since_watch
+                .or(config.min_days_since_watch)
+                .is_some(),
+            "min_days_since_watch",
+        ),
+        (config.rules.protects_users(), "protected users"),
+        (has_exclude_tags, "exclude_tags"),
+        (
+            plex.is_some_and(|plex| !plex.protected_collections.is_empty()),
+            "protected_collections",
+        ),
+        (
+            plex.is_some_and(|plex| !plex.protected_labels.is_empty()),
+            "protected_labels",
+        ),
+        (
+            plex.is_some_and(|plex| plex.watchlist.is_some()),
+            "Plex watchlists",
+        ),
+    ]
+    .into_iter()
+    .filter_map(|(set, rule)| set.then_some(rule))
+    .collect()
+}
+
+/// The items everyone who watched them has finished, which is all the rules let through,
+/// taken in order until --limit or --max-bytes is reached. They get the --action,
+/// or are deleted.
+pub fn choose_items(
+    items: &[CompleteMediaItem],
+    run_report: &mut RunReport,
+) -> Vec<(usize, Action)> {
+    let action = Arguments::get_args().action.unwrap_or(Action::Delete);
+    let eligible = eligible_items(items);
+
+    let count = within_limits(eligible.iter().map(|(_, item)| item.get_disk_size()));
+    run_report.limit(eligible.len(), count);
+    let size: i64 = eligible[..count]
+        .iter()
+        .map(|(_, item)| item.get_disk_size())
+        .sum();
+    let chosen: Vec<(usize, Action)> = eligible[..count]
+        .iter()
+        .map(|(selection, _)| (*selection, action))
+        .collect();
+
+    message!(
+        "{} of the {} items were finished by everyone who watched them, choosing {} of them ({}) to {}.",
+        eligible.len(),
+        items.len(),
+        chosen.len(),
+        human_file_size(size),
+        action.to_string().to_lowercase()
+    );
+
+    chosen
+}
+
+/// How many of the items with these sizes are taken, in order, before there are --limit items
+/// or their total reaches --max-bytes. The last item taken can take the total over it.
+pub fn within_limits(sizes: impl Iterator<Item = i64>) -> usize {
+    let args = Arguments::get_args();
+    count_within(sizes, args.limit, args.max_bytes)
+}
+
+fn count_within(
+    sizes: impl Iterator<Item = i64>,
+    limit: Option<usize>,
+    max_bytes: Option<i64>,
+) -> usize {
+    let mut count = 0;
+    let mut total = 0;
+    for size in sizes {
+        if limit.is_some_and(|limit| count >= limit) || max_bytes.is_some_and(|max| total >= max) {
+            break;
+        }
+        count += 1;
+        total += size;
+    }
+
+    count
+}
+
+/// How many items the rules let through.
+pub fn eligible_count(items: &[CompleteMediaItem]) -> usize {
+    eligible_items(items).len()
+}
+
+fn eligible_items(items: &[CompleteMediaItem]) -> Vec<(usize, &CompleteMediaItem)> {
+    items
+        .iter()
+        .enumerate()
+        .filter(|(_, item)| item.watched_by_all())
+        .collect()
+}
+
+/// Writes what was done to the --report file, or the report file next to the config.
+pub fn write_report(eligible: usize, handled: &[HandledItem], summary: &RunReport) -> Result<()> {
+    let report = Report {
+        finished_at: Utc::now(),
+        dry_run: http::is_dry_run(),
+        media_types: MediaScope::of_run().media_types(),
+        action: Arguments::get_args().action.unwrap_or(Action::Delete),
+        eligible,
+        freed_bytes: handled.iter().map(|item| item.freed_bytes).sum(),
+        items: handled,
+        summary,
+    };
+
+    let path = Arguments::get_args()
+        .report_file
+        .clone()
+        .unwrap_or_else(|| Config::file_next_to_config(REPORT_FILE));
+    fs::write(&path, serde_json::to_string_pretty(&report)?)
+        .map_err(|err| eyre!("Failed to write the report to {}: {}", path.display(), err))?;
+    message!("Wrote what was done to {}.", path.display());
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{test_support, utils::parse_file_size};
+
+    const SIZES: [i64; 4] = [100, 200, 300, 50];
+
+    #[test]
+    fn takes_every_item_without_limits() {
+        test_support::init();
+        assert_eq!(within_limits(SIZES.into_iter()), 4);
+        assert_eq!(count_within(SIZES.into_iter(), None, None), 4);
+    }
+
+    #[test]
+    fn stops_at_the_limit() {
+        assert_eq!(count_within(SIZES.into_iter(), Some(2), None), 2);
+        assert_eq!(count_within(SIZES.into_iter(), Some(0), None), 0);
+        assert_eq!(count_within(SIZES.into_iter(), Some(10), None), 4);
+    }
+
+    #[test]
+    fn stops_once_the_max_bytes_are_reached() {
+        assert_eq!(count_within(SIZES.into_iter(), None, Some(300)), 2);
+        assert_eq!(count_within(SIZES.into_iter(), None, Some(301)), 3);
+        // The item that reaches the target is taken, even when it goes over it.
+        assert_eq!(count_within(SIZES.into_iter(), None, Some(250)), 2);
+        assert_eq!(count_within(SIZES.into_iter(), None, Some(50)), 1);
+        assert_eq!(count_within(SIZES.into_iter(), None, Some(10_000)), 4);
+    }
+
+    #[test]
+    fn stops_at_whichever_limit_comes_first() {
+        assert_eq!(count_within(SIZES.into_iter(), Some(1), Some(1000)), 1);
+        assert_eq!(count_within(SIZES.into_iter(), Some(3), Some(300)), 2);
+        assert_eq!(count_within(SIZES.into_iter(), Some(2), Some(350)), 2);
+    }
+
+    #[test]
+    fn takes_the_max_bytes_in_decimal_or_binary_units() {
+        let max_bytes = parse_file_size("500GB");
+        assert_eq!(max_bytes, Some(500_000_000_000));
+        assert_eq!(parse_file_size("1.5TiB"), Some(1_649_267_441_664));
+        assert_eq!(parse_file_size("500 gigs"), None);
+
+        let sizes = [200_000_000_000, 300_000_000_000, 1];
+        assert_eq!(count_within(sizes.into_iter(), None, max_bytes), 2);
+    }
+
+    #[test]
+    fn the_summary_counts_the_items_left_for_later() {
+        test_support::init();
+        let mut report = RunReport::start();
+        report.limit(4, 4);
+        assert!(report.limited.is_none());
+
+        report.limit(4, 1);
+        let limited = report.limited.unwrap();
+        assert_eq!((limited.eligible, limited.unprocessed), (4, 3));
+    }
+}
@@ -1,51 +1,471 @@
-use color_eyre::Result;
-use once_cell::sync::OnceCell;
-use serde::Deserialize;
-use std::fs;
+use chrono_tz::Tz;
+use color_eyre::{eyre::eyre, owo_colors::OwoColorize, Result};
+use itertools::Itertools;
+use once_cell::sync::{Lazy, OnceCell};
+use regex::Regex;
+use reqwest::Url;
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+use serde_yaml::{Mapping, Value};
+use std::{
+    collections::{BTreeMap, HashMap},
+    env, fs,
+    path::{Path, PathBuf},
+};
+
+use crate::{arguments::Arguments, overseerr::MediaStatus, shared::MediaScope, title_filter, toml};
 
 static INSTANCE: OnceCell<Config> = OnceCell::new();
-#[derive(Debug, Deserialize)]
+/// The names of the config file, in each of the places it is looked for. The first is
+/// the one new configs are written to, the YAML one is still read for older configs.
+const CONFIG_FILES: [&str; 2] = ["config.toml", "config.yaml"];
+/// The folder in the platform's config directory the config is looked for in.
+const CONFIG_DIR: &str = "media-cleaner";
+/// The start of the environment variables that override settings, like `MEDIA_CLEANER__TAUTULLI__API_KEY`.
+const ENV_PREFIX: &str = "MEDIA_CLEANER__";
+/// The settings that are hidden when the config is shown, and can be read from a file
+/// with the same name ending in `_file`.
+const SECRET_SETTINGS: [&str; 3] = ["api_key", "token", "client_secret"];
+/// The settings that are one instance or a list of them, like a Radarr and a 4K Radarr.
+const INSTANCE_LISTS: [&str; 2] = ["radarr", "sonarr"];
+/// The settings that are lists, which an environment variable sets with the items separated by
+/// commas. Written without the index of an instance or the profile they are in.
+const LIST_SETTINGS: [&str; 20] = [
+    "ignored_users",
+    "libraries.include",
+    "libraries.exclude",
+    "rules.protected_requesters",
+    "rules.protected_watchers",
+    "rules.exclude_titles",
+    "plex.protected_collections",
+    "plex.protected_labels",
+    "overseerr.statuses",
+    "tautulli.exclude_users",
+    "tautulli.include_users",
+    "jellyfin.exclude_users",
+    "jellyfin.include_users",
+    "emby.exclude_users",
+    "emby.include_users",
+    "trakt.accounts",
+    "radarr.exclude_tags",
+    "radarr_4k.exclude_tags",
+    "sonarr.exclude_tags",
+    "sonarr_4k.exclude_tags",
+];
+/// The version of the config, raised when a setting is renamed.
+pub const CONFIG_VERSION: u64 = 2;
+/// The settings that were renamed, with the last version that had the old name.
+const RENAMED_SETTINGS: [(u64, &str, &str); 1] =
+    [(1, "watched_threshold", "rules.watched_threshold")];
+/// The settings a profile can change, the ones about what is shown.
+const PROFILE_SETTINGS: [&str; 5] = [
+    "rules",
+    "libraries",
+    "min_request_age_days",
+    "min_days_since_watch",
+    "ignored_users",
+];
+static INDEXED_SEGMENT: Lazy<Regex> = Lazy::new(|| Regex::new(r"^(.+)_(\d+)$").unwrap());
+static UNKNOWN_FIELD: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"^unknown (?:field|variant) `([^`]*)`, expected (.*)$").unwrap());
+static MISSING_FIELD: Lazy<Regex> = Lazy::new(|| Regex::new(r"^missing field `([^`]*)`$").unwrap());
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
 pub struct Config {
+    /// The version of the config the file was written for.
+    #[serde(default = "default_version")]
+    pub version: u64,
     #[serde(default = "default_items_shown")]
     pub items_shown: usize,
-    pub plex: Plex,
-    pub overseerr: Overseerr,
-    pub tautulli: Tautulli,
+    #[serde(default = "default_min_request_age_days")]
+    pub min_request_age_days: i64,
+    /// Only media nobody watched in the last this many days is shown, off if not set.
+    pub min_days_since_watch: Option<i64>,
+    /// Only used when the watch history comes from Tautulli.
+    pub plex: Option<Plex>,
+    /// Which request manager the `overseerr` settings are for.
+    #[serde(default)]
+    pub request_provider: RequestProvider,
+    /// Where the candidates come from. Without it, everything Radarr and Sonarr manage is a candidate.
+    pub overseerr: Option<Overseerr>,
+    /// Where the watch history comes from.
+    #[serde(default)]
+    pub watch_provider: WatchProviderType,
+    pub tautulli: Option<Tautulli>,
+    pub jellyfin: Option<Jellyfin>,
+    /// Emby has the same API as Jellyfin, so it has the same settings.
+    pub emby: Option<Jellyfin>,
+    /// Adds who watched the media on Trakt, off if not set.
+    pub trakt: Option<Trakt>,
+    /// One Sonarr, or a list of a Sonarr and a 4K Sonarr. They are split into `sonarr` and `sonarr_4k`.
+    #[serde(
+        rename = "sonarr",
+        default,
+        deserialize_with = "deserialize_sonarr",
+        skip_serializing
+    )]
+    sonarr_instances: Vec<Sonarr>,
+    #[serde(skip_deserializing)]
     pub sonarr: Option<Sonarr>,
     pub sonarr_4k: Option<Sonarr>,
+    /// One Radarr, or a list of a Radarr and a 4K Radarr. They are split into `radarr` and `radarr_4k`.
+    #[serde(
+        rename = "radarr",
+        default,
+        deserialize_with = "deserialize_radarr",
+        skip_serializing
+    )]
+    radarr_instances: Vec<Radarr>,
+    #[serde(skip_deserializing)]
     pub radarr: Option<Radarr>,
     pub radarr_4k: Option<Radarr>,
     pub ignored_users: Option<Vec<String>>,
+    #[serde(default)]
+    pub display: Display,
+    /// Checks the folders of removed media are gone, for when this runs on the same machine.
+    #[serde(default)]
+    pub check_deleted_paths: bool,
+    /// Moves the files of removed media into a folder instead of deleting them, off if not set.
+    pub trash: Option<Trash>,
+    #[serde(default)]
+    pub libraries: Libraries,
+    #[serde(default)]
+    pub rules: Rules,
+    /// Named sets of settings that replace the ones above when chosen with --profile.
+    #[serde(default, skip_serializing)]
+    profiles: BTreeMap<String, Value>,
+    /// The profile that was chosen with --profile.
+    #[serde(skip)]
+    pub profile: Option<String>,
+    /// The settings that were set by environment variables, shown with --show-config.
+    #[serde(skip)]
+    env_overrides: Vec<String>,
+    /// The files secrets were read from, by the path of their setting like `tautulli.api_key`,
+    /// so --show-config can say where they came from.
+    #[serde(skip)]
+    secret_files: HashMap<String, PathBuf>,
+    /// Where the config was read from.
+    #[serde(skip)]
+    path: PathBuf,
+}
+
+/// Where removed media is moved to, so it can be restored until it is purged.
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct Trash {
+    pub path: PathBuf,
+    /// How the paths Radarr and Sonarr know are reached from here, when the media is mounted elsewhere.
+    #[serde(default)]
+    pub path_mappings: Vec<PathMapping>,
+}
+
+/// Replaces the start of a path in Radarr or Sonarr with where it is on this machine.
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct PathMapping {
+    pub from: String,
+    pub to: String,
 }
 
-#[derive(Debug, Deserialize)]
+/// The Plex library sections to clean up, by name or id. All of them if `include` is empty.
+#[derive(Debug, Default, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct Libraries {
+    #[serde(default)]
+    pub include: Vec<String>,
+    #[serde(default)]
+    pub exclude: Vec<String>,
+}
+
+/// The users whose media is never shown, matched by any of their names on the request manager
+/// or watch provider (username, display or friendly name, or email) without regard to case.
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct Rules {
+    /// How far (in percent) a movie, episode or track has to be played to count as watched.
+    #[serde(default = "default_watched_threshold")]
+    pub watched_threshold: u8,
+    /// Media requested by any of these users is never shown.
+    #[serde(default)]
+    pub protected_requesters: Vec<String>,
+    /// Media watched by any of these users is never shown.
+    #[serde(default)]
+    pub protected_watchers: Vec<String>,
+    /// Only the watches of the protected watchers in the last this many days count, all of them if it isn't set.
+    pub protected_watch_days: Option<u32>,
+    /// Media anyone watched in the last this many days is never shown.
+    pub keep_days_after_watch: Option<u32>,
+    /// Media added to the server in the last this many days is never shown.
+    pub keep_days_after_added: Option<u32>,
+    /// Only this media is cleaned up, unless --media-type says otherwise.
+    #[serde(default)]
+    pub media_type: MediaScope,
+    /// Media whose "Title (Year)" matches any of these regexes is never shown, regardless of case.
+    #[serde(default)]
+    pub exclude_titles: Vec<String>,
+}
+
+impl Default for Rules {
+    fn default() -> Self {
+        Self {
+            watched_threshold: default_watched_threshold(),
+            protected_requesters: Vec::new(),
+            protected_watchers: Vec::new(),
+            protected_watch_days: None,
+            keep_days_after_watch: None,
+            keep_days_after_added: None,
+            media_type: MediaScope::All,
+            exclude_titles: Vec::new(),
+        }
+    }
+}
+
+impl Rules {
+    pub fn protects_users(&self) -> bool {
+        !self.protected_requesters.is_empty() || !self.protected_watchers.is_empty()
+    }
+}
+
+#[derive(Debug, Default, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct Display {
+    /// The IANA timezone dates are shown in, the system timezone is used if it is not set.
+    pub timezone: Option<Tz>,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
 pub struct Plex {
     pub url: String,
     pub token: String,
+    /// Deletes media straight from Plex when Radarr and Sonarr don't have it. This can't be undone.
+    #[serde(default)]
+    pub allow_delete: bool,
+    /// Media in any of these collections is never shown, matched without regard to case.
+    #[serde(default)]
+    pub protected_collections: Vec<String>,
+    /// Media with any of these labels is never shown, matched without regard to case.
+    #[serde(default)]
+    pub protected_labels: Vec<String>,
+    /// Scans the library sections media was removed from after the run.
+    #[serde(default)]
+    pub refresh_after_delete: bool,
+    /// Checks the watchlists of these plex.tv accounts, off if not set.
+    pub watchlist: Option<Watchlist>,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct Watchlist {
+    pub accounts: Vec<WatchlistAccount>,
+    #[serde(default)]
+    pub action: WatchlistAction,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct WatchlistAccount {
+    /// The name the account is shown with.
+    pub name: String,
+    /// The plex.tv token of the account, the watchlist is only available through plex.tv.
+    pub token: String,
+}
+
+/// What happens to media on a watchlist.
+#[derive(Debug, Default, Deserialize, Serialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum WatchlistAction {
+    /// Leaves it out, like media in a protected collection.
+    #[default]
+    Skip,
+    /// Shows it with whose watchlists it is on, to decide yourself.
+    Annotate,
+}
+
+/// The request managers that can be used, Jellyseerr is a fork of Overseerr with the same API.
+#[derive(Debug, Default, Deserialize, Serialize, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+pub enum RequestProvider {
+    #[default]
+    Overseerr,
+    Jellyseerr,
 }
 
-#[derive(Debug, Deserialize)]
+impl std::fmt::Display for RequestProvider {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Overseerr => write!(f, "Overseerr"),
+            Self::Jellyseerr => write!(f, "Jellyseerr"),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
 pub struct Overseerr {
     pub url: String,
     pub api_key: String,
+    #[serde(
+        default,
+        deserialize_with = "deserialize_statuses",
+        serialize_with = "serialize_statuses"
+    )]
+    pub statuses: Option<Vec<MediaStatus>>,
+    #[serde(default)]
+    pub cleanup: Cleanup,
+    /// Adds removed media to Overseerr's blacklist, so it isn't requested again right away.
+    #[serde(default)]
+    pub blacklist_after_delete: bool,
+    /// Runs Overseerr's availability sync after removing media, so it doesn't wait for the next scan.
+    #[serde(default)]
+    pub trigger_sync: bool,
+    /// The most requests or media to get from Overseerr, mostly useful for testing.
+    pub limit: Option<usize>,
+    /// Leaves out items with open issues when not running interactively.
+    #[serde(default)]
+    pub skip_items_with_issues: bool,
+    #[serde(default = "default_retry_attempts")]
+    pub retry_attempts: u32,
+    #[serde(default = "default_retry_delay_ms")]
+    pub retry_delay_ms: u64,
 }
 
-#[derive(Debug, Deserialize)]
+/// What to do with the Overseerr request once the media is removed.
+#[derive(Debug, Default, Deserialize, Serialize, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+pub enum Cleanup {
+    Delete,
+    #[default]
+    MarkUnavailable,
+    None,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
 pub struct Tautulli {
     pub url: String,
     pub api_key: String,
+    pub exclude_users: Option<Vec<String>>,
+    pub include_users: Option<Vec<String>>,
+    #[serde(default = "default_retry_attempts")]
+    pub retry_attempts: u32,
+    #[serde(default = "default_retry_delay_ms")]
+    pub retry_delay_ms: u64,
+    #[serde(default = "default_timeout_seconds")]
+    pub timeout_seconds: u64,
+    #[serde(default = "default_concurrency")]
+    pub concurrency: usize,
+    #[serde(default = "default_requests_per_second")]
+    pub requests_per_second: f64,
+    #[serde(default = "default_verify_tls")]
+    pub verify_tls: bool,
+    pub ca_cert_path: Option<PathBuf>,
+    /// Gets the file details of movies Radarr doesn't have from Plex, through Tautulli.
+    #[serde(default)]
+    pub media_details: bool,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
 pub struct Sonarr {
+    /// The name the instance is shown with.
+    pub name: Option<String>,
     pub api_key: String,
     pub url: String,
+    #[serde(default)]
+    pub is_4k: bool,
+    #[serde(default = "default_delete_files")]
+    pub delete_files: bool,
+    /// Keeps Sonarr from adding the series again through an import list.
+    #[serde(default)]
+    pub add_import_list_exclusion: bool,
+    #[serde(default)]
+    pub series_without_files: SeriesWithoutFiles,
+    /// Unmonitors all seasons too, when unmonitoring a series.
+    #[serde(default = "default_unmonitor_seasons")]
+    pub unmonitor_seasons: bool,
+    /// Series with any of these tags are never shown.
+    #[serde(default)]
+    pub exclude_tags: Vec<String>,
+}
+
+/// What to do with a series that is in Sonarr, but has no files.
+#[derive(Debug, Default, Deserialize, Serialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum SeriesWithoutFiles {
+    /// Deletes the series from Sonarr anyway.
+    #[default]
+    Delete,
+    /// Leaves the series in Sonarr.
+    Skip,
+}
+
+/// The services the watch history can come from.
+#[derive(Debug, Default, Deserialize, Serialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum WatchProviderType {
+    /// Tautulli, for Plex.
+    #[default]
+    Tautulli,
+    Jellyfin,
+    Emby,
+}
+
+impl std::fmt::Display for WatchProviderType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Tautulli => write!(f, "tautulli"),
+            Self::Jellyfin => write!(f, "jellyfin"),
+            Self::Emby => write!(f, "emby"),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct Jellyfin {
+    pub url: String,
+    pub api_key: String,
+    pub exclude_users: Option<Vec<String>>,
+    pub include_users: Option<Vec<String>>,
+    #[serde(default = "default_retry_attempts")]
+    pub retry_attempts: u32,
+    #[serde(default = "default_retry_delay_ms")]
+    pub retry_delay_ms: u64,
+    /// How many items to get the watch history of at the same time.
+    #[serde(default = "default_concurrency")]
+    pub concurrency: usize,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct Trakt {
+    /// The id and secret of the Trakt API app.
+    pub client_id: String,
+    pub client_secret: String,
+    /// The names of the accounts to check, each is connected once with --trakt-login.
+    pub accounts: Vec<String>,
+    /// Leaves out media watched on Trakt when not running interactively.
+    #[serde(default)]
+    pub skip_watched_when_unattended: bool,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
 pub struct Radarr {
+    /// The name the instance is shown with.
+    pub name: Option<String>,
     pub api_key: String,
     pub url: String,
+    #[serde(default)]
+    pub is_4k: bool,
+    #[serde(default = "default_delete_files")]
+    pub delete_files: bool,
+    /// Keeps Radarr from adding the movie again through an import list.
+    #[serde(default)]
+    pub add_import_exclusion: bool,
+    /// Movies with any of these tags are never shown.
+    #[serde(default)]
+    pub exclude_tags: Vec<String>,
 }
 
 impl Config {
@@ -54,14 +474,55 @@ impl Config {
     }
 
     pub fn read_conf() -> Result<()> {
-        if let Some(_) = INSTANCE.get() {
+        if INSTANCE.get().is_some() {
             return Ok(());
         }
 
-        let reader = fs::File::open("config.yaml")?;
-        let mut conf: Config = serde_yaml::from_reader(reader)?;
+        let path = find_config()?;
+        let text = fs::read_to_string(&path)
+            .map_err(|err| eyre!("{} could not be opened: {}", path.display(), err))?;
+        message!("Using the config at {}.", path.display());
+        let overrides = env::vars()
+            .filter(|(name, _)| name.starts_with(ENV_PREFIX))
+            .sorted()
+            .collect_vec();
 
+        let mut value = parse_config(&path, &text)?;
+        let migrated = migrate(&mut value)?;
+        if !migrated.is_empty() {
+            message!(
+                "{}",
+                format!(
+                    "The config is for an older version of media-cleaner, so these settings were read from their old names:\n- {}\nRun `media-cleaner config migrate` to update the file.",
+                    migrated.join("\n- ")
+                )
+                .yellow()
+            );
+        }
+        let profile = apply_profile(&mut value, Arguments::get_args().profile.as_deref())?;
+        let overridden = apply_env_overrides(&mut value, overrides)?;
+        let secret_files = read_secret_files(&mut value, "")?;
+        let changed = !migrated.is_empty()
+            || profile.is_some()
+            || !overridden.is_empty()
+            || !secret_files.is_empty();
+
+        let mut conf: Config = if !changed && !is_toml(&path) {
+            serde_yaml::from_str(&text).map_err(|err| eyre!(describe_error(&err, true)))?
+        } else {
+            // The lines of the merged (or TOML) config don't match the file, so they aren't mentioned.
+            serde_yaml::from_str(&serde_yaml::to_string(&value)?)
+                .map_err(|err| eyre!(describe_error(&err, false)))?
+        };
+        conf.env_overrides = overridden;
+        conf.secret_files = secret_files;
+        conf.profile = profile;
+
+        conf.path = path;
+        Self::assign_instances(&mut conf)?;
+        Self::check_watch_provider(&mut conf)?;
         Self::clean_urls(&mut conf);
+        Self::validate(&conf)?;
 
         INSTANCE
             .set(conf)
@@ -69,10 +530,223 @@ impl Config {
         Ok(())
     }
 
+    /// Sets the config to one read from TOML, without checking it like a config file is checked.
+    #[cfg(test)]
+    pub fn init_for_tests(text: &str) {
+        let value = toml::from_str(text).unwrap();
+        let mut conf: Config =
+            serde_yaml::from_str(&serde_yaml::to_string(&value).unwrap()).unwrap();
+        Self::assign_instances(&mut conf).unwrap();
+        Self::clean_urls(&mut conf);
+        INSTANCE.get_or_init(|| conf);
+    }
+
+    /// The path of a file that is kept next to the config, like the ignore list.
+    pub fn file_next_to_config(name: &str) -> PathBuf {
+        match Self::global().path.parent() {
+            Some(dir) => dir.join(name),
+            None => PathBuf::from(name),
+        }
+    }
+
+    /// Prints the config in use, with the environment variables and defaults applied
+    /// and the API keys, tokens and secrets hidden.
+    pub fn print_effective(&self) -> Result<()> {
+        let mut value = serde_yaml::to_value(self)?;
+        redact_secrets(&mut value, "", &self.secret_files);
+        print!("{}", write_config(&self.path, &value)?);
+
+        match self.profile {
+            Some(ref profile) => {
+                message!("\nThe settings of the profile {} are included.", profile)
+            }
+            None if !self.profiles.is_empty() => message!(
+                "\nPass --profile to include the settings of one of the profiles: {}.",
+                self.profiles.keys().join(", ")
+            ),
+            None => {}
+        }
+
+        if self.env_overrides.is_empty() {
+            message!("\nNothing was set by environment variables.");
+        } else {
+            message!(
+                "\nSet by environment variables: {}",
+                self.env_overrides.join(", ")
+            );
+        }
+
+        Ok(())
+    }
+
+    /// Splits the listed Radarr and Sonarr instances into the regular and the 4K one.
+    fn assign_instances(conf: &mut Config) -> Result<()> {
+        if let Some(ref mut radarr) = conf.radarr_4k {
+            radarr.is_4k = true;
+        }
+        if let Some(ref mut sonarr) = conf.sonarr_4k {
+            sonarr.is_4k = true;
+        }
+
+        for radarr in std::mem::take(&mut conf.radarr_instances) {
+            let slot = if radarr.is_4k {
+                &mut conf.radarr_4k
+            } else {
+                &mut conf.radarr
+            };
+            assign_instance(slot, radarr, "Radarr", |radarr| radarr.is_4k)?;
+        }
+        for sonarr in std::mem::take(&mut conf.sonarr_instances) {
+            let slot = if sonarr.is_4k {
+                &mut conf.sonarr_4k
+            } else {
+                &mut conf.sonarr
+            };
+            assign_instance(slot, sonarr, "Sonarr", |sonarr| sonarr.is_4k)?;
+        }
+
+        Ok(())
+    }
+
+    /// Checks the settings of the watch provider are there. The Plex and Tautulli settings are
+    /// dropped when the watch history comes from Jellyfin or Emby, so nothing Plex only runs.
+    fn check_watch_provider(conf: &mut Config) -> Result<()> {
+        let missing = match conf.watch_provider {
+            WatchProviderType::Tautulli => [
+                ("plex", conf.plex.is_none()),
+                ("tautulli", conf.tautulli.is_none()),
+            ]
+            .into_iter()
+            .filter(|(_, missing)| *missing)
+            .map(|(name, _)| name)
+            .collect::<Vec<_>>(),
+            WatchProviderType::Jellyfin | WatchProviderType::Emby => {
+                conf.plex = None;
+                conf.tautulli = None;
+                match conf.watch_provider {
+                    WatchProviderType::Jellyfin if conf.jellyfin.is_none() => vec!["jellyfin"],
+                    WatchProviderType::Emby if conf.emby.is_none() => vec!["emby"],
+                    _ => Vec::new(),
+                }
+            }
+        };
+
+        if !missing.is_empty() {
+            return Err(eyre!(
+                "The {} settings are missing, they are needed as watch_provider is {}",
+                missing.join(" and "),
+                conf.watch_provider
+            ));
+        }
+
+        Ok(())
+    }
+
+    /// Checks the values that can be read but make no sense, listing every problem at once.
+    fn validate(conf: &Config) -> Result<()> {
+        let mut urls = Vec::new();
+        let mut keys = Vec::new();
+
+        if let Some(ref overseerr) = conf.overseerr {
+            urls.push(("overseerr.url", &overseerr.url));
+            keys.push(("overseerr.api_key", &overseerr.api_key));
+        }
+        if let Some(ref plex) = conf.plex {
+            urls.push(("plex.url", &plex.url));
+            keys.push(("plex.token", &plex.token));
+            if let Some(ref watchlist) = plex.watchlist {
+                keys.extend(
+                    watchlist
+                        .accounts
+                        .iter()
+                        .map(|account| ("plex.watchlist.accounts.token", &account.token)),
+                );
+            }
+        }
+        if let Some(ref tautulli) = conf.tautulli {
+            urls.push(("tautulli.url", &tautulli.url));
+            keys.push(("tautulli.api_key", &tautulli.api_key));
+        }
+        for (url_key, api_key_key, server) in [
+            ("jellyfin.url", "jellyfin.api_key", &conf.jellyfin),
+            ("emby.url", "emby.api_key", &conf.emby),
+        ] {
+            if let Some(server) = server {
+                urls.push((url_key, &server.url));
+                keys.push((api_key_key, &server.api_key));
+            }
+        }
+        if let Some(ref trakt) = conf.trakt {
+            keys.push(("trakt.client_id", &trakt.client_id));
+            keys.push(("trakt.client_secret", &trakt.client_secret));
+        }
+        for radarr in [&conf.radarr, &conf.radarr_4k].into_iter().flatten() {
+            urls.push(("radarr.url", &radarr.url));
+            keys.push(("radarr.api_key", &radarr.api_key));
+        }
+        for sonarr in [&conf.sonarr, &conf.sonarr_4k].into_iter().flatten() {
+            urls.push(("sonarr.url", &sonarr.url));
+            keys.push(("sonarr.api_key", &sonarr.api_key));
+        }
+
+        let mut problems = urls
+            .into_iter()
+            .filter_map(|(key, url)| match Url::parse(url) {
+                Ok(_) => None,
+                Err(err) => Some(format!(
+                    "`{}` is not a valid URL: \"{}\" ({}).",
+                    key, url, err
+                )),
+            })
+            .collect_vec();
+        problems.extend(
+            keys.into_iter()
+                .filter(|(_, value)| value.trim().is_empty())
+                .map(|(key, _)| format!("`{}` is empty.", key)),
+        );
+        problems.extend(conf.rules.exclude_titles.iter().filter_map(|pattern| {
+            title_filter::compile(pattern).err().map(|err| {
+                format!(
+                    "`rules.exclude_titles` has \"{}\", which is not a valid regex: {}",
+                    pattern, err
+                )
+            })
+        }));
+        if !(1..=100).contains(&conf.rules.watched_threshold) {
+            problems.push(format!(
+                "`rules.watched_threshold` is a percentage, so it has to be between 1 and 100, not {}.",
+                conf.rules.watched_threshold
+            ));
+        }
+
+        if !problems.is_empty() {
+            return Err(eyre!(
+                "The config has {} problem{}:\n- {}",
+                problems.len(),
+                if problems.len() == 1 { "" } else { "s" },
+                problems.join("\n- ")
+            ));
+        }
+
+        Ok(())
+    }
+
     fn clean_urls(conf: &mut Config) {
-        clean_url(&mut conf.overseerr.url);
-        clean_url(&mut conf.plex.url);
-        clean_url(&mut conf.tautulli.url);
+        if let Some(ref mut overseerr) = conf.overseerr {
+            clean_url(&mut overseerr.url);
+        }
+        if let Some(ref mut plex) = conf.plex {
+            clean_url(&mut plex.url);
+        }
+        if let Some(ref mut tautulli) = conf.tautulli {
+            clean_url(&mut tautulli.url);
+        }
+        if let Some(ref mut jellyfin) = conf.jellyfin {
+            clean_url(&mut jellyfin.url);
+        }
+        if let Some(ref mut emby) = conf.emby {
+            clean_url(&mut emby.url);
+        }
 
         if let Some(ref mut radarr) = conf.radarr {
             clean_url(&mut radarr.url);
@@ -96,8 +770,990 @@ fn default_items_shown() -> usize {
     5
 }
 
+fn default_version() -> u64 {
+    CONFIG_VERSION
+}
+
+fn default_watched_threshold() -> u8 {
+    85
+}
+
+fn default_min_request_age_days() -> i64 {
+    30
+}
+
+fn default_retry_attempts() -> u32 {
+    3
+}
+
+fn default_retry_delay_ms() -> u64 {
+    500
+}
+
+fn default_timeout_seconds() -> u64 {
+    30
+}
+
+fn default_concurrency() -> usize {
+    4
+}
+
+fn default_requests_per_second() -> f64 {
+    5.0
+}
+
+fn default_delete_files() -> bool {
+    true
+}
+
+fn default_unmonitor_seasons() -> bool {
+    true
+}
+
+fn default_verify_tls() -> bool {
+    true
+}
+
+/// Finds the config: the file given with --config, or the first one found in the config directory
+/// of the platform or the working directory, TOML before YAML in each. The error lists every place
+/// that was tried.
+fn find_config() -> Result<PathBuf> {
+    let candidates = match Arguments::get_args().config_path {
+        Some(ref path) => vec![path.clone()],
+        None => config_dirs()
+            .into_iter()
+            .map(|dir| dir.join(CONFIG_DIR))
+            .chain([PathBuf::new()])
+            .flat_map(|dir| CONFIG_FILES.map(|file| dir.join(file)))
+            .collect(),
+    };
+
+    match candidates.iter().find(|path| path.is_file()) {
+        Some(path) => Ok(path.clone()),
+        None => Err(eyre!(
+            "No config file was found, these places were tried:\n{}\nCreate one in any of them, or pass --config <PATH>.",
+            candidates
+                .iter()
+                .map(|path| format!("- {}", path.display()))
+                .join("\n")
+        )),
+    }
+}
+
+/// Where a new config is written: in the config directory of the platform, or the working directory
+/// when there is none.
+pub fn default_config_path() -> PathBuf {
+    match config_dirs().into_iter().next() {
+        Some(dir) => dir.join(CONFIG_DIR).join(CONFIG_FILES[0]),
+        None => PathBuf::from(CONFIG_FILES[0]),
+    }
+}
+
+/// The directories of the platform that hold the config of programs, in the order they are looked in.
+fn config_dirs() -> Vec<PathBuf> {
+    let env_dir = |name: &str| {
+        env::var_os(name)
+            .map(PathBuf::from)
+            .filter(|dir| dir.is_absolute())
+    };
+    let home = env_dir("HOME");
+
+    if cfg!(windows) {
+        env_dir("APPDATA").into_iter().collect()
+    } else if cfg!(target_os = "macos") {
+        home.map(|home| home.join("Library").join("Application Support"))
+            .into_iter()
+            .collect()
+    } else {
+        [
+            env_dir("XDG_CONFIG_HOME"),
+            home.map(|home| home.join(".config")),
+        ]
+        .into_iter()
+        .flatten()
+        .unique()
+        .collect()
+    }
+}
+
+fn serialize_statuses<S>(
+    statuses: &Option<Vec<MediaStatus>>,
+    serializer: S,
+) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+{
+    statuses
+        .as_ref()
+        .map(|statuses| statuses.iter().map(MediaStatus::config_name).collect_vec())
+        .serialize(serializer)
+}
+
+fn deserialize_statuses<'de, D>(deserializer: D) -> Result<Option<Vec<MediaStatus>>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    match Option::<Vec<String>>::deserialize(deserializer)? {
+        Some(statuses) => statuses
+            .iter()
+            .map(|status| status.parse().map_err(de::Error::custom))
+            .collect::<Result<_, _>>()
+            .map(Some),
+        None => Ok(None),
+    }
+}
+
+fn assign_instance<T>(
+    slot: &mut Option<T>,
+    instance: T,
+    service: &str,
+    is_4k: impl Fn(&T) -> bool,
+) -> Result<()> {
+    if slot.is_some() {
+        let kind = if is_4k(&instance) { "4K " } else { "" };
+        return Err(eyre!(
+            "More than one {}{} instance is configured, only one regular and one 4K instance are supported.",
+            kind,
+            service
+        ));
+    }
+
+    *slot = Some(instance);
+    Ok(())
+}
+
+fn deserialize_radarr<'de, D>(deserializer: D) -> Result<Vec<Radarr>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    deserialize_instances(deserializer, "radarr")
+}
+
+fn deserialize_sonarr<'de, D>(deserializer: D) -> Result<Vec<Sonarr>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    deserialize_instances(deserializer, "sonarr")
+}
+
+/// Reads one instance or a list of them. This isn't done with an untagged enum, as that would
+/// hide what is wrong with the instance behind "did not match any variant". The error is
+/// prefixed with where the instance is, as reading it separately loses that.
+fn deserialize_instances<'de, D, T>(deserializer: D, name: &str) -> Result<Vec<T>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: de::DeserializeOwned,
+{
+    let read = |instance, path: String| {
+        serde_yaml::from_value(instance)
+            .map_err(|err| de::Error::custom(format!("{}: {}", path, err)))
+    };
+
+    match Option::<serde_yaml::Value>::deserialize(deserializer)? {
+        Some(serde_yaml::Value::Sequence(instances)) => instances
+            .into_iter()
+            .enumerate()
+            .map(|(index, instance)| read(instance, format!("{}[{}]", name, index)))
+            .collect(),
+        Some(instance) => Ok(vec![read(instance, name.to_string())?]),
+        None => Ok(Vec::new()),
+    }
+}
+
+/// Updates a config written for an older version to the current one, moving the settings that
+/// were renamed. A config without a version is from before versions were added. Returns the
+/// changes that were made, like "`watched_threshold` is now `rules.watched_threshold`".
+fn migrate(config: &mut Value) -> Result<Vec<String>> {
+    let version = match config.get("version") {
+        Some(version) => version
+            .as_u64()
+            .ok_or_else(|| eyre!("`version` has to be a number, not {:?}.", version))?,
+        None => 1,
+    };
+    if version > CONFIG_VERSION {
+        return Err(eyre!(
+            "This config was written for a newer media-cleaner (config version {}, this one knows up to version {}). Please update media-cleaner.",
+            version,
+            CONFIG_VERSION
+        ));
+    }
+
+    let mut changes = Vec::new();
+    for (from_version, old, new) in RENAMED_SETTINGS {
+        if from_version >= version && move_setting(config, old, new)? {
+            changes.push(format!("`{}` is now `{}`", old, new));
+        }
+    }
+
+    // The version goes first, so it's what a migrated file starts with.
+    if let Some(mapping) = config.as_mapping_mut().filter(|_| version < CONFIG_VERSION) {
+        let settings = std::mem::take(mapping);
+        mapping.insert("version".into(), CONFIG_VERSION.into());
+        mapping.extend(settings.into_iter().filter(|(key, _)| key != "version"));
+    }
+    Ok(changes)
+}
+
+/// Moves a setting to another place, given as paths like `rules.watched_threshold`.
+/// Returns whether the config had the setting.
+fn move_setting(config: &mut Value, old: &str, new: &str) -> Result<bool> {
+    let (old_parent, old_key) = old.rsplit_once('.').unwrap_or(("", old));
+    let mut parent = &mut *config;
+    for segment in old_parent.split('.').filter(|segment| !segment.is_empty()) {
+        parent = match parent.get_mut(segment) {
+            Some(value) => value,
+            None => return Ok(false),
+        };
+    }
+    let value = match parent
+        .as_mapping_mut()
+        .and_then(|mapping| mapping.remove(old_key))
+    {
+        Some(value) => value,
+        None => return Ok(false),
+    };
+
+    let mut target = &mut *config;
+    for segment in new.split('.') {
+        target = env_segment(target, segment).ok_or_else(|| {
+            eyre!(
+                "`{}` can't be moved to `{}`, as the config has something else there.",
+                old,
+                new
+            )
+        })?;
+    }
+    if !target.is_null() {
+        return Err(eyre!(
+            "`{}` and `{}` are both set, please remove `{}`.",
+            old,
+            new,
+            old
+        ));
+    }
+    *target = value;
+
+    Ok(true)
+}
+
+/// Rewrites the config in the current version, keeping the old file next to it.
+/// The comments of the file are lost, so it is only done when asked for with `config migrate`.
+pub fn migrate_file() -> Result<()> {
+    let path = find_config()?;
+    let text = fs::read_to_string(&path)
+        .map_err(|err| eyre!("{} could not be opened: {}", path.display(), err))?;
+    let mut value = parse_config(&path, &text)?;
+
+    let changes = migrate(&mut value)?;
+    if changes.is_empty() {
+        message!(
+            "{} is already up to date, nothing was changed.",
+            path.display()
+        );
+        return Ok(());
+    }
+
+    let mut backup = path.clone().into_os_string();
+    backup.push(".bak");
+    let backup = PathBuf::from(backup);
+    fs::copy(&path, &backup)
+        .map_err(|err| eyre!("Failed to keep a copy at {}: {}", backup.display(), err))?;
+    fs::write(&path, write_config(&path, &value)?)
+        .map_err(|err| eyre!("Failed to write {}: {}", path.display(), err))?;
+
+    message!(
+        "Updated {} to config version {}:\n- {}\nThe old file is kept at {}, copy any comments you want to keep from it.",
+        path.display(),
+        CONFIG_VERSION,
+        changes.join("\n- "),
+        backup.display()
+    );
+    Ok(())
+}
+
+/// Replaces the settings of the base config with the ones of the profile chosen with --profile.
+/// Groups of settings are merged, so a profile only needs the settings it changes. Returns the
+/// name of the profile that was applied.
+fn apply_profile(config: &mut Value, name: Option<&str>) -> Result<Option<String>> {
+    let name = match name {
+        Some(name) => name,
+        None => return Ok(None),
+    };
+
+    let profiles = config.get("profiles").and_then(Value::as_mapping);
+    let profile = match profiles.and_then(|profiles| profiles.get(name)) {
+        Some(profile) => profile.clone(),
+        None => {
+            let available = profiles
+                .map(|profiles| profiles.keys().filter_map(Value::as_str).join(", "))
+                .filter(|available| !available.is_empty());
+            return Err(match available {
+                Some(available) => eyre!(
+                    "There is no profile named {}, the config has these profiles: {}.",
+                    name,
+                    available
+                ),
+                None => eyre!(
+                    "There is no profile named {}, the config doesn't have any profiles.",
+                    name
+                ),
+            });
+        }
+    };
+
+    let settings = match profile {
+        Value::Mapping(settings) => settings,
+        Value::Null => Mapping::new(),
+        _ => {
+            return Err(eyre!(
+                "`profiles.{}` in the config isn't a group of settings.",
+                name
+            ))
+        }
+    };
+    for (key, value) in settings {
+        let key_name = key.as_str().unwrap_or_default();
+        if !PROFILE_SETTINGS.contains(&key_name) {
+            return Err(eyre!(
+                "`profiles.{}.{}` can't be set in a profile, only {} can.",
+                name,
+                key_name,
+                PROFILE_SETTINGS
+                    .iter()
+                    .map(|setting| format!("`{}`", setting))
+                    .join(", ")
+            ));
+        }
+
+        let base = env_segment(config, key_name).expect("The config is a group of settings.");
+        merge_values(base, value);
+    }
+
+    message!("Using the profile {}.", name);
+    Ok(Some(name.to_string()))
+}
+
+/// Puts the settings of `other` into `base`, merging groups of settings and replacing everything else.
+fn merge_values(base: &mut Value, other: Value) {
+    match (base, other) {
+        (Value::Mapping(base), Value::Mapping(other)) => {
+            for (key, value) in other {
+                merge_values(base.entry(key).or_insert(Value::Null), value);
+            }
+        }
+        (base, other) => *base = other,
+    }
+}
+
+/// Sets the settings given as environment variables in the config read from the file, returning
+/// the names of the settings that were set. The variable names are the path to the setting split
+/// by `__`, with the position added to the name for one of a list of instances, like
+/// `MEDIA_CLEANER__RADARR_0__URL`. The values of list settings are split at their commas.
+fn apply_env_overrides(config: &mut Value, vars: Vec<(String, String)>) -> Result<Vec<String>> {
+    let mut overridden = Vec::new();
+
+    for (name, raw) in vars {
+        let segments = name[ENV_PREFIX.len()..]
+            .split("__")
+            .map(str::to_lowercase)
+            .collect_vec();
+        if segments.iter().any(String::is_empty) {
+            return Err(eyre!(
+                "The environment variable {} doesn't name a setting.",
+                name
+            ));
+        }
+
+        // Written like the paths in errors, as `radarr[1].url`.
+        let path = segments
+            .iter()
+            .map(|segment| INDEXED_SEGMENT.replace(segment, "$1[$2]"))
+            .collect_vec();
+
+        let mut current = &mut *config;
+        for (depth, segment) in segments.iter().enumerate() {
+            let replaced = replaced_secret(segment)
+                .filter(|_| depth + 1 == segments.len())
+                .filter(|replaced| {
+                    let replaced_path = path[..depth]
+                        .iter()
+                        .map(|part| part.as_ref())
+                        .chain([replaced.as_str()])
+                        .join(".");
+                    !overridden.contains(&replaced_path)
+                });
+            if let (Some(replaced), Some(settings)) = (replaced, current.as_mapping_mut()) {
+                settings.remove(replaced.as_str());
+            }
+
+            current = env_segment(current, segment).ok_or_else(|| {
+                eyre!(
+                    "The environment variable {} can't be applied, as `{}` in the config isn't a group of settings.",
+                    name,
+                    path[..depth].join(".")
+                )
+            })?;
+        }
+
+        *current = if is_list_setting(&segments) {
+            Value::Sequence(
+                split_list(&raw)
+                    .iter()
+                    .map(|item| item.trim())
+                    .filter(|item| !item.is_empty())
+                    .map(env_scalar)
+                    .collect(),
+            )
+        } else {
+            env_scalar(&raw)
+        };
+        overridden.push(path.join("."));
+    }
+
+    Ok(overridden)
+}
+
+/// Whether the setting an environment variable names, like `radarr_1` `exclude_tags`, is one
+/// of `LIST_SETTINGS`. The last part having an index, like `ignored_users_0`, names an item instead.
+fn is_list_setting(segments: &[String]) -> bool {
+    let segments = match segments {
+        [profiles, _, settings @ ..] if profiles == "profiles" => settings,
+        _ => segments,
+    };
+    let (setting, groups) = match segments.split_last() {
+        Some(split) => split,
+        None => return false,
+    };
+
+    let path = groups
+        .iter()
+        .map(|group| INDEXED_SEGMENT.replace(group, "$1"))
+        .chain([setting.into()])
+        .join(".");
+    LIST_SETTINGS.contains(&path.as_str())
+}
+
+/// Splits a list given in an environment variable at its commas. Commas inside brackets, braces
+/// or parentheses, like in the regex `Earth{1,2}`, don't split it, and neither do ones escaped
+/// with a backslash, which is left out.
+fn split_list(raw: &str) -> Vec<String> {
+    let mut items = vec![String::new()];
+    let mut depth = 0usize;
+    let mut chars = raw.chars().peekable();
+    while let Some(c) = chars.next() {
+        let item = items.last_mut().unwrap();
+        match c {
+            '\\' if chars.peek() == Some(&',') => item.push(chars.next().unwrap()),
+            ',' if depth == 0 => items.push(String::new()),
+            '(' | '[' | '{' => {
+                depth += 1;
+                item.push(c);
+            }
+            ')' | ']' | '}' => {
+                depth = depth.saturating_sub(1);
+                item.push(c);
+            }
+            _ => item.push(c),
+        }
+    }
+
+    items
+}
+
+/// Gets the setting a part of an environment variable name is about, creating it if the file doesn't
+/// have it. A single instance is used like a list with one instance. `None` if the config has
+/// a value where a group of settings is needed.
+fn env_segment<'a>(current: &'a mut Value, segment: &str) -> Option<&'a mut Value> {
+    let (key, index) = match INDEXED_SEGMENT.captures(segment) {
+        Some(captures) => (captures[1].to_string(), captures[2].parse::<usize>().ok()),
+        None => (segment.to_string(), None),
+    };
+
+    if current.is_null() {
+        *current = Value::Mapping(Mapping::new());
+    }
+    let value = current
+        .as_mapping_mut()?
+        .entry(Value::String(key))
+        .or_insert(Value::Null);
+
+    let index = match index {
+        Some(index) => index,
+        None => return Some(value),
+    };
+    if !value.is_sequence() {
+        let instance = std::mem::take(value);
+        *value = Value::Sequence(if instance.is_null() {
+            Vec::new()
+        } else {
+            vec![instance]
+        });
+    }
+
+    let instances = value.as_sequence_mut()?;
+    if instances.len() <= index {
+        instances.resize(index + 1, Value::Null);
+    }
+    Some(&mut instances[index])
+}
+
+/// The setting a secret setting replaces when it is set by an environment variable, so an
+/// `api_key_file` given that way is used instead of the `api_key` in the file and the other way around.
+fn replaced_secret(setting: &str) -> Option<String> {
+    match setting.strip_suffix("_file") {
+        Some(secret) if SECRET_SETTINGS.contains(&secret) => Some(secret.to_string()),
+        None if SECRET_SETTINGS.contains(&setting) => Some(format!("{}_file", setting)),
+        _ => None,
+    }
+}
+
+/// Reads a value from an environment variable, numbers and booleans are kept as they are
+/// written so they can be read as text as well.
+fn env_scalar(raw: &str) -> Value {
+    match serde_yaml::from_str::<Value>(raw) {
+        Ok(value @ (Value::Bool(_) | Value::Number(_))) => value,
+        _ => Value::String(raw.to_string()),
+    }
+}
+
+/// Reads the secrets given as a file, like `api_key_file: /run/secrets/tautulli`, into the setting
+/// the file is for. The file is trimmed, so a trailing newline isn't part of the secret. Returns
+/// the files that were read by the path of their setting, named like in the config that is used
+/// so the Radarr with `is_4k` in a list of them is `radarr_4k`.
+fn read_secret_files(config: &mut Value, path: &str) -> Result<HashMap<String, PathBuf>> {
+    let mut files = HashMap::new();
+    let join = |key: &str| {
+        if path.is_empty() {
+            key.to_string()
+        } else {
+            format!("{}.{}", path, key)
+        }
+    };
+
+    match config {
+        Value::Mapping(mapping) => {
+            let settings = mapping
+                .keys()
+                .filter_map(Value::as_str)
+                .filter_map(|key| key.strip_suffix("_file"))
+                .filter(|setting| SECRET_SETTINGS.contains(setting))
+                .map(str::to_string)
+                .collect_vec();
+
+            for setting in settings {
+                let name = join(&setting);
+                if mapping.contains_key(setting.as_str()) {
+                    return Err(eyre!(
+                        "`{}` and `{}_file` are both set, only one of them can be used.",
+                        name,
+                        name
+                    ));
+                }
+
+                let file = match mapping.remove(format!("{}_file", setting)) {
+                    Some(Value::String(file)) => PathBuf::from(file),
+                    _ => return Err(eyre!("`{}_file` has to be the path of a file.", name)),
+                };
+                let secret = fs::read_to_string(&file)
+                    .map_err(|err| {
+                        eyre!(
+                            "`{}_file` could not be read from {}: {}",
+                            name,
+                            file.display(),
+                            err
+                        )
+                    })?
+                    .trim()
+                    .to_string();
+
+                mapping.insert(setting.into(), secret.into());
+                files.insert(name, file);
+            }
+
+            for (key, value) in mapping.iter_mut() {
+                let key = key.as_str().unwrap_or_default();
+                match value {
+                    Value::Sequence(instances)
+                        if path.is_empty() && INSTANCE_LISTS.contains(&key) =>
+                    {
+                        for instance in instances {
+                            files.extend(read_secret_files(
+                                instance,
+                                &instance_name(key, instance),
+                            )?);
+                        }
+                    }
+                    _ if path.is_empty() && INSTANCE_LISTS.contains(&key) => {
+                        let name = instance_name(key, value);
+                        files.extend(read_secret_files(value, &name)?);
+                    }
+                    _ => files.extend(read_secret_files(value, &join(key))?),
+                }
+            }
+        }
+        Value::Sequence(values) => {
+            for (index, value) in values.iter_mut().enumerate() {
+                files.extend(read_secret_files(value, &format!("{}[{}]", path, index))?);
+            }
+        }
+        _ => {}
+    }
+
+    Ok(files)
+}
+
+/// The name of a Radarr or Sonarr in the config that is used, where a 4K one given in the list
+/// of them under `key` is `radarr_4k` or `sonarr_4k`.
+fn instance_name(key: &str, instance: &Value) -> String {
+    match instance.get("is_4k") {
+        Some(Value::Bool(true)) if !key.ends_with("_4k") => format!("{}_4k", key),
+        _ => key.to_string(),
+    }
+}
+
+/// Hides the values of the settings named in `SECRET_SETTINGS`, saying which file a secret
+/// was read from. `path` is the path of `value`, like `secret_files` has them.
+fn redact_secrets(value: &mut Value, path: &str, secret_files: &HashMap<String, PathBuf>) {
+    match value {
+        Value::Mapping(mapping) => {
+            for (key, value) in mapping.iter_mut() {
+                let key = key.as_str().unwrap_or_default();
+                let key = if path.is_empty() {
+                    key.to_string()
+                } else {
+                    format!("{}.{}", path, key)
+                };
+                let is_secret = key
+                    .rsplit('.')
+                    .next()
+                    .is_some_and(|setting| SECRET_SETTINGS.contains(&setting));
+                if !is_secret || value.is_null() {
+                    redact_secrets(value, &key, secret_files);
+                    continue;
+                }
+
+                *value = match secret_files.get(&key) {
+                    Some(file) => {
+                        Value::String(format!("<redacted> (from file {})", file.display()))
+                    }
+                    None => Value::String("<redacted>".to_string()),
+                };
+            }
+        }
+        Value::Sequence(values) => {
+            for (index, value) in values.iter_mut().enumerate() {
+                redact_secrets(value, &format!("{}[{}]", path, index), secret_files);
+            }
+        }
+        _ => {}
+    }
+}
+
+/// A config ending in `.toml` is TOML, any other is YAML.
+pub fn is_toml(path: &Path) -> bool {
+    path.extension()
+        .is_some_and(|extension| extension == "toml")
+}
+
+/// Reads the settings of the config, in the format of the file.
+fn parse_config(path: &Path, text: &str) -> Result<Value> {
+    if is_toml(path) {
+        toml::from_str(text).map_err(|err| {
+            eyre!(
+                "The config (line {} column {}) can't be read: {}.",
+                err.line,
+                err.column,
+                err.reason
+            )
+        })
+    } else {
+        serde_yaml::from_str(text).map_err(|err| eyre!(describe_error(&err, true)))
+    }
+}
+
+/// Writes the settings in the format of the config at the path.
+pub fn write_config(path: &Path, value: &Value) -> Result<String> {
+    if is_toml(path) {
+        toml::to_string(value).map_err(|err| eyre!(err))
+    } else {
+        Ok(serde_yaml::to_string(value)?)
+    }
+}
+
+/// Turns an error from reading the config into a message saying which setting is wrong and why,
+/// suggesting the closest known name for a misspelled one.
+fn describe_error(err: &serde_yaml::Error, with_location: bool) -> String {
+    let message = err.to_string();
+    let (message, location) = match message.rfind(" at line ") {
+        Some(index) if with_location => {
+            (&message[..index], format!(" ({})", &message[index + 1..]))
+        }
+        Some(index) => (&message[..index], String::new()),
+        None => (message.as_str(), String::new()),
+    };
+    let (path, reason) = match message.split_once(": ") {
+        Some((path, reason)) if !path.contains(' ') => (Some(path), reason),
+        _ => (None, message),
+    };
+    let key = |name: &str| match path {
+        Some(path) => format!("`{}.{}`", path, name),
+        None => format!("`{}`", name),
+    };
+
+    if let Some(captures) = UNKNOWN_FIELD.captures(reason) {
+        let unknown = &captures[1];
+        let expected = captures[2].split('`').skip(1).step_by(2).collect_vec();
+        let hint = match closest_name(unknown, &expected) {
+            Some(name) => format!(" Did you mean `{}`?", name),
+            None => format!(" The known ones are: {}.", expected.join(", ")),
+        };
+
+        return format!(
+            "{}{} is not a known setting.{}",
+            key(unknown),
+            location,
+            hint
+        );
+    }
+
+    if let Some(captures) = MISSING_FIELD.captures(reason) {
+        return format!(
+            "{}{} is missing, it has to be filled in.",
+            key(&captures[1]),
+            location
+        );
+    }
+
+    match path {
+        Some(path) => format!("`{}`{} is wrong: {}.", path, location, reason),
+        None => format!("The config{} can't be read: {}.", location, reason),
+    }
+}
+
+/// The known name that is closest to a misspelled one, if any is close enough to be meant.
+fn closest_name<'a>(name: &str, known: &[&'a str]) -> Option<&'a str> {
+    known
+        .iter()
+        .map(|candidate| (*candidate, edit_distance(name, candidate)))
+        .filter(|(candidate, distance)| *distance <= (candidate.len() / 3).max(2))
+        .min_by_key(|(_, distance)| *distance)
+        .map(|(candidate, _)| candidate)
+}
+
+/// The number of characters that have to be inserted, removed or changed to turn `a` into `b`.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b = b.chars().collect_vec();
+    let mut previous = (0..=b.len()).collect_vec();
+
+    for (i, a_char) in a.chars().enumerate() {
+        let mut current = vec![i + 1];
+        for (j, b_char) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(a_char != *b_char);
+            current.push(substitution.min(previous[j + 1] + 1).min(current[j] + 1));
+        }
+        previous = current;
+    }
+
+    previous[b.len()]
+}
+
 fn clean_url(url: &mut String) {
-    if url.ends_with("/") {
+    while url.ends_with('/') {
         url.pop();
     }
+
+    if !url.contains("://") {
+        url.insert_str(0, "http://");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Reads a config file the way `read_conf` does, up to where it is checked.
+    fn read(path: &str, text: &str) -> Result<(Config, Vec<String>)> {
+        let mut value = parse_config(Path::new(path), text)?;
+        let changes = migrate(&mut value)?;
+        let config = serde_yaml::from_value(value)?;
+        Ok((config, changes))
+    }
+
+    #[test]
+    fn migrates_a_renamed_setting() {
+        let old = "watched_threshold = 70\n\n[tautulli]\nurl = \"http://tautulli:8181\"\napi_key = \"KEY\"\n";
+        let (config, changes) = read("config.toml", old).unwrap();
+
+        assert_eq!(
+            changes,
+            ["`watched_threshold` is now `rules.watched_threshold`"]
+        );
+        assert_eq!(config.version, CONFIG_VERSION);
+        assert_eq!(config.rules.watched_threshold, 70);
+
+        // The same goes for YAML, and a config migrated once has nothing left to migrate.
+        let (config, _) = read("config.yaml", "watched_threshold: 70\n").unwrap();
+        assert_eq!(config.rules.watched_threshold, 70);
+        let mut value = parse_config(Path::new("config.toml"), old).unwrap();
+        migrate(&mut value).unwrap();
+        let migrated = write_config(Path::new("config.toml"), &value).unwrap();
+        assert!(migrated.starts_with("version = 2\n"), "{}", migrated);
+        let (config, changes) = read("config.toml", &migrated).unwrap();
+        assert!(changes.is_empty());
+        assert_eq!(config.rules.watched_threshold, 70);
+    }
+
+    #[test]
+    fn does_not_migrate_a_current_config() {
+        let text = "version = 2\n\n[rules]\nwatched_threshold = 60\n";
+        let (config, changes) = read("config.toml", text).unwrap();
+
+        assert!(changes.is_empty());
+        assert_eq!(config.rules.watched_threshold, 60);
+    }
+
+    #[test]
+    fn rejects_settings_under_both_names() {
+        let text = "watched_threshold = 70\n\n[rules]\nwatched_threshold = 60\n";
+        let err = read("config.toml", text).unwrap_err();
+
+        assert_eq!(
+            err.to_string(),
+            "`watched_threshold` and `rules.watched_threshold` are both set, please remove `watched_threshold`."
+        );
+    }
+
+    #[test]
+    fn rejects_configs_for_a_newer_version() {
+        let err = read("config.toml", "version = 3\n").unwrap_err();
+
+        assert!(err
+            .to_string()
+            .starts_with("This config was written for a newer media-cleaner (config version 3"));
+    }
+
+    #[test]
+    fn splits_only_the_lists_in_environment_variables() {
+        let mut value = parse_config(
+            Path::new("config.toml"),
+            "[tautulli]\nurl = \"http://tautulli:8181\"\napi_key = \"KEY\"\n",
+        )
+        .unwrap();
+        let vars = [
+            ("TAUTULLI__API_KEY", "abc,def"),
+            ("IGNORED_USERS", "alice"),
+            (
+                "RULES__EXCLUDE_TITLES",
+                r"^Planet Earth{1,2}$,Cosmos\, A Spacetime Odyssey",
+            ),
+            ("PROFILES__WEEKLY__RADARR_0__EXCLUDE_TAGS", "keep,4k"),
+        ]
+        .map(|(name, raw)| (format!("{}{}", ENV_PREFIX, name), raw.to_string()));
+        apply_env_overrides(&mut value, vars.to_vec()).unwrap();
+
+        assert_eq!(value["tautulli"]["api_key"], Value::from("abc,def"));
+        assert_eq!(value["ignored_users"], Value::from(vec!["alice"]));
+        assert_eq!(
+            value["rules"]["exclude_titles"],
+            Value::from(vec![r"^Planet Earth{1,2}$", "Cosmos, A Spacetime Odyssey"])
+        );
+        assert_eq!(
+            value["profiles"]["weekly"]["radarr"][0]["exclude_tags"],
+            Value::from(vec!["keep", "4k"])
+        );
+    }
+
+    /// Reads the secret files of a config file with the environment variables applied.
+    fn read_secrets(
+        text: &str,
+        vars: &[(&str, &str)],
+    ) -> Result<(Value, HashMap<String, PathBuf>)> {
+        let mut value = parse_config(Path::new("config.toml"), text)?;
+        let vars = vars
+            .iter()
+            .map(|(name, raw)| (format!("{}{}", ENV_PREFIX, name), raw.to_string()))
+            .collect();
+        apply_env_overrides(&mut value, vars)?;
+        let files = read_secret_files(&mut value, "")?;
+        Ok((value, files))
+    }
+
+    #[test]
+    fn uses_a_secret_file_from_an_environment_variable_instead_of_the_secret() {
+        let file = env::temp_dir().join("media-cleaner-test-tautulli-key");
+        fs::write(&file, "FROM_FILE\n").unwrap();
+        let file_path = file.to_str().unwrap();
+
+        let text = "[tautulli]\nurl = \"http://tautulli:8181\"\napi_key = \"KEY\"\n";
+        let (value, files) = read_secrets(text, &[("TAUTULLI__API_KEY_FILE", file_path)]).unwrap();
+        assert_eq!(value["tautulli"]["api_key"], Value::from("FROM_FILE"));
+        assert_eq!(
+            files,
+            HashMap::from([("tautulli.api_key".to_string(), file.clone())])
+        );
+
+        // And the other way around, a secret from an environment variable replaces the file.
+        let text = format!("[tautulli]\napi_key_file = \"{}\"\n", file_path);
+        let (value, files) = read_secrets(&text, &[("TAUTULLI__API_KEY", "KEY")]).unwrap();
+        assert_eq!(value["tautulli"]["api_key"], Value::from("KEY"));
+        assert!(files.is_empty());
+
+        // Both in the config, or both in environment variables, is still a mistake.
+        let both = format!(
+            "[tautulli]\napi_key = \"KEY\"\napi_key_file = \"{}\"\n",
+            file_path
+        );
+        let err = read_secrets(&both, &[]).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "`tautulli.api_key` and `tautulli.api_key_file` are both set, only one of them can be used."
+        );
+        let vars = [
+            ("TAUTULLI__API_KEY", "KEY"),
+            ("TAUTULLI__API_KEY_FILE", file_path),
+        ];
+        assert!(read_secrets("", &vars).is_err());
+    }
+
+    #[test]
+    fn finds_the_files_of_secrets_by_their_setting() {
+        let file = env::temp_dir().join("media-cleaner-test-radarr-key");
+        fs::write(&file, "SAME").unwrap();
+        let text = format!(
+            "[[radarr]]\nurl = \"http://radarr:7878\"\napi_key = \"SAME\"\n\n\
+             [[radarr]]\nurl = \"http://radarr4k:7878\"\napi_key_file = \"{}\"\nis_4k = true\n",
+            file.display()
+        );
+        let (mut value, files) = read_secrets(&text, &[]).unwrap();
+        assert_eq!(
+            files,
+            HashMap::from([("radarr_4k.api_key".to_string(), file.clone())])
+        );
+
+        // The Radarr with the same key written in the config isn't said to be from the file.
+        let radarr = value["radarr"].as_sequence().unwrap().clone();
+        value["radarr"] = radarr[0].clone();
+        value["radarr_4k"] = radarr[1].clone();
+        redact_secrets(&mut value, "", &files);
+        assert_eq!(value["radarr"]["api_key"], Value::from("<redacted>"));
+        assert_eq!(
+            value["radarr_4k"]["api_key"],
+            Value::from(format!("<redacted> (from file {})", file.display()))
+        );
+    }
+
+    #[test]
+    fn cleans_up_the_urls() {
+        let cases = [
+            ("host:8181", "http://host:8181"),
+            ("host/tautulli", "http://host/tautulli"),
+            ("https://host/tautulli/", "https://host/tautulli"),
+            ("http://host:8181//", "http://host:8181"),
+        ];
+        for (url, cleaned) in cases {
+            let mut url = url.to_string();
+            clean_url(&mut url);
+            assert_eq!(url, cleaned);
+        }
+    }
 }
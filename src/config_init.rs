@@ -0,0 +1,282 @@
+use color_eyre::{eyre::eyre, owo_colors::OwoColorize, Result};
+use dialoguer::{Confirm, Input, Select};
+use serde_json::Value;
+use serde_yaml::Mapping;
+use std::{fs, path::Path, time::Duration};
+
+use crate::{
+    arguments::ConfigInit,
+    config,
+    http::{ApiError, SendLogged},
+    utils::join_url,
+};
+
+/// The commented config with every setting, written unless the config is asked for.
+const TEMPLATE: &str = include_str!("config_template.toml");
+
+/// How long an entered service gets to answer the check.
+const CHECK_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// How a service wants the API key (or token).
+enum Auth {
+    /// As the `apikey` query parameter.
+    Query,
+    /// In a header with this name.
+    Header(&'static str),
+}
+
+/// A service that can be set up while asking for the config.
+struct Service {
+    name: &'static str,
+    /// The config section it is written to.
+    section: &'static str,
+    /// The setting holding the API key.
+    key_setting: &'static str,
+    auth: Auth,
+    /// A path that needs the API key and answers with the version.
+    version_path: &'static str,
+    /// Where the version is in the answer.
+    version_pointer: &'static str,
+}
+
+const PLEX: Service = Service {
+    name: "Plex",
+    section: "plex",
+    key_setting: "token",
+    auth: Auth::Header("X-Plex-Token"),
+    version_path: "/",
+    version_pointer: "/MediaContainer/version",
+};
+
+const TAUTULLI: Service = Service {
+    name: "Tautulli",
+    section: "tautulli",
+    key_setting: "api_key",
+    auth: Auth::Query,
+    version_path: "api/v2?cmd=get_tautulli_info",
+    version_pointer: "/response/data/tautulli_version",
+};
+
+const JELLYFIN: Service = Service {
+    name: "Jellyfin",
+    section: "jellyfin",
+    key_setting: "api_key",
+    auth: Auth::Header("X-Emby-Token"),
+    version_path: "System/Info",
+    version_pointer: "/Version",
+};
+
+const EMBY: Service = Service {
+    name: "Emby",
+    section: "emby",
+    ..JELLYFIN
+};
+
+const OVERSEERR: Service = Service {
+    name: "Overseerr",
+    section: "overseerr",
+    key_setting: "api_key",
+    auth: Auth::Header("X-Api-Key"),
+    version_path: "api/v1/settings/about",
+    version_pointer: "/version",
+};
+
+const RADARR: Service = Service {
+    name: "Radarr",
+    section: "radarr",
+    key_setting: "api_key",
+    auth: Auth::Header("X-Api-Key"),
+    version_path: "api/v3/system/status",
+    version_pointer: "/version",
+};
+
+const SONARR: Service = Service {
+    name: "Sonarr",
+    section: "sonarr",
+    ..RADARR
+};
+
+/// Writes a starter config, the commented template or the services asked for.
+/// An existing file is only overwritten with --force.
+pub async fn init(options: &ConfigInit) -> Result<()> {
+    let path = options
+        .path
+        .clone()
+        .unwrap_or_else(config::default_config_path);
+
+    if path.exists() && !options.force {
+        return Err(eyre!(
+            "{} already exists, pass --force to overwrite it.",
+            path.display()
+        ));
+    }
+
+    let contents = if options.interactive {
+        ask_config(&path).await?
+    } else if config::is_toml(&path) {
+        TEMPLATE.to_string()
+    } else {
+        return Err(eyre!(
+            "The starter config is TOML, so the path has to end in .toml (or use --interactive)."
+        ));
+    };
+
+    if let Some(parent) = path
+        .parent()
+        .filter(|parent| !parent.as_os_str().is_empty())
+    {
+        fs::create_dir_all(parent)
+            .map_err(|err| eyre!("Failed to create the folder {}: {}", parent.display(), err))?;
+    }
+    fs::write(&path, contents)
+        .map_err(|err| eyre!("Failed to write the config to {}: {}", path.display(), err))?;
+
+    if options.interactive {
+        message!("Wrote the config to {}.", path.display());
+    } else {
+        message!(
+            "Wrote a starter config to {}, fill in the settings marked REQUIRED before running.",
+            path.display()
+        );
+    }
+
+    Ok(())
+}
+
+/// Asks for the URL and API key of each service, checking them as they are entered,
+/// and puts together a config with only the services that were filled in, in the format of the path.
+async fn ask_config(path: &Path) -> Result<String> {
+    let providers = ["tautulli", "jellyfin", "emby"];
+    let provider = Select::new()
+        .with_prompt("Where does the watch history come from?")
+        .items(&providers)
+        .default(0)
+        .interact()?;
+
+    let mut services = match providers[provider] {
+        "jellyfin" => vec![&JELLYFIN],
+        "emby" => vec![&EMBY],
+        _ => vec![&PLEX, &TAUTULLI],
+    };
+    services.extend([&OVERSEERR, &RADARR, &SONARR]);
+
+    let mut config = Mapping::new();
+    config.insert("version".into(), config::CONFIG_VERSION.into());
+    if provider != 0 {
+        config.insert("watch_provider".into(), providers[provider].into());
+    }
+
+    message!("Leave a URL empty to leave that service out.");
+    for service in services {
+        if let Some(section) = ask_service(service).await? {
+            config.insert(service.section.into(), section.into());
+        }
+    }
+
+    if !config.contains_key("radarr") && !config.contains_key("sonarr") {
+        message!(
+            "{}",
+            "At least one of Radarr and Sonarr has to be added to the config before it can be used."
+                .yellow()
+        );
+    }
+
+    Ok(format!(
+        "# Written by media-cleaner config init. Every other setting is described in the readme.\n{}",
+        config::write_config(path, &config.into())?
+    ))
+}
+
+/// Asks for the URL and API key of a service until it answers, or it is kept as it is.
+/// `None` if the service is left out.
+async fn ask_service(service: &Service) -> Result<Option<Mapping>> {
+    loop {
+        let url: String = Input::new()
+            .with_prompt(format!("{} URL", service.name))
+            .allow_empty(true)
+            .interact_text()?;
+        let url = url.trim().to_string();
+        if url.is_empty() {
+            return Ok(None);
+        }
+
+        let key: String = Input::new()
+            .with_prompt(format!(
+                "{} {}",
+                service.name,
+                service.key_setting.replace('_', " ")
+            ))
+            .interact_text()?;
+        let key = key.trim().to_string();
+
+        let answered = match check_service(service, &url, &key).await {
+            Ok(version) => {
+                message!("{} {}  Version {}", "✓".green(), service.name, version);
+                true
+            }
+            Err(err) => {
+                message!("{} {}  {}", "✗".red(), service.name, err);
+                false
+            }
+        };
+
+        if answered
+            || !Confirm::new()
+                .with_prompt(format!("Enter the {} settings again?", service.name))
+                .default(true)
+                .interact()?
+        {
+            let mut section = Mapping::new();
+            section.insert("url".into(), url.into());
+            section.insert(service.key_setting.into(), key.into());
+            return Ok(Some(section));
+        }
+    }
+}
+
+/// Gets the version of an entered service, which only works with the right URL and API key.
+async fn check_service(service: &Service, url: &str, key: &str) -> Result<String> {
+    let client = reqwest::Client::builder().timeout(CHECK_TIMEOUT).build()?;
+    let request = match service.auth {
+        Auth::Query => client
+            .get(join_url(url, service.version_path))
+            .query(&[("apikey", key)]),
+        Auth::Header(header) => client
+            .get(join_url(url, service.version_path))
+            .header(header, key),
+    };
+
+    let response = request
+        .header("Accept", "application/json")
+        .send_logged(service.name)
+        .await
+        .map_err(|err| eyre!("Could not connect to {}: {}", url, err.without_url()))?;
+    if !response.status().is_success() {
+        return Err(ApiError::from_status(
+            service.name,
+            service.version_path,
+            response.status().as_u16(),
+        )
+        .into());
+    }
+
+    let body: Value = response.json().await.map_err(|_| {
+        eyre!(
+            "{} did not answer like {}, please check the URL (including any base path).",
+            url,
+            service.name
+        )
+    })?;
+
+    match body
+        .pointer(service.version_pointer)
+        .and_then(Value::as_str)
+    {
+        Some(version) => Ok(version.to_string()),
+        None => Err(eyre!(
+            "{} answered, but not with its version. Please check the {}.",
+            service.name,
+            service.key_setting.replace('_', " ")
+        )),
+    }
+}
@@ -0,0 +1,183 @@
+use chrono::Local;
+use color_eyre::{eyre::eyre, owo_colors::OwoColorize, Result};
+use std::{
+    env, fs,
+    path::Path,
+    process::{Child, Command},
+    sync::atomic::{AtomicBool, Ordering},
+    thread,
+    time::Duration,
+};
+
+use crate::{arguments::Daemon, config::Config, utils::human_date_time};
+
+/// The file that starts a run right away when it is touched, next to the config unless
+/// --run-now-file is given.
+const RUN_NOW_FILE: &str = "run-now";
+
+/// Set for the runs the daemon starts, which are given the same arguments and leave out the
+/// daemon command because of it.
+pub const RUN_ENV: &str = "MEDIA_CLEANER_DAEMON_RUN";
+
+/// How often the daemon looks whether it is time to run, or it was asked to stop.
+const POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Set by SIGTERM and SIGINT once they are handled, see `defer_stop_signals`.
+static STOP: AtomicBool = AtomicBool::new(false);
+
+/// Set by SIGHUP in the daemon.
+static RUN_NOW: AtomicBool = AtomicBool::new(false);
+
+/// Runs the cleanup with --auto on the schedule until it is stopped. Every run is a new process,
+/// so it reads the config again and nothing it fetched is kept until the next run. The next run
+/// is only planned once a run is done, so runs never overlap.
+pub fn run(daemon: &Daemon) -> Result<()> {
+    defer_stop_signals();
+    handle_run_now_signal();
+
+    let run_now_file = daemon
+        .run_now_file
+        .clone()
+        .unwrap_or_else(|| Config::file_next_to_config(RUN_NOW_FILE));
+    let args = run_args();
+    tracing::info!(args = %args.join(" "), schedule = %daemon.schedule, "Daemon started");
+    message!(
+        "Running the cleanup {}. Touch {} or send SIGHUP to run right away.",
+        daemon.schedule,
+        run_now_file.display()
+    );
+
+    loop {
+        let next_run = daemon
+            .schedule
+            .next_after(Local::now())
+            .ok_or_else(|| eyre!("Running {} never happens again.", daemon.schedule))?;
+        tracing::info!(next_run = %next_run.to_rfc3339(), "Next run scheduled");
+        message!("The next run is at {}.", human_date_time(next_run.into()));
+
+        while Local::now() < next_run && !run_now_requested(&run_now_file) {
+            if stop_requested() {
+                message!("Stopped.");
+                return Ok(());
+            }
+            thread::sleep(POLL_INTERVAL);
+        }
+
+        run_once(&args)?;
+        if stop_requested() {
+            message!("Stopped after the run.");
+            return Ok(());
+        }
+    }
+}
+
+/// Runs the cleanup once and waits for it, passing a stop on so it finishes the item it is on.
+fn run_once(args: &[String]) -> Result<()> {
+    tracing::info!("Run started");
+    let mut child = Command::new(env::current_exe()?)
+        .args(args)
+        .env(RUN_ENV, "1")
+        .spawn()
+        .map_err(|err| eyre!("Failed to start a run: {}", err))?;
+
+    let mut stop_passed_on = false;
+    let status = loop {
+        if let Some(status) = child.try_wait()? {
+            break status;
+        }
+        if stop_requested() && !stop_passed_on {
+            message!("Stopping the run, an item being removed is finished first.");
+            pass_on_stop(&child);
+            stop_passed_on = true;
+        }
+        // A run asked for in the middle of one is already taken care of by it.
+        RUN_NOW.store(false, Ordering::SeqCst);
+        thread::sleep(POLL_INTERVAL);
+    };
+
+    match status.code() {
+        Some(0) => tracing::info!("Run finished"),
+        code => {
+            tracing::warn!(code, "Run failed");
+            message!(
+                "{}",
+                format!(
+                    "The run failed ({}), the next one is still planned.",
+                    code.map_or("stopped by a signal".to_string(), |code| format!(
+                        "exit code {}",
+                        code
+                    ))
+                )
+                .yellow()
+            );
+        }
+    }
+
+    Ok(())
+}
+
+/// The arguments the daemon was started with for each run, with --auto as nobody is there
+/// to answer.
+fn run_args() -> Vec<String> {
+    let mut args: Vec<String> = env::args().skip(1).collect();
+    if !args.iter().any(|arg| arg == "--auto" || arg == "--yes") {
+        args.push("--auto".to_string());
+    }
+
+    args
+}
+
+/// Whether a run was asked for with SIGHUP or by touching the file, which is removed again.
+fn run_now_requested(run_now_file: &Path) -> bool {
+    let touched = run_now_file.exists() && fs::remove_file(run_now_file).is_ok();
+    let signalled = RUN_NOW.swap(false, Ordering::SeqCst);
+    if touched || signalled {
+        tracing::info!("Run asked for");
+    }
+
+    touched || signalled
+}
+
+/// Whether SIGTERM or SIGINT was received since `defer_stop_signals`.
+pub fn stop_requested() -> bool {
+    STOP.load(Ordering::SeqCst)
+}
+
+/// Keeps SIGTERM and SIGINT from ending the program right away, they only set `stop_requested`
+/// so what is being removed can be finished. Nothing happens on platforms without signals.
+pub fn defer_stop_signals() {
+    #[cfg(unix)]
+    // SAFETY: The handler only stores to an atomic, which is safe to do in a signal handler.
+    unsafe {
+        libc::signal(libc::SIGTERM, on_stop as *const () as libc::sighandler_t);
+        libc::signal(libc::SIGINT, on_stop as *const () as libc::sighandler_t);
+    }
+}
+
+fn handle_run_now_signal() {
+    #[cfg(unix)]
+    // SAFETY: As above.
+    unsafe {
+        libc::signal(libc::SIGHUP, on_run_now as *const () as libc::sighandler_t);
+    }
+}
+
+#[cfg(unix)]
+extern "C" fn on_stop(_: libc::c_int) {
+    STOP.store(true, Ordering::SeqCst);
+}
+
+#[cfg(unix)]
+extern "C" fn on_run_now(_: libc::c_int) {
+    RUN_NOW.store(true, Ordering::SeqCst);
+}
+
+fn pass_on_stop(child: &Child) {
+    #[cfg(unix)]
+    // SAFETY: Sending a signal to the run process, which is still running as it wasn't waited on.
+    unsafe {
+        libc::kill(child.id() as libc::pid_t, libc::SIGTERM);
+    }
+    #[cfg(not(unix))]
+    let _ = child;
+}
@@ -0,0 +1,102 @@
+use chrono::{DateTime, Utc};
+use color_eyre::Result;
+use futures::future::BoxFuture;
+use std::collections::HashSet;
+
+use crate::{
+    jellyfin::{api, responses::ItemsResponse, Jellyfin},
+    plex::PlexData,
+    shared::MediaType,
+    tautulli::{User, WatchHistory},
+    watch_provider::WatchProvider,
+};
+
+/// Emby as the watch provider. Jellyfin started as a fork of Emby and still has almost the same
+/// API, so everything but finding items by their provider ids is done the way it is for Jellyfin.
+pub struct Emby;
+
+impl WatchProvider for Emby {
+    fn name(&self) -> &'static str {
+        "Emby"
+    }
+
+    fn item_watches<'a>(
+        &'a self,
+        id: &'a str,
+        media_type: MediaType,
+        since: Option<DateTime<Utc>>,
+    ) -> BoxFuture<'a, Result<WatchHistory>> {
+        Jellyfin.item_watches(id, media_type, since)
+    }
+
+    fn users(&self) -> BoxFuture<'_, Result<&'static [User]>> {
+        Jellyfin.users()
+    }
+
+    fn active_item_ids(&self) -> BoxFuture<'_, Result<HashSet<String>>> {
+        Jellyfin.active_item_ids()
+    }
+
+    fn item_data<'a>(
+        &'a self,
+        id: &'a str,
+        media_type: MediaType,
+    ) -> BoxFuture<'a, Result<PlexData>> {
+        Jellyfin.item_data(id, media_type)
+    }
+
+    fn version(&self) -> BoxFuture<'_, Result<String>> {
+        Jellyfin.version()
+    }
+
+    fn concurrency(&self) -> usize {
+        Jellyfin.concurrency()
+    }
+
+    fn has_plex_ids(&self) -> bool {
+        false
+    }
+
+    fn find_item_id(
+        &self,
+        media_type: MediaType,
+        tmdb_id: Option<u32>,
+        tvdb_id: Option<u32>,
+    ) -> BoxFuture<'_, Result<Option<String>>> {
+        Box::pin(find_item_id(media_type, tmdb_id, tvdb_id))
+    }
+}
+
+/// Finds an item by the TMDB id of a movie or the TVDB id of a show, `None` if Emby doesn't
+/// have it or more than one item has the id.
+async fn find_item_id(
+    media_type: MediaType,
+    tmdb_id: Option<u32>,
+    tvdb_id: Option<u32>,
+) -> Result<Option<String>> {
+    let (provider_id, item_type) = match media_type {
+        MediaType::Movie => (tmdb_id.map(|id| format!("Tmdb.{}", id)), "Movie"),
+        MediaType::Tv => (tvdb_id.map(|id| format!("Tvdb.{}", id)), "Series"),
+        MediaType::Music => return Ok(None),
+    };
+    let provider_id = match provider_id {
+        Some(provider_id) => provider_id,
+        None => return Ok(None),
+    };
+
+    let items: ItemsResponse = api::get(
+        "/Items",
+        Some(vec![
+            ("AnyProviderIdEquals", &provider_id),
+            ("IncludeItemTypes", item_type),
+            ("Recursive", "true"),
+        ]),
+    )
+    .await?;
+
+    let mut items = items.items.into_iter();
+    match (items.next(), items.next()) {
+        (Some(item), None) => Ok(Some(item.id)),
+        _ => Ok(None),
+    }
+}
@@ -0,0 +1,177 @@
+use itertools::Itertools;
+use std::{collections::BTreeMap, fmt::Display};
+
+/// Why an item was left out of the list.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ExclusionReason {
+    /// It has one of the `exclude_tags` in Radarr or Sonarr.
+    ArrTag,
+    /// It's in one of the `protected_collections` in Plex.
+    ProtectedCollection(String),
+    /// It has one of the `protected_labels` in Plex.
+    PlexLabel(String),
+    /// It's on the Plex watchlist of these accounts.
+    Watchlist(Vec<String>),
+    /// It's in a Plex library that isn't cleaned up.
+    Library(String),
+    /// It was requested by one of the `protected_requesters`.
+    ProtectedRequester(String),
+    /// It was watched by one of the `protected_watchers`.
+    ProtectedWatcher(String),
+    /// It was watched this many days ago, within `keep_days_after_watch`.
+    RecentlyWatched { days_ago: i64, keep_days: u32 },
+    /// It was added this many days ago, within `keep_days_after_added`.
+    RecentlyAdded { days_ago: i64, keep_days: u32 },
+    /// It was watched this many days ago, fewer than `min_days_since_watch`.
+    WatchedWithin { days_ago: i64, min_days: i64 },
+    /// Its title matches this pattern from --exclude or `rules.exclude_titles`.
+    ExcludedTitle(String),
+    /// Its title doesn't match any --include pattern.
+    NotIncluded,
+}
+
+impl ExclusionReason {
+    /// The setting that left the item out.
+    fn rule(&self) -> &'static str {
+        match self {
+            Self::ArrTag => "exclude_tags",
+            Self::ProtectedCollection(_) => "protected_collections",
+            Self::PlexLabel(_) => "protected_labels",
+            Self::Watchlist(_) => "watchlist",
+            Self::Library(_) => "libraries",
+            Self::ProtectedRequester(_) => "protected_requesters",
+            Self::ProtectedWatcher(_) => "protected_watchers",
+            Self::RecentlyWatched { .. } => "keep_days_after_watch",
+            Self::RecentlyAdded { .. } => "keep_days_after_added",
+            Self::WatchedWithin { .. } => "min_days_since_watch",
+            Self::ExcludedTitle(_) => "exclude_titles",
+            Self::NotIncluded => "--include",
+        }
+    }
+
+    /// The reason without what differs per item, so items can be counted together.
+    fn summary(&self) -> String {
+        match self {
+            Self::Watchlist(_) => "on a watchlist".to_string(),
+            Self::RecentlyWatched { keep_days, .. } => {
+                format!("watched in the last {} days", keep_days)
+            }
+            Self::RecentlyAdded { keep_days, .. } => {
+                format!("added in the last {} days", keep_days)
+            }
+            Self::WatchedWithin { min_days, .. } => {
+                format!(
+                    "watched in the last {} days (min_days_since_watch)",
+                    min_days
+                )
+            }
+            reason => reason.to_string(),
+        }
+    }
+}
+
+impl Display for ExclusionReason {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::ArrTag => write!(f, "excluded tag in Radarr or Sonarr"),
+            Self::ProtectedCollection(collection) => {
+                write!(f, "protected collection '{}'", collection)
+            }
+            Self::PlexLabel(label) => write!(f, "plex label '{}'", label),
+            Self::Watchlist(accounts) => {
+                write!(f, "on the watchlist of {}", accounts.join(", "))
+            }
+            Self::Library(library) => write!(f, "library '{}'", library),
+            Self::ProtectedRequester(user) => write!(f, "requested by protected user '{}'", user),
+            Self::ProtectedWatcher(user) => write!(f, "watched by protected user '{}'", user),
+            Self::RecentlyWatched {
+                days_ago,
+                keep_days,
+            } => write!(
+                f,
+                "watched {} days ago, kept for {} days after a watch",
+                days_ago, keep_days
+            ),
+            Self::RecentlyAdded {
+                days_ago,
+                keep_days,
+            } => write!(
+                f,
+                "added {} days ago, kept for {} days after being added",
+                days_ago, keep_days
+            ),
+            Self::ExcludedTitle(pattern) => write!(f, "title matches '{}'", pattern),
+            Self::NotIncluded => write!(f, "title doesn't match --include"),
+            Self::WatchedWithin { days_ago, min_days } => write!(
+                f,
+                "watched {} days ago, only media unwatched for {} days is shown",
+                days_ago, min_days
+            ),
+        }
+    }
+}
+
+/// The items that were left out, with why.
+#[derive(Debug, Default)]
+pub struct Exclusions {
+    items: Vec<(String, ExclusionReason)>,
+    /// Items left out before their title was known, counted by reason.
+    counted: Vec<(String, usize)>,
+}
+
+impl Exclusions {
+    pub fn add(&mut self, title: String, reason: ExclusionReason) {
+        tracing::info!(item = %title, rule = reason.rule(), reason = %reason, "Excluded");
+        self.items.push((title, reason));
+    }
+
+    /// Counts items that were left out before their title was known, which already said so.
+    pub fn add_count(&mut self, reason: &str, count: usize) {
+        if count > 0 {
+            tracing::info!(count, reason, "Excluded");
+            self.counted.push((reason.to_string(), count));
+        }
+    }
+
+    pub fn items(&self) -> &[(String, ExclusionReason)] {
+        &self.items
+    }
+
+    /// How many items were left out for each reason, including the counted ones.
+    pub fn counts(&self) -> BTreeMap<String, usize> {
+        let mut counts = BTreeMap::new();
+        for (_, reason) in &self.items {
+            *counts.entry(reason.summary()).or_default() += 1;
+        }
+        for (reason, count) in &self.counted {
+            *counts.entry(reason.clone()).or_default() += count;
+        }
+        counts
+    }
+
+    /// Prints how many items were left out for each reason, like "2 items skipped: plex label 'keep'".
+    /// The counted items aren't in it, they were reported when they were left out.
+    pub fn print_summary(&self) {
+        let counts = self
+            .items
+            .iter()
+            .map(|(_, reason)| reason.summary())
+            .counts();
+        for (summary, count) in counts.into_iter().sorted() {
+            let plural = if count == 1 { "" } else { "s" };
+            message!("{} item{} skipped: {}.", count, plural, summary);
+        }
+    }
+
+    /// Lists every item that was left out with its reason, for --show-excluded.
+    pub fn print_items(&self) {
+        if self.items.is_empty() {
+            return;
+        }
+
+        message!("These items were left out:");
+        for (title, reason) in &self.items {
+            message!("- {}: {}", title, reason);
+        }
+    }
+}
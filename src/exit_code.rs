@@ -0,0 +1,166 @@
+use color_eyre::Report;
+use std::{fmt::Display, io};
+
+use crate::report::HandledItem;
+
+/// How the program ended, so scripts can tell what happened. Errors get their code by being
+/// wrapped in it with `wrap_err`, everything else that fails is an `Error`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExitCode {
+    /// Everything went fine, also when there was nothing to do.
+    Success = 0,
+    /// Anything unexpected, like a service returning an error while the data is gathered.
+    Error = 1,
+    /// The config or the arguments aren't valid.
+    Config = 2,
+    /// A service that is needed failed the health check.
+    HealthCheck = 3,
+    /// The run finished, but some items failed or were only partially removed.
+    DeletionsFailed = 4,
+    /// Whoever ran it stopped it, by cancelling or with Ctrl-C while being asked something.
+    Aborted = 5,
+}
+
+impl ExitCode {
+    pub fn of(err: &Report) -> Self {
+        if let Some(code) = err.downcast_ref::<Self>() {
+            return *code;
+        }
+
+        let interrupted = err
+            .chain()
+            .filter_map(|err| err.downcast_ref::<io::Error>())
+            .any(|err| err.kind() == io::ErrorKind::Interrupted);
+        if interrupted {
+            Self::Aborted
+        } else {
+            Self::Error
+        }
+    }
+
+    /// The code of a run that got to handle the chosen items.
+    pub fn of_handled(handled: &[HandledItem]) -> Self {
+        if handled.iter().any(HandledItem::failed) {
+            Self::DeletionsFailed
+        } else {
+            Self::Success
+        }
+    }
+
+    pub fn exit(self) -> ! {
+        std::process::exit(self as i32)
+    }
+}
+
+/// Has Ctrl-C end the program with `Aborted`. Runs that finish what they are removing first
+/// handle it themselves, see `daemon::defer_stop_signals`.
+pub fn abort_on_interrupt() {
+    #[cfg(unix)]
+    // SAFETY: The handler only calls `_exit`, which is safe to do in a signal handler.
+    unsafe {
+        libc::signal(
+            libc::SIGINT,
+            on_interrupt as *const () as libc::sighandler_t,
+        );
+    }
+}
+
+#[cfg(unix)]
+extern "C" fn on_interrupt(_: libc::c_int) {
+    // SAFETY: See `abort_on_interrupt`.
+    unsafe { libc::_exit(ExitCode::Aborted as i32) }
+}
+
+impl Display for ExitCode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let description = match self {
+            Self::Success => "Finished",
+            Self::Error => "Failed",
+            Self::Config => "The config or the arguments are not valid",
+            Self::HealthCheck => "A service failed the health check",
+            Self::DeletionsFailed => "Some items failed to be removed",
+            Self::Aborted => "Stopped before finishing",
+        };
+
+        write!(f, "{} (exit code {})", description, *self as i32)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use color_eyre::eyre::{eyre, WrapErr};
+
+    use super::*;
+    use crate::{
+        arguments::Arguments,
+        report::Outcome,
+        shared::{Action, MediaType},
+    };
+
+    fn handled(outcome: Outcome) -> HandledItem {
+        HandledItem {
+            title: "Some Movie".to_string(),
+            media_type: MediaType::Movie,
+            action: Action::Delete,
+            freed_bytes: 0,
+            outcome,
+        }
+    }
+
+    #[test]
+    fn invalid_arguments_are_a_config_error() {
+        let err = Arguments::parse(vec![
+            "media-cleaner".to_string(),
+            "--sort".to_string(),
+            "bogus".to_string(),
+        ])
+        .wrap_err(ExitCode::Config)
+        .unwrap_err();
+
+        assert_eq!(ExitCode::of(&err), ExitCode::Config);
+    }
+
+    #[test]
+    fn keeps_the_code_when_more_context_is_added() {
+        let err = Err::<(), _>(eyre!("Tautulli did not answer"))
+            .wrap_err(ExitCode::HealthCheck)
+            .wrap_err("Failed to start")
+            .unwrap_err();
+
+        assert_eq!(ExitCode::of(&err), ExitCode::HealthCheck);
+    }
+
+    #[test]
+    fn being_interrupted_is_aborting() {
+        let err = Report::new(io::Error::from(io::ErrorKind::Interrupted)).wrap_err("No answer");
+
+        assert_eq!(ExitCode::of(&err), ExitCode::Aborted);
+    }
+
+    #[test]
+    fn anything_else_is_an_error() {
+        assert_eq!(ExitCode::of(&eyre!("Something broke")), ExitCode::Error);
+    }
+
+    #[test]
+    fn some_items_failing_is_a_partial_failure() {
+        let partially_removed = Outcome::PartiallyRemoved {
+            remaining_paths: vec!["/movies/Some Movie".to_string()],
+        };
+        let failed = Outcome::failed(&eyre!("Radarr refused"));
+
+        assert_eq!(ExitCode::of_handled(&[]), ExitCode::Success);
+        assert_eq!(
+            ExitCode::of_handled(&[handled(Outcome::Done), handled(Outcome::Done)]),
+            ExitCode::Success
+        );
+        assert_eq!(
+            ExitCode::of_handled(&[handled(Outcome::Done), handled(partially_removed)]),
+            ExitCode::DeletionsFailed
+        );
+        assert_eq!(
+            ExitCode::of_handled(&[handled(failed), handled(Outcome::Done)]),
+            ExitCode::DeletionsFailed
+        );
+    }
+}
@@ -0,0 +1,208 @@
+use color_eyre::{eyre::eyre, owo_colors::OwoColorize, Result};
+use std::{future::Future, time::Instant};
+
+use crate::{
+    arr,
+    config::{Config, WatchProviderType, WatchlistAction},
+    overseerr, plex,
+    shared::MediaType,
+    trakt,
+    utils::major_version,
+    watch_provider,
+};
+
+/// The oldest major versions that are supported, as older ones have a different API.
+const MIN_TAUTULLI_VERSION: u32 = 2;
+const MIN_JELLYFIN_VERSION: u32 = 10;
+const MIN_EMBY_VERSION: u32 = 4;
+const MIN_OVERSEERR_VERSION: u32 = 1;
+
+/// The result of checking one service.
+struct ServiceCheck {
+    name: String,
+    /// Whether nothing can be done without the service, optional services only warn.
+    required: bool,
+    /// The version and round trip time, or why the service can't be used.
+    result: Result<String>,
+}
+
+/// Checks all configured services at the same time and prints how each went.
+/// Fails if any required service can't be used, before anything else is done.
+pub async fn check_services() -> Result<()> {
+    let config = Config::global();
+    let provider = watch_provider::provider();
+    let min_provider_version = match config.watch_provider {
+        WatchProviderType::Tautulli => MIN_TAUTULLI_VERSION,
+        WatchProviderType::Jellyfin => MIN_JELLYFIN_VERSION,
+        WatchProviderType::Emby => MIN_EMBY_VERSION,
+    };
+    let (watch_provider, overseerr, radarr, radarr_4k, sonarr, sonarr_4k, trakt, watchlist) = tokio::join!(
+        check(provider.name().to_string(), provider.version(), |version| {
+            check_min_version(version, min_provider_version)
+        }),
+        check_overseerr(config.overseerr.is_some()),
+        check_arr(MediaType::Movie, false, config.radarr.is_some()),
+        check_arr(MediaType::Movie, true, config.radarr_4k.is_some()),
+        check_arr(MediaType::Tv, false, config.sonarr.is_some()),
+        check_arr(MediaType::Tv, true, config.sonarr_4k.is_some()),
+        check_trakt(config.trakt.is_some()),
+        check_watchlist(
+            config
+                .plex
+                .as_ref()
+                .and_then(|plex| plex.watchlist.as_ref())
+                .map(|watchlist| watchlist.action)
+        ),
+    );
+
+    let checks = [
+        Some(watch_provider),
+        overseerr,
+        radarr,
+        radarr_4k,
+        sonarr,
+        sonarr_4k,
+        trakt,
+        watchlist,
+    ]
+    .into_iter()
+    .flatten()
+    .collect::<Vec<_>>();
+    let name_width = checks
+        .iter()
+        .map(|check| check.name.len())
+        .max()
+        .unwrap_or(0);
+
+    let mut failed = Vec::new();
+    for check in &checks {
+        let name = format!("{:<width$}", check.name, width = name_width);
+        match check.result {
+            Ok(ref details) => message!("{} {}  {}", "✓".green(), name, details),
+            Err(ref err) if check.required => {
+                message!("{} {}  {}", "✗".red(), name, err);
+                failed.push(check.name.as_str());
+            }
+            Err(ref err) => message!("{} {}  {}", "!".yellow(), name, err.yellow()),
+        }
+    }
+
+    if !failed.is_empty() {
+        return Err(eyre!(
+            "{} failed the health check. Please fix the config, or pass --skip-health-check to run anyway.",
+            failed.join(", ")
+        ));
+    }
+
+    Ok(())
+}
+
+/// Checks the request manager, `None` if it isn't configured.
+async fn check_overseerr(configured: bool) -> Option<ServiceCheck> {
+    if !configured {
+        return None;
+    }
+
+    Some(
+        check(overseerr::service(), overseerr::get_version(), |version| {
+            check_min_version(version, MIN_OVERSEERR_VERSION)
+        })
+        .await,
+    )
+}
+
+/// Checks a Radarr or Sonarr instance, `None` if it isn't configured.
+async fn check_arr(media_type: MediaType, is_4k: bool, configured: bool) -> Option<ServiceCheck> {
+    if !configured {
+        return None;
+    }
+
+    Some(
+        check(
+            arr::instance_name(media_type, is_4k),
+            arr::get_version(media_type, is_4k),
+            |version| arr::check_version(media_type, is_4k, version),
+        )
+        .await,
+    )
+}
+
+/// Checks the Trakt accounts, `None` if Trakt isn't configured. Trakt only adds to the watch
+/// history, so the run goes on without it.
+async fn check_trakt(configured: bool) -> Option<ServiceCheck> {
+    if !configured {
+        return None;
+    }
+
+    let start = Instant::now();
+    let result = trakt::check_accounts().await.map(|accounts| {
+        format!(
+            "{} accounts connected, answered in {}ms",
+            accounts,
+            start.elapsed().as_millis()
+        )
+    });
+
+    Some(ServiceCheck {
+        name: "Trakt".to_string(),
+        required: false,
+        result,
+    })
+}
+
+/// Gets the Plex watchlists, `None` if they aren't configured. They are only required when
+/// they keep watchlisted media from being cleaned up, otherwise they are only shown.
+async fn check_watchlist(action: Option<WatchlistAction>) -> Option<ServiceCheck> {
+    let action = action?;
+
+    let start = Instant::now();
+    let result = plex::get_watchlists().await.map(|watchlists| {
+        format!(
+            "{} watchlists, answered in {}ms",
+            watchlists.len(),
+            start.elapsed().as_millis()
+        )
+    });
+
+    Some(ServiceCheck {
+        name: "Plex watchlist".to_string(),
+        required: action == WatchlistAction::Skip,
+        result,
+    })
+}
+
+/// Times how long getting the version of a service takes, and checks it is supported.
+async fn check<F, V>(name: String, version: F, validate: V) -> ServiceCheck
+where
+    F: Future<Output = Result<String>>,
+    V: FnOnce(&str) -> Result<()>,
+{
+    let start = Instant::now();
+    let result = version.await.and_then(|version| {
+        let latency = start.elapsed();
+        validate(&version)?;
+
+        Ok(format!(
+            "Version {}, answered in {}ms",
+            version,
+            latency.as_millis()
+        ))
+    });
+
+    ServiceCheck {
+        name,
+        required: true,
+        result,
+    }
+}
+
+fn check_min_version(version: &str, min_version: u32) -> Result<()> {
+    match major_version(version) {
+        Some(major) if major < min_version => Err(eyre!(
+            "Version {} is too old, at least version {} is needed.",
+            version,
+            min_version
+        )),
+        _ => Ok(()),
+    }
+}
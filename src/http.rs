@@ -0,0 +1,333 @@
+use color_eyre::{eyre::eyre, owo_colors::OwoColorize, Result};
+use reqwest::{header::RETRY_AFTER, Method, RequestBuilder, Response, StatusCode, Url};
+use serde_json::Value;
+use std::{
+    error::Error,
+    fmt::Display,
+    future::Future,
+    time::{Duration, Instant},
+};
+use tokio::time::sleep;
+
+use crate::{
+    arguments::Arguments,
+    utils::{create_api_error_message, retry_delay},
+};
+
+/// What everything that would have been done with --dry-run starts with.
+pub const DRY_RUN_PREFIX: &str = "[DRY RUN]";
+
+/// Query parameters whose values are never logged.
+const SECRET_PARAMS: [&str; 4] = ["apikey", "api_key", "token", "x-plex-token"];
+
+/// The longest `Retry-After` that is waited for, so a misbehaving server can't stall the run.
+const MAX_RETRY_AFTER: Duration = Duration::from_secs(120);
+
+/// How often, and how long apart, a failing request is tried.
+pub struct RetryPolicy {
+    pub attempts: u32,
+    pub base_delay_ms: u64,
+    /// The timeout of the client, only used to explain timeouts in errors.
+    pub timeout: Option<Duration>,
+}
+
+/// The failures a service answers with, telling apart the ones that need the user to change something.
+#[derive(Debug)]
+pub enum ApiError {
+    BadApiKey {
+        service: String,
+    },
+    Forbidden {
+        service: String,
+    },
+    NotFound {
+        service: String,
+        path: String,
+    },
+    RateLimited {
+        service: String,
+    },
+    ServerError {
+        service: String,
+        code: u16,
+    },
+    Other {
+        service: String,
+        path: String,
+        code: u16,
+    },
+}
+
+impl ApiError {
+    pub fn from_status(service: &str, path: &str, code: u16) -> Self {
+        let service = service.to_string();
+        match code {
+            401 => Self::BadApiKey { service },
+            403 => Self::Forbidden { service },
+            404 => Self::NotFound {
+                service,
+                path: path.to_string(),
+            },
+            429 => Self::RateLimited { service },
+            500..=599 => Self::ServerError { service, code },
+            code => Self::Other {
+                service,
+                path: path.to_string(),
+                code,
+            },
+        }
+    }
+
+    /// The service that answered.
+    pub fn service(&self) -> &str {
+        match self {
+            Self::BadApiKey { service }
+            | Self::Forbidden { service }
+            | Self::NotFound { service, .. }
+            | Self::RateLimited { service }
+            | Self::ServerError { service, .. }
+            | Self::Other { service, .. } => service,
+        }
+    }
+
+    /// The HTTP status code it answered with.
+    pub fn status(&self) -> u16 {
+        match self {
+            Self::BadApiKey { .. } => 401,
+            Self::Forbidden { .. } => 403,
+            Self::NotFound { .. } => 404,
+            Self::RateLimited { .. } => 429,
+            Self::ServerError { code, .. } | Self::Other { code, .. } => *code,
+        }
+    }
+
+    /// Whether trying again later could help.
+    fn is_retryable(&self) -> bool {
+        matches!(self, Self::RateLimited { .. } | Self::ServerError { .. })
+    }
+}
+
+impl Display for ApiError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::BadApiKey { service } => write!(
+                f,
+                "Got 401 Unauthorized from {}, please check the {} API key in the config.",
+                service, service
+            ),
+            Self::Forbidden { service } => write!(
+                f,
+                "Got 403 Forbidden from {}, the API key is not allowed to do this. Please check the {} API key in the config.",
+                service, service
+            ),
+            Self::NotFound { service, path } => write!(
+                f,
+                "Got 404 Not Found from {} at {}. The media may no longer exist, or the URL in the config is wrong.",
+                service, path
+            ),
+            Self::RateLimited { service } => write!(
+                f,
+                "Got 429 Too Many Requests from {}, it is getting more requests than it can handle. Please try again later.",
+                service
+            ),
+            Self::ServerError { service, code } => write!(
+                f,
+                "Got {} server error from {}. Please try again later.",
+                code, service
+            ),
+            Self::Other {
+                service,
+                path,
+                code,
+            } => write!(f, "{}", create_api_error_message(*code, path, service)),
+        }
+    }
+}
+
+impl Error for ApiError {}
+
+/// Whether the error is a service answering that the path does not exist.
+pub fn is_not_found(err: &color_eyre::Report) -> bool {
+    matches!(
+        err.downcast_ref::<ApiError>(),
+        Some(ApiError::NotFound { .. })
+    )
+}
+
+/// Whether changes are only shown instead of made, with --dry-run or `list`, which never changes
+/// anything.
+pub fn is_dry_run() -> bool {
+    let args = Arguments::get_args();
+    args.dry_run || args.list
+}
+
+/// Sends a request that changes something on a service. Every change goes through here, so with
+/// --dry-run the request is printed instead of sent and `None` is returned.
+pub async fn send_change<F, Fut, T>(
+    method: Method,
+    url: &str,
+    body: Option<&Value>,
+    send: F,
+) -> Result<Option<T>>
+where
+    F: FnOnce() -> Fut,
+    Fut: Future<Output = Result<T>>,
+{
+    if !is_dry_run() {
+        return send().await.map(Some);
+    }
+
+    match body {
+        Some(body) => message!("{} {} {} {}", DRY_RUN_PREFIX.yellow(), method, url, body),
+        None => message!("{} {} {}", DRY_RUN_PREFIX.yellow(), method, url),
+    }
+    Ok(None)
+}
+
+/// Sends a request like `send`, logging at debug level which endpoint of which service
+/// was called, with what parameters, how long it took and how it answered. The values of
+/// secret parameters are left out, and headers (where most API keys go) aren't logged at all.
+pub trait SendLogged {
+    fn send_logged(self, service: &str) -> impl Future<Output = reqwest::Result<Response>>;
+}
+
+impl SendLogged for RequestBuilder {
+    fn send_logged(self, service: &str) -> impl Future<Output = reqwest::Result<Response>> {
+        // Only the method and URL are looked at, the body is never logged.
+        let target = self
+            .try_clone()
+            .and_then(|builder| builder.build().ok())
+            .map(|request| (request.method().clone(), request.url().clone()));
+        let service = service.to_string();
+
+        async move {
+            let start = Instant::now();
+            let result = self.send().await;
+            let duration_ms = start.elapsed().as_millis() as u64;
+
+            if let Some((method, url)) = target {
+                let params = redacted_params(&url);
+                match result {
+                    Ok(ref response) => tracing::debug!(
+                        service,
+                        %method,
+                        endpoint = url.path(),
+                        params,
+                        status = response.status().as_u16(),
+                        duration_ms,
+                        "Request answered"
+                    ),
+                    Err(ref err) => tracing::debug!(
+                        service,
+                        %method,
+                        endpoint = url.path(),
+                        params,
+                        error = failure_reason(err),
+                        duration_ms,
+                        "Request failed"
+                    ),
+                }
+            }
+
+            result
+        }
+    }
+}
+
+/// Why a request failed, from the source of the error, as its own message includes the URL.
+fn failure_reason(err: &reqwest::Error) -> String {
+    match err.source() {
+        _ if err.is_timeout() => "timed out".to_string(),
+        Some(source) => source.to_string(),
+        None => "no response".to_string(),
+    }
+}
+
+/// The query of a URL, with the values of secret parameters replaced.
+fn redacted_params(url: &Url) -> String {
+    url.query_pairs()
+        .map(|(name, value)| {
+            if SECRET_PARAMS.contains(&name.to_lowercase().as_str()) {
+                format!("{}=REDACTED", name)
+            } else {
+                format!("{}={}", name, value)
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("&")
+}
+
+/// Sends a request made by `send`, retrying server errors, rate limiting (waiting as long as the
+/// service asks for with `Retry-After`), timeouts and connection failures with exponential backoff.
+/// Any other unsuccessful status is returned as an [`ApiError`] right away.
+pub async fn send_with_retry<F, Fut>(
+    service: &str,
+    path: &str,
+    policy: &RetryPolicy,
+    send: F,
+) -> Result<Response>
+where
+    F: Fn() -> Fut,
+    Fut: Future<Output = reqwest::Result<Response>>,
+{
+    let mut attempts = 0;
+    loop {
+        attempts += 1;
+
+        let (last_error, retry_after) = match send().await {
+            Ok(response) if response.status().is_success() => return Ok(response),
+            Ok(response) => {
+                let error = ApiError::from_status(service, path, response.status().as_u16());
+                if !error.is_retryable() {
+                    return Err(error.into());
+                }
+                (error.to_string(), retry_after(&response))
+            }
+            Err(err) if err.is_timeout() => match policy.timeout {
+                Some(timeout) => (
+                    format!(
+                        "no response within the configured timeout of {} seconds",
+                        timeout.as_secs()
+                    ),
+                    None,
+                ),
+                None => ("no response in time".to_string(), None),
+            },
+            Err(err) if err.is_connect() => (
+                format!("{} while connecting to {}", err.without_url(), path),
+                None,
+            ),
+            Err(err) => return Err(err.without_url().into()),
+        };
+
+        if attempts >= policy.attempts {
+            return Err(eyre!(
+                "{} request to {} failed after {} attempts, the last attempt got: {}",
+                service,
+                path,
+                attempts,
+                last_error
+            ));
+        }
+
+        sleep(retry_after.unwrap_or_else(|| retry_delay(policy.base_delay_ms, attempts))).await;
+    }
+}
+
+/// How long a rate limited response asks to wait, only the form in seconds is understood.
+fn retry_after(response: &Response) -> Option<Duration> {
+    if response.status() != StatusCode::TOO_MANY_REQUESTS {
+        return None;
+    }
+
+    let seconds: u64 = response
+        .headers()
+        .get(RETRY_AFTER)?
+        .to_str()
+        .ok()?
+        .trim()
+        .parse()
+        .ok()?;
+
+    Some(Duration::from_secs(seconds).min(MAX_RETRY_AFTER))
+}
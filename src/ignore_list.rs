@@ -0,0 +1,68 @@
+use color_eyre::{eyre::eyre, owo_colors::OwoColorize, Result};
+use serde::{Deserialize, Serialize};
+use std::{collections::HashSet, fs};
+
+use crate::{config::Config, http};
+
+/// The file media chosen to be ignored is kept in, next to the config.
+const IGNORE_LIST_FILE: &str = "ignored_media.yaml";
+
+/// Media that was chosen to be ignored, so it isn't shown in later runs.
+#[derive(Debug, Deserialize, Serialize)]
+pub struct IgnoredMedia {
+    /// The id Overseerr has for the media.
+    pub media_id: u32,
+    /// Only kept so the file can be read and edited by hand.
+    pub title: String,
+}
+
+/// Reads the ids of the ignored media, an empty list if nothing was ignored yet.
+pub fn read_ignored_ids() -> Result<HashSet<u32>> {
+    Ok(read_ignored()?
+        .into_iter()
+        .map(|media| media.media_id)
+        .collect())
+}
+
+/// Adds media to the ignore list, only showing what would be added with --dry-run.
+pub fn add(media: Vec<IgnoredMedia>) -> Result<()> {
+    if media.is_empty() {
+        return Ok(());
+    }
+
+    let path = Config::file_next_to_config(IGNORE_LIST_FILE);
+    if http::is_dry_run() {
+        for media in &media {
+            message!(
+                "{} Add {} to the ignore list {}",
+                http::DRY_RUN_PREFIX.yellow(),
+                media.title,
+                path.display()
+            );
+        }
+        return Ok(());
+    }
+
+    let mut ignored = read_ignored()?;
+    ignored.extend(media);
+
+    let yaml = serde_yaml::to_string(&ignored)?;
+    fs::write(&path, yaml).map_err(|err| {
+        eyre!(
+            "Failed to write the ignore list {}: {}",
+            path.display(),
+            err
+        )
+    })
+}
+
+fn read_ignored() -> Result<Vec<IgnoredMedia>> {
+    let path = Config::file_next_to_config(IGNORE_LIST_FILE);
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+
+    let reader = fs::File::open(&path)?;
+    serde_yaml::from_reader(reader)
+        .map_err(|err| eyre!("Failed to read the ignore list {}: {}", path.display(), err))
+}
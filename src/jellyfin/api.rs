@@ -0,0 +1,71 @@
+use color_eyre::{eyre::eyre, Result};
+use serde::de::DeserializeOwned;
+
+use crate::{
+    config::{Config, Jellyfin, WatchProviderType},
+    http::{self, RetryPolicy, SendLogged},
+    utils::{create_param_string, join_url},
+};
+
+pub async fn get<T>(path: &str, params: Option<Vec<(&str, &str)>>) -> Result<T>
+where
+    T: DeserializeOwned,
+{
+    let config = config()?;
+    let client = reqwest::Client::new();
+    let url = format!(
+        "{}?{}",
+        join_url(&config.url, path),
+        create_param_string(params)
+    );
+
+    let response = http::send_with_retry(service(), path, &retry_policy(config), || {
+        client
+            .get(&url)
+            .header("X-Emby-Token", &config.api_key)
+            .send_logged(service())
+    })
+    .await?;
+
+    response.json().await.map_err(|err| {
+        eyre!(
+            "{} returned a response for {} that could not be read: {}",
+            service(),
+            path,
+            err
+        )
+    })
+}
+
+/// The settings of the server in use, as Emby is talked to through here as well.
+pub fn config() -> Result<&'static Jellyfin> {
+    let config = Config::global();
+    let (settings, name) = match config.watch_provider {
+        WatchProviderType::Emby => (&config.emby, "emby"),
+        _ => (&config.jellyfin, "jellyfin"),
+    };
+
+    settings.as_ref().ok_or_else(|| {
+        eyre!(
+            "{} is not configured, fill in the {} settings.",
+            service(),
+            name
+        )
+    })
+}
+
+/// The name of the server, Jellyfin or Emby.
+pub fn service() -> &'static str {
+    match Config::global().watch_provider {
+        WatchProviderType::Emby => "Emby",
+        _ => "Jellyfin",
+    }
+}
+
+fn retry_policy(config: &Jellyfin) -> RetryPolicy {
+    RetryPolicy {
+        attempts: config.retry_attempts,
+        base_delay_ms: config.retry_delay_ms,
+        timeout: None,
+    }
+}
@@ -0,0 +1,342 @@
+pub mod api;
+pub mod responses;
+
+use chrono::{DateTime, Utc};
+use color_eyre::{eyre::eyre, Result};
+use futures::future::{self, BoxFuture};
+use itertools::Itertools;
+use once_cell::sync::Lazy;
+use std::collections::HashSet;
+use tokio::sync::OnceCell;
+
+use self::responses::{
+    ItemResponse, ItemsResponse, SessionResponse, SystemInfo, UserItemData, UserResponse,
+};
+use crate::{
+    plex::PlexData,
+    shared::MediaType,
+    tautulli::{self, HistoryItem, User, WatchHistory},
+    watch_provider::WatchProvider,
+};
+
+/// Jellyfin and Emby count time in ticks of 100 nanoseconds.
+const TICKS_PER_MILLISECOND: i64 = 10_000;
+
+static USERS: Lazy<OnceCell<JellyfinUsers>> = Lazy::new(OnceCell::new);
+
+/// The users of Jellyfin. They are numbered in the order Jellyfin lists them, as
+/// the history is grouped by numeric user ids while Jellyfin's ids are GUIDs.
+struct JellyfinUsers {
+    /// The Jellyfin id of each user, at the same position as the user.
+    ids: Vec<String>,
+    users: Vec<User>,
+}
+
+/// Jellyfin as the watch provider, using the played state it keeps for every user.
+/// Emby is handled by this as well, as [`crate::emby::Emby`] only differs in a few places.
+pub struct Jellyfin;
+
+impl WatchProvider for Jellyfin {
+    fn name(&self) -> &'static str {
+        "Jellyfin"
+    }
+
+    fn item_watches<'a>(
+        &'a self,
+        id: &'a str,
+        media_type: MediaType,
+        since: Option<DateTime<Utc>>,
+    ) -> BoxFuture<'a, Result<WatchHistory>> {
+        Box::pin(get_item_watches(id, media_type, since))
+    }
+
+    fn users(&self) -> BoxFuture<'_, Result<&'static [User]>> {
+        Box::pin(async { get_users().await.map(|users| users.users.as_slice()) })
+    }
+
+    fn active_item_ids(&self) -> BoxFuture<'_, Result<HashSet<String>>> {
+        Box::pin(get_active_ids())
+    }
+
+    fn item_data<'a>(
+        &'a self,
+        id: &'a str,
+        _media_type: MediaType,
+    ) -> BoxFuture<'a, Result<PlexData>> {
+        Box::pin(async move {
+            match get_item(id).await? {
+                Some(item) => Ok(PlexData {
+                    title: item.name,
+                    section_id: None,
+                    added_at: None,
+                }),
+                None => Err(eyre!(
+                    "{} does not know the item with id {}.",
+                    api::service(),
+                    id
+                )),
+            }
+        })
+    }
+
+    fn version(&self) -> BoxFuture<'_, Result<String>> {
+        Box::pin(async {
+            let info: SystemInfo = api::get("/System/Info", None).await?;
+            Ok(info.version)
+        })
+    }
+
+    fn concurrency(&self) -> usize {
+        api::config().map_or(1, |config| config.concurrency)
+    }
+
+    fn has_plex_ids(&self) -> bool {
+        false
+    }
+}
+
+/// Gets all users that aren't disabled. The list is only requested once per run.
+async fn get_users() -> Result<&'static JellyfinUsers> {
+    USERS
+        .get_or_try_init(|| async {
+            let responses: Vec<UserResponse> = api::get("/Users", None).await?;
+            let (ids, users) = responses
+                .into_iter()
+                .filter(|user| {
+                    !user
+                        .policy
+                        .as_ref()
+                        .is_some_and(|policy| policy.is_disabled)
+                })
+                .enumerate()
+                .map(|(index, user)| {
+                    let watch_user = User {
+                        user_id: index as u32 + 1,
+                        username: user.name,
+                        friendly_name: None,
+                        email: None,
+                    };
+                    (user.id, watch_user)
+                })
+                .unzip();
+
+            Ok::<JellyfinUsers, color_eyre::Report>(JellyfinUsers { ids, users })
+        })
+        .await
+}
+
+/// Gets the watch history of an item from what every user has played of it.
+async fn get_item_watches(
+    id: &str,
+    media_type: MediaType,
+    since: Option<DateTime<Utc>>,
+) -> Result<WatchHistory> {
+    if get_item(id).await?.is_none() {
+        return Ok(WatchHistory::NoData);
+    }
+
+    let users = get_users().await?;
+    let config = api::config()?;
+    let user_items = future::try_join_all(
+        users
+            .ids
+            .iter()
+            .map(|user_id| get_user_items(user_id, id, media_type)),
+    )
+    .await?;
+
+    let rows = users
+        .users
+        .iter()
+        .zip(user_items)
+        .flat_map(|(user, items)| items.into_iter().filter_map(|item| history_row(user, item)))
+        .filter(|row| since.is_none_or(|since| row.date >= since.timestamp()))
+        .filter(|row| tautulli::user_counted(row, &config.exclude_users, &config.include_users))
+        .collect_vec();
+
+    WatchHistory::from_rows(&rows, &users.users, &media_type, id)
+}
+
+/// Finds an item by its id, `None` if Jellyfin doesn't know it.
+async fn get_item(id: &str) -> Result<Option<ItemResponse>> {
+    let items: ItemsResponse =
+        api::get("/Items", Some(vec![("Ids", id), ("Recursive", "true")])).await?;
+
+    Ok(items.items.into_iter().next())
+}
+
+/// Gets what a user has played of an item: the movie itself, or the episodes or tracks of a show or album.
+async fn get_user_items(
+    user_id: &str,
+    id: &str,
+    media_type: MediaType,
+) -> Result<Vec<ItemResponse>> {
+    let item_type = match media_type {
+        MediaType::Movie => {
+            let item: ItemResponse =
+                api::get(&format!("/Users/{}/Items/{}", user_id, id), None).await?;
+            return Ok(vec![item]);
+        }
+        MediaType::Tv => "Episode",
+        MediaType::Music => "Audio",
+    };
+
+    let items: ItemsResponse = api::get(
+        &format!("/Users/{}/Items", user_id),
+        Some(vec![
+            ("ParentId", id),
+            ("Recursive", "true"),
+            ("IncludeItemTypes", item_type),
+        ]),
+    )
+    .await?;
+
+    Ok(items.items)
+}
+
+/// Turns what a user played of an item into a history row like the ones Tautulli has. Items
+/// without a last played date are left out, like ones that were only marked as played, as
+/// when they were watched isn't known.
+fn history_row(user: &User, item: ItemResponse) -> Option<HistoryItem> {
+    let user_data = item.user_data?;
+    let last_played = DateTime::parse_from_rfc3339(user_data.last_played_date.as_deref()?).ok()?;
+
+    Some(HistoryItem {
+        user: user.username.clone(),
+        user_id: user.user_id,
+        date: last_played.timestamp(),
+        percent_complete: progress(&user_data, item.run_time_ticks),
+        media_index: item.index_number,
+        parent_media_index: item.parent_index_number,
+        group_count: Some(user_data.play_count),
+        group_ids: None,
+        state: None,
+        view_offset: u64::try_from(user_data.playback_position_ticks / TICKS_PER_MILLISECOND).ok(),
+        platform: None,
+        player: None,
+        product: None,
+    })
+}
+
+/// Jellyfin doesn't keep how much of an item was watched, only whether it is played and where
+/// playback was stopped. A played item counts as 100% watched, otherwise it's how far into the
+/// runtime playback got, or 0% if the runtime isn't known.
+fn progress(user_data: &UserItemData, run_time_ticks: Option<i64>) -> u8 {
+    if user_data.played {
+        return 100;
+    }
+
+    match run_time_ticks {
+        Some(run_time) if run_time > 0 => {
+            (user_data.playback_position_ticks.clamp(0, run_time) * 100 / run_time) as u8
+        }
+        _ => 0,
+    }
+}
+
+/// Gets the ids of everything being played, with the show, season and album they belong to.
+async fn get_active_ids() -> Result<HashSet<String>> {
+    let sessions: Vec<SessionResponse> = api::get("/Sessions", None).await?;
+
+    Ok(sessions
+        .into_iter()
+        .filter_map(|session| session.now_playing_item)
+        .flat_map(|item| [Some(item.id), item.series_id, item.season_id, item.album_id])
+        .flatten()
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn user() -> User {
+        User {
+            user_id: 1,
+            username: "alice".to_string(),
+            friendly_name: None,
+            email: None,
+        }
+    }
+
+    fn user_data(played: bool, playback_position_ticks: i64) -> UserItemData {
+        UserItemData {
+            played,
+            playback_position_ticks,
+            play_count: 0,
+            last_played_date: None,
+        }
+    }
+
+    #[test]
+    fn reads_emby_episodes() {
+        let fixture = include_str!("../../fixtures/emby/user_items_episodes.json");
+        let items: ItemsResponse = serde_json::from_str(fixture).unwrap();
+
+        // The episode that was only marked as played has no date, so it's left out.
+        let rows = items
+            .items
+            .into_iter()
+            .filter_map(|item| history_row(&user(), item))
+            .collect_vec();
+        let watches = rows
+            .iter()
+            .map(|row| {
+                (
+                    row.parent_media_index,
+                    row.media_index,
+                    row.percent_complete,
+                    row.view_offset,
+                )
+            })
+            .collect_vec();
+        assert_eq!(
+            watches,
+            [
+                (Some(1), Some(1), 100, Some(0)),
+                (Some(1), Some(2), 50, Some(1_590_000)),
+                (Some(0), None, 0, Some(60_000)),
+            ]
+        );
+        assert_eq!(rows[0].date, 1_704_226_530);
+        assert_eq!(rows[0].plays(), 2);
+    }
+
+    #[test]
+    fn reads_an_emby_movie() {
+        let fixture = include_str!("../../fixtures/emby/user_item_movie.json");
+        let item: ItemResponse = serde_json::from_str(fixture).unwrap();
+
+        let row = history_row(&user(), item).unwrap();
+
+        assert_eq!(row.percent_complete, 75);
+        assert_eq!((row.media_index, row.parent_media_index), (None, None));
+    }
+
+    #[test]
+    fn reads_the_items_found_by_their_provider_id() {
+        let fixture = include_str!("../../fixtures/emby/items_by_provider_id.json");
+        let items: ItemsResponse = serde_json::from_str(fixture).unwrap();
+
+        let ids = items
+            .items
+            .iter()
+            .map(|item| item.id.as_str())
+            .collect_vec();
+        assert_eq!(ids, ["5012"]);
+    }
+
+    #[test]
+    fn works_out_the_progress_from_the_ticks() {
+        let run_time = Some(36_000_000_000);
+
+        assert_eq!(progress(&user_data(false, 9_000_000_000), run_time), 25);
+        assert_eq!(progress(&user_data(false, 35_999_999_999), run_time), 99);
+        assert_eq!(progress(&user_data(true, 0), run_time), 100);
+        // Positions past the end or before the start are kept within the runtime.
+        assert_eq!(progress(&user_data(false, 40_000_000_000), run_time), 100);
+        assert_eq!(progress(&user_data(false, -1), run_time), 0);
+        assert_eq!(progress(&user_data(false, 9_000_000_000), Some(0)), 0);
+        assert_eq!(progress(&user_data(false, 9_000_000_000), None), 0);
+    }
+}
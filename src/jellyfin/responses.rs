@@ -0,0 +1,72 @@
+use serde::Deserialize;
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct UserResponse {
+    pub id: String,
+    pub name: String,
+    pub policy: Option<UserPolicy>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct UserPolicy {
+    #[serde(default)]
+    pub is_disabled: bool,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct ItemsResponse {
+    #[serde(default)]
+    pub items: Vec<ItemResponse>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct ItemResponse {
+    pub id: String,
+    pub name: String,
+    /// The episode or track number.
+    pub index_number: Option<u32>,
+    /// The season or disc number.
+    pub parent_index_number: Option<u32>,
+    /// The runtime in ticks of 100 nanoseconds.
+    pub run_time_ticks: Option<i64>,
+    pub user_data: Option<UserItemData>,
+}
+
+/// What a user has played of an item.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct UserItemData {
+    #[serde(default)]
+    pub played: bool,
+    /// Where playback was stopped, in ticks of 100 nanoseconds.
+    #[serde(default)]
+    pub playback_position_ticks: i64,
+    #[serde(default)]
+    pub play_count: u32,
+    pub last_played_date: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct SessionResponse {
+    pub now_playing_item: Option<NowPlayingItem>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct NowPlayingItem {
+    pub id: String,
+    pub series_id: Option<String>,
+    pub season_id: Option<String>,
+    pub album_id: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct SystemInfo {
+    pub version: String,
+}
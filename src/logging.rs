@@ -0,0 +1,151 @@
+use chrono::Utc;
+use color_eyre::{eyre::eyre, Result};
+use serde_json::{Map, Value};
+use std::{
+    env,
+    fmt::Debug,
+    fs::{File, OpenOptions},
+    io::Write,
+    path::Path,
+    str::FromStr,
+    sync::Mutex,
+};
+use tracing::{
+    dispatcher::{self, DefaultGuard},
+    field::{Field, Visit},
+    level_filters::LevelFilter,
+    Dispatch, Event, Subscriber,
+};
+use tracing_subscriber::{
+    filter::Targets,
+    layer::{Context, SubscriberExt},
+    Layer,
+};
+
+use crate::arguments::Arguments;
+
+/// What is logged to stderr without --log-level or RUST_LOG.
+const DEFAULT_LEVEL: LevelFilter = LevelFilter::WARN;
+
+/// What the libraries may log, so --log-level debug isn't drowned out by connection details.
+const LIBRARY_LEVEL: LevelFilter = LevelFilter::WARN;
+
+/// Reads a level for --log-level, one of error, warn, info, debug and trace.
+pub fn parse_level(level: &str) -> Result<LevelFilter> {
+    match level.to_lowercase().as_str() {
+        "error" => Ok(LevelFilter::ERROR),
+        "warn" => Ok(LevelFilter::WARN),
+        "info" => Ok(LevelFilter::INFO),
+        "debug" => Ok(LevelFilter::DEBUG),
+        "trace" => Ok(LevelFilter::TRACE),
+        _ => Err(eyre!(
+            "{} is not a log level, it has to be error, warn, info, debug or trace.",
+            level
+        )),
+    }
+}
+
+/// Starts logging to stderr, from --log-level or else RUST_LOG, and to the --log-file as JSON
+/// lines. Everything down to debug goes to the file, whatever is shown on stderr. Only RUST_LOG
+/// can get the libraries to log more than warnings.
+///
+/// The guard has to be kept until the end: errors made while reading the arguments already
+/// looked for a subscriber, which left the main thread remembering there is none.
+pub fn init() -> Result<DefaultGuard> {
+    let args = Arguments::get_args();
+    let console_filter = match args.log_level {
+        Some(level) => own_logs(level),
+        None => match env::var("RUST_LOG") {
+            Ok(filter) if !filter.trim().is_empty() => Targets::from_str(&filter)
+                .map_err(|err| eyre!("RUST_LOG is not a valid filter: {}", err))?,
+            _ => Targets::new().with_default(DEFAULT_LEVEL),
+        },
+    };
+    let console = tracing_subscriber::fmt::layer()
+        .with_writer(std::io::stderr)
+        .with_filter(console_filter);
+
+    let registry = tracing_subscriber::registry().with(console);
+    let dispatch = match args.log_file {
+        Some(ref path) => Dispatch::new(
+            registry.with(JsonLines::open(path)?.with_filter(own_logs(LevelFilter::DEBUG))),
+        ),
+        None => Dispatch::new(registry),
+    };
+
+    dispatcher::set_global_default(dispatch.clone())
+        .map_err(|err| eyre!("Failed to start logging: {}", err))?;
+    Ok(dispatcher::set_default(&dispatch))
+}
+
+/// Logs this program down to the level, and only warnings and errors of the libraries.
+fn own_logs(level: LevelFilter) -> Targets {
+    Targets::new()
+        .with_target(env!("CARGO_CRATE_NAME"), level)
+        .with_default(LIBRARY_LEVEL)
+}
+
+/// Writes every event as a line of JSON, with the time, level, target and fields.
+struct JsonLines {
+    file: Mutex<File>,
+}
+
+impl JsonLines {
+    fn open(path: &Path) -> Result<Self> {
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .map_err(|err| eyre!("Failed to open the log file {}: {}", path.display(), err))?;
+
+        Ok(Self {
+            file: Mutex::new(file),
+        })
+    }
+}
+
+impl<S: Subscriber> Layer<S> for JsonLines {
+    fn on_event(&self, event: &Event<'_>, _ctx: Context<'_, S>) {
+        let metadata = event.metadata();
+        let mut line = Map::new();
+        line.insert("timestamp".into(), Utc::now().to_rfc3339().into());
+        line.insert("level".into(), metadata.level().as_str().into());
+        line.insert("target".into(), metadata.target().into());
+        event.record(&mut FieldVisitor(&mut line));
+
+        // A log line that can't be written isn't worth stopping the run for.
+        if let Ok(mut file) = self.file.lock() {
+            let _ = writeln!(file, "{}", Value::Object(line));
+        }
+    }
+}
+
+/// Puts the fields of an event into a JSON object, keeping numbers and booleans as they are.
+struct FieldVisitor<'a>(&'a mut Map<String, Value>);
+
+impl Visit for FieldVisitor<'_> {
+    fn record_i64(&mut self, field: &Field, value: i64) {
+        self.0.insert(field.name().into(), value.into());
+    }
+
+    fn record_u64(&mut self, field: &Field, value: u64) {
+        self.0.insert(field.name().into(), value.into());
+    }
+
+    fn record_f64(&mut self, field: &Field, value: f64) {
+        self.0.insert(field.name().into(), value.into());
+    }
+
+    fn record_bool(&mut self, field: &Field, value: bool) {
+        self.0.insert(field.name().into(), value.into());
+    }
+
+    fn record_str(&mut self, field: &Field, value: &str) {
+        self.0.insert(field.name().into(), value.into());
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn Debug) {
+        self.0
+            .insert(field.name().into(), format!("{:?}", value).into());
+    }
+}
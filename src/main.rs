@@ -1,70 +1,669 @@
+/// Prints a message for whoever runs the program, like `println!`. The messages go to stderr when
+/// stdout is kept for a document scripts read, like with --output json, and lose their colors
+/// with --no-color.
+macro_rules! message {
+    () => {
+        message!("")
+    };
+    ($($arg:tt)*) => {
+        $crate::output::print_message(format_args!($($arg)*))
+    };
+}
+
 mod arguments;
 mod arr;
+mod auto;
 mod config;
+mod config_init;
+mod daemon;
+mod emby;
+mod exclusion;
+mod exit_code;
+mod health;
+mod http;
+mod ignore_list;
+mod jellyfin;
+mod logging;
 mod media_item;
+mod output;
 mod overseerr;
+mod picker;
 mod plex;
+mod progress;
+mod report;
+mod schedule;
 mod shared;
+mod table;
 mod tautulli;
+#[cfg(test)]
+mod test_support;
+mod title_filter;
+mod toml;
+mod trakt;
+mod trash;
 mod utils;
+mod watch_provider;
 
-use color_eyre::{eyre::eyre, Report, Result};
+use color_eyre::{
+    eyre::{eyre, WrapErr},
+    Report, Result,
+};
 use futures::future;
 use itertools::Itertools;
 use overseerr::MediaRequest;
-use shared::{Order, SortingOption, SortingValue};
-use std::{io, process::Command};
+use shared::{Action, MediaScope, SortingOption};
+use std::{
+    collections::{HashMap, HashSet},
+    fs,
+    io::{self, IsTerminal},
+    process::Command,
+};
+use tautulli::{RatingKeyCandidate, WatchHistory};
 
 use arguments::Arguments;
-use config::Config;
-use dialoguer::MultiSelect;
+use color_eyre::config::{HookBuilder, Theme};
+use color_eyre::owo_colors::OwoColorize;
+use config::{Config, WatchlistAction};
+use dialoguer::{console, MultiSelect, Select};
+use exclusion::{ExclusionReason, Exclusions};
+use exit_code::ExitCode;
 use media_item::{CompleteMediaItem, MediaItem};
+use output::OutputFormat;
+use progress::Progress;
+use report::{HandledItem, Outcome, RunReport};
+use table::Table;
+use title_filter::TitleFilter;
 
 use crate::{overseerr::ServerItem, utils::human_file_size};
 
+/// How long an item has to have gone unplayed to be discovered, if --min-days-since-watch isn't given.
+const DEFAULT_DISCOVER_DAYS: i64 = 365;
+
 #[tokio::main]
-async fn main() -> Result<()> {
-    color_eyre::install()?;
+async fn main() {
+    let code = match run().await {
+        Ok(()) => ExitCode::Success,
+        Err(err) => {
+            let code = ExitCode::of(&err);
+            eprintln!("Error: {:?}", err);
+            code
+        }
+    };
 
-    read_and_validate_config()?;
+    code.exit();
+}
 
-    Arguments::read_args()?;
+async fn run() -> Result<()> {
+    if output::colors_enabled() {
+        color_eyre::install()?;
+    } else {
+        HookBuilder::new().theme(Theme::new()).install()?;
+        console::set_colors_enabled(false);
+        console::set_colors_enabled_stderr(false);
+    }
 
-    let mut deletion_items = get_deletion_items().await?;
+    exit_code::abort_on_interrupt();
 
-    show_requests_result(&deletion_items)?;
+    // The arguments come first, as they can say where the config is.
+    Arguments::read_args().wrap_err(ExitCode::Config)?;
+    let _logging = logging::init()?;
+    let mut run_report = RunReport::start();
 
-    clear_screen()?;
+    if let Some(ref options) = Arguments::get_args().config_init {
+        return config_init::init(options).await;
+    }
+
+    if Arguments::get_args().migrate_config {
+        return config::migrate_file();
+    }
+
+    read_and_validate_config().wrap_err(ExitCode::Config)?;
+
+    if Arguments::get_args().show_config {
+        return Config::global().print_effective();
+    }
+
+    if let Some(ref title) = Arguments::get_args().restore {
+        return trash::restore(title);
+    }
+
+    if let Some(older_than) = Arguments::get_args().purge_trash {
+        return trash::purge(older_than);
+    }
+
+    if let Some(ref daemon) = Arguments::get_args().daemon {
+        return daemon::run(daemon);
+    }
+
+    if Arguments::get_args().check_connections {
+        return health::check_services()
+            .await
+            .wrap_err(ExitCode::HealthCheck);
+    }
+
+    if Arguments::get_args().trakt_login {
+        return trakt::login().await;
+    }
+
+    if let Some(ref rating_key) = Arguments::get_args().export_history {
+        return export_history(rating_key).await;
+    }
+
+    if Arguments::get_args().auto {
+        auto::check_protective_rules()?;
+    }
+
+    if Arguments::get_args().skip_health_check {
+        arr::check_versions().await
+    } else {
+        health::check_services().await
+    }
+    .wrap_err(ExitCode::HealthCheck)?;
+    arr::resolve_exclude_tags().await?;
+
+    let (mut deletion_items, excluded) = get_deletion_items().await?;
+    if let Some(ref query) = Arguments::get_args().show {
+        return show_item(&deletion_items, &excluded, query);
+    }
+    let auto = Arguments::get_args().auto;
+    let output = Arguments::output_format();
+    // Without a command, the candidates are also only listed when they are written for a script.
+    let list_only = Arguments::get_args().list || (!auto && output != OutputFormat::Text);
+
+    // Nobody is asked anything with --auto or when the candidates are only listed.
+    let chosen = if auto || list_only {
+        let sort = Arguments::get_args().sorting.clone().unwrap_or_default();
+        sort_items(&mut deletion_items, &sort);
+        message!("Sorted by {}.", sort);
+        if auto {
+            auto::choose_items(&deletion_items, &mut run_report)
+        } else {
+            limit_items(&mut deletion_items, &mut run_report);
+            Vec::new()
+        }
+    } else {
+        show_requests_result(&deletion_items)?;
+
+        clear_screen()?;
+
+        choose_items_to_delete(&mut deletion_items, &mut run_report).await?
+    };
+    let eligible = auto::eligible_count(&deletion_items);
+    let mut candidates = match output {
+        OutputFormat::Text => Vec::new(),
+        _ => deletion_items
+            .iter()
+            .map(CompleteMediaItem::to_candidate)
+            .collect_vec(),
+    };
+
+    let handled = if list_only {
+        Vec::new()
+    } else {
+        delete_chosen_items(&mut deletion_items, &chosen, &mut run_report).await?
+    };
+    run_report.finish(&excluded);
+    if auto {
+        auto::write_report(eligible, &handled, &run_report)?;
+    }
+    if !list_only {
+        run_report.print();
+    }
+    let code = ExitCode::of_handled(&handled);
 
-    let chosen = choose_items_to_delete(&mut deletion_items)?;
+    // The items were handled last to first.
+    for ((selection, _), handled) in chosen.iter().rev().zip(handled) {
+        if let Some(candidate) = candidates.get_mut(*selection) {
+            candidate.set_handled(handled);
+        }
+    }
+    match output {
+        OutputFormat::Text if list_only => list_items(&deletion_items),
+        OutputFormat::Text => (),
+        OutputFormat::Json => output::print_json(&candidates, &excluded, &run_report)?,
+        OutputFormat::Csv => output::print_csv(&candidates, &excluded),
+    }
 
-    delete_chosen_items(&mut deletion_items, &chosen).await?;
+    if code != ExitCode::Success {
+        code.exit();
+    }
 
     Ok(())
 }
 
 fn read_and_validate_config() -> Result<()> {
     if let Err(err) = Config::read_conf() {
-        return Err(eyre!("Failed to read the config, with the following error: {}.\nPlease make sure all fields are filled.", err));
+        return Err(eyre!("Failed to read the config: {}", err));
     }
 
     let config = Config::global();
     if let (None, None) = (&config.radarr, &config.sonarr) {
+        // The requests are enough to list the candidates, only without the sizes Radarr and Sonarr know.
+        if Arguments::get_args().list && config.overseerr.is_some() {
+            message!(
+                "{}",
+                "Radarr and Sonarr are not configured, so sizes are the ones Plex knows. Cleaning up requires at least one of them."
+                    .yellow()
+            );
+            return Ok(());
+        }
         return Err(eyre!("You have not configured Sonarr or Radarr. Application can't continue without at least one of these."));
     }
 
     Ok(())
 }
 
-async fn get_deletion_items() -> Result<Vec<CompleteMediaItem>> {
-    println!("Gathering all required data from your services.\nDepending on the amount of data and your connection speed, this could take a while...");
+/// Prints the candidates of `list` as a table, along with their total size.
+fn list_items(items: &[CompleteMediaItem]) {
+    let mut table = Table::new(&table::ITEM_COLUMNS);
+    items
+        .iter()
+        .for_each(|item| table.add_row(item.table_row()));
+    for line in table.lines(0) {
+        message!("{}", line);
+    }
 
-    let all_items = Arguments::get_args().all_media;
+    message!(
+        "{} candidates, {} in total.",
+        items.len(),
+        human_file_size(items.iter().map(CompleteMediaItem::get_disk_size).sum())
+    );
+}
+
+async fn export_history(rating_key: &str) -> Result<()> {
+    let json = tautulli::export_history(rating_key).await?;
+
+    match Arguments::get_args().export_file {
+        Some(ref path) => {
+            fs::write(path, json)?;
+            message!(
+                "Wrote the watch history of {} to {}.",
+                rating_key,
+                path.display()
+            );
+        }
+        None => message!("{}", json),
+    }
 
+    Ok(())
+}
+
+/// Prints the detail view of the candidates with the rating key or title, also matching part of
+/// the title if nothing has the whole title, like `restore`. Items the rules left out have no
+/// watch history gathered, so for them only the reason is shown.
+fn show_item(items: &[CompleteMediaItem], excluded: &Exclusions, query: &str) -> Result<()> {
+    let mut matches = items
+        .iter()
+        .filter(|item| item.rating_key() == Some(query) || item.title.eq_ignore_ascii_case(query))
+        .collect_vec();
+    if matches.is_empty() {
+        let query = query.to_lowercase();
+        matches = items
+            .iter()
+            .filter(|item| item.title.to_lowercase().contains(&query))
+            .collect();
+    }
+
+    let titles = matches
+        .iter()
+        .map(|item| item.title.to_lowercase())
+        .unique()
+        .count();
+    if titles > 1 {
+        return Err(eyre!(
+            "{} matches more than one candidate, pass the whole title or the rating key of one of them: {}.",
+            query,
+            matches.iter().map(|item| &item.title).unique().join(", ")
+        ));
+    }
+
+    if matches.is_empty() {
+        let reasons = excluded
+            .items()
+            .iter()
+            .filter(|(title, _)| title.eq_ignore_ascii_case(query))
+            .map(|(_, reason)| reason.to_string())
+            .collect_vec();
+        if reasons.is_empty() {
+            return Err(eyre!(
+                "Nothing called {} is a candidate for cleaning up.",
+                query
+            ));
+        }
+        return Err(eyre!(
+            "{} is left out of the cleanup: {}.",
+            query,
+            reasons.join(", ")
+        ));
+    }
+
+    print_line();
+    for item in matches {
+        message!("{}", item.detail_view());
+        print_line();
+    }
+
+    Ok(())
+}
+
+/// Gets the items that can be cleaned up, along with the ones the rules left out.
+async fn get_deletion_items() -> Result<(Vec<CompleteMediaItem>, Exclusions)> {
+    message!("Gathering all required data from your services.\nDepending on the amount of data and your connection speed, this could take a while...");
+    message!(
+        "Media counts as watched once {}% of it has been played (rules.watched_threshold).",
+        Config::global().rules.watched_threshold
+    );
+    let scope = MediaScope::of_run();
+    if scope != MediaScope::All {
+        message!("Looking at {} (--media-type or rules.media_type).", scope);
+    }
+    let min_days_since_watch = Arguments::get_args()
+        .min_days_since_watch
+        .or(Config::global().min_days_since_watch);
+    message!(
+        "Leaving out media requested in the last {} days (min_request_age_days){}.",
+        min_request_age_days(),
+        match min_days_since_watch {
+            Some(days) => format!(
+                " and media watched in the last {} days (min_days_since_watch)",
+                days
+            ),
+            None => String::new(),
+        }
+    );
+
+    // Without a request manager, everything Radarr and Sonarr manage is a candidate.
+    let mut media_items = match Config::global().overseerr {
+        Some(_) => get_requested_items().await?,
+        None => arr::get_managed_items()
+            .await?
+            .into_iter()
+            .map(MediaItem::from_managed)
+            .collect_vec(),
+    };
+    media_items.retain(|item| scope.includes(item.media_type));
+
+    let mut excluded = Exclusions::default();
+    let title_filter = TitleFilter::of_run();
+    if !title_filter.is_empty() {
+        let titles = arr::get_titles().await?;
+        media_items.retain(|item| {
+            let title = item.arr_title(&titles);
+            match title_filter.exclusion(title) {
+                Some(reason) => {
+                    let title = title.map_or_else(
+                        || format!("{} without a title in Radarr or Sonarr", item.media_type),
+                        str::to_string,
+                    );
+                    excluded.add(title, reason);
+                    false
+                }
+                None => true,
+            }
+        });
+    }
+
+    MediaItem::scope_to_seasons(&mut media_items);
+
+    media_items = remove_recent_requests(media_items, &mut excluded);
+
+    resolve_missing_rating_keys(&mut media_items).await?;
+
+    if Arguments::get_args().discover {
+        media_items = keep_discovered_items(media_items).await?;
+    }
+
+    let media_items = remove_active_items(media_items, &mut excluded).await?;
+
+    let library_filter = tautulli::get_library_filter().await?;
+    let media_items =
+        remove_excluded_libraries(media_items, library_filter.as_ref(), &mut excluded);
+
+    // Requests without a rating key never became available, they are kept so they can be cleaned up too.
+    let (media_items, items_without_rating_key): (Vec<MediaItem>, Vec<MediaItem>) = media_items
+        .into_iter()
+        .filter(|i| {
+            i.has_wanted_status()
+                && (i.has_manager_active() || Arguments::get_args().list)
+                && !i.user_ignored()
+        })
+        .partition(|item| item.rating_key.is_some());
+
+    let mut errors: Vec<Report> = Vec::new();
+
+    let history_keys = media_items
+        .iter()
+        .filter_map(|item| Some((item.rating_key.clone()?, item.media_type)))
+        .collect_vec();
+    let histories = watch_provider::get_items_watches(&history_keys).await;
+
+    let mut futures = Vec::new();
+    for (mut item, history) in media_items.into_iter().zip(histories) {
+        let history = match history {
+            Ok(WatchHistory::NoData) => resolve_stale_rating_key(&mut item).await,
+            history => history,
+        };
+
+        match history {
+            Ok(history) => futures.push(tokio::spawn(async move {
+                let item = item.into_complete_media(history).await?;
+
+                Ok::<CompleteMediaItem, Report>(item)
+            })),
+            Err(err) => errors.push(err),
+        }
+    }
+    for item in items_without_rating_key {
+        futures.push(tokio::spawn(item.into_not_on_disk()));
+    }
+
+    let open_issues = match Config::global().overseerr {
+        Some(_) => overseerr::get_open_issues().await?,
+        None => HashMap::new(),
+    };
+    let skip_items_with_issues = Config::global()
+        .overseerr
+        .as_ref()
+        .is_some_and(|overseerr| overseerr.skip_items_with_issues)
+        && !is_interactive();
+    let skip_watched_on_trakt = Config::global()
+        .trakt
+        .as_ref()
+        .is_some_and(|trakt| trakt.skip_watched_when_unattended)
+        && !is_interactive();
+
+    let progress = Progress::start("Getting the details", futures.len());
+    let mut results = Vec::new();
+    for future in futures {
+        let result = future.await?;
+        progress.inc(result.as_ref().map_or("", |item| item.title.as_str()));
+        results.push(result);
+    }
+    drop(progress);
+
+    let complete_items: Vec<CompleteMediaItem> = results
+        .into_iter()
+        .filter_map(|f| match f {
+            Ok(item) => Some(item),
+            Err(err) => {
+                errors.push(err);
+                None
+            }
+        })
+        // Never watched items are always kept.
+        .filter(
+            |item| match (min_days_since_watch, item.days_since_watch()) {
+                (Some(min_days), Some(days_ago)) if days_ago < min_days => {
+                    excluded.add(
+                        item.title.clone(),
+                        ExclusionReason::WatchedWithin { days_ago, min_days },
+                    );
+                    false
+                }
+                _ => true,
+            },
+        )
+        .map(|mut item| {
+            item.add_open_issues(&open_issues);
+            item
+        })
+        .filter(|item| !(skip_items_with_issues && item.has_open_issues()))
+        .filter(|item| !(skip_watched_on_trakt && item.watched_on_trakt()))
+        .unique_by(|item| {
+            (
+                item.title.clone(),
+                item.is_4k(),
+                item.seasons().map(<[u32]>::to_vec),
+            )
+        })
+        .sorted_by(|item1, item2| item1.title.cmp(&item2.title))
+        .collect();
+
+    let complete_items = match library_filter {
+        Some(ref filter) => complete_items
+            .into_iter()
+            .filter(|item| match filter.excluded_section(item.section_id()) {
+                Some(library) => {
+                    excluded.add(
+                        item.title.clone(),
+                        ExclusionReason::Library(library.to_string()),
+                    );
+                    false
+                }
+                None => true,
+            })
+            .collect_vec(),
+        None => complete_items,
+    };
+
+    let keep_days_after_watch = Arguments::get_args()
+        .keep_days_after_watch
+        .or(Config::global().rules.keep_days_after_watch);
+    let keep_days_after_added = Arguments::get_args()
+        .keep_days_after_added
+        .or(Config::global().rules.keep_days_after_added);
+    let complete_items = complete_items
+        .into_iter()
+        .filter(|item| {
+            match item.retention_exclusion(keep_days_after_watch, keep_days_after_added) {
+                Some(reason) => {
+                    excluded.add(item.title.clone(), reason);
+                    false
+                }
+                None => true,
+            }
+        })
+        .collect_vec();
+
+    let (tagged_items, complete_items): (Vec<_>, Vec<_>) = complete_items
+        .into_iter()
+        .partition(CompleteMediaItem::has_excluded_tag);
+    for item in tagged_items {
+        excluded.add(item.title, ExclusionReason::ArrTag);
+    }
+
+    let complete_items = match Config::global().plex {
+        Some(ref plex_config)
+            if !plex_config.protected_collections.is_empty()
+                || !plex_config.protected_labels.is_empty() =>
+        {
+            let reasons = future::join_all(complete_items.iter().map(|item| {
+                item.plex_exclusion(
+                    &plex_config.protected_collections,
+                    &plex_config.protected_labels,
+                )
+            }))
+            .await;
+
+            let mut unprotected = Vec::new();
+            for (item, reason) in complete_items.into_iter().zip(reasons) {
+                match reason {
+                    Ok(None) => unprotected.push(item),
+                    Ok(Some(reason)) => excluded.add(item.title, reason),
+                    Err(err) => errors.push(err),
+                }
+            }
+
+            unprotected
+        }
+        _ => complete_items,
+    };
+
+    let rules = &Config::global().rules;
+    let complete_items = if rules.protects_users() {
+        let reasons =
+            future::join_all(complete_items.iter().map(|item| item.protected_user(rules))).await;
+
+        let mut unprotected = Vec::new();
+        for (item, reason) in complete_items.into_iter().zip(reasons) {
+            match reason {
+                Ok(None) => unprotected.push(item),
+                Ok(Some(reason)) => excluded.add(item.title, reason),
+                Err(err) => errors.push(err.wrap_err(format!(
+                    "Failed to check whether {} is protected, so it was left out.",
+                    item.title
+                ))),
+            }
+        }
+
+        unprotected
+    } else {
+        complete_items
+    };
+
+    let watchlist = Config::global()
+        .plex
+        .as_ref()
+        .and_then(|plex| plex.watchlist.as_ref());
+    let mut complete_items = match watchlist {
+        Some(watchlist) => {
+            check_watchlists(complete_items, watchlist.action, &mut excluded, &mut errors).await?
+        }
+        None => complete_items,
+    };
+
+    if let Some(ref quality) = Arguments::get_args().quality {
+        let count = complete_items.len();
+        complete_items.retain(|item| item.quality_matches(quality));
+        message!(
+            "Left out {} items whose quality doesn't match --quality.",
+            count - complete_items.len()
+        );
+    }
+
+    excluded.print_summary();
+    if Arguments::get_args().show_excluded {
+        excluded.print_items();
+    }
+
+    let requester_ids = complete_items
+        .iter()
+        .filter_map(CompleteMediaItem::requester_id)
+        .collect_vec();
+    match overseerr::get_user_quotas(requester_ids).await {
+        Ok(quotas) => complete_items
+            .iter_mut()
+            .for_each(|item| item.add_requester_quota(&quotas)),
+        Err(err) => errors.push(err.wrap_err("Failed to get the request quotas from Overseerr.")),
+    }
+
+    show_potential_request_errors(errors)?;
+
+    Ok((complete_items, excluded))
+}
+
+/// Gets the requests from Overseerr, along with everything else on the server with `-C`
+/// or `--discover`. Media on the ignore list is left out.
+async fn get_requested_items() -> Result<Vec<MediaItem>> {
+    // Discovering looks through everything on the server, not just requests.
+    let all_items = Arguments::get_args().all_media || Arguments::get_args().discover;
+
+    let ignored_ids = ignore_list::read_ignored_ids()?;
     let mut media_items = MediaRequest::get_all()
         .await?
         .into_iter()
         .map(MediaItem::from_request)
+        .filter(|item| !item.media_id.is_some_and(|id| ignored_ids.contains(&id)))
         .collect_vec();
 
     // This is done by merging the two lists, because Overseerr does not send who requested the media along
@@ -73,77 +672,394 @@ async fn get_deletion_items() -> Result<Vec<CompleteMediaItem>> {
     //
     // If that was allowed, this could be made much nicer and more performance friendly.
     if all_items {
+        let requested_ids: HashSet<u32> = media_items
+            .iter()
+            .filter_map(|item| item.media_id)
+            .collect();
+
         let mut not_requested_media_items = ServerItem::get_all()
             .await?
             .into_iter()
+            .filter(|item| {
+                !requested_ids.contains(&item.media_id) && !ignored_ids.contains(&item.media_id)
+            })
             .map(MediaItem::from_server_item)
             .collect_vec();
 
         media_items.append(&mut not_requested_media_items);
+    }
 
-        media_items.sort_by(|item1, item2| {
-            (&item1.rating_key, !item1.request.is_some())
-                .cmp(&(&item2.rating_key, !item2.request.is_some()))
-        });
-        media_items.dedup_by(|item1, item2| item1.rating_key == item2.rating_key);
+    Ok(media_items)
+}
+
+/// Looks up the rating keys of available items the request manager has none for, as
+/// Jellyseerr only knows Jellyfin ids. The items are searched in Tautulli by their title,
+/// or on the media server by their TMDB or TVDB id when there is no Tautulli.
+async fn resolve_missing_rating_keys(media_items: &mut [MediaItem]) -> Result<()> {
+    let has_tautulli = Config::global().tautulli.is_some();
+
+    for item in media_items
+        .iter_mut()
+        .filter(|item| item.rating_key.is_none() && item.is_available())
+    {
+        item.rating_key = if has_tautulli {
+            find_rating_key(item).await?
+        } else {
+            item.find_server_id().await?
+        };
+    }
+
+    Ok(())
+}
+
+/// Tries to find the item in Tautulli by its title, when Tautulli does not know its rating key.
+/// Returns the history for the new rating key if one is found, and `NoData` otherwise.
+async fn resolve_stale_rating_key(item: &mut MediaItem) -> Result<WatchHistory> {
+    if Config::global().tautulli.is_none() {
+        return Ok(WatchHistory::NoData);
     }
 
-    let futures = media_items
+    let rating_key = match find_rating_key(item).await? {
+        Some(rating_key) => rating_key,
+        None => return Ok(WatchHistory::NoData),
+    };
+
+    let history = tautulli::get_item_watches(
+        &rating_key,
+        &item.media_type,
+        Arguments::get_args().watched_since,
+        false,
+    )
+    .await?;
+    item.rating_key = Some(rating_key);
+
+    Ok(history)
+}
+
+/// Searches Tautulli for an item with the title and year of the item.
+async fn find_rating_key(item: &MediaItem) -> Result<Option<String>> {
+    let (title, year) = match item.retrieve_title_and_year().await {
+        Ok(title_and_year) => title_and_year,
+        Err(_) => return Ok(None),
+    };
+
+    let candidates = tautulli::search_rating_keys(&title, year, item.media_type).await?;
+    if let Some(rating_key) = match_external_ids(item, &candidates).await {
+        return Ok(Some(rating_key));
+    }
+
+    choose_rating_key(&title, year, candidates)
+}
+
+/// Finds the only candidate Plex matched to the TMDB id (for movies) or TVDB id (for shows)
+/// of the item, so it can be used whatever its title is.
+async fn match_external_ids(item: &MediaItem, candidates: &[RatingKeyCandidate]) -> Option<String> {
+    let external_ids = future::join_all(
+        candidates
+            .iter()
+            .map(|candidate| tautulli::get_external_ids(&candidate.rating_key)),
+    )
+    .await;
+
+    let matches = candidates
+        .iter()
+        .zip(external_ids)
+        .filter(|(_, ids)| ids.as_ref().is_ok_and(|ids| item.has_external_ids(ids)))
+        .map(|(candidate, _)| candidate.rating_key.clone())
+        .collect_vec();
+
+    match matches.as_slice() {
+        [rating_key] => Some(rating_key.clone()),
+        _ => None,
+    }
+}
+
+fn choose_rating_key(
+    title: &str,
+    year: Option<i32>,
+    candidates: Vec<RatingKeyCandidate>,
+) -> Result<Option<String>> {
+    let exact_matches = candidates
+        .iter()
+        .filter(|candidate| candidate.is_exact_match(title, year))
+        .count();
+
+    if candidates.is_empty() {
+        message!(
+            "{}",
+            format!(
+                "Warning: {} is unknown to Tautulli, and no item with the same title was found.",
+                title
+            )
+            .yellow()
+        );
+        return Ok(None);
+    }
+
+    if exact_matches == 1 && candidates.len() == 1 {
+        return Ok(Some(candidates[0].rating_key.clone()));
+    }
+
+    if !is_interactive() {
+        if exact_matches == 1 {
+            return Ok(Some(candidates[0].rating_key.clone()));
+        }
+
+        message!(
+            "{}",
+            format!("Warning: {} is unknown to Tautulli, and no single exact match was found. Skipping its history.", title).yellow()
+        );
+        return Ok(None);
+    }
+
+    let mut options = candidates
+        .iter()
+        .map(|candidate| candidate.to_string())
+        .collect_vec();
+    options.push("None of these".to_string());
+
+    let chosen = Select::new()
+        .with_prompt(format!(
+            "The rating key of {} is unknown to Tautulli. Which of these is it?",
+            title
+        ))
+        .items(&options)
+        .default(0)
+        .interact()?;
+
+    Ok(candidates
+        .get(chosen)
+        .map(|candidate| candidate.rating_key.clone()))
+}
+
+/// Keeps only the items that Tautulli reports as not played for a long time,
+/// after listing all such items in the libraries.
+async fn keep_discovered_items(media_items: Vec<MediaItem>) -> Result<Vec<MediaItem>> {
+    let days = Arguments::get_args()
+        .min_days_since_watch
+        .or(Config::global().min_days_since_watch)
+        .unwrap_or(DEFAULT_DISCOVER_DAYS);
+    let library_items = tautulli::get_unplayed_library_items(days).await?;
+
+    message!(
+        "Found {} items in your libraries that have not been played in the last {} days:",
+        library_items.len(),
+        days
+    );
+    library_items.iter().for_each(|item| message!("- {}", item));
+
+    let discovered_keys: HashSet<&str> = library_items
+        .iter()
+        .map(|item| item.rating_key.as_str())
+        .collect();
+    let media_items = media_items
         .into_iter()
-        .filter(|i| i.is_available() && i.has_manager_active() && !i.user_ignored())
-        .map(|item| {
-            tokio::spawn(async move {
-                let item = item.into_complete_media().await?;
+        .filter(|item| match item.rating_key {
+            Some(ref rating_key) => discovered_keys.contains(rating_key.as_str()),
+            None => false,
+        })
+        .collect_vec();
 
-                Ok::<CompleteMediaItem, Report>(item)
-            })
+    if media_items.len() < library_items.len() {
+        message!(
+            "{} of them are not known to Overseerr, so they can't be deleted from here.",
+            library_items.len() - media_items.len()
+        );
+    }
+    message!();
+
+    Ok(media_items)
+}
+
+/// --min-request-age-days, or else `min_request_age_days`.
+fn min_request_age_days() -> i64 {
+    Arguments::get_args()
+        .min_request_age_days
+        .unwrap_or(Config::global().min_request_age_days)
+}
+
+/// Leaves out items that were requested (or added) too recently, so people get time to watch them.
+/// Items without any known date are kept.
+fn remove_recent_requests(
+    media_items: Vec<MediaItem>,
+    excluded: &mut Exclusions,
+) -> Vec<MediaItem> {
+    let min_age = min_request_age_days();
+
+    let (media_items, recent_items): (Vec<MediaItem>, Vec<MediaItem>) = media_items
+        .into_iter()
+        .partition(|item| match item.days_since_requested() {
+            Some(days) => days >= min_age,
+            None => true,
         });
 
-    let mut errors: Vec<Report> = Vec::new();
+    if !recent_items.is_empty() {
+        message!(
+            "Left out {} items requested less than {} days ago. Pass --min-request-age-days to change this.",
+            recent_items.len(),
+            min_age
+        );
+    }
+    excluded.add_count(
+        &format!("requested in the last {} days", min_age),
+        recent_items.len(),
+    );
 
-    let complete_items = future::try_join_all(futures)
-        .await?
+    let undated_items = media_items
+        .iter()
+        .filter(|item| item.days_since_requested().is_none())
+        .count();
+    if undated_items > 0 && min_age > 0 {
+        message!(
+            "Kept {} items without a request or added date, as their age is unknown.",
+            undated_items
+        );
+    }
+
+    media_items
+}
+
+async fn remove_active_items(
+    media_items: Vec<MediaItem>,
+    excluded: &mut Exclusions,
+) -> Result<Vec<MediaItem>> {
+    if Arguments::get_args().ignore_active {
+        return Ok(media_items);
+    }
+
+    let active_rating_keys = watch_provider::provider().active_item_ids().await?;
+    let (active_items, media_items): (Vec<MediaItem>, Vec<MediaItem>) = media_items
         .into_iter()
-        .filter_map(|f| match f {
-            Ok(item) => Some(item),
-            Err(err) => {
+        .partition(|item| match item.rating_key {
+            Some(ref rating_key) => active_rating_keys.contains(rating_key),
+            None => false,
+        });
+
+    if !active_items.is_empty() {
+        message!(
+            "Skipping {} items that are currently being streamed. Pass --ignore-active to include them.",
+            active_items.len()
+        );
+    }
+    excluded.add_count("being streamed", active_items.len());
+
+    Ok(media_items)
+}
+
+/// Leaves out the movies and shows in Plex libraries that aren't cleaned up, before anything
+/// else is fetched for them. Albums are only left out once their library is known.
+fn remove_excluded_libraries(
+    media_items: Vec<MediaItem>,
+    filter: Option<&tautulli::LibraryFilter>,
+    excluded: &mut Exclusions,
+) -> Vec<MediaItem> {
+    let filter = match filter {
+        Some(filter) => filter,
+        None => return media_items,
+    };
+
+    let (media_items, excluded_items): (Vec<MediaItem>, Vec<MediaItem>) = media_items
+        .into_iter()
+        .partition(|item| match item.rating_key {
+            Some(ref rating_key) => filter.allows_rating_key(rating_key),
+            None => true,
+        });
+
+    if !excluded_items.is_empty() {
+        message!(
+            "Left out {} items in Plex libraries that aren't cleaned up.",
+            excluded_items.len()
+        );
+    }
+    excluded.add_count(
+        "in a Plex library that isn't cleaned up",
+        excluded_items.len(),
+    );
+
+    media_items
+}
+
+/// Looks up the items in the Plex watchlists, leaving out the watchlisted ones or noting whose
+/// watchlists they're on. When skipping, not being able to get the watchlists fails the run, so
+/// nothing that's on one is shown by accident.
+async fn check_watchlists(
+    items: Vec<CompleteMediaItem>,
+    action: WatchlistAction,
+    excluded: &mut Exclusions,
+    errors: &mut Vec<Report>,
+) -> Result<Vec<CompleteMediaItem>> {
+    if let Err(err) = plex::get_watchlists().await {
+        return match action {
+            WatchlistAction::Skip => Err(err.wrap_err(
+                "Failed to get the Plex watchlists, so watchlisted media can't be left out.",
+            )),
+            WatchlistAction::Annotate => {
                 errors.push(err);
-                None
+                Ok(items)
             }
-        })
-        .unique_by(|item| item.title.clone())
-        .sorted_by(|item1, item2| item1.title.cmp(&item2.title))
-        .collect();
+        };
+    }
 
-    show_potential_request_errors(errors)?;
+    let accounts = future::join_all(items.iter().map(CompleteMediaItem::watchlist_accounts)).await;
 
-    Ok(complete_items)
+    let mut kept = Vec::new();
+    for (mut item, accounts) in items.into_iter().zip(accounts) {
+        match accounts {
+            Ok(accounts) if accounts.is_empty() => kept.push(item),
+            Ok(accounts) if action == WatchlistAction::Skip => {
+                excluded.add(item.title, ExclusionReason::Watchlist(accounts))
+            }
+            Ok(accounts) => {
+                item.set_watchlisted_by(accounts);
+                kept.push(item);
+            }
+            Err(err) if action == WatchlistAction::Skip => errors.push(err),
+            Err(err) => {
+                errors.push(err);
+                kept.push(item);
+            }
+        }
+    }
+    Ok(kept)
 }
 
 fn show_potential_request_errors(errs: Vec<Report>) -> Result<()> {
-    if errs.len() == 0 {
+    if errs.is_empty() {
+        return Ok(());
+    }
+
+    if !is_interactive() {
+        message!(
+            "{}",
+            format!(
+                "Got {} errors while gathering data, the errored items are left out:",
+                errs.len()
+            )
+            .yellow()
+        );
+        errs.iter().for_each(|err| message!("- {}", err));
         return Ok(());
     }
 
-    println!("You got {} errors while gathering data. Press y to show them, or any other input to continue with the errored items ignored.", errs.len());
+    message!("You got {} errors while gathering data. Press y to show them, or any other input to continue with the errored items ignored.", errs.len());
     let input = get_user_input()?;
-    if !input.starts_with("y") {
+    if !input.starts_with('y') {
         return Ok(());
     }
 
     errs.iter().enumerate().for_each(|(i, err)| {
-        println!("Error {} was {}", i, err);
+        message!("Error {} was {}", i, err);
         print_line();
     });
 
-    println!("Do you want to see the full stack traces? Press y. Otherwise continuing to deletion screen with errored items ignored.");
+    message!("Do you want to see the full stack traces? Press y. Otherwise continuing to deletion screen with errored items ignored.");
     let inp = get_user_input()?;
-    if !inp.starts_with("y") {
+    if !inp.starts_with('y') {
         return Ok(());
     }
 
     errs.iter().enumerate().for_each(|(i, err)| {
-        println!("Error {} was {:?}", i + 1, err);
+        message!("Error {} was {:?}", i + 1, err);
         print_line();
     });
 
@@ -154,13 +1070,13 @@ fn show_potential_request_errors(errs: Vec<Report>) -> Result<()> {
     Ok(())
 }
 
-fn show_requests_result(requests: &Vec<CompleteMediaItem>) -> Result<()> {
-    if requests.len() == 0 {
-        println!("You do not seem to have any valid requests, with data available.");
-        println!("Are you sure all your requests are available and downloaded? Or some data was unable to be acquired from other services.");
-        println!("Either try again later, or look over your requests.");
+fn show_requests_result(requests: &[CompleteMediaItem]) -> Result<()> {
+    if requests.is_empty() {
+        message!("You do not seem to have any valid requests, with data available.");
+        message!("Are you sure all your requests are available and downloaded? Or some data was unable to be acquired from other services.");
+        message!("Either try again later, or look over your requests.");
 
-        println!();
+        message!();
         wait(None)?;
         std::process::exit(0);
     }
@@ -168,31 +1084,150 @@ fn show_requests_result(requests: &Vec<CompleteMediaItem>) -> Result<()> {
     Ok(())
 }
 
-fn choose_items_to_delete(requests: &mut Vec<CompleteMediaItem>) -> Result<Vec<usize>> {
-    choose_sorting(requests)?;
+async fn choose_items_to_delete(
+    requests: &mut Vec<CompleteMediaItem>,
+    run_report: &mut RunReport,
+) -> Result<Vec<(usize, Action)>> {
+    let sort = choose_sorting(requests)?;
+    limit_items(requests, run_report);
 
     clear_screen()?;
 
-    let items_to_show = Config::global().items_shown;
-    let chosen: Vec<usize> = MultiSelect::new()
-        .with_prompt("Choose what media to delete (SPACE to select, ENTER to confirm selection)")
-        .max_length(items_to_show)
-        .items(&requests)
-        .interact()?;
+    let chosen = picker::pick_items(requests, sort, Config::global().items_shown)?;
 
-    if chosen.len() == 0 {
-        println!("No items selected. Exiting...");
+    if chosen.is_empty() {
+        message!("No items selected. Exiting...");
         std::process::exit(0);
     }
 
     clear_screen()?;
 
-    verify_chosen(requests, &chosen)?;
+    let mut chosen = choose_actions(requests, &chosen)?;
+    verify_chosen(requests, &chosen).await?;
 
+    // The actions were asked for in the order the items were shown, but they are removed by
+    // position, which needs them in ascending order.
+    chosen.sort_by_key(|(selection, _)| *selection);
     Ok(chosen)
 }
 
-fn choose_sorting(requests: &mut Vec<CompleteMediaItem>) -> Result<()> {
+/// Keeps the first of the sorted items, up to --limit and --max-bytes.
+fn limit_items(items: &mut Vec<CompleteMediaItem>, run_report: &mut RunReport) {
+    let count = auto::within_limits(items.iter().map(CompleteMediaItem::get_disk_size));
+    if count < items.len() {
+        message!(
+            "Only looking at the first {} of the {} items (--limit or --max-bytes).",
+            count,
+            items.len()
+        );
+    }
+    run_report.limit(items.len(), count);
+    items.truncate(count);
+}
+
+/// Asks what to do with each chosen item, unless an action was passed with --action.
+fn choose_actions(
+    requests: &mut [CompleteMediaItem],
+    chosen: &[usize],
+) -> Result<Vec<(usize, Action)>> {
+    if let Some(action) = Arguments::get_args().action {
+        return Ok(chosen
+            .iter()
+            .map(|selection| (*selection, action))
+            .collect());
+    }
+
+    let mut actions = Vec::new();
+    let mut selected_size = 0;
+    for selection in chosen {
+        let media_item = match requests.get_mut(*selection) {
+            Some(media_item) => media_item,
+            None => continue,
+        };
+        let size = match media_item.disk_size() {
+            Some(size) => human_file_size(size),
+            None => "unknown size".to_string(),
+        };
+        let can_delete_seasons = !media_item.removable_seasons().is_empty();
+        let choices = Action::ALL
+            .into_iter()
+            .filter(|action| *action != Action::DeleteSeasons || can_delete_seasons)
+            .collect_vec();
+
+        let action = Select::new()
+            .with_prompt(format!(
+                "What should be done with {} ({})? Total selected for deletion: {}",
+                media_item.title,
+                size,
+                human_file_size(selected_size)
+            ))
+            .items(&choices)
+            .default(0)
+            .interact()?;
+        let action = match choices[action] {
+            Action::DeleteSeasons => choose_seasons(media_item)?,
+            action => action,
+        };
+        if action.deletes() {
+            selected_size += media_item.get_disk_size();
+        }
+        actions.push((*selection, action));
+    }
+
+    Ok(actions)
+}
+
+/// Asks which seasons of a show to delete, with the seasons everyone has finished selected.
+/// Seasons someone is in the middle of are refused, and if no season is left the show is skipped.
+fn choose_seasons(media_item: &mut CompleteMediaItem) -> Result<Action> {
+    let seasons = media_item.removable_seasons();
+    let labels = seasons
+        .iter()
+        .map(|season| media_item.season_label(*season))
+        .collect_vec();
+    let defaults = seasons
+        .iter()
+        .map(|season| media_item.season_finished_by_all(*season))
+        .collect_vec();
+
+    let chosen = MultiSelect::new()
+        .with_prompt(format!(
+            "Choose the seasons of {} to delete (SPACE to select, ENTER to confirm selection)",
+            media_item.title
+        ))
+        .items(&labels)
+        .defaults(&defaults)
+        .interact()?;
+
+    let mut chosen_seasons = Vec::new();
+    for season in chosen.into_iter().map(|i| seasons[i]) {
+        let watching = media_item.watching_season(season);
+        if watching.is_empty() {
+            chosen_seasons.push(season);
+        } else {
+            message!(
+                "{}",
+                format!(
+                    "Not deleting season {} of {}, as {} is in the middle of an episode of it.",
+                    season,
+                    media_item.title,
+                    watching.join(", ")
+                )
+                .yellow()
+            );
+        }
+    }
+
+    if chosen_seasons.is_empty() {
+        message!("No seasons of {} will be deleted.", media_item.title);
+        return Ok(Action::Skip);
+    }
+
+    media_item.set_seasons(chosen_seasons);
+    Ok(Action::DeleteSeasons)
+}
+
+fn choose_sorting(requests: &mut [CompleteMediaItem]) -> Result<SortingOption> {
     clear_screen()?;
 
     let args = Arguments::get_args();
@@ -201,107 +1236,315 @@ fn choose_sorting(requests: &mut Vec<CompleteMediaItem>) -> Result<()> {
         Some(ref sort) => sort.clone(),
         None => choose_sorting_dialogue()?,
     };
+    sort_items(requests, &sort);
 
-    match sort.sorting_value {
-        SortingValue::Name => (),
-        SortingValue::Size => requests.sort_by_key(|req| req.get_disk_size()),
-        SortingValue::Type => requests.sort_by_key(|req| req.media_type),
-    };
-
-    match sort.sorting_direction {
-        Order::Asc => (),
-        Order::Desc => requests.reverse(),
-    };
+    Ok(sort)
+}
 
-    Ok(())
+fn sort_items(requests: &mut [CompleteMediaItem], sort: &SortingOption) {
+    requests.sort_by(|a, b| sort.compare(a, b));
 }
 
 fn choose_sorting_dialogue() -> Result<SortingOption> {
     loop {
-        println!("Choose sorting method:");
-        println!("Name - Ascending: n (or just enter, it's the default)");
-        println!("Name - Descending: nd");
-        println!("Size - Descending: s");
-        println!("Size - Ascending: sa");
-        println!("Type - Descending: t");
+        message!("Choose sorting method:");
+        message!("Name - Ascending: n (or just enter, it's the default)");
+        message!("Name - Descending: nd");
+        message!("Size - Descending: s");
+        message!("Size - Ascending: sa");
+        message!("Type - Descending: t");
+        message!("Last watched - Oldest first (never watched first): w");
+        message!("Last watched - Newest first (never watched still first): wd");
+        message!("Quota pressure - Requesters closest to their quota first: q");
+        message!("Or a name with an optional :asc or :desc, like size, last-watched, request-age, title or requester");
 
         let input = get_user_input()?;
 
         if let Ok(sort) = SortingOption::from_str(&input) {
             return Ok(sort);
         }
-        if input.eq("") {
+        if input.is_empty() {
             return Ok(SortingOption::default());
         }
     }
 }
 
-fn verify_chosen(requests: &Vec<CompleteMediaItem>, chosen: &Vec<usize>) -> Result<()> {
+async fn verify_chosen(requests: &[CompleteMediaItem], chosen: &[(usize, Action)]) -> Result<()> {
     let total_size: String = human_file_size(
         chosen
             .iter()
-            .filter_map(|selection| {
-                if let Some(media_item) = requests.get(*selection) {
-                    Some(media_item.get_disk_size())
-                } else {
-                    None
-                }
+            .filter(|(_, action)| action.deletes())
+            .filter_map(|(selection, _)| {
+                requests
+                    .get(*selection)
+                    .map(|media_item| media_item.get_disk_size())
             })
             .sum(),
     );
 
-    println!(
-        "Are you sure you want to delete the following items ({}):",
+    if chosen
+        .iter()
+        .all(|(_, action)| matches!(action, Action::Skip | Action::Ignore))
+    {
+        return Ok(());
+    }
+
+    message!(
+        "Are you sure you want to do the following ({} will be deleted):",
         total_size
     );
-    chosen.iter().for_each(|selection| {
-        if let Some(media_item) = requests.get(*selection) {
-            let media_type = media_item.media_type;
-            println!("- {} - {}", &media_item.title, media_type.to_string());
-        } else {
-            println!("- Unknown item");
+    let mut table = Table::new(&[&["Action"][..], &table::ITEM_COLUMNS].concat());
+    for (selection, action) in chosen {
+        if let Some(media_item) = requests
+            .get(*selection)
+            .filter(|_| !matches!(action, Action::Skip | Action::Ignore))
+        {
+            table.add_row([vec![action.to_string()], media_item.table_row()].concat());
         }
-    });
+    }
+    table
+        .lines(2)
+        .iter()
+        .for_each(|line| message!("  {}", line));
+    message!();
+
+    for (selection, action) in chosen {
+        if matches!(action, Action::Skip | Action::Ignore) {
+            continue;
+        }
+
+        let media_item = match requests.get(*selection) {
+            Some(media_item) => media_item,
+            None => {
+                message!("- Unknown item");
+                continue;
+            }
+        };
+
+        // The details are only fetched here, for the few chosen items.
+        match media_item.details().await {
+            Ok(Some(details)) => {
+                message!("- {}: {} - {}", action, details, media_item.media_type);
+                if let Some(overview) = details.short_overview() {
+                    message!("    {}", overview);
+                }
+            }
+            _ => message!(
+                "- {}: {} - {}",
+                action,
+                &media_item.title,
+                media_item.media_type
+            ),
+        }
+        message!("    {}", media_item);
+        if action.deletes() && media_item.deletes_from_plex() {
+            message!(
+                "    {}",
+                "Will delete directly from Plex (not managed by Radarr/Sonarr).".red()
+            );
+        }
+    }
 
-    println!("\ny/n:");
+    message!("\ny/n:");
     let user_input = get_user_input()?;
 
-    if !user_input.starts_with("y") {
-        println!("Cancelling...");
-        std::process::exit(0);
+    if !user_input.starts_with('y') {
+        message!("Cancelling...");
+        ExitCode::Aborted.exit();
     }
 
     Ok(())
 }
 
+/// Handles the chosen items, counting each in the run report. Returns what happened to each of
+/// them, where removals that left files on disk count as failed. The chosen items have to be in
+/// ascending order, as they are taken out of `requests` from the back.
 async fn delete_chosen_items(
     requests: &mut Vec<CompleteMediaItem>,
-    chosen: &Vec<usize>,
-) -> Result<()> {
-    let mut errs: Vec<(String, Report)> = Vec::new();
+    chosen: &[(usize, Action)],
+    run_report: &mut RunReport,
+) -> Result<Vec<HandledItem>> {
+    let mut partially_removed: Vec<(String, Vec<String>)> = Vec::new();
+    let mut removed = 0;
+    let mut freed_size = 0;
+    let mut ignored = Vec::new();
+    let mut handled = Vec::new();
+    // The Plex library sections anything was removed from, to be scanned afterwards.
+    let mut removed_sections = HashSet::new();
+    // Nobody is there to stop an unattended run at the right moment, so a stop waits for the current item.
+    if Arguments::get_args().auto {
+        daemon::defer_stop_signals();
+    }
 
-    for selection in chosen.into_iter().rev() {
+    for (position, (selection, action)) in chosen.iter().rev().enumerate() {
+        if daemon::stop_requested() {
+            message!(
+                "{}",
+                format!(
+                    "Stopping, the other {} chosen items are left alone.",
+                    chosen.len() - position
+                )
+                .yellow()
+            );
+            break;
+        }
         let media_item = requests.swap_remove(*selection);
         let title = media_item.title.clone();
-        if let Err(err) = media_item.remove_from_server().await {
-            errs.push((title, err));
+        let media_type = media_item.media_type;
+        let section_id = media_item.section_id().map(str::to_string);
+        let freed_before = freed_size;
+        let result = match action {
+            Action::Delete | Action::DeleteAndBlock | Action::DeleteSeasons => {
+                let block = *action == Action::DeleteAndBlock;
+                match media_item.remove_from_server(&mut freed_size, block).await {
+                    Ok(remaining) if remaining.is_empty() => {
+                        removed += 1;
+                        removed_sections.extend(section_id);
+                        Ok(Outcome::Done)
+                    }
+                    Ok(remaining) => {
+                        removed += 1;
+                        partially_removed.push((title.clone(), remaining.clone()));
+                        Ok(Outcome::PartiallyRemoved {
+                            remaining_paths: remaining,
+                        })
+                    }
+                    Err(err) => Err(err),
+                }
+            }
+            Action::DeleteFiles => match media_item.remove_files(&mut freed_size).await {
+                Ok(()) => {
+                    removed += 1;
+                    removed_sections.extend(section_id);
+                    Ok(Outcome::Done)
+                }
+                Err(err) => Err(err),
+            },
+            Action::Unmonitor => media_item.unmonitor().await.map(|()| Outcome::Done),
+            Action::Skip => Ok(Outcome::Done),
+            Action::Ignore => {
+                match media_item.to_ignored() {
+                    Some(media) => ignored.push(media),
+                    None => message!(
+                        "{}",
+                        format!(
+                            "{} can't be added to the ignore list, as only media Overseerr knows can be ignored.",
+                            title
+                        )
+                        .yellow()
+                    ),
+                }
+                Ok(Outcome::Done)
+            }
+        };
+
+        handled.push(HandledItem {
+            title,
+            media_type,
+            action: *action,
+            freed_bytes: freed_size - freed_before,
+            outcome: result.unwrap_or_else(|err| Outcome::failed(&err)),
+        });
+    }
+
+    if let Err(err) = ignore_list::add(ignored) {
+        handled
+            .iter_mut()
+            .filter(|item| item.action == Action::Ignore)
+            .for_each(|item| item.outcome = Outcome::failed(&err));
+    }
+    handled.iter().for_each(|item| run_report.add(item));
+
+    let scope = MediaScope::of_run();
+    if scope != MediaScope::All {
+        message!("This run covered {}.", scope);
+    }
+    if !partially_removed.is_empty() {
+        message!(
+            "{}",
+            format!(
+                "Partially removed {} items, they are gone from Radarr or Sonarr but their files are still on disk:",
+                partially_removed.len()
+            )
+            .yellow()
+        );
+        for (title, paths) in &partially_removed {
+            message!("- {}: {}", title, paths.join(", "));
         }
     }
+    if removed > 0
+        && Config::global()
+            .overseerr
+            .as_ref()
+            .is_some_and(|overseerr| overseerr.trigger_sync)
+    {
+        trigger_availability_sync().await;
+    }
+    if Config::global()
+        .plex
+        .as_ref()
+        .is_some_and(|plex| plex.refresh_after_delete)
+    {
+        refresh_plex_sections(&removed_sections).await;
+    }
 
-    if errs.len() > 0 {
-        println!("Had some errors deleting items:\n");
-        errs.iter().for_each(|err| {
-            println!(
-                "Got the following error while deleting {}: {}",
-                err.0, err.0
-            );
-            print_line();
-        });
+    Ok(handled)
+}
 
-        wait(None)?;
+/// Asks Plex to scan the library sections media was removed from, failing only prints
+/// a warning as the next scheduled scan will catch up.
+async fn refresh_plex_sections(section_ids: &HashSet<String>) {
+    for section_id in section_ids.iter().sorted() {
+        match plex::refresh_section(section_id).await {
+            Ok(()) if http::is_dry_run() => (),
+            Ok(()) => message!("Asked Plex to scan library section {}.", section_id),
+            Err(err) => message!(
+                "{}",
+                format!(
+                    "Warning: Could not ask Plex to scan library section {}, the removed media shows until its next scan: {}",
+                    section_id, err
+                )
+                .yellow()
+            ),
+        }
     }
+}
 
-    Ok(())
+/// Asks the request manager to sync availability, failing only prints a warning as the media
+/// is already removed and the next scheduled sync will catch up.
+async fn trigger_availability_sync() {
+    let service = overseerr::service();
+    match overseerr::trigger_availability_sync().await {
+        Ok(true) if http::is_dry_run() => (),
+        Ok(true) => message!("Triggered {} availability sync.", service),
+        Ok(false) => message!(
+            "{}",
+            format!(
+                "Warning: {} has no availability sync job, removed media is shown as available until its next scan.",
+                service
+            )
+            .yellow()
+        ),
+        Err(err) => message!(
+            "{}",
+            format!(
+                "Warning: Failed to trigger the {} availability sync: {}",
+                service, err
+            )
+            .yellow()
+        ),
+    }
+}
+
+/// Whether someone can answer prompts, which isn't the case when the input isn't a terminal,
+/// with --auto or when the output is for a script.
+fn is_interactive() -> bool {
+    let args = Arguments::get_args();
+    io::stdin().is_terminal()
+        && !args.auto
+        && !args.list
+        && args.show.is_none()
+        && Arguments::output_format() == OutputFormat::Text
 }
 
 fn clear_screen() -> Result<()> {
@@ -323,21 +1566,21 @@ fn get_user_input() -> Result<String> {
 
     Ok(user_input
         .strip_suffix("\r\n")
-        .or(user_input.strip_suffix("\n"))
+        .or(user_input.strip_suffix('\n'))
         .unwrap_or(&user_input)
         .to_string())
 }
 
 fn wait(custom_msg: Option<&str>) -> Result<()> {
     if let Some(msg) = custom_msg {
-        println!("{}", msg);
+        message!("{}", msg);
     } else {
-        println!("Press enter to continue.");
+        message!("Press enter to continue.");
     }
     get_user_input()?;
     Ok(())
 }
 
 fn print_line() {
-    println!("-----------------------------------------------------------------------------");
+    message!("-----------------------------------------------------------------------------");
 }
@@ -1,74 +1,335 @@
+use chrono::{DateTime, Duration, Utc};
 use color_eyre::{eyre::eyre, owo_colors::OwoColorize, Result};
-use std::fmt::{Debug, Display};
+use itertools::Itertools;
+use regex::Regex;
+use std::{
+    collections::{HashMap, HashSet},
+    fmt::{Debug, Display},
+};
 use tokio::try_join;
 
 use crate::{
-    arr::{self, ArrData},
-    config::Config,
-    overseerr::{MediaRequest, MediaStatus, ServerItem},
-    plex::PlexData,
-    shared::MediaType,
-    tautulli::{self, WatchHistory},
-    utils::human_file_size,
+    arr::{self, ArrData, ManagedItem, Titles},
+    config::{Config, Rules},
+    exclusion::ExclusionReason,
+    http,
+    ignore_list::IgnoredMedia,
+    output::{Candidate, Requester},
+    overseerr::{
+        self, MediaDetails, MediaRequest, MediaStatus, OpenIssues, Quota, ServerItem, UserQuota,
+    },
+    plex::{self, PlexData},
+    shared::{Action, MediaType},
+    table::Table,
+    tautulli::{self, ExternalIds, MediaVersion, RequesterWatch, WatchHistory},
+    trakt::{self, TraktWatch},
+    trash,
+    utils::{human_file_size, relative_time},
+    watch_provider,
 };
 
+/// How far the tables of the detail view are indented, like the lines about the item.
+const DETAIL_INDENT: usize = 6;
+
 #[derive(Debug)]
 pub struct MediaItem {
+    #[allow(dead_code)]
     pub title: Option<String>,
+    /// The id Overseerr has for the media, `None` when the item comes from Radarr or Sonarr.
+    pub media_id: Option<u32>,
+    tmdb_id: Option<u32>,
+    tvdb_id: Option<u32>,
     pub rating_key: Option<String>,
     manager_id: Option<i32>,
     manager_4k_id: Option<i32>,
     pub media_type: MediaType,
     media_status: MediaStatus,
     pub request: Option<MediaRequest>,
+    /// When the media was added to the server, if the request manager or Radarr and Sonarr know it.
+    added_at: Option<DateTime<Utc>>,
+    /// The seasons this item stands for, when the seasons of a show were requested separately.
+    /// `None` means the whole item.
+    seasons: Option<Vec<u32>>,
 }
 
 impl MediaItem {
+    /// Creates an item for the version (4K or not) the request is for, so
+    /// only that version is removed.
     pub fn from_request(request: MediaRequest) -> Self {
+        let (manager_id, manager_4k_id) = if request.is_4k {
+            (None, request.manager_4k_id)
+        } else {
+            (request.manager_id, None)
+        };
+
         Self {
             title: None,
+            media_id: Some(request.media_id),
+            tmdb_id: request.tmdb_id,
+            tvdb_id: request.tvdb_id,
             rating_key: request.rating_key.clone(),
-            manager_id: request.manager_id,
-            manager_4k_id: request.manager_4k_id,
+            manager_id,
+            manager_4k_id,
             media_type: request.media_type,
             media_status: request.media_status,
+            added_at: request.media_added_at,
             request: Some(request),
+            seasons: None,
         }
     }
 
+    /// Creates an item for all versions of the media that are available.
     pub fn from_server_item(item: ServerItem) -> Self {
+        let is_available = |status| {
+            matches!(
+                status,
+                MediaStatus::Available | MediaStatus::PartiallyAvailable
+            )
+        };
+        let manager_id = item.manager_id.filter(|_| is_available(item.media_status));
+        let manager_4k_id = item
+            .manager_id_4k
+            .filter(|_| is_available(item.media_status_4k));
+        let media_status = if manager_id.is_none() && manager_4k_id.is_some() {
+            item.media_status_4k
+        } else {
+            item.media_status
+        };
+
+        Self {
+            title: None,
+            media_id: Some(item.media_id),
+            tmdb_id: item.tmdb_id,
+            tvdb_id: item.tvdb_id,
+            rating_key: item.rating_key,
+            manager_id,
+            manager_4k_id,
+            media_type: item.media_type,
+            media_status,
+            request: None,
+            added_at: item.added_at,
+            seasons: None,
+        }
+    }
+
+    /// Creates an item for a movie or series Radarr or Sonarr manages, for when there is no
+    /// request manager. Items with files count as available.
+    pub fn from_managed(item: ManagedItem) -> Self {
+        let (manager_id, manager_4k_id) = if item.is_4k {
+            (None, Some(item.manager_id))
+        } else {
+            (Some(item.manager_id), None)
+        };
+
         Self {
             title: None,
-            rating_key: Some(item.rating_key),
-            manager_id: item.manager_id,
-            manager_4k_id: item.manager_id_4k,
+            media_id: None,
+            tmdb_id: item.tmdb_id,
+            tvdb_id: item.tvdb_id,
+            rating_key: None,
+            manager_id,
+            manager_4k_id,
             media_type: item.media_type,
-            media_status: item.media_status,
+            media_status: if item.has_files {
+                MediaStatus::Available
+            } else {
+                MediaStatus::Processing
+            },
             request: None,
+            added_at: item.added_at,
+            seasons: None,
         }
     }
 
-    pub async fn into_complete_media(self) -> Result<CompleteMediaItem> {
+    pub async fn into_complete_media(self, history: WatchHistory) -> Result<CompleteMediaItem> {
         let metadata = self.retrieve_metadata();
-        let history = self.retrieve_history();
         let data = self.retrieve_arr_data();
 
-        let (details, history, (arr_data, arr_4k_data)) = try_join!(metadata, history, data)?;
+        let (details, (arr_data, arr_4k_data)) = try_join!(metadata, data)?;
+
+        // Without Radarr data, the sizes only Plex knows are better than nothing.
+        let plex_versions = match self.rating_key {
+            Some(ref rating_key)
+                if Config::global()
+                    .tautulli
+                    .as_ref()
+                    .is_some_and(|tautulli| tautulli.media_details)
+                    && arr_data.is_none()
+                    && arr_4k_data.is_none() =>
+            {
+                tautulli::get_media_versions(rating_key)
+                    .await
+                    .unwrap_or_default()
+            }
+            _ => Vec::new(),
+        };
+
+        let trakt_watches = if Config::global().trakt.is_some() {
+            let (tmdb_id, tvdb_id) = self.external_ids().await;
+            trakt::watched_by(self.media_type, tmdb_id, tvdb_id).await
+        } else {
+            Vec::new()
+        };
+
+        let requester_watch = match self.request {
+            Some(ref request) => Some(
+                tautulli::requester_watch(
+                    &history,
+                    request.requester_plex_id,
+                    request.requester_username.as_deref(),
+                    Some(&request.requester_email),
+                )
+                .await?,
+            ),
+            None => None,
+        };
 
         Ok(CompleteMediaItem {
+            rating_key: self.rating_key.clone(),
+            section_id: details.section_id.clone(),
             title: details.title.clone(),
             media_type: self.media_type,
             request: self.request,
             history,
+            requester_watch,
+            media_status: self.media_status,
+            on_disk: true,
+            seasons: self.seasons,
+            media_id: self.media_id,
+            added_at: self.added_at.or(details.added_at),
+            open_issues: None,
+            requester_quota: None,
+            watchlisted_by: Vec::new(),
+            plex_versions,
+            trakt_watches,
+            arr_data,
+            arr_4k_data,
+        })
+    }
+
+    /// Completes a requested item that never became available, so it has no rating key
+    /// and no history. Radarr and Sonarr data is included if they already know the item.
+    pub async fn into_not_on_disk(self) -> Result<CompleteMediaItem> {
+        let (arr_data, arr_4k_data) = self.retrieve_arr_data().await.unwrap_or((None, None));
+
+        let title = match (&arr_data, &arr_4k_data, &self.request) {
+            (Some(data), _, _) | (None, Some(data), _) => data.title_and_year().0.to_string(),
+            (None, None, Some(request)) => match request.tmdb_id {
+                Some(tmdb_id) => overseerr::get_details(self.media_type, tmdb_id)
+                    .await
+                    .map(|details| details.title)
+                    .unwrap_or_else(|_| request.placeholder_title()),
+                None => request.placeholder_title(),
+            },
+            (None, None, None) => {
+                return Err(eyre!(
+                "No rating key was found for item. Unable to gather data from the media server."
+            ))
+            }
+        };
+
+        Ok(CompleteMediaItem {
+            rating_key: None,
+            section_id: None,
+            title,
+            media_type: self.media_type,
+            request: self.request,
+            history: WatchHistory::NoData,
+            requester_watch: None,
+            media_status: self.media_status,
+            on_disk: false,
+            seasons: self.seasons,
+            media_id: self.media_id,
+            added_at: self.added_at,
+            open_issues: None,
+            requester_quota: None,
+            watchlisted_by: Vec::new(),
+            plex_versions: Vec::new(),
+            trakt_watches: Vec::new(),
             arr_data,
             arr_4k_data,
         })
     }
 
+    /// When different requests were made for seasons of the same show, each request only
+    /// stands for the seasons nobody else requested, so removing it leaves the others' seasons.
+    /// Shows where a request doesn't list its seasons are left as whole shows.
+    pub fn scope_to_seasons(items: &mut [MediaItem]) {
+        let mut shows: HashMap<(String, Option<i32>, Option<i32>), Vec<usize>> = HashMap::new();
+        for (i, item) in items.iter().enumerate() {
+            if let (MediaType::Tv, Some(rating_key), Some(_)) =
+                (item.media_type, &item.rating_key, &item.request)
+            {
+                shows
+                    .entry((rating_key.clone(), item.manager_id, item.manager_4k_id))
+                    .or_default()
+                    .push(i);
+            }
+        }
+
+        let mut scoped = Vec::new();
+        for requests in shows.values().filter(|requests| requests.len() > 1) {
+            let seasons_of = |i: usize| {
+                items[i]
+                    .request
+                    .as_ref()
+                    .map(|request| request.seasons.clone())
+                    .unwrap_or_default()
+            };
+
+            if requests.iter().any(|i| seasons_of(*i).is_empty()) {
+                continue;
+            }
+
+            for i in requests {
+                let requested_by_others: HashSet<u32> = requests
+                    .iter()
+                    .filter(|other| *other != i)
+                    .flat_map(|other| seasons_of(*other))
+                    .collect();
+                let seasons = seasons_of(*i)
+                    .into_iter()
+                    .filter(|season| !requested_by_others.contains(season))
+                    .sorted()
+                    .collect();
+
+                scoped.push((*i, seasons));
+            }
+        }
+
+        for (i, seasons) in scoped {
+            items[i].seasons = Some(seasons);
+        }
+    }
+
+    /// How many days ago the item was requested, or added to the server if it wasn't requested.
+    /// `None` if neither is known.
+    pub fn days_since_requested(&self) -> Option<i64> {
+        let requested_at = match self.request {
+            Some(ref request) => request.created_at,
+            None => self.added_at?,
+        };
+
+        Some(Utc::now().signed_duration_since(requested_at).num_days())
+    }
+
     pub fn is_available(&self) -> bool {
-        match &self.media_status {
-            MediaStatus::Available | MediaStatus::PartiallyAvailable => true,
-            _ => false,
+        matches!(
+            &self.media_status,
+            MediaStatus::Available | MediaStatus::PartiallyAvailable
+        )
+    }
+
+    /// Whether the item has one of the statuses from the config. Without any configured, available
+    /// items are wanted, along with requests that never became available (and so have no rating key).
+    pub fn has_wanted_status(&self) -> bool {
+        let statuses = Config::global()
+            .overseerr
+            .as_ref()
+            .and_then(|overseerr| overseerr.statuses.as_ref());
+        match statuses {
+            Some(statuses) => statuses.contains(&self.media_status),
+            None => self.rating_key.is_none() || self.is_available(),
         }
     }
 
@@ -76,6 +337,7 @@ impl MediaItem {
         match &self.media_type {
             MediaType::Movie => arr::movie_manger_active() || arr::movie_4k_manager_active(),
             MediaType::Tv => arr::tv_manager_active() || arr::tv_4k_manager_active(),
+            MediaType::Music => false,
         }
     }
 
@@ -90,43 +352,56 @@ impl MediaItem {
             Some(ref users) => users,
         };
 
-        if ignored_users.contains(&request.requested_by)
-        // .iter()
-        // .any(|user| user.eq(&request.requested_by))
-        {
-            true
-        } else {
-            false
-        }
+        ignored_users.contains(&request.requested_by)
     }
 
-    async fn retrieve_history(&self) -> Result<WatchHistory> {
+    async fn retrieve_metadata(&self) -> Result<PlexData> {
         let rating_key = match self.rating_key {
             Some(ref rating_key) => rating_key,
             None => {
                 return Err(eyre!(
-                "No rating key was found for request. Unable to gather watch history from Tautulli."
-            ))
+                    "No rating key was found for request. Unable to gather metadata from the media server."
+                ))
             }
         };
 
-        tautulli::get_item_watches(rating_key, &self.media_type).await
+        watch_provider::provider()
+            .item_data(rating_key, self.media_type)
+            .await
     }
 
-    async fn retrieve_metadata(&self) -> Result<PlexData> {
-        let rating_key = match self.rating_key {
-            Some(ref rating_key) => rating_key,
-            None => {
-                return Err(eyre!(
-                    "No rating key was found for request. Unable to gather metadata from Plex."
-                ))
-            }
-        };
+    /// The title and year of the item according to Radarr or Sonarr, or TMDB if they don't know it,
+    /// used to find the item when its rating key is not known.
+    /// The title Radarr or Sonarr has for the item, like "Title (Year)".
+    pub fn arr_title<'a>(&self, titles: &'a Titles) -> Option<&'a str> {
+        [(false, self.manager_id), (true, self.manager_4k_id)]
+            .into_iter()
+            .find_map(|(is_4k, id)| titles.get(&(is_4k, self.media_type, id?)))
+            .map(String::as_str)
+    }
 
-        PlexData::get_data(rating_key, self.media_type).await
+    pub async fn retrieve_title_and_year(&self) -> Result<(String, Option<i32>)> {
+        let (data, data_4k) = self.retrieve_arr_data().await.unwrap_or((None, None));
+
+        match (data.or(data_4k), self.tmdb_id) {
+            (Some(data), _) => {
+                let (title, year) = data.title_and_year();
+                Ok((title.to_string(), year))
+            }
+            (None, Some(tmdb_id)) => {
+                let details = overseerr::get_details(self.media_type, tmdb_id).await?;
+                Ok((details.title, details.year))
+            }
+            (None, None) => Err(eyre!("No *arr data or TMDB id was found for the item.")),
+        }
     }
 
     async fn retrieve_arr_data(&self) -> Result<(Option<ArrData>, Option<ArrData>)> {
+        // `list` works without Radarr and Sonarr.
+        if !self.has_manager_active() {
+            return Ok((None, None));
+        }
+
         match (self.manager_id, self.manager_4k_id) {
             (Some(id), Some(id_4k)) => {
                 let data_standard = ArrData::get_data(self.media_type, id);
@@ -138,52 +413,788 @@ impl MediaItem {
             }
             (Some(id), _) => Ok((Some(ArrData::get_data(self.media_type, id).await?), None)),
             (None, Some(id_4k)) => Ok((
-                Some(ArrData::get_4k_data(self.media_type, id_4k).await?),
                 None,
+                Some(ArrData::get_4k_data(self.media_type, id_4k).await?),
             )),
-            (None, None) => Err(eyre!(
-                "No *arr id was found for request. Unable to gather file data."
-            )),
+            // Overseerr doesn't always know the Radarr or Sonarr id, so the media is looked up
+            // by its TMDB or TVDB id. The ids from Overseerr are preferred, and Plex's are used
+            // when Overseerr doesn't have them.
+            (None, None) => {
+                let is_4k = self.request.as_ref().is_some_and(|request| request.is_4k);
+                let (tmdb_id, tvdb_id) = self.external_ids().await;
+                let data = match (self.media_type, tmdb_id, tvdb_id) {
+                    (MediaType::Movie, Some(tmdb_id), _) => {
+                        ArrData::find_movie(tmdb_id, is_4k).await?
+                    }
+                    (MediaType::Tv, _, Some(tvdb_id)) => {
+                        ArrData::find_series(tvdb_id, is_4k).await?
+                    }
+                    _ => {
+                        return Err(eyre!(
+                            "No *arr id was found for request. Unable to gather file data."
+                        ))
+                    }
+                };
+
+                match data {
+                    Some(data) if is_4k => Ok((None, Some(data))),
+                    data => Ok((data, None)),
+                }
+            }
+        }
+    }
+
+    /// The TMDB and TVDB ids of the item, from Overseerr or else from the guids in Plex.
+    /// Whether the ids belong to the item, by the TMDB id of a movie or the TVDB id of a show.
+    pub fn has_external_ids(&self, ids: &ExternalIds) -> bool {
+        match self.media_type {
+            MediaType::Movie => self.tmdb_id.is_some() && self.tmdb_id == ids.tmdb_id,
+            MediaType::Tv => self.tvdb_id.is_some() && self.tvdb_id == ids.tvdb_id,
+            MediaType::Music => false,
         }
     }
+
+    /// Finds the id the watch provider has for the item by its TMDB or TVDB id.
+    pub async fn find_server_id(&self) -> Result<Option<String>> {
+        watch_provider::provider()
+            .find_item_id(self.media_type, self.tmdb_id, self.tvdb_id)
+            .await
+    }
+
+    async fn external_ids(&self) -> (Option<u32>, Option<u32>) {
+        let needs_lookup = match self.media_type {
+            MediaType::Movie => self.tmdb_id.is_none(),
+            _ => self.tvdb_id.is_none(),
+        };
+        let plex_ids = match self.rating_key {
+            Some(ref rating_key) if needs_lookup => tautulli::get_external_ids(rating_key)
+                .await
+                .unwrap_or_default(),
+            _ => ExternalIds::default(),
+        };
+
+        (
+            self.tmdb_id.or(plex_ids.tmdb_id),
+            self.tvdb_id.or(plex_ids.tvdb_id),
+        )
+    }
 }
 
 #[derive(Debug)]
 pub struct CompleteMediaItem {
     pub title: String,
+    rating_key: Option<String>,
+    /// The Plex library section the item is in.
+    section_id: Option<String>,
     pub media_type: MediaType,
     request: Option<MediaRequest>,
     history: WatchHistory,
+    /// Whether the requester watched the item, `None` if it was not requested or is not on disk.
+    requester_watch: Option<RequesterWatch>,
+    media_status: MediaStatus,
+    /// Whether the item ever became available, requests that didn't have no files or history.
+    on_disk: bool,
+    /// The seasons to remove, when only some seasons of the show should be removed.
+    seasons: Option<Vec<u32>>,
+    media_id: Option<u32>,
+    /// When the media was added to the server, from the request manager, Radarr and Sonarr or the media server.
+    added_at: Option<DateTime<Utc>>,
+    open_issues: Option<OpenIssues>,
+    /// The requester's quota for the media type, `None` if it was not requested or isn't known.
+    requester_quota: Option<Quota>,
+    /// The accounts with the item on their Plex watchlist, when watchlists are only shown.
+    watchlisted_by: Vec<String>,
+    /// The versions Plex has of the item, only fetched when Radarr and Sonarr don't have it.
+    plex_versions: Vec<MediaVersion>,
+    /// The Trakt accounts that watched the item, besides the watch history.
+    trakt_watches: Vec<TraktWatch>,
     arr_data: Option<ArrData>,
     arr_4k_data: Option<ArrData>,
 }
 
 impl CompleteMediaItem {
-    pub async fn remove_from_server(self) -> Result<()> {
+    /// Removes the item from Radarr or Sonarr, and then cleans up its request in Overseerr
+    /// (and blacklists it, if set in the config). The size of what was removed from each
+    /// instance is added to `freed_size`, so a failure in one instance doesn't count the other.
+    /// A failing cleanup is reported as an error, but the files are already removed by then.
+    /// With `block`, Radarr or Sonarr is kept from adding the item again through an import list.
+    /// Each removal is checked to have gone through, and the paths that are still on disk
+    /// are returned. With --dry-run nothing is removed, so there is nothing to check.
+    /// With the trash set, the files are moved there before Radarr or Sonarr removes the item.
+    pub async fn remove_from_server(
+        self,
+        freed_size: &mut i64,
+        block: bool,
+    ) -> Result<Vec<String>> {
+        self.check_trashable()?;
+        let action = if block {
+            Action::DeleteAndBlock
+        } else {
+            Action::Delete
+        };
+        let mut remaining_paths = Vec::new();
+        if let Some(rating_key) = self
+            .rating_key
+            .as_ref()
+            .filter(|_| self.deletes_from_plex())
+        {
+            plex::delete_item(rating_key, self.media_type)
+                .await
+                .map_err(|err| eyre!("Deleting it from Plex failed: {}", err))?;
+            *freed_size += self
+                .plex_versions
+                .iter()
+                .map(|version| version.size)
+                .sum::<i64>();
+        }
+        for arr_data in [&self.arr_data, &self.arr_4k_data].into_iter().flatten() {
+            let instance = arr_data.instance_name();
+            let size = match self.seasons {
+                Some(ref seasons) => arr_data.get_seasons_disk_size(seasons),
+                None => arr_data.get_disk_size(),
+            };
+            let trashed =
+                self.seasons.is_none() && trash::config().is_some() && arr_data.deletes_files();
+            if trashed {
+                self.move_to_trash(arr_data, action)?;
+            }
+            let removal = match self.seasons {
+                Some(ref seasons) if seasons.is_empty() => Ok(()),
+                Some(ref seasons) => arr_data.remove_seasons(seasons).await,
+                None => arr_data.remove_data(block, trashed).await,
+            };
+            removal.map_err(|err| eyre!("Removing it from {} failed: {}", instance, err))?;
+
+            let remaining = match self.seasons {
+                _ if http::is_dry_run() => Vec::new(),
+                Some(ref seasons) if seasons.is_empty() => Vec::new(),
+                _ => arr_data
+                    .check_removed(self.seasons())
+                    .await
+                    .map_err(|err| eyre!("Removing it from {} failed: {}", instance, err))?,
+            };
+            if remaining.is_empty() {
+                *freed_size += size;
+            }
+            remaining_paths.extend(remaining);
+        }
+
         if let Some(request) = self.request {
-            request.remove_request().await?;
+            request
+                .clean_up(self.seasons.is_some())
+                .await
+                .map_err(|err| {
+                    eyre!(
+                        "The media was removed, but cleaning up its Overseerr request failed: {}",
+                        err
+                    )
+                })?;
+
+            // Blacklisting is done last, as clearing the media status would remove it again.
+            if Config::global()
+                .overseerr
+                .as_ref()
+                .is_some_and(|overseerr| overseerr.blacklist_after_delete)
+                && self.seasons.is_none()
+            {
+                request.blacklist().await.map_err(|err| {
+                    eyre!(
+                        "The media was removed, but adding it to the Overseerr blacklist failed: {}",
+                        err
+                    )
+                })?;
+            }
         }
 
-        if let Some(arr_data) = self.arr_data {
-            arr_data.remove_data().await?;
+        Ok(remaining_paths)
+    }
+
+    /// The Plex library section the item is in, if it is known.
+    pub fn section_id(&self) -> Option<&str> {
+        self.section_id.as_deref()
+    }
+
+    /// Makes sure the files can be moved to the trash if it is set, which is only done for the
+    /// whole folder of an item in Radarr or Sonarr.
+    fn check_trashable(&self) -> Result<()> {
+        if trash::config().is_none() {
+            return Ok(());
         }
 
-        if let Some(arr_data) = self.arr_4k_data {
-            arr_data.remove_data().await?;
+        if self
+            .seasons
+            .as_ref()
+            .is_some_and(|seasons| !seasons.is_empty())
+        {
+            return Err(eyre!(
+                "Seasons can't be removed with the trash set, as only whole folders are moved to the trash."
+            ));
+        }
+        if self.deletes_from_plex() {
+            return Err(eyre!(
+                "Only Plex has the item, which would delete its files instead of moving them to the trash."
+            ));
         }
 
         Ok(())
     }
 
+    /// Moves the folder the item has in an instance to the trash.
+    fn move_to_trash(&self, arr_data: &ArrData, action: Action) -> Result<()> {
+        let instance = arr_data.instance_name();
+        let path = arr_data.path().ok_or_else(|| {
+            eyre!(
+                "{} doesn't say where its files are, so they can't be moved to the trash.",
+                instance
+            )
+        })?;
+
+        trash::move_to_trash(
+            &self.title,
+            self.media_type,
+            action,
+            instance,
+            path,
+            arr_data.get_disk_size(),
+        )
+        .map_err(|err| eyre!("Moving it to the trash failed: {}", err))
+    }
+
+    /// Whether the item is deleted straight from Plex, as Radarr and Sonarr don't have it.
+    pub fn deletes_from_plex(&self) -> bool {
+        Config::global()
+            .plex
+            .as_ref()
+            .is_some_and(|plex| plex.allow_delete)
+            && self.on_disk
+            && self.rating_key.is_some()
+            && self.arr_data.is_none()
+            && self.arr_4k_data.is_none()
+    }
+
+    /// Deletes the files of the item and unmonitors it, but keeps it in Radarr or Sonarr so it can
+    /// be grabbed again later. The size of the removed files is added to `freed_size`, and the
+    /// request is cleaned up like when only some seasons are removed. With the trash set, the
+    /// files are moved there first.
+    pub async fn remove_files(self, freed_size: &mut i64) -> Result<()> {
+        self.check_trashable()?;
+        let arr_data = [&self.arr_data, &self.arr_4k_data]
+            .into_iter()
+            .flatten()
+            .collect_vec();
+        if arr_data.is_empty() {
+            return Err(eyre!(
+                "The item is not in Radarr or Sonarr, so its files can't be removed."
+            ));
+        }
+
+        for arr_data in arr_data {
+            let instance = arr_data.instance_name();
+            let size = match self.seasons {
+                Some(ref seasons) => arr_data.get_seasons_disk_size(seasons),
+                None => arr_data.get_disk_size(),
+            };
+            if trash::config().is_some() {
+                self.move_to_trash(arr_data, Action::DeleteFiles)?;
+            }
+            arr_data
+                .remove_files(self.seasons())
+                .await
+                .map_err(|err| eyre!("Removing its files from {} failed: {}", instance, err))?;
+            if !http::is_dry_run() {
+                arr_data
+                    .check_files_removed(self.seasons())
+                    .await
+                    .map_err(|err| eyre!("Removing its files from {} failed: {}", instance, err))?;
+            }
+            *freed_size += size;
+        }
+
+        if let Some(request) = self.request {
+            request.clean_up(true).await.map_err(|err| {
+                eyre!(
+                    "The files were removed, but cleaning up its Overseerr request failed: {}",
+                    err
+                )
+            })?;
+        }
+
+        Ok(())
+    }
+
+    /// Stops Radarr or Sonarr from monitoring the item, leaving its files and request alone.
+    pub async fn unmonitor(&self) -> Result<()> {
+        let arr_data = [&self.arr_data, &self.arr_4k_data]
+            .into_iter()
+            .flatten()
+            .collect_vec();
+        if arr_data.is_empty() {
+            return Err(eyre!(
+                "The item is not in Radarr or Sonarr, so it can't be unmonitored."
+            ));
+        }
+
+        for arr_data in arr_data {
+            arr_data.unmonitor(self.seasons()).await?;
+        }
+
+        Ok(())
+    }
+
+    /// Whether the item has a tag in Radarr or Sonarr that keeps it from being cleaned up.
+    pub fn has_excluded_tag(&self) -> bool {
+        [&self.arr_data, &self.arr_4k_data]
+            .into_iter()
+            .flatten()
+            .any(ArrData::has_excluded_tag)
+    }
+
+    /// Whether the item is in one of the protected collections or has one of the protected
+    /// labels in Plex, matched without regard to case.
+    pub async fn plex_exclusion(
+        &self,
+        protected_collections: &[String],
+        protected_labels: &[String],
+    ) -> Result<Option<ExclusionReason>> {
+        let rating_key = match self.rating_key {
+            Some(ref rating_key) => rating_key,
+            None => return Ok(None),
+        };
+
+        let (collections, labels) = tautulli::get_collections_and_labels(rating_key)
+            .await
+            .map_err(|err| {
+                eyre!(
+                    "Failed to get the collections and labels of {}, so it was left out: {}",
+                    self.title,
+                    err
+                )
+            })?;
+
+        let first_protected = |names: Vec<String>, protected: &[String]| {
+            names.into_iter().find(|name| {
+                protected
+                    .iter()
+                    .any(|protected| protected.eq_ignore_ascii_case(name))
+            })
+        };
+
+        Ok(first_protected(collections, protected_collections)
+            .map(ExclusionReason::ProtectedCollection)
+            .or_else(|| first_protected(labels, protected_labels).map(ExclusionReason::PlexLabel)))
+    }
+
+    /// Whether the item was requested by one of the protected requesters, or watched by one of
+    /// the protected watchers (within the protected watch days), matched by any of their names.
+    pub async fn protected_user(&self, rules: &Rules) -> Result<Option<ExclusionReason>> {
+        if let Some(ref request) = self.request {
+            let requester = tautulli::find_requester(
+                request.requester_plex_id,
+                request.requester_username.as_deref(),
+                Some(&request.requester_email),
+            )
+            .await?;
+            let request_names = [
+                Some(&request.requested_by),
+                request.requester_username.as_ref(),
+                Some(&request.requester_email),
+            ];
+
+            let protected = rules.protected_requesters.iter().find(|protected| {
+                request_names
+                    .iter()
+                    .flatten()
+                    .any(|name| name.eq_ignore_ascii_case(protected))
+                    || requester.is_some_and(|user| user.is_named(protected))
+            });
+            if let Some(protected) = protected {
+                return Ok(Some(ExclusionReason::ProtectedRequester(protected.clone())));
+            }
+        }
+
+        if rules.protected_watchers.is_empty() {
+            return Ok(None);
+        }
+
+        let users = watch_provider::provider().users().await?;
+        let since = rules
+            .protected_watch_days
+            .map(|days| Utc::now() - Duration::days(days.into()));
+        let protected =
+            self.history
+                .watchers_since(since)
+                .into_iter()
+                .find_map(|(user_id, display_name)| {
+                    let user = users.iter().find(|user| user.user_id == user_id);
+                    rules.protected_watchers.iter().find(|protected| {
+                        display_name.eq_ignore_ascii_case(protected)
+                            || user.is_some_and(|user| user.is_named(protected))
+                    })
+                });
+
+        Ok(protected.map(|protected| ExclusionReason::ProtectedWatcher(protected.clone())))
+    }
+
+    /// The accounts in the watchlist config with the item on their Plex watchlist.
+    pub async fn watchlist_accounts(&self) -> Result<Vec<String>> {
+        let rating_key = match self.rating_key {
+            Some(ref rating_key) => rating_key,
+            None => return Ok(Vec::new()),
+        };
+
+        let guids = tautulli::get_guids(rating_key).await.map_err(|err| {
+            eyre!(
+                "Failed to get the guids of {} to look it up in the watchlists: {}",
+                self.title,
+                err
+            )
+        })?;
+
+        plex::watchlisted_by(&guids).await
+    }
+
+    pub fn set_watchlisted_by(&mut self, accounts: Vec<String>) {
+        self.watchlisted_by = accounts;
+    }
+
+    /// Whether any of the Trakt accounts watched the item.
+    pub fn watched_on_trakt(&self) -> bool {
+        !self.trakt_watches.is_empty()
+    }
+
+    /// Whether the quality of the item's files matches, in either version if it has both.
+    pub fn quality_matches(&self, quality: &Regex) -> bool {
+        [&self.arr_data, &self.arr_4k_data]
+            .into_iter()
+            .flatten()
+            .filter_map(ArrData::quality_name)
+            .any(|name| quality.is_match(name))
+    }
+
+    /// The entry for the ignore list, `None` if Overseerr doesn't know the item.
+    pub fn to_ignored(&self) -> Option<IgnoredMedia> {
+        Some(IgnoredMedia {
+            media_id: self.media_id?,
+            title: self.title.clone(),
+        })
+    }
+
+    /// The size on disk, `None` if neither Radarr nor Sonarr knows the item and Plex didn't say.
+    pub fn disk_size(&self) -> Option<i64> {
+        match (&self.arr_data, &self.arr_4k_data) {
+            (None, None) if self.plex_versions.is_empty() => None,
+            _ => Some(self.get_disk_size()),
+        }
+    }
+
+    /// The Plex rating key, or the id on Jellyfin or Emby, `None` if it wasn't found.
+    pub fn rating_key(&self) -> Option<&str> {
+        self.rating_key.as_deref()
+    }
+
     pub fn get_disk_size(&self) -> i64 {
+        if let Some(ref seasons) = self.seasons {
+            return [&self.arr_data, &self.arr_4k_data]
+                .into_iter()
+                .flatten()
+                .map(|arr_data| arr_data.get_seasons_disk_size(seasons))
+                .sum();
+        }
+
         match (self.arr_data.as_ref(), self.arr_4k_data.as_ref()) {
             (Some(arr_data), None) => arr_data.get_disk_size(),
             (None, Some(arr_data)) => arr_data.get_disk_size(),
             (Some(arr_data), Some(arr_data_4k)) => {
                 arr_data.get_disk_size() + arr_data_4k.get_disk_size()
             }
-            (None, None) => panic!("Tried to get size of none existant object!"),
+            (None, None) => self.plex_versions.iter().map(|version| version.size).sum(),
+        }
+    }
+
+    pub fn last_activity(&self) -> Option<DateTime<Utc>> {
+        self.history.last_activity()
+    }
+
+    /// Gets the TMDB details of the item from Overseerr, `None` if it wasn't requested.
+    pub async fn details(&self) -> Result<Option<MediaDetails>> {
+        match self.request.as_ref().and_then(|request| request.tmdb_id) {
+            Some(tmdb_id) => Ok(Some(
+                overseerr::get_details(self.media_type, tmdb_id).await?,
+            )),
+            None => Ok(None),
+        }
+    }
+
+    /// Adds the open Overseerr issues for the item, if there are any among the given issues.
+    pub fn add_open_issues(&mut self, issues: &HashMap<u32, OpenIssues>) {
+        self.open_issues = self.media_id.and_then(|id| issues.get(&id).cloned());
+    }
+
+    pub fn has_open_issues(&self) -> bool {
+        self.open_issues.is_some()
+    }
+
+    /// The Overseerr id of the user who requested the item.
+    pub fn requester_id(&self) -> Option<u32> {
+        self.request.as_ref().map(|request| request.requester_id)
+    }
+
+    /// Adds the requester's quota for the item's media type, if it is among the given quotas.
+    pub fn add_requester_quota(&mut self, quotas: &HashMap<u32, UserQuota>) {
+        self.requester_quota = self
+            .requester_id()
+            .and_then(|user_id| quotas.get(&user_id))
+            .and_then(|quota| quota.for_media_type(self.media_type));
+    }
+
+    /// How much of their quota the requester has used, `None` if it's unlimited or unknown.
+    /// When the item was requested, or added to the server if it wasn't requested.
+    pub fn requested_at(&self) -> Option<DateTime<Utc>> {
+        match self.request {
+            Some(ref request) => Some(request.created_at),
+            None => self.added_at,
+        }
+    }
+
+    pub fn requester_name(&self) -> Option<&str> {
+        self.request
+            .as_ref()
+            .map(|request| request.requested_by.as_str())
+    }
+
+    pub fn quota_pressure(&self) -> Option<u32> {
+        self.requester_quota.and_then(|quota| quota.pressure())
+    }
+
+    /// The seasons this item stands for, when it is only some seasons of a show.
+    pub fn seasons(&self) -> Option<&[u32]> {
+        self.seasons.as_deref()
+    }
+
+    /// The seasons that can be removed on their own: the seasons the item stands for,
+    /// or every season with files on disk if it stands for the whole show.
+    pub fn removable_seasons(&self) -> Vec<u32> {
+        match self.seasons {
+            Some(ref seasons) => seasons.clone(),
+            None => [&self.arr_data, &self.arr_4k_data]
+                .into_iter()
+                .flatten()
+                .flat_map(ArrData::seasons_on_disk)
+                .sorted()
+                .dedup()
+                .collect(),
+        }
+    }
+
+    /// Whether everyone who watched the show has finished the season.
+    pub fn season_finished_by_all(&self, season: u32) -> bool {
+        self.history
+            .season_finished_by_all(season, self.season_episode_count(season))
+    }
+
+    /// The users who are in the middle of an episode of the season.
+    pub fn watching_season(&self, season: u32) -> Vec<&str> {
+        self.history.watching_season(season)
+    }
+
+    /// Only removes the given seasons, instead of the whole show.
+    pub fn set_seasons(&mut self, seasons: Vec<u32>) {
+        self.seasons = Some(seasons);
+    }
+
+    /// The season with who has watched it, as shown when choosing seasons.
+    pub fn season_label(&self, season: u32) -> String {
+        format!(
+            "Season {}: {}",
+            season,
+            self.history
+                .season_summary(season, self.season_episode_count(season))
+        )
+    }
+
+    /// The seasons this item stands for with their episode count, the seasons on disk if it
+    /// stands for the whole show. Empty for movies and music.
+    fn season_episode_counts(&self) -> Vec<(u32, Option<i32>)> {
+        let seasons = match self.seasons {
+            Some(ref seasons) => seasons.clone(),
+            None => self
+                .arr_data
+                .as_ref()
+                .or(self.arr_4k_data.as_ref())
+                .map_or_else(Vec::new, ArrData::seasons_on_disk),
+        };
+
+        seasons
+            .into_iter()
+            .map(|season| (season, self.season_episode_count(season)))
+            .collect()
+    }
+
+    fn season_episode_count(&self, season: u32) -> Option<i32> {
+        self.arr_data
+            .as_ref()
+            .or(self.arr_4k_data.as_ref())
+            .and_then(|arr_data| arr_data.season_episode_count(season))
+    }
+
+    /// Everything known about the item, followed by the latest watch of each user and, for shows,
+    /// how many episodes of each season they finished. Shown by the picker and with `show`.
+    pub fn detail_view(&self) -> String {
+        let mut view = self.to_string();
+
+        if let Some(table) = self.history.watch_table() {
+            push_table(&mut view, "Watches by user:", &table);
+        }
+
+        if let Some(grid) = self.history.season_grid(&self.season_episode_counts()) {
+            push_table(&mut view, "Episodes finished per season:", &grid);
+        }
+
+        view
+    }
+
+    fn print_seasons(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let seasons = match self.seasons {
+            Some(ref seasons) if seasons.is_empty() => {
+                return write!(
+                    f,
+                    "\n      {}",
+                    "All requested seasons were also requested by someone else, only the request is removed.".yellow()
+                )
+            }
+            Some(ref seasons) => seasons,
+            None => return Ok(()),
+        };
+
+        for season in seasons {
+            write!(
+                f,
+                "\n      Season {}: {}",
+                season.yellow(),
+                self.history
+                    .season_summary(*season, self.season_episode_count(*season))
+            )?;
+        }
+
+        Ok(())
+    }
+
+    /// Whether this is only the 4K version of the media.
+    pub fn is_4k(&self) -> bool {
+        self.arr_data.is_none() && self.arr_4k_data.is_some()
+    }
+
+    /// Whether the item is kept because it was watched or added too recently, the number of days
+    /// coming from the arguments or the rules. Items without a known date for a rule are not kept by it.
+    pub fn retention_exclusion(
+        &self,
+        keep_days_after_watch: Option<u32>,
+        keep_days_after_added: Option<u32>,
+    ) -> Option<ExclusionReason> {
+        let days_ago = |date: DateTime<Utc>| Utc::now().signed_duration_since(date).num_days();
+
+        if let (Some(keep_days), Some(last_activity)) =
+            (keep_days_after_watch, self.last_activity())
+        {
+            let days_ago = days_ago(last_activity);
+            if days_ago < keep_days.into() {
+                return Some(ExclusionReason::RecentlyWatched {
+                    days_ago,
+                    keep_days,
+                });
+            }
         }
+
+        if let (Some(keep_days), Some(added_at)) = (keep_days_after_added, self.added_at) {
+            let days_ago = days_ago(added_at);
+            if days_ago < keep_days.into() {
+                return Some(ExclusionReason::RecentlyAdded {
+                    days_ago,
+                    keep_days,
+                });
+            }
+        }
+
+        None
+    }
+
+    /// The item as shown in tables, in the order of `table::ITEM_COLUMNS`.
+    pub fn table_row(&self) -> Vec<String> {
+        let title = match self.seasons {
+            Some(ref seasons) if !seasons.is_empty() => {
+                format!(
+                    "{}{} (seasons {})",
+                    self.status_4k(),
+                    self.title,
+                    seasons.iter().join(", ")
+                )
+            }
+            _ => format!("{}{}", self.status_4k(), self.title),
+        };
+
+        vec![
+            title,
+            self.media_type.to_string(),
+            match self.disk_size() {
+                Some(size) => human_file_size(size),
+                None => "unknown".to_string(),
+            },
+            match self.last_activity() {
+                Some(last_activity) => relative_time(last_activity),
+                None => "never".to_string(),
+            },
+            self.request
+                .as_ref()
+                .map_or_else(|| "n/a".to_string(), |request| request.requested_by.clone()),
+            self.history.finished_summary(&self.season_episode_counts()),
+        ]
+    }
+
+    /// The item as written with --output json, before anything is done with it.
+    pub fn to_candidate(&self) -> Candidate {
+        let arr_data = self.arr_data.as_ref().or(self.arr_4k_data.as_ref());
+        Candidate {
+            rating_key: self.rating_key.clone(),
+            title: self.title.clone(),
+            year: arr_data.and_then(|arr_data| arr_data.title_and_year().1),
+            media_type: self.media_type,
+            size: self.disk_size(),
+            seasons: self.seasons.clone(),
+            watches: self.history.entries(),
+            requester: self.request.as_ref().map(|request| Requester {
+                name: request.requested_by.clone(),
+                watched: self
+                    .requester_watch
+                    .as_ref()
+                    .and_then(RequesterWatch::is_finished),
+            }),
+            action: None,
+            outcome: None,
+        }
+    }
+
+    /// Whether everyone who watched the item finished it, and someone did. Shows have to have
+    /// been watched to the end of every season this item stands for.
+    pub fn watched_by_all(&self) -> bool {
+        self.history.is_watched_by_all(
+            Config::global().rules.watched_threshold,
+            &self.season_episode_counts(),
+        )
+    }
+
+    /// How many days ago anyone last watched the item, `None` if nobody ever did.
+    pub fn days_since_watch(&self) -> Option<i64> {
+        self.last_activity()
+            .map(|last_activity| Utc::now().signed_duration_since(last_activity).num_days())
+    }
+
+    /// The Radarr or Sonarr instances the item is in.
+    fn instance_names(&self) -> Vec<String> {
+        [&self.arr_data, &self.arr_4k_data]
+            .into_iter()
+            .flatten()
+            .map(ArrData::instance_name)
+            .collect()
     }
 
     fn print_arr_data(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
@@ -191,9 +1202,38 @@ impl CompleteMediaItem {
             (Some(arr_data), None) => write!(f, "\n      {}", arr_data)?,
             (None, Some(arr_data_4k)) => write!(f, "\n       {}", arr_data_4k)?,
             (Some(arr_data), Some(_)) => write!(f, "\n      {}", arr_data)?,
-            (None, None) => {
-                panic!("Tried to write non-existant item")
+            (None, None) if self.deletes_from_plex() => write!(
+                f,
+                "\n      {}",
+                "Orphaned: exists in Plex but is not managed by Radarr or Sonarr. Deleting it will delete it directly from Plex."
+                    .yellow()
+            )?,
+            (None, None) if self.on_disk => {
+                let manager = match self.media_type {
+                    MediaType::Movie => "Radarr",
+                    _ => "Sonarr",
+                };
+                write!(
+                    f,
+                    "\n      {}",
+                    format!(
+                        "Orphaned: exists in Plex but is not managed by {}, so its files can't be removed from here.",
+                        manager
+                    )
+                    .yellow()
+                )?
             }
+            (None, None) => (),
+        }
+        match self.plex_versions.as_slice() {
+            [] => (),
+            [version] => write!(f, "\n      In Plex: {}.", version)?,
+            versions => write!(
+                f,
+                "\n      Plex has {} versions: {}.",
+                versions.len(),
+                versions.iter().join("; ")
+            )?,
         }
 
         Ok(())
@@ -202,33 +1242,121 @@ impl CompleteMediaItem {
     fn status_4k(&self) -> &str {
         match (self.arr_data.as_ref(), self.arr_4k_data.as_ref()) {
             (Some(_), None) => "",
-            (None, Some(_)) => "Only 4K ",
-            (Some(_), Some(_)) => "4K ",
-            (None, None) => {
-                panic!("Tried to write non-existant item")
-            }
+            (None, Some(_)) => "4K ",
+            (Some(_), Some(_)) => "4K and HD ",
+            (None, None) => "",
         }
     }
 }
 
+/// Adds a table to the detail view below a heading.
+fn push_table(view: &mut String, heading: &str, table: &Table) {
+    view.push_str(&format!("\n\n{}", heading));
+    for line in table.lines(DETAIL_INDENT) {
+        view.push_str(&format!("\n{}{}", " ".repeat(DETAIL_INDENT), line));
+    }
+}
+
 impl Display for CompleteMediaItem {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let seasons = match self.seasons {
+            Some(ref seasons) if !seasons.is_empty() => {
+                format!(" (seasons {})", seasons.iter().join(", "))
+            }
+            _ => String::new(),
+        };
+
         write!(
             f,
-            "{}{} {} {}.",
+            "{}{} {}{} {}.",
             self.status_4k().yellow(),
             self.media_type.to_string().blue(),
             self.title.green(),
-            human_file_size(self.get_disk_size()).red()
+            seasons.green(),
+            match self.disk_size() {
+                Some(size) => human_file_size(size),
+                None => "unknown size".to_string(),
+            }
+            .red()
         )?;
+        let instances = self.instance_names();
+        if !instances.is_empty() {
+            write!(f, " In {}.", instances.join(" and ").blue())?;
+        }
+        if let Some(ref issues) = self.open_issues {
+            write!(f, " {}", issues)?;
+        }
+        if let MediaStatus::PartiallyAvailable = self.media_status {
+            write!(f, " {}.", self.media_status)?;
+        }
         if let Some(ref request) = self.request {
             write!(f, " {}", request)?;
+        } else if Config::global().overseerr.is_none() {
+            write!(f, " Requested by {}.", "n/a".yellow())?;
+        }
+        if let (Some(ref request), Some(requester_watch)) = (&self.request, self.requester_watch) {
+            write!(
+                f,
+                " Requested by {}, {}.",
+                request.requested_by.yellow(),
+                requester_watch
+            )?;
+        }
+        if let Some(quota) = self.requester_quota {
+            write!(f, " Quota used: {}.", quota)?;
+        }
+        if !self.watchlisted_by.is_empty() {
+            let plural = if self.watchlisted_by.len() == 1 {
+                ""
+            } else {
+                "s"
+            };
+            write!(
+                f,
+                " {} ({}).",
+                format!("On {} watchlist{}", self.watchlisted_by.len(), plural).yellow(),
+                self.watchlisted_by.join(", ")
+            )?;
         }
 
         self.print_arr_data(f)?;
 
-        write!(f, "\n      {}", self.history)?;
+        if self.on_disk {
+            write!(
+                f,
+                "\n      {}",
+                self.history.display(&self.season_episode_counts())
+            )?;
+            self.print_seasons(f)?;
+        } else if let MediaStatus::Available | MediaStatus::PartiallyAvailable = self.media_status {
+            write!(
+                f,
+                "\n      {}",
+                format!(
+                    "Not found in {}, so its watch history is unknown.",
+                    watch_provider::provider().name()
+                )
+                .red()
+            )?;
+        } else {
+            write!(
+                f,
+                "\n      {}",
+                "Not on disk, it never became available.".red()
+            )?;
+        }
+        if !self.trakt_watches.is_empty() {
+            write!(
+                f,
+                "\n      {}",
+                format!(
+                    "Watched on Trakt by {}.",
+                    self.trakt_watches.iter().join(", ")
+                )
+                .green()
+            )?;
+        }
 
-        write!(f, "\n")
+        writeln!(f)
     }
 }
@@ -0,0 +1,324 @@
+use chrono::{DateTime, Utc};
+use color_eyre::{eyre::eyre, Result};
+use itertools::Itertools;
+use once_cell::sync::Lazy;
+use regex::Regex;
+use serde::Serialize;
+use std::{env, fmt};
+
+use crate::{
+    arguments::Arguments,
+    exclusion::Exclusions,
+    http, progress,
+    report::{HandledItem, Outcome, RunReport},
+    shared::{Action, MediaScope, MediaType},
+    utils::human_file_size,
+};
+
+/// The codes that color terminal text.
+static ANSI_ESCAPE: Lazy<Regex> = Lazy::new(|| Regex::new(r"\x1b\[[0-9;]*m").unwrap());
+
+/// The version of the JSON document, raised whenever a field is renamed, removed or changes meaning.
+const JSON_VERSION: u32 = 1;
+
+/// The columns of the CSV table, in the order they are written. Columns are only ever added at the end.
+const CSV_COLUMNS: [&str; 10] = [
+    "title",
+    "year",
+    "type",
+    "seasons",
+    "size_bytes",
+    "size",
+    "last_watched",
+    "requester",
+    "requester_watched",
+    "exclusion_reason",
+];
+
+/// How the candidates are written, with --output.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum OutputFormat {
+    /// For people, asking what to do with the candidates.
+    #[default]
+    Text,
+    /// A JSON document on stdout, for scripts.
+    Json,
+    /// A CSV table on stdout, for spreadsheets.
+    Csv,
+}
+
+impl OutputFormat {
+    pub fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "text" => Ok(Self::Text),
+            "json" => Ok(Self::Json),
+            "csv" => Ok(Self::Csv),
+            _ => Err(eyre!(
+                "{} is not an output format, it has to be text, json or csv.",
+                s
+            )),
+        }
+    }
+}
+
+/// Whether messages go to stderr, as stdout is kept for a document scripts read.
+pub fn messages_to_stderr() -> bool {
+    Arguments::output_format() != OutputFormat::Text
+}
+
+/// Leaves the colors out of text, unless colors are enabled.
+pub fn with_colors(text: String) -> String {
+    if colors_enabled() {
+        text
+    } else {
+        ANSI_ESCAPE.replace_all(&text, "").into_owned()
+    }
+}
+
+/// Whether messages are colored, which is turned off with --no-color or the `NO_COLOR`
+/// environment variable. The arguments are looked at directly, as errors are colored
+/// before the arguments are read.
+pub fn colors_enabled() -> bool {
+    env::var_os("NO_COLOR").is_none_or(|value| value.is_empty())
+        && !env::args().any(|arg| arg == "--no-color")
+}
+
+/// Prints a message, see `message!`.
+pub fn print_message(message: fmt::Arguments) {
+    let message = with_colors(message.to_string());
+
+    progress::print_above(|| {
+        if messages_to_stderr() {
+            eprintln!("{}", message);
+        } else {
+            println!("{}", message);
+        }
+    });
+}
+
+/// Everything that was found and done in a run, written with --output json.
+#[derive(Debug, Serialize)]
+struct Document<'a> {
+    version: u32,
+    generated_at: DateTime<Utc>,
+    dry_run: bool,
+    /// The media types the run looked at, from --media-type or `rules.media_type`.
+    media_types: Vec<MediaType>,
+    candidates: &'a [Candidate],
+    excluded: Vec<ExcludedItem<'a>>,
+    /// What the run did, counted up.
+    summary: &'a RunReport,
+}
+
+/// An item that could be cleaned up.
+#[derive(Debug, Serialize)]
+pub struct Candidate {
+    pub rating_key: Option<String>,
+    pub title: String,
+    pub year: Option<i32>,
+    pub media_type: MediaType,
+    /// The size of the files in bytes, `None` if it isn't known.
+    pub size: Option<i64>,
+    /// The seasons the candidate stands for, `None` for the whole show or a movie.
+    pub seasons: Option<Vec<u32>>,
+    pub watches: Vec<WatchEntry>,
+    pub requester: Option<Requester>,
+    /// What was done with the candidate (or would have been with --dry-run), `None` if nothing was.
+    pub action: Option<Action>,
+    #[serde(flatten)]
+    pub outcome: Option<Outcome>,
+}
+
+/// The latest watch of one user.
+#[derive(Debug, Serialize)]
+pub struct WatchEntry {
+    pub name: String,
+    /// How much of it was played, in percent.
+    pub progress: u8,
+    pub last_watched: DateTime<Utc>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Requester {
+    pub name: String,
+    /// Whether the requester finished it, `None` if it isn't known.
+    pub watched: Option<bool>,
+}
+
+/// An item the rules left out, with why.
+#[derive(Debug, Serialize)]
+struct ExcludedItem<'a> {
+    title: &'a str,
+    reason: String,
+}
+
+impl Candidate {
+    /// The row of the candidate in the CSV table, in the order of `CSV_COLUMNS`.
+    fn csv_row(&self) -> [String; CSV_COLUMNS.len()] {
+        let last_watched = self.watches.iter().map(|watch| watch.last_watched).max();
+        [
+            self.title.clone(),
+            self.year.map(|year| year.to_string()).unwrap_or_default(),
+            self.media_type.to_string(),
+            self.seasons
+                .as_ref()
+                .map(|seasons| seasons.iter().join(" "))
+                .unwrap_or_default(),
+            self.size.map(|size| size.to_string()).unwrap_or_default(),
+            self.size.map(human_file_size).unwrap_or_default(),
+            last_watched
+                .map(|date| date.format("%Y-%m-%d").to_string())
+                .unwrap_or_default(),
+            self.requester
+                .as_ref()
+                .map(|requester| requester.name.clone())
+                .unwrap_or_default(),
+            self.requester
+                .as_ref()
+                .and_then(|requester| requester.watched)
+                .map(|watched| if watched { "yes" } else { "no" }.to_string())
+                .unwrap_or_default(),
+            String::new(),
+        ]
+    }
+
+    /// Adds what was done with the candidate.
+    pub fn set_handled(&mut self, handled: HandledItem) {
+        self.action = Some(handled.action);
+        self.outcome = Some(handled.outcome);
+    }
+}
+
+/// Prints the candidates, the excluded items and the run report as JSON, the only thing
+/// written to stdout.
+pub fn print_json(
+    candidates: &[Candidate],
+    excluded: &Exclusions,
+    summary: &RunReport,
+) -> Result<()> {
+    let document = Document {
+        version: JSON_VERSION,
+        generated_at: Utc::now(),
+        dry_run: http::is_dry_run(),
+        media_types: MediaScope::of_run().media_types(),
+        candidates,
+        excluded: excluded
+            .items()
+            .iter()
+            .map(|(title, reason)| ExcludedItem {
+                title,
+                reason: reason.to_string(),
+            })
+            .collect(),
+        summary,
+    };
+
+    println!("{}", serde_json::to_string_pretty(&document)?);
+    Ok(())
+}
+
+/// Prints the candidates as a CSV table with a header, followed by the excluded items
+/// with only their title and why they were left out.
+pub fn print_csv(candidates: &[Candidate], excluded: &Exclusions) {
+    println!("{}", csv_line(CSV_COLUMNS.map(str::to_string)));
+    for candidate in candidates {
+        println!("{}", csv_line(candidate.csv_row()));
+    }
+    for (title, reason) in excluded.items() {
+        let mut row = [(); CSV_COLUMNS.len()].map(|_| String::new());
+        row[0] = title.clone();
+        row[CSV_COLUMNS.len() - 1] = reason.to_string();
+        println!("{}", csv_line(row));
+    }
+}
+
+/// Joins the fields with commas, quoting those with commas, quotes or line breaks.
+fn csv_line(fields: [String; CSV_COLUMNS.len()]) -> String {
+    fields
+        .iter()
+        .map(|field| {
+            if field.contains([',', '"', '\n', '\r']) {
+                format!("\"{}\"", field.replace('"', "\"\""))
+            } else {
+                field.clone()
+            }
+        })
+        .join(",")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn candidate() -> Candidate {
+        Candidate {
+            rating_key: Some("40001".to_string()),
+            title: "Love, Death & Robots".to_string(),
+            year: Some(2019),
+            media_type: MediaType::Tv,
+            size: Some(2_147_483_648),
+            seasons: Some(vec![1, 2]),
+            watches: vec![
+                WatchEntry {
+                    name: "alice".to_string(),
+                    progress: 100,
+                    last_watched: "2024-03-01T20:00:00Z".parse().unwrap(),
+                },
+                WatchEntry {
+                    name: "bob".to_string(),
+                    progress: 40,
+                    last_watched: "2024-05-12T21:30:00Z".parse().unwrap(),
+                },
+            ],
+            requester: Some(Requester {
+                name: "bob".to_string(),
+                watched: Some(false),
+            }),
+            action: None,
+            outcome: None,
+        }
+    }
+
+    #[test]
+    fn keeps_the_order_of_the_csv_columns() {
+        assert_eq!(
+            csv_line(CSV_COLUMNS.map(str::to_string)),
+            "title,year,type,seasons,size_bytes,size,last_watched,requester,requester_watched,exclusion_reason"
+        );
+    }
+
+    #[test]
+    fn writes_a_csv_row_per_candidate() {
+        assert_eq!(
+            csv_line(candidate().csv_row()),
+            "\"Love, Death & Robots\",2019,TV,1 2,2147483648,2.00 GiB,2024-05-12,bob,no,"
+        );
+
+        let unknown = Candidate {
+            year: None,
+            size: None,
+            seasons: None,
+            watches: Vec::new(),
+            requester: None,
+            ..candidate()
+        };
+        assert_eq!(
+            csv_line(unknown.csv_row()),
+            "\"Love, Death & Robots\",,TV,,,,,,,"
+        );
+    }
+
+    #[test]
+    fn quotes_csv_fields_that_need_it() {
+        let mut fields = [(); CSV_COLUMNS.len()].map(|_| String::new());
+        fields[0] = "The \"Best\" Of".to_string();
+        fields[1] = "two\nlines".to_string();
+        fields[2] = "plain".to_string();
+        fields[3] = "a,b".to_string();
+
+        assert_eq!(
+            csv_line(fields),
+            "\"The \"\"Best\"\" Of\",\"two\nlines\",plain,\"a,b\",,,,,,"
+        );
+    }
+}
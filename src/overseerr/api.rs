@@ -1,67 +1,261 @@
 use color_eyre::{eyre::eyre, Result};
+use reqwest::Method;
 use serde::de::DeserializeOwned;
+use serde_json::Value;
 
 use super::responses::RequestResponse;
 use crate::{
-    config::Config,
-    utils::{create_api_error_message, create_param_string},
+    config::{Config, Overseerr},
+    http::{self, RetryPolicy, SendLogged},
+    progress::Progress,
+    utils::create_param_string,
 };
 
-pub async fn get<T>(path: &str, params: Option<Vec<(&str, &str)>>) -> Result<RequestResponse<T>>
+/// The most results Overseerr sends in one page.
+const PAGE_SIZE: usize = 100;
+
+/// Gets all results of a paged endpoint, requesting page after page until as many results as
+/// Overseerr reports in the page info have been received (or the configured limit is reached).
+pub async fn get<T>(path: &str, params: Option<Vec<(&str, &str)>>) -> Result<Vec<T>>
 where
     T: DeserializeOwned,
 {
     let client = reqwest::Client::new();
-    let config = &Config::global().overseerr;
-    let response = client
-        .get(format!(
-            "{}/api/v1{}?take=100&{}",
-            &config.url,
-            path,
-            &create_param_string(params)
-        ))
-        .header("X-API-Key", &config.api_key)
-        .send()
-        .await?;
+    let limit = config()?.limit;
+    let params = create_param_string(params);
+    let mut results: Vec<T> = Vec::new();
+    let mut progress = None;
+
+    loop {
+        let mut page: RequestResponse<T> = get_page(&client, path, &params, results.len()).await?;
 
-    if !(response.status().as_u16() >= 200 && response.status().as_u16() < 300) {
-        let code = response.status().as_u16();
-        return Err(eyre!(create_api_error_message(code, path, "Overseerr")));
+        let total = match limit {
+            Some(limit) => page.page_info.results.min(limit),
+            None => page.page_info.results,
+        };
+        let page_is_empty = page.results.is_empty();
+        results.append(&mut page.results);
+
+        if page.page_info.pages > 1 {
+            progress
+                .get_or_insert_with(|| {
+                    Progress::start(format!("Fetching {} {}", service(), path), total)
+                })
+                .set(results.len());
+        }
+
+        if page_is_empty || results.len() >= total {
+            results.truncate(total);
+            break;
+        }
     }
 
-    let mut response_data: RequestResponse<T> = response.json().await?;
-
-    let page_size = response_data.page_info.page_size;
-    for page in 1..response_data.page_info.pages {
-        let mut page_data: RequestResponse<T> = client
-            .get(format!(
-                "{}/api/v1{}?take={}&skip={}",
-                &config.url,
-                path,
-                page_size,
-                page_size * page
-            ))
+    Ok(results)
+}
+
+/// Gets a single object that isn't paged.
+pub async fn get_obj<T>(path: &str) -> Result<T>
+where
+    T: DeserializeOwned,
+{
+    let config = config()?;
+    let client = reqwest::Client::new();
+    let url = format!("{}/api/v1{}", &config.url, path);
+
+    let service = service();
+    let response = http::send_with_retry(&service, path, &retry_policy(), || {
+        client
+            .get(&url)
             .header("X-API-Key", &config.api_key)
-            .send()
-            .await?
-            .json()
-            .await?;
+            .send_logged(&service)
+    })
+    .await?;
 
-        response_data.results.append(&mut page_data.results);
-    }
+    response.json().await.map_err(|err| {
+        eyre!(
+            "{} returned a response for {} that could not be read: {}",
+            service,
+            path,
+            err
+        )
+    })
+}
+
+/// Gets a single page, starting at `skip`.
+async fn get_page<T>(
+    client: &reqwest::Client,
+    path: &str,
+    params: &str,
+    skip: usize,
+) -> Result<RequestResponse<T>>
+where
+    T: DeserializeOwned,
+{
+    let config = config()?;
+    let url = format!(
+        "{}/api/v1{}?take={}&skip={}&{}",
+        &config.url, path, PAGE_SIZE, skip, params
+    );
+
+    let service = service();
+    let response = http::send_with_retry(&service, path, &retry_policy(), || {
+        client
+            .get(&url)
+            .header("X-API-Key", &config.api_key)
+            .send_logged(&service)
+    })
+    .await
+    .map_err(|err| {
+        err.wrap_err(format!(
+            "Failed to get the page starting at {} from {} {}",
+            skip, service, path
+        ))
+    })?;
 
-    Ok(response_data)
+    response.json().await.map_err(|err| {
+        eyre!(
+            "{} returned a page for {} that could not be read: {}",
+            service,
+            path,
+            err
+        )
+    })
 }
 
 pub async fn delete(path: &str) -> Result<()> {
-    let config = &Config::global().overseerr;
+    let config = config()?;
     let client = reqwest::Client::new();
+    let url = format!("{}/api/v1{}", &config.url, path);
 
-    client
-        .delete(format!("{}/api/v1{}", &config.url, path))
-        .header("X-API-Key", &config.api_key)
-        .send()
-        .await?;
+    let (service, retry_policy) = (service(), retry_policy());
+    http::send_change(Method::DELETE, &url, None, || {
+        http::send_with_retry(&service, path, &retry_policy, || {
+            client
+                .delete(&url)
+                .header("X-API-Key", &config.api_key)
+                .send_logged(&service)
+        })
+    })
+    .await?;
 
     Ok(())
 }
+
+/// Posts a JSON body to Overseerr. Returns false if Overseerr does not have the endpoint,
+/// as happens for features older versions don't support.
+pub async fn post(path: &str, body: &Value) -> Result<bool> {
+    let config = config()?;
+    let client = reqwest::Client::new();
+    let url = format!("{}/api/v1{}", &config.url, path);
+
+    let (service, retry_policy) = (service(), retry_policy());
+    let result = http::send_change(Method::POST, &url, Some(body), || {
+        http::send_with_retry(&service, path, &retry_policy, || {
+            client
+                .post(&url)
+                .header("X-API-Key", &config.api_key)
+                .json(body)
+                .send_logged(&service)
+        })
+    })
+    .await;
+
+    // Nothing is sent with --dry-run, so the endpoint is taken to be there.
+    match result {
+        Ok(_) => Ok(true),
+        Err(err) if http::is_not_found(&err) => Ok(false),
+        Err(err) => Err(err),
+    }
+}
+
+/// The name of the request manager, as Overseerr and Jellyseerr are both talked to through here.
+pub fn service() -> String {
+    Config::global().request_provider.to_string()
+}
+
+pub fn config() -> Result<&'static Overseerr> {
+    Config::global().overseerr.as_ref().ok_or_else(|| {
+        eyre!(
+            "{} is not configured, fill in the overseerr settings.",
+            service()
+        )
+    })
+}
+
+fn retry_policy() -> RetryPolicy {
+    let (attempts, base_delay_ms) = config().map_or((1, 0), |config| {
+        (config.retry_attempts, config.retry_delay_ms)
+    });
+    RetryPolicy {
+        attempts,
+        base_delay_ms,
+        timeout: None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{self, mock};
+
+    /// A page of `count` results, numbered from `first`, out of `total` results in all.
+    fn page(first: usize, count: usize, total: usize) -> String {
+        let results = (first..first + count)
+            .map(|id| format!(r#"{{"id": {}}}"#, id))
+            .collect::<Vec<_>>()
+            .join(", ");
+
+        format!(
+            r#"{{"pageInfo": {{"pages": {}, "pageSize": {}, "results": {}, "page": {}}}, "results": [{}]}}"#,
+            total.div_ceil(PAGE_SIZE),
+            PAGE_SIZE,
+            total,
+            first / PAGE_SIZE + 1,
+            results
+        )
+    }
+
+    #[tokio::test]
+    async fn gets_every_page() {
+        test_support::init();
+        let pages = [
+            mock("/test1008?take=100&skip=0&", &[(200, &page(0, 100, 250))]),
+            // A page that fails is retried before giving up.
+            mock(
+                "/test1008?take=100&skip=100&",
+                &[(502, ""), (200, &page(100, 100, 250))],
+            ),
+            mock(
+                "/test1008?take=100&skip=200&",
+                &[(200, &page(200, 50, 250))],
+            ),
+        ];
+
+        let results: Vec<Value> = get("/test1008", None).await.unwrap();
+
+        let ids = results
+            .iter()
+            .map(|result| result["id"].as_u64().unwrap() as usize)
+            .collect::<Vec<_>>();
+        assert_eq!(ids, (0..250).collect::<Vec<_>>());
+        let hits = pages.iter().map(|page| page.hits()).collect::<Vec<_>>();
+        assert_eq!(hits, [1, 2, 1]);
+    }
+
+    #[tokio::test]
+    async fn names_the_page_that_failed() {
+        test_support::init();
+        mock("/test1009?take=100&skip=0&", &[(200, &page(0, 100, 150))]);
+        let failing = mock("/test1009?take=100&skip=100&", &[(500, "")]);
+
+        let err = get::<Value>("/test1009", None).await.unwrap_err();
+
+        assert_eq!(failing.hits(), 3);
+        assert!(
+            err.to_string()
+                .starts_with("Failed to get the page starting at 100"),
+            "{:?}",
+            err
+        );
+    }
+}
@@ -3,46 +3,136 @@ mod responses;
 
 use chrono::prelude::*;
 use color_eyre::{eyre::eyre, owo_colors::OwoColorize, Result};
-use std::fmt::Display;
+use futures::future;
+use once_cell::sync::Lazy;
+use serde_json::json;
+use std::{
+    collections::{HashMap, HashSet},
+    fmt::Display,
+    sync::Once,
+};
+use tokio::sync::Mutex;
 
 use self::responses::MediaResponse;
 use crate::{
-    overseerr::responses::{MediaRequestResponse, RequestResponse},
+    config::{Cleanup, Config, WatchProviderType},
+    overseerr::responses::{
+        IssueResponse, IssueStatus, MediaRequestResponse, MovieDetailsResponse, QuotaResponse,
+        QuotaStatusResponse, StatusResponse, TvDetailsResponse,
+    },
     shared::MediaType,
+    utils::{human_date, relative_time},
 };
-pub use responses::MediaStatus;
+pub use api::service;
+pub use responses::{MediaStatus, RequestStatus};
+
+static BLACKLIST_UNSUPPORTED_WARNING: Once = Once::new();
+static DETAILS_CACHE: Lazy<Mutex<HashMap<(MediaType, u32), MediaDetails>>> =
+    Lazy::new(|| Mutex::new(HashMap::new()));
 
 #[derive(Debug)]
 pub struct MediaRequest {
     pub id: u32,
     pub media_id: u32,
+    pub tmdb_id: Option<u32>,
+    pub tvdb_id: Option<u32>,
     pub rating_key: Option<String>,
     pub manager_id: Option<i32>,
     pub manager_4k_id: Option<i32>,
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
+    /// When the media was added to the server, if Overseerr knows it.
+    pub media_added_at: Option<DateTime<Utc>>,
     pub requested_by: String,
+    /// The Overseerr id of the user who made the request.
+    pub requester_id: u32,
+    pub requester_plex_id: Option<u32>,
+    /// The requester's username on the media server the watch history comes from.
+    pub requester_username: Option<String>,
+    pub requester_email: String,
+    pub status: RequestStatus,
+    /// Whether this is a request for the 4K version, which Overseerr tracks separately.
+    pub is_4k: bool,
+    /// The seasons that were requested, empty for movies.
+    pub seasons: Vec<u32>,
+    /// The status of the version (4K or not) that was requested.
     pub media_status: responses::MediaStatus,
     pub media_type: MediaType,
 }
 
 impl MediaRequest {
-    pub async fn remove_request(self) -> Result<()> {
-        let path = format!("/media/{}", self.media_id);
-        api::delete(&path).await?;
+    /// Cleans up Overseerr after the media has been removed, as set in the config.
+    /// When only some seasons were removed, the media is still there, so only the request is deleted.
+    pub async fn clean_up(&self, only_seasons: bool) -> Result<()> {
+        match api::config()?.cleanup {
+            Cleanup::Delete => self.delete_request().await,
+            Cleanup::MarkUnavailable if only_seasons => self.delete_request().await,
+            Cleanup::MarkUnavailable => self.clear_media_status().await,
+            Cleanup::None => Ok(()),
+        }
+    }
+
+    /// Adds the media to Overseerr's blacklist, so it can't be requested again right away.
+    /// Overseerr versions without a blacklist are reported once, without failing.
+    pub async fn blacklist(&self) -> Result<()> {
+        let tmdb_id = match self.tmdb_id {
+            Some(tmdb_id) => tmdb_id,
+            None => {
+                return Err(eyre!(
+                    "Overseerr media {} has no TMDB id, so it can't be blacklisted.",
+                    self.media_id
+                ))
+            }
+        };
+        let media_type = match self.media_type {
+            MediaType::Movie => "movie",
+            MediaType::Tv => "tv",
+            MediaType::Music => return Err(eyre!("Music can't be blacklisted in Overseerr.")),
+        };
+
+        let body = json!({
+            "tmdbId": tmdb_id,
+            "mediaType": media_type,
+            "title": "Removed by Media Cleaner",
+        });
+        if !api::post("/blacklist", &body).await? {
+            BLACKLIST_UNSUPPORTED_WARNING.call_once(|| {
+                message!(
+                    "{}",
+                    "Your Overseerr version has no blacklist, so removed items were not blacklisted."
+                        .yellow()
+                )
+            });
+        }
 
         Ok(())
     }
 
+    /// Deletes the request itself.
+    pub async fn delete_request(&self) -> Result<()> {
+        let path = format!("/request/{}", self.id);
+        api::delete(&path).await
+    }
+
+    /// Deletes Overseerr's data for the media, which makes it unavailable and requestable again.
+    pub async fn clear_media_status(&self) -> Result<()> {
+        let path = format!("/media/{}", self.media_id);
+        api::delete(&path).await
+    }
+
     pub async fn get_all() -> Result<Vec<Self>> {
-        let response_data: RequestResponse<MediaRequestResponse> =
-            api::get("/request", None).await?;
+        // Overseerr's own filter is only used when just fully available media is wanted,
+        // everything else is filtered after getting all requests.
+        let params = match api::config()?.statuses {
+            Some(ref statuses) if *statuses == [MediaStatus::Available] => {
+                Some(vec![("filter", "available")])
+            }
+            _ => None,
+        };
+        let response_data: Vec<MediaRequestResponse> = api::get("/request", params).await?;
 
-        let requests: Vec<Result<MediaRequest>> = response_data
-            .results
-            .into_iter()
-            .map(Self::from_response)
-            .collect();
+        let requests: Vec<Result<MediaRequest>> =
+            response_data.into_iter().map(Self::from_response).collect();
 
         let requests = requests
             .into_iter()
@@ -59,63 +149,104 @@ impl MediaRequest {
             None => created_at,
         };
 
-        let requested_by = match &response.requested_by.display_name {
-            Some(display_name) => display_name.clone(),
+        let requested_by = match response.requested_by.display_name {
+            Some(ref display_name) => display_name.clone(),
             None => response.requested_by.email.clone(),
         };
 
         Ok(MediaRequest {
             id: response.id,
             media_id: response.media.id,
-            rating_key: response.media.rating_key,
+            tmdb_id: response.media.tmdb_id,
+            tvdb_id: response.media.tvdb_id,
+            rating_key: server_id(
+                Config::global().watch_provider,
+                response.media.rating_key,
+                response.media.jellyfin_media_id,
+            ),
             manager_id: response.media.external_service_id,
             manager_4k_id: response.media.external_service_id_4k,
             created_at: created_at.with_timezone(&Utc),
             updated_at: updated_at.with_timezone(&Utc),
-            media_status: response.media.status,
+            media_added_at: match response.media.media_added_at {
+                Some(ref added_at) => {
+                    Some(DateTime::parse_from_rfc3339(added_at)?.with_timezone(&Utc))
+                }
+                None => None,
+            },
+            requester_id: response.requested_by.id,
+            requester_plex_id: response.requested_by.plex_id,
+            requester_username: requester_username(
+                Config::global().watch_provider,
+                response.requested_by.plex_username,
+                response.requested_by.jellyfin_username,
+            ),
+            requester_email: response.requested_by.email,
+            status: response.status,
+            is_4k: response.is_4k,
+            seasons: response
+                .seasons
+                .iter()
+                .map(|season| season.season_number)
+                .collect(),
+            media_status: if response.is_4k {
+                response.media.status_4k
+            } else {
+                response.media.status
+            },
             media_type: response.media.media_type,
             requested_by,
         })
     }
 }
 
+impl MediaRequest {
+    /// A name for the requested media, for when no title can be found anywhere else.
+    pub fn placeholder_title(&self) -> String {
+        match (self.media_type, self.tmdb_id, self.tvdb_id) {
+            (MediaType::Tv, _, Some(tvdb_id)) => format!("TVDB id {}", tvdb_id),
+            (_, Some(tmdb_id), _) => format!("TMDB id {}", tmdb_id),
+            _ => format!("Overseerr media {}", self.media_id),
+        }
+    }
+}
+
 impl Display for MediaRequest {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(
             f,
-            "Requested by {} at {}, which is {} days ago.",
+            "Requested by {} at {}, {} ({}).",
             self.requested_by.yellow(),
-            self.updated_at.format("%d/%m/%Y").blue(),
-            Utc::now()
-                .signed_duration_since(self.updated_at)
-                .num_days()
-                .red()
+            human_date(self.updated_at).blue(),
+            relative_time(self.updated_at).red(),
+            self.status
         )
     }
 }
 
 #[derive(Debug)]
 pub struct ServerItem {
-    pub id: u32,
-    pub rating_key: String,
+    pub media_id: u32,
+    pub tmdb_id: Option<u32>,
+    pub tvdb_id: Option<u32>,
+    /// `None` when the server isn't Plex, the rating key is then looked up by title.
+    pub rating_key: Option<String>,
     pub manager_id: Option<i32>,
     pub manager_id_4k: Option<i32>,
-    pub created_at: DateTime<Utc>,
-    pub updated_at: DateTime<Utc>,
     pub media_status: responses::MediaStatus,
+    pub media_status_4k: responses::MediaStatus,
     pub media_type: MediaType,
+    /// When the media was added to the server, if Overseerr knows it.
+    pub added_at: Option<DateTime<Utc>>,
 }
 
 impl ServerItem {
     pub async fn get_all() -> Result<Vec<Self>> {
-        let response_data: RequestResponse<MediaResponse> =
+        let response_data: Vec<MediaResponse> =
             api::get("/media", Some(vec![("filter", "available")])).await?;
 
-        let requests: Vec<Result<Self>> = response_data
-            .results
-            .into_iter()
-            .map(Self::from_response)
-            .collect();
+        let requests: Vec<Result<Self>> =
+            response_data.into_iter().map(Self::from_response).collect();
 
         let requests = requests
             .into_iter()
@@ -126,30 +257,338 @@ impl ServerItem {
     }
 
     fn from_response(response: MediaResponse) -> Result<Self> {
-        let created_at = DateTime::parse_from_rfc3339(&response.created_at)?;
-        let updated_at = match &response.updated_at {
-            Some(updated_at) => DateTime::parse_from_rfc3339(updated_at)?,
-            None => created_at,
-        };
-
         Ok(Self {
-            id: response.id,
-            rating_key: match response.rating_key {
-                Some(rating_key) => rating_key,
-                None => {
-                    return Err(eyre!(
-                        "No rating key found for item {} of type {}.",
-                        response.id,
-                        response.media_type
-                    ))
-                }
-            },
+            media_id: response.id,
+            tmdb_id: response.tmdb_id,
+            tvdb_id: response.tvdb_id,
+            rating_key: server_id(
+                Config::global().watch_provider,
+                response.rating_key,
+                response.jellyfin_media_id,
+            ),
             manager_id: response.external_service_id,
             manager_id_4k: response.external_service_id_4k,
-            created_at: created_at.with_timezone(&Utc),
-            updated_at: updated_at.with_timezone(&Utc),
             media_status: response.status,
+            media_status_4k: response.status_4k,
             media_type: response.media_type,
+            added_at: match response.media_added_at {
+                Some(ref added_at) => {
+                    Some(DateTime::parse_from_rfc3339(added_at)?.with_timezone(&Utc))
+                }
+                None => None,
+            },
         })
     }
 }
+
+/// The open issues reported in Overseerr for a piece of media.
+#[derive(Debug, Default, Clone)]
+pub struct OpenIssues {
+    pub count: usize,
+    pub latest_comment: Option<String>,
+}
+
+impl Display for OpenIssues {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}",
+            format!("[{} open issues]", self.count).black().on_yellow()
+        )?;
+
+        if let Some(ref comment) = self.latest_comment {
+            write!(f, " Latest comment: \"{}\"", comment.yellow())?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Gets all open issues in Overseerr, grouped by the id of the media they are about.
+pub async fn get_open_issues() -> Result<HashMap<u32, OpenIssues>> {
+    let issues: Vec<IssueResponse> = api::get("/issue", Some(vec![("filter", "open")])).await?;
+
+    let mut open_issues: HashMap<u32, (OpenIssues, String)> = HashMap::new();
+    for issue in issues
+        .into_iter()
+        .filter(|issue| issue.status == IssueStatus::Open)
+    {
+        let (media_issues, latest_comment_at) = open_issues.entry(issue.media.id).or_default();
+        media_issues.count += 1;
+
+        // The dates are RFC 3339, so they can be compared as strings.
+        if let Some(comment) = issue
+            .comments
+            .into_iter()
+            .max_by(|a, b| a.created_at.cmp(&b.created_at))
+        {
+            if comment.created_at > *latest_comment_at {
+                *latest_comment_at = comment.created_at;
+                media_issues.latest_comment = Some(comment.message);
+            }
+        }
+    }
+
+    Ok(open_issues
+        .into_iter()
+        .map(|(media_id, (issues, _))| (media_id, issues))
+        .collect())
+}
+
+/// Gets the version of the request manager, which also checks it can be reached with the API key.
+pub async fn get_version() -> Result<String> {
+    let status: StatusResponse = api::get_obj("/status").await?;
+    Ok(status.version)
+}
+
+/// Runs the availability sync job, which makes Overseerr notice removed media right away instead
+/// of at its next scheduled sync. Returns false if Overseerr has no such job.
+pub async fn trigger_availability_sync() -> Result<bool> {
+    api::post("/settings/jobs/availability-sync/run", &json!({})).await
+}
+
+/// How much of their request quota for a media type a user has used.
+#[derive(Debug, Clone, Copy)]
+pub struct Quota {
+    pub used: u32,
+    /// `None` when the user can request as much as they want.
+    pub limit: Option<u32>,
+}
+
+impl Quota {
+    fn from_response(response: &QuotaStatusResponse) -> Self {
+        Quota {
+            used: response.used,
+            limit: response.limit.filter(|limit| *limit > 0),
+        }
+    }
+
+    /// The part of the quota that is used, in permille. `None` for unlimited quotas, so they come
+    /// last when sorting by quota pressure.
+    pub fn pressure(&self) -> Option<u32> {
+        self.limit.map(|limit| self.used * 1000 / limit)
+    }
+}
+
+impl Display for Quota {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.limit {
+            Some(limit) if self.used >= limit => {
+                write!(f, "{}", format!("{}/{}", self.used, limit).red())
+            }
+            Some(limit) => write!(f, "{}/{}", self.used, limit),
+            None => write!(f, "{}/∞", self.used),
+        }
+    }
+}
+
+/// The movie and show quotas of a user.
+#[derive(Debug, Clone, Copy)]
+pub struct UserQuota {
+    pub movie: Quota,
+    pub tv: Quota,
+}
+
+impl UserQuota {
+    /// The quota requests for the given media type count against, music has none.
+    pub fn for_media_type(&self, media_type: MediaType) -> Option<Quota> {
+        match media_type {
+            MediaType::Movie => Some(self.movie),
+            MediaType::Tv => Some(self.tv),
+            MediaType::Music => None,
+        }
+    }
+}
+
+/// Gets the request quotas of the given users, each user is only requested once.
+pub async fn get_user_quotas(
+    user_ids: impl IntoIterator<Item = u32>,
+) -> Result<HashMap<u32, UserQuota>> {
+    let user_ids: HashSet<u32> = user_ids.into_iter().collect();
+
+    let quotas = future::try_join_all(user_ids.into_iter().map(|user_id| async move {
+        let quota: QuotaResponse = api::get_obj(&format!("/user/{}/quota", user_id)).await?;
+
+        Ok::<(u32, UserQuota), color_eyre::Report>((
+            user_id,
+            UserQuota {
+                movie: Quota::from_response(&quota.movie),
+                tv: Quota::from_response(&quota.tv),
+            },
+        ))
+    }))
+    .await?;
+
+    Ok(quotas.into_iter().collect())
+}
+
+/// What TMDB (through Overseerr) knows about a movie or show.
+#[derive(Debug, Clone)]
+pub struct MediaDetails {
+    pub title: String,
+    pub year: Option<i32>,
+    /// The runtime of the movie, or of an episode of the show, in minutes.
+    pub runtime: Option<u32>,
+    pub overview: Option<String>,
+}
+
+impl MediaDetails {
+    /// The first line of the overview, shortened if it is very long.
+    pub fn short_overview(&self) -> Option<String> {
+        let overview = self.overview.as_ref()?.lines().next()?.trim();
+        if overview.is_empty() {
+            return None;
+        }
+
+        match overview.char_indices().nth(OVERVIEW_LENGTH) {
+            Some((end, _)) => Some(format!("{}...", &overview[..end])),
+            None => Some(overview.to_string()),
+        }
+    }
+}
+
+impl Display for MediaDetails {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.title.green())?;
+        if let Some(year) = self.year {
+            write!(f, " ({})", year)?;
+        }
+        if let Some(runtime) = self.runtime {
+            write!(f, ", {} minutes", runtime.blue())?;
+        }
+
+        Ok(())
+    }
+}
+
+const OVERVIEW_LENGTH: usize = 200;
+
+/// Gets the details of a movie or show by its TMDB id. The details are only requested once per run,
+/// as the same show can be in several requests.
+pub async fn get_details(media_type: MediaType, tmdb_id: u32) -> Result<MediaDetails> {
+    if let Some(details) = DETAILS_CACHE.lock().await.get(&(media_type, tmdb_id)) {
+        return Ok(details.clone());
+    }
+
+    let details = match media_type {
+        MediaType::Movie => {
+            let movie: MovieDetailsResponse = api::get_obj(&format!("/movie/{}", tmdb_id)).await?;
+            MediaDetails {
+                title: movie.title,
+                year: year_of(&movie.release_date),
+                runtime: movie.runtime.filter(|runtime| *runtime > 0),
+                overview: movie.overview,
+            }
+        }
+        MediaType::Tv => {
+            let show: TvDetailsResponse = api::get_obj(&format!("/tv/{}", tmdb_id)).await?;
+            MediaDetails {
+                title: show.name,
+                year: year_of(&show.first_air_date),
+                runtime: show.episode_run_time.first().copied(),
+                overview: show.overview,
+            }
+        }
+        MediaType::Music => return Err(eyre!("Overseerr has no details for music.")),
+    };
+
+    DETAILS_CACHE
+        .lock()
+        .await
+        .insert((media_type, tmdb_id), details.clone());
+
+    Ok(details)
+}
+
+fn year_of(date: &Option<String>) -> Option<i32> {
+    date.as_ref()?.get(..4)?.parse().ok()
+}
+
+/// The id of the item on the media server the watch history comes from.
+fn server_id(
+    watch_provider: WatchProviderType,
+    rating_key: Option<String>,
+    jellyfin_id: Option<String>,
+) -> Option<String> {
+    match watch_provider {
+        WatchProviderType::Tautulli => rating_key,
+        WatchProviderType::Jellyfin | WatchProviderType::Emby => jellyfin_id,
+    }
+}
+
+fn requester_username(
+    watch_provider: WatchProviderType,
+    plex_username: Option<String>,
+    jellyfin_username: Option<String>,
+) -> Option<String> {
+    match watch_provider {
+        WatchProviderType::Tautulli => plex_username,
+        WatchProviderType::Jellyfin | WatchProviderType::Emby => jellyfin_username,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{overseerr::responses::RequestResponse, test_support};
+
+    fn jellyseerr_requests() -> Vec<MediaRequestResponse> {
+        let fixture = include_str!("../../fixtures/overseerr/jellyseerr_requests.json");
+        serde_json::from_str::<RequestResponse<MediaRequestResponse>>(fixture)
+            .unwrap()
+            .results
+    }
+
+    #[test]
+    fn reads_jellyseerr_requests() {
+        test_support::init();
+        let requests = jellyseerr_requests()
+            .into_iter()
+            .map(MediaRequest::from_response)
+            .collect::<Result<Vec<_>>>()
+            .unwrap();
+
+        let movie = &requests[0];
+        assert_eq!(movie.media_type, MediaType::Movie);
+        assert_eq!((movie.tmdb_id, movie.tvdb_id), (Some(603), None));
+        assert_eq!(movie.media_status, MediaStatus::Available);
+        assert_eq!(movie.requested_by, "alice");
+        assert!(movie.media_added_at.is_some());
+        // The tests watch Tautulli, and Jellyseerr has no Plex ids to give for it.
+        assert_eq!(movie.rating_key, None);
+        assert_eq!(movie.requester_username, None);
+
+        let show = &requests[1];
+        assert_eq!(show.media_type, MediaType::Tv);
+        assert_eq!(show.tvdb_id, Some(371980));
+        assert_eq!(show.media_status, MediaStatus::PartiallyAvailable);
+        assert_eq!(show.seasons, [1, 2]);
+    }
+
+    #[test]
+    fn uses_the_ids_of_the_watch_provider() {
+        for request in jellyseerr_requests() {
+            let ids = |watch_provider| {
+                (
+                    server_id(
+                        watch_provider,
+                        request.media.rating_key.clone(),
+                        request.media.jellyfin_media_id.clone(),
+                    ),
+                    requester_username(
+                        watch_provider,
+                        request.requested_by.plex_username.clone(),
+                        request.requested_by.jellyfin_username.clone(),
+                    ),
+                )
+            };
+
+            for watch_provider in [WatchProviderType::Jellyfin, WatchProviderType::Emby] {
+                let (server_id, username) = ids(watch_provider);
+                assert_eq!(server_id, request.media.jellyfin_media_id);
+                assert_eq!(username, request.requested_by.jellyfin_username);
+            }
+            assert_eq!(ids(WatchProviderType::Tautulli), (None, None));
+        }
+    }
+}
@@ -1,7 +1,7 @@
 use color_eyre::owo_colors::OwoColorize;
 use serde::Deserialize;
 use serde_repr::Deserialize_repr;
-use std::fmt::Display;
+use std::{fmt::Display, str::FromStr};
 
 use crate::shared::MediaType;
 
@@ -15,10 +15,11 @@ pub struct RequestResponse<T> {
 #[derive(Debug, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct PageInfo {
+    #[allow(dead_code)]
     pub page: u32,
     pub pages: u32,
-    pub results: u32,
-    pub page_size: u32,
+    /// The total amount of results for all pages.
+    pub results: usize,
 }
 
 #[derive(Debug, Deserialize)]
@@ -27,22 +28,32 @@ pub struct UserResponse {
     pub id: u32,
     pub email: String,
     pub display_name: Option<String>,
+    pub plex_id: Option<u32>,
+    pub plex_username: Option<String>,
+    /// Only set by Jellyseerr.
+    pub jellyfin_username: Option<String>,
 }
 
 #[derive(Debug, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct MediaResponse {
     pub id: u32,
+    pub tmdb_id: Option<u32>,
+    pub tvdb_id: Option<u32>,
     pub external_service_id: Option<i32>,
     pub external_service_id_4k: Option<i32>,
+    /// Only set when the server is Plex, Jellyseerr with Jellyfin or Emby leaves it out.
     pub rating_key: Option<String>,
+    /// Only set by Jellyseerr.
+    pub jellyfin_media_id: Option<String>,
     pub status: MediaStatus,
+    #[serde(rename = "status4k")]
+    pub status_4k: MediaStatus,
+    pub media_added_at: Option<String>,
     pub media_type: MediaType,
-    pub created_at: String,
-    pub updated_at: Option<String>,
 }
 
-#[derive(Debug, Deserialize_repr, Clone, Copy)]
+#[derive(Debug, Deserialize_repr, Clone, Copy, PartialEq, Eq)]
 #[repr(u8)]
 pub enum MediaStatus {
     Unknown = 1,
@@ -52,14 +63,67 @@ pub enum MediaStatus {
     Available,
 }
 
+impl FromStr for MediaStatus {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "unknown" => Ok(Self::Unknown),
+            "pending" => Ok(Self::Pending),
+            "processing" => Ok(Self::Processing),
+            "partially_available" => Ok(Self::PartiallyAvailable),
+            "available" => Ok(Self::Available),
+            status => Err(format!(
+                "{} is not a media status, it has to be one of unknown, pending, processing, partially_available or available",
+                status
+            )),
+        }
+    }
+}
+
+impl MediaStatus {
+    /// The name of the status in the config.
+    pub fn config_name(&self) -> &'static str {
+        match self {
+            Self::Unknown => "unknown",
+            Self::Pending => "pending",
+            Self::Processing => "processing",
+            Self::PartiallyAvailable => "partially_available",
+            Self::Available => "available",
+        }
+    }
+}
+
 impl Display for MediaStatus {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
-            Self::Unknown => write!(f, "{}", "Unknown".red().to_string()),
-            Self::Pending => write!(f, "{}", "Pending".yellow().to_string()),
-            Self::Processing => write!(f, "{}", "Processing".yellow().to_string()),
-            Self::PartiallyAvailable => write!(f, "{}", "Partially Available".blue().to_string()),
-            Self::Available => write!(f, "{}", "Available".green().to_string()),
+            Self::Unknown => write!(f, "{}", "Unknown".red()),
+            Self::Pending => write!(f, "{}", "Pending".yellow()),
+            Self::Processing => write!(f, "{}", "Processing".yellow()),
+            Self::PartiallyAvailable => write!(f, "{}", "Partially Available".blue()),
+            Self::Available => write!(f, "{}", "Available".green()),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize_repr, Clone, Copy)]
+#[repr(u8)]
+pub enum RequestStatus {
+    PendingApproval = 1,
+    Approved,
+    Declined,
+    Failed,
+    Completed,
+}
+
+impl Display for RequestStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::PendingApproval => write!(f, "{}", "Pending approval".yellow()),
+            Self::Approved => write!(f, "{}", "Approved".green()),
+            Self::Declined => write!(f, "{}", "Declined".red()),
+            Self::Failed => write!(f, "{}", "Failed".red()),
+            Self::Completed => write!(f, "{}", "Completed".green()),
         }
     }
 }
@@ -68,8 +132,119 @@ impl Display for MediaStatus {
 #[serde(rename_all = "camelCase")]
 pub struct MediaRequestResponse {
     pub id: u32,
+    pub status: RequestStatus,
+    #[serde(rename = "is4k", default)]
+    pub is_4k: bool,
     pub media: MediaResponse,
     pub created_at: String,
     pub updated_at: Option<String>,
     pub requested_by: UserResponse,
+    #[serde(default)]
+    pub seasons: Vec<SeasonRequestResponse>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SeasonRequestResponse {
+    pub season_number: u32,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct IssueResponse {
+    pub status: IssueStatus,
+    pub media: IssueMediaResponse,
+    #[serde(default)]
+    pub comments: Vec<IssueCommentResponse>,
+}
+
+#[derive(Debug, Deserialize_repr, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum IssueStatus {
+    Open = 1,
+    Resolved,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct IssueMediaResponse {
+    pub id: u32,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct IssueCommentResponse {
+    pub message: String,
+    pub created_at: String,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MovieDetailsResponse {
+    pub title: String,
+    pub release_date: Option<String>,
+    pub runtime: Option<u32>,
+    pub overview: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TvDetailsResponse {
+    pub name: String,
+    pub first_air_date: Option<String>,
+    #[serde(default)]
+    pub episode_run_time: Vec<u32>,
+    pub overview: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct QuotaResponse {
+    pub movie: QuotaStatusResponse,
+    pub tv: QuotaStatusResponse,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct QuotaStatusResponse {
+    pub limit: Option<u32>,
+    #[serde(default)]
+    pub used: u32,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct StatusResponse {
+    pub version: String,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const STATUSES: [(u8, MediaStatus); 5] = [
+        (1, MediaStatus::Unknown),
+        (2, MediaStatus::Pending),
+        (3, MediaStatus::Processing),
+        (4, MediaStatus::PartiallyAvailable),
+        (5, MediaStatus::Available),
+    ];
+
+    #[test]
+    fn reads_the_numeric_media_statuses() {
+        for (code, status) in STATUSES {
+            assert_eq!(
+                serde_json::from_str::<MediaStatus>(&code.to_string()).unwrap(),
+                status
+            );
+        }
+        assert!(serde_json::from_str::<MediaStatus>("0").is_err());
+        assert!(serde_json::from_str::<MediaStatus>("6").is_err());
+    }
+
+    #[test]
+    fn reads_the_media_statuses_by_their_config_names() {
+        for (_, status) in STATUSES {
+            assert_eq!(status.config_name().parse::<MediaStatus>(), Ok(status));
+        }
+        let err = "partially available".parse::<MediaStatus>().unwrap_err();
+        assert!(err.starts_with("partially available is not a media status"));
+    }
 }
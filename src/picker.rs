@@ -0,0 +1,209 @@
+use color_eyre::Result;
+use dialoguer::console::{Key, Term};
+use itertools::Itertools;
+
+use crate::{
+    media_item::CompleteMediaItem,
+    output,
+    shared::SortingOption,
+    table::{self, Table},
+    utils::human_file_size,
+};
+
+/// What is shown in front of each item, like "> [x] ".
+const CHECKBOX_WIDTH: usize = 6;
+
+/// The list the items to clean up are picked from.
+struct Picker<'a> {
+    items: &'a [CompleteMediaItem],
+    /// The table lines of the items, after the header.
+    lines: Vec<String>,
+    header: String,
+    selected: Vec<bool>,
+    /// The items in the order they are shown, switched with the left and right arrow keys.
+    order: Vec<usize>,
+    sort: SortingOption,
+    /// Typed text the titles have to contain, ignoring case.
+    filter: String,
+    /// The position of the cursor among the items that pass the filter.
+    cursor: usize,
+    /// The first of the items passing the filter that is shown.
+    offset: usize,
+    page_size: usize,
+}
+
+/// Lets the items to clean up be picked from a list, moving with the arrow keys, selecting with
+/// space and filtering by typing. Left and right switch the sort, enter shows the details of an
+/// item, and tab finishes. Ctrl-C stops the program at any point, before anything is done.
+/// The items come sorted by `sort`. Returns the picked items in the order they are shown.
+pub fn pick_items(
+    items: &[CompleteMediaItem],
+    sort: SortingOption,
+    page_size: usize,
+) -> Result<Vec<usize>> {
+    let mut table = Table::new(&table::ITEM_COLUMNS);
+    items
+        .iter()
+        .for_each(|item| table.add_row(item.table_row()));
+    let mut lines = table.lines(CHECKBOX_WIDTH).into_iter();
+
+    let mut picker = Picker {
+        items,
+        header: lines.next().unwrap_or_default(),
+        lines: lines.collect(),
+        selected: vec![false; items.len()],
+        order: (0..items.len()).collect(),
+        sort,
+        filter: String::new(),
+        cursor: 0,
+        offset: 0,
+        page_size: page_size.max(1),
+    };
+
+    let term = Term::stderr();
+    term.hide_cursor()?;
+    let picked = picker.run(&term);
+    term.show_cursor()?;
+    term.clear_screen()?;
+
+    picked
+}
+
+impl Picker<'_> {
+    fn run(&mut self, term: &Term) -> Result<Vec<usize>> {
+        loop {
+            self.draw(term)?;
+
+            let shown = self.shown();
+            match term.read_key()? {
+                Key::ArrowUp => self.cursor = self.cursor.saturating_sub(1),
+                Key::ArrowDown => self.cursor += 1,
+                Key::PageUp => self.cursor = self.cursor.saturating_sub(self.page_size),
+                Key::PageDown => self.cursor += self.page_size,
+                Key::Home => self.cursor = 0,
+                Key::End => self.cursor = shown.len().saturating_sub(1),
+                Key::Char(' ') => {
+                    if let Some(&index) = shown.get(self.cursor) {
+                        self.selected[index] = !self.selected[index];
+                    }
+                }
+                Key::Enter => {
+                    if let Some(&index) = shown.get(self.cursor) {
+                        self.show_details(term, index)?;
+                    }
+                }
+                Key::ArrowLeft => self.switch_sort(SortingOption::CYCLE.len() - 1),
+                Key::ArrowRight => self.switch_sort(1),
+                Key::Tab => {
+                    return Ok(self
+                        .order
+                        .iter()
+                        .copied()
+                        .filter(|index| self.selected[*index])
+                        .collect())
+                }
+                Key::Escape => self.set_filter(String::new()),
+                Key::Backspace => {
+                    let mut filter = self.filter.clone();
+                    filter.pop();
+                    self.set_filter(filter);
+                }
+                Key::Char(c) if !c.is_control() => self.set_filter(format!("{}{}", self.filter, c)),
+                _ => (),
+            }
+
+            let shown_count = self.shown().len();
+            self.cursor = self.cursor.min(shown_count.saturating_sub(1));
+            if self.cursor < self.offset {
+                self.offset = self.cursor;
+            } else if self.cursor >= self.offset + self.page_size {
+                self.offset = self.cursor + 1 - self.page_size;
+            }
+        }
+    }
+
+    /// The indices of the items that pass the filter, in the order they are shown.
+    fn shown(&self) -> Vec<usize> {
+        let filter = self.filter.to_lowercase();
+        self.order
+            .iter()
+            .copied()
+            .filter(|index| self.items[*index].title.to_lowercase().contains(&filter))
+            .collect()
+    }
+
+    /// Sorts the list by the sort `steps` further along the cycle, starting over at the top.
+    fn switch_sort(&mut self, steps: usize) {
+        let position = SortingOption::CYCLE
+            .iter()
+            .position(|sort| *sort == self.sort)
+            .unwrap_or(0);
+        self.sort = SortingOption::CYCLE[(position + steps) % SortingOption::CYCLE.len()].clone();
+
+        let (items, sort) = (self.items, &self.sort);
+        self.order
+            .sort_by(|a, b| sort.compare(&items[*a], &items[*b]));
+        self.cursor = 0;
+        self.offset = 0;
+    }
+
+    fn set_filter(&mut self, filter: String) {
+        self.filter = filter;
+        self.cursor = 0;
+        self.offset = 0;
+    }
+
+    fn draw(&self, term: &Term) -> Result<()> {
+        let shown = self.shown();
+        let selected = (0..self.items.len())
+            .filter(|index| self.selected[*index])
+            .collect_vec();
+        let selected_size: i64 = selected
+            .iter()
+            .map(|index| self.items[*index].get_disk_size())
+            .sum();
+
+        term.clear_screen()?;
+        term.write_line(
+            "Choose what media to delete: SPACE selects, typing filters, LEFT and RIGHT change the sort, ENTER shows the details, TAB finishes.",
+        )?;
+        term.write_line(&format!(
+            "Sorted by {}  Filter: {}  ({} of {} items shown, {} selected, {})",
+            self.sort,
+            self.filter,
+            shown.len(),
+            self.items.len(),
+            selected.len(),
+            human_file_size(selected_size)
+        ))?;
+        term.write_line(&format!("{}{}", " ".repeat(CHECKBOX_WIDTH), self.header))?;
+
+        for (position, index) in shown
+            .iter()
+            .enumerate()
+            .skip(self.offset)
+            .take(self.page_size)
+        {
+            term.write_line(&format!(
+                "{} [{}] {}",
+                if position == self.cursor { ">" } else { " " },
+                if self.selected[*index] { "x" } else { " " },
+                self.lines[*index]
+            ))?;
+        }
+
+        Ok(())
+    }
+
+    /// Shows everything known about an item, including its whole watch history,
+    /// until any key is pressed.
+    fn show_details(&self, term: &Term, index: usize) -> Result<()> {
+        term.clear_screen()?;
+        term.write_line(&output::with_colors(self.items[index].detail_view()))?;
+        term.write_line("")?;
+        term.write_line("Press any key to go back to the list.")?;
+        term.read_key()?;
+
+        Ok(())
+    }
+}
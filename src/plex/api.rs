@@ -1,17 +1,24 @@
-use color_eyre::Result;
+use color_eyre::{eyre::eyre, Result};
+use reqwest::Method;
 use serde::de::DeserializeOwned;
 
+use super::responses::WatchlistResponse;
 use crate::{
-    config::Config,
-    utils::{create_api_error_message, create_param_string},
+    config::{Config, Plex},
+    http::{self, ApiError, SendLogged},
+    utils::create_param_string,
 };
-use color_eyre::eyre::eyre;
+
+/// Watchlists live on plex.tv, not on the server.
+const WATCHLIST_URL: &str = "https://discover.provider.plex.tv/library/sections/watchlist/all";
+/// How many watchlist items are asked for at once.
+const WATCHLIST_PAGE_SIZE: usize = 100;
 
 pub async fn get<T>(path: &str, params: Option<Vec<(&str, &str)>>) -> Result<T>
 where
     T: DeserializeOwned,
 {
-    let config = &Config::global().plex;
+    let config = config()?;
     let client = reqwest::Client::new();
     let params = create_param_string(params);
 
@@ -20,16 +27,83 @@ where
             "{}{}?X-Plex-Token={}&{}",
             config.url, path, config.token, params
         ))
-        .send()
-        .await?;
+        .send_logged("Plex")
+        .await
+        // The URL has the token in it.
+        .map_err(reqwest::Error::without_url)?;
 
     if !(response.status().as_u16() >= 200 && response.status().as_u16() < 300) {
         let code = response.status().as_u16();
-        return Err(eyre!(create_api_error_message(code, path, "Plex")));
+        return Err(ApiError::from_status("Plex", path, code).into());
     }
 
-    let response_text = response.text().await?;
+    let response_text = response.text().await.map_err(reqwest::Error::without_url)?;
     let parsed_response: T = serde_xml_rs::from_str(&response_text)?;
 
     Ok(parsed_response)
 }
+
+/// Deletes an item, returning false with --dry-run when nothing was deleted.
+pub async fn delete(path: &str) -> Result<bool> {
+    send_change(Method::DELETE, path).await
+}
+
+/// Sends a request that changes something and has no response worth reading,
+/// returning false with --dry-run when it wasn't sent.
+pub async fn send(path: &str) -> Result<bool> {
+    send_change(Method::GET, path).await
+}
+
+async fn send_change(method: Method, path: &str) -> Result<bool> {
+    let config = config()?;
+    let client = reqwest::Client::new();
+    // The token is sent as a header, so it isn't part of the URL that is shown or in errors.
+    let url = format!("{}{}", config.url, path);
+
+    let sent = http::send_change(method.clone(), &url, None, || async {
+        let response = client
+            .request(method.clone(), &url)
+            .header("X-Plex-Token", &config.token)
+            .send_logged("Plex")
+            .await?;
+
+        if !response.status().is_success() {
+            let code = response.status().as_u16();
+            return Err(ApiError::from_status("Plex", path, code).into());
+        }
+
+        Ok(())
+    })
+    .await?;
+
+    Ok(sent.is_some())
+}
+
+/// Gets a page of the watchlist of the account the plex.tv token belongs to, starting at `start`.
+pub async fn get_watchlist_page(token: &str, start: usize) -> Result<WatchlistResponse> {
+    let client = reqwest::Client::new();
+    let path = "/library/sections/watchlist/all";
+
+    let response = client
+        .get(format!(
+            "{}?includeGuids=1&X-Plex-Container-Start={}&X-Plex-Container-Size={}&X-Plex-Token={}",
+            WATCHLIST_URL, start, WATCHLIST_PAGE_SIZE, token
+        ))
+        .header("Accept", "application/json")
+        .send_logged("Plex")
+        .await
+        .map_err(reqwest::Error::without_url)?;
+
+    if !response.status().is_success() {
+        let code = response.status().as_u16();
+        return Err(ApiError::from_status("plex.tv", path, code).into());
+    }
+
+    Ok(response.json().await.map_err(reqwest::Error::without_url)?)
+}
+
+fn config() -> Result<&'static Plex> {
+    Config::global().plex.as_ref().ok_or_else(|| {
+        eyre!("Plex is not configured, it is only used when watch_provider is tautulli.")
+    })
+}
@@ -5,10 +5,26 @@ use crate::{plex::responses::MovieData, shared::MediaType};
 
 use self::responses::TvData;
 
-use color_eyre::Result;
+use chrono::{DateTime, TimeZone, Utc};
+use color_eyre::{eyre::eyre, Result};
+use once_cell::sync::Lazy;
+use std::collections::HashSet;
+use tokio::sync::OnceCell;
+
+use crate::{config::Config, http};
+
+/// The guids on the watchlist of each configured account, by account name.
+type Watchlists = Vec<(String, HashSet<String>)>;
+
+/// The watchlists are fetched once per run, as plex.tv can be slow.
+static WATCHLISTS: Lazy<OnceCell<Watchlists>> = Lazy::new(OnceCell::new);
 
 pub struct PlexData {
     pub title: String,
+    /// The id of the library section the item is in.
+    pub section_id: Option<String>,
+    /// When the item was added to the media server, if it says.
+    pub added_at: Option<DateTime<Utc>>,
 }
 
 impl PlexData {
@@ -20,15 +36,122 @@ impl PlexData {
 
                 Ok(Self {
                     title: raw_plex_data.video.title,
+                    section_id: raw_plex_data.library_section_id,
+                    added_at: raw_plex_data
+                        .video
+                        .added_at
+                        .and_then(|added_at| Utc.timestamp_opt(added_at, 0).single()),
                 })
             }
-            MediaType::Tv => {
+            // Albums are directories in Plex, just like shows.
+            MediaType::Tv | MediaType::Music => {
                 let raw_plex_data: TvData = api::get(&path, None).await?;
 
                 Ok(Self {
                     title: raw_plex_data.directory.title,
+                    section_id: raw_plex_data.library_section_id,
+                    added_at: raw_plex_data
+                        .directory
+                        .added_at
+                        .and_then(|added_at| Utc.timestamp_opt(added_at, 0).single()),
                 })
             }
         }
     }
 }
+
+/// Deletes an item and its files straight from Plex, for media Radarr and Sonarr don't have.
+/// Only done when `allow_delete` is set in the Plex config, as it can't be undone. Afterwards
+/// Plex is asked for the item again, to make sure it's gone (unless nothing was deleted with --dry-run).
+pub async fn delete_item(rating_key: &str, media_type: MediaType) -> Result<()> {
+    if !Config::global()
+        .plex
+        .as_ref()
+        .is_some_and(|plex| plex.allow_delete)
+    {
+        return Err(eyre!(
+            "Deleting from Plex is not allowed, set allow_delete in the Plex config to allow it."
+        ));
+    }
+
+    let path = format!("/library/metadata/{}", rating_key);
+    if !api::delete(&path).await? {
+        return Ok(());
+    }
+
+    match PlexData::get_data(rating_key, media_type).await {
+        Ok(_) => Err(eyre!("Plex still has the item after deleting it.")),
+        Err(err) if http::is_not_found(&err) => Ok(()),
+        Err(err) => Err(eyre!("could not check the item is gone from Plex: {}", err)),
+    }
+}
+
+/// Asks Plex to scan a library section, so removed media disappears right away
+/// instead of at the next scheduled scan.
+pub async fn refresh_section(section_id: &str) -> Result<()> {
+    let path = format!("/library/sections/{}/refresh", section_id);
+    api::send(&path).await?;
+    Ok(())
+}
+
+/// Gets the watchlists of the accounts in the config, fetched the first time they're needed,
+/// which is during the health check unless it is skipped.
+pub async fn get_watchlists() -> Result<&'static Watchlists> {
+    WATCHLISTS.get_or_try_init(fetch_watchlists).await
+}
+
+async fn fetch_watchlists() -> Result<Watchlists> {
+    let accounts = match Config::global()
+        .plex
+        .as_ref()
+        .and_then(|plex| plex.watchlist.as_ref())
+    {
+        Some(watchlist) => &watchlist.accounts,
+        None => return Ok(Vec::new()),
+    };
+
+    let mut watchlists = Vec::new();
+    for account in accounts {
+        let guids = fetch_watchlist(&account.token).await.map_err(|err| {
+            eyre!(
+                "Failed to get the Plex watchlist of {}: {}",
+                account.name,
+                err
+            )
+        })?;
+        watchlists.push((account.name.clone(), guids));
+    }
+
+    Ok(watchlists)
+}
+
+/// Gets the Plex guids and external ids of everything on a watchlist, page by page.
+async fn fetch_watchlist(token: &str) -> Result<HashSet<String>> {
+    let mut guids = HashSet::new();
+    let mut start = 0;
+
+    loop {
+        let page = api::get_watchlist_page(token, start).await?.media_container;
+        let page_size = page.metadata.len();
+        for item in page.metadata {
+            guids.extend(item.guid);
+            guids.extend(item.guids.into_iter().map(|guid| guid.id));
+        }
+
+        start += page_size;
+        if page_size == 0 || start >= page.total_size.unwrap_or(0) {
+            return Ok(guids);
+        }
+    }
+}
+
+/// The names of the accounts with any of the guids on their watchlist.
+pub async fn watchlisted_by(guids: &[String]) -> Result<Vec<String>> {
+    let watchlists = get_watchlists().await?;
+
+    Ok(watchlists
+        .iter()
+        .filter(|(_, watchlist)| guids.iter().any(|guid| watchlist.contains(guid)))
+        .map(|(name, _)| name.clone())
+        .collect())
+}
@@ -2,6 +2,8 @@ use serde::Deserialize;
 
 #[derive(Debug, Deserialize)]
 pub struct TvData {
+    #[serde(rename = "librarySectionID")]
+    pub library_section_id: Option<String>,
     #[serde(rename = "Directory")]
     pub directory: Directory,
 }
@@ -9,10 +11,15 @@ pub struct TvData {
 #[derive(Debug, Deserialize)]
 pub struct Directory {
     pub title: String,
+    /// When the item was added to Plex, in seconds since the epoch.
+    #[serde(rename = "addedAt")]
+    pub added_at: Option<i64>,
 }
 
 #[derive(Debug, Deserialize)]
 pub struct MovieData {
+    #[serde(rename = "librarySectionID")]
+    pub library_section_id: Option<String>,
     #[serde(rename = "Video")]
     pub video: Video,
 }
@@ -20,4 +27,36 @@ pub struct MovieData {
 #[derive(Debug, Deserialize)]
 pub struct Video {
     pub title: String,
+    /// When the item was added to Plex, in seconds since the epoch.
+    #[serde(rename = "addedAt")]
+    pub added_at: Option<i64>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct WatchlistResponse {
+    #[serde(rename = "MediaContainer")]
+    pub media_container: WatchlistContainer,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WatchlistContainer {
+    /// The size of the whole watchlist, not only this page.
+    pub total_size: Option<usize>,
+    #[serde(rename = "Metadata", default)]
+    pub metadata: Vec<WatchlistItem>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct WatchlistItem {
+    /// The Plex guid, like "plex://movie/5d776b59ad5437001f79c6f8".
+    pub guid: Option<String>,
+    /// The external ids, like "tmdb://603".
+    #[serde(rename = "Guid", default)]
+    pub guids: Vec<Guid>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Guid {
+    pub id: String,
 }
@@ -0,0 +1,144 @@
+use dialoguer::console::Term;
+use once_cell::sync::Lazy;
+use std::sync::Mutex;
+
+use crate::arguments::Arguments;
+
+/// How many lines a step is logged with when no bar is drawn.
+const LOG_LINES: usize = 10;
+
+/// The width of the bar itself, without the label and counts.
+const BAR_WIDTH: usize = 30;
+
+/// The step whose progress is shown, only one at a time.
+static CURRENT: Lazy<Mutex<Option<State>>> = Lazy::new(|| Mutex::new(None));
+
+struct State {
+    label: String,
+    done: usize,
+    total: usize,
+    /// The item that was finished last.
+    item: String,
+    /// How many were done at the last logged line, when no bar is drawn.
+    logged: usize,
+}
+
+/// Shows how far a long step is, as a bar on the last line of the terminal. When stdout isn't a
+/// terminal or with --quiet, a line like "Fetched 200/900 histories" is printed every so often
+/// instead. The bar is removed once the `Progress` is dropped.
+pub struct Progress;
+
+impl Progress {
+    pub fn start(label: impl Into<String>, total: usize) -> Self {
+        *CURRENT.lock().unwrap() = Some(State {
+            label: label.into(),
+            done: 0,
+            total,
+            item: String::new(),
+            logged: 0,
+        });
+        draw();
+        Progress
+    }
+
+    /// Counts one more item as done, `item` being what is shown as the current item.
+    pub fn inc(&self, item: &str) {
+        self.update(|state| {
+            state.done += 1;
+            item.clone_into(&mut state.item);
+        })
+    }
+
+    /// Sets how many items are done, for steps that finish several at once.
+    pub fn set(&self, done: usize) {
+        self.update(|state| state.done = done)
+    }
+
+    fn update(&self, change: impl FnOnce(&mut State)) {
+        let line = {
+            let mut current = CURRENT.lock().unwrap();
+            let Some(state) = current.as_mut() else {
+                return;
+            };
+            change(state);
+            state.done = state.done.min(state.total);
+
+            if draws_bar() {
+                None
+            } else {
+                // One line every tenth of the way, and one at the end.
+                let step = (state.total / LOG_LINES).max(1);
+                let reached = state.done == state.total || state.done >= state.logged + step;
+                if reached && state.done > state.logged {
+                    state.logged = state.done;
+                    Some(format!("{} {}/{}", state.label, state.done, state.total))
+                } else {
+                    return;
+                }
+            }
+        };
+
+        match line {
+            Some(line) => message!("{}", line),
+            None => draw(),
+        }
+    }
+}
+
+impl Drop for Progress {
+    fn drop(&mut self) {
+        clear();
+        *CURRENT.lock().unwrap() = None;
+    }
+}
+
+/// Prints something without it getting mixed up with the bar, which is drawn again below it.
+pub fn print_above(print: impl FnOnce()) {
+    clear();
+    print();
+    draw();
+}
+
+/// Whether a bar is drawn, which only works when stdout and stderr are terminals.
+fn draws_bar() -> bool {
+    !Arguments::get_args().quiet && Term::stdout().is_term() && Term::stderr().is_term()
+}
+
+fn draw() {
+    let current = match CURRENT.try_lock() {
+        Ok(current) => current,
+        Err(_) => return,
+    };
+    let Some(ref state) = *current else {
+        return;
+    };
+    if !draws_bar() {
+        return;
+    }
+
+    let filled = match state.total {
+        0 => BAR_WIDTH,
+        total => BAR_WIDTH * state.done / total,
+    };
+    let line = format!(
+        "{} [{}{}] {}/{} {}",
+        state.label,
+        "#".repeat(filled),
+        "-".repeat(BAR_WIDTH - filled),
+        state.done,
+        state.total,
+        state.item
+    );
+
+    let term = Term::stderr();
+    let width = term.size().1 as usize;
+    let line: String = line.chars().take(width.saturating_sub(1)).collect();
+    let _ = term.clear_line();
+    let _ = term.write_str(&line);
+}
+
+fn clear() {
+    if CURRENT.try_lock().is_ok_and(|current| current.is_some()) && draws_bar() {
+        let _ = Term::stderr().clear_line();
+    }
+}
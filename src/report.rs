@@ -0,0 +1,278 @@
+use chrono::{DateTime, Utc};
+use color_eyre::{owo_colors::OwoColorize, Report};
+use serde::Serialize;
+use std::{collections::BTreeMap, time::Instant};
+
+use crate::{
+    exclusion::Exclusions,
+    http::{self, ApiError},
+    shared::{Action, MediaScope, MediaType},
+    table::Table,
+    utils::{human_duration, human_file_size},
+};
+
+/// What was done with a chosen item.
+#[derive(Debug, Serialize)]
+pub struct HandledItem {
+    pub title: String,
+    pub media_type: MediaType,
+    pub action: Action,
+    /// The size of the files that were removed, 0 if nothing was removed.
+    pub freed_bytes: i64,
+    #[serde(flatten)]
+    pub outcome: Outcome,
+}
+
+impl HandledItem {
+    pub fn failed(&self) -> bool {
+        !matches!(self.outcome, Outcome::Done)
+    }
+}
+
+/// How handling an item went.
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "outcome", rename_all = "snake_case")]
+pub enum Outcome {
+    Done,
+    /// The item is gone from Radarr or Sonarr, but these paths are still on disk.
+    PartiallyRemoved {
+        remaining_paths: Vec<String>,
+    },
+    Failed {
+        error: String,
+        /// The service that refused, if it answered at all.
+        #[serde(skip_serializing_if = "Option::is_none")]
+        service: Option<String>,
+        /// The HTTP status it answered with.
+        #[serde(skip_serializing_if = "Option::is_none")]
+        status: Option<u16>,
+    },
+}
+
+impl Outcome {
+    /// A failure, with the service and status of the answer that caused it.
+    pub fn failed(err: &Report) -> Self {
+        let api_error = err.chain().find_map(|err| err.downcast_ref::<ApiError>());
+        Self::Failed {
+            error: err.to_string(),
+            service: api_error.map(|err| err.service().to_string()),
+            status: api_error.map(ApiError::status),
+        }
+    }
+}
+
+/// The recap of a run, counted up as items are handled and shown at the end.
+/// Written with --output json and in the --auto report.
+#[derive(Debug, Serialize)]
+pub struct RunReport {
+    #[serde(skip)]
+    started: Instant,
+    pub started_at: DateTime<Utc>,
+    pub duration_seconds: u64,
+    pub dry_run: bool,
+    /// The media types the run looked at, from --media-type or `rules.media_type`.
+    pub media_types: Vec<MediaType>,
+    /// Items removed from Radarr or Sonarr along with their files.
+    pub deleted: usize,
+    pub deleted_bytes: i64,
+    /// How many of the deleted items were blocked from being added again.
+    pub blocked: usize,
+    /// Items whose files were removed while they were kept in Radarr or Sonarr.
+    pub files_deleted: usize,
+    pub files_deleted_bytes: i64,
+    pub unmonitored: usize,
+    pub skipped: usize,
+    pub ignored: usize,
+    pub partially_removed: usize,
+    /// How many items the rules left out, by reason.
+    pub left_out: BTreeMap<String, usize>,
+    pub failures: Vec<Failure>,
+    /// Set when --limit or --max-bytes left out items that could have been handled.
+    pub limited: Option<Limited>,
+}
+
+/// How many items were left for a later run by --limit or --max-bytes.
+#[derive(Debug, Serialize)]
+pub struct Limited {
+    /// The items that could have been handled.
+    pub eligible: usize,
+    pub unprocessed: usize,
+}
+
+/// An item that couldn't be handled.
+#[derive(Debug, Serialize)]
+pub struct Failure {
+    pub title: String,
+    pub action: Action,
+    pub error: String,
+    pub service: Option<String>,
+    pub status: Option<u16>,
+}
+
+impl RunReport {
+    pub fn start() -> Self {
+        Self {
+            started: Instant::now(),
+            started_at: Utc::now(),
+            duration_seconds: 0,
+            dry_run: http::is_dry_run(),
+            media_types: Vec::new(),
+            deleted: 0,
+            deleted_bytes: 0,
+            blocked: 0,
+            files_deleted: 0,
+            files_deleted_bytes: 0,
+            unmonitored: 0,
+            skipped: 0,
+            ignored: 0,
+            partially_removed: 0,
+            left_out: BTreeMap::new(),
+            failures: Vec::new(),
+            limited: None,
+        }
+    }
+
+    /// Notes that only `taken` of the `eligible` items were taken because of the limits.
+    pub fn limit(&mut self, eligible: usize, taken: usize) {
+        if taken < eligible {
+            self.limited = Some(Limited {
+                eligible,
+                unprocessed: eligible - taken,
+            });
+        }
+    }
+
+    /// Counts a handled item.
+    pub fn add(&mut self, item: &HandledItem) {
+        match item.outcome {
+            Outcome::Done => match item.action {
+                Action::Delete | Action::DeleteAndBlock | Action::DeleteSeasons => {
+                    self.deleted += 1;
+                    self.deleted_bytes += item.freed_bytes;
+                    if item.action == Action::DeleteAndBlock {
+                        self.blocked += 1;
+                    }
+                }
+                Action::DeleteFiles => {
+                    self.files_deleted += 1;
+                    self.files_deleted_bytes += item.freed_bytes;
+                }
+                Action::Unmonitor => self.unmonitored += 1,
+                Action::Skip => self.skipped += 1,
+                Action::Ignore => self.ignored += 1,
+            },
+            Outcome::PartiallyRemoved { .. } => {
+                self.partially_removed += 1;
+                self.deleted_bytes += item.freed_bytes;
+            }
+            Outcome::Failed {
+                ref error,
+                ref service,
+                status,
+            } => self.failures.push(Failure {
+                title: item.title.clone(),
+                action: item.action,
+                error: error.clone(),
+                service: service.clone(),
+                status,
+            }),
+        }
+    }
+
+    /// Takes in what the rules left out and how long the run took.
+    pub fn finish(&mut self, excluded: &Exclusions) {
+        self.duration_seconds = self.started.elapsed().as_secs();
+        self.media_types = MediaScope::of_run().media_types();
+        self.left_out = excluded.counts();
+    }
+
+    pub fn freed_bytes(&self) -> i64 {
+        self.deleted_bytes + self.files_deleted_bytes
+    }
+
+    /// Shows the recap as a table, followed by the failures.
+    pub fn print(&self) {
+        let mut table = Table::new(&["Result", "Items", "Size"]);
+        let rows = [
+            ("Deleted", self.deleted, Some(self.deleted_bytes)),
+            ("  blocked from being added again", self.blocked, None),
+            (
+                "Files deleted",
+                self.files_deleted,
+                Some(self.files_deleted_bytes),
+            ),
+            ("Partially removed", self.partially_removed, None),
+            ("Unmonitored", self.unmonitored, None),
+            ("Skipped", self.skipped, None),
+            ("Ignored", self.ignored, None),
+            ("Failed", self.failures.len(), None),
+        ];
+        for (result, count, size) in rows {
+            table.add_row(vec![
+                result.to_string(),
+                count.to_string(),
+                size.map(human_file_size).unwrap_or_default(),
+            ]);
+        }
+        for (reason, count) in &self.left_out {
+            table.add_row(vec![
+                format!("Left out: {}", reason),
+                count.to_string(),
+                String::new(),
+            ]);
+        }
+
+        let title = if self.dry_run {
+            format!("{} What this run would have done:", http::DRY_RUN_PREFIX)
+        } else {
+            "What this run did:".to_string()
+        };
+        message!("\n{}", title.bold());
+        for line in table.lines(0) {
+            message!("{}", line);
+        }
+        if self.dry_run {
+            message!(
+                "{} Would have freed {} across {} items, in {}.",
+                http::DRY_RUN_PREFIX.yellow(),
+                human_file_size(self.freed_bytes()),
+                self.deleted + self.files_deleted,
+                human_duration(self.started.elapsed())
+            );
+        } else {
+            message!(
+                "Freed {} in {}.",
+                human_file_size(self.freed_bytes()),
+                human_duration(self.started.elapsed())
+            );
+        }
+
+        if let Some(ref limited) = self.limited {
+            message!(
+                "{}",
+                format!(
+                    "The run was limited by --limit or --max-bytes, {} of the {} eligible items were left for a later run.",
+                    limited.unprocessed, limited.eligible
+                )
+                .yellow()
+            );
+        }
+
+        if !self.failures.is_empty() {
+            message!("{}", "These items could not be handled:".red());
+            for failure in &self.failures {
+                let answer = match (&failure.service, failure.status) {
+                    (Some(service), Some(status)) => format!(", {} answered {}", service, status),
+                    _ => String::new(),
+                };
+                message!(
+                    "- {} ({}{}): {}",
+                    failure.title,
+                    failure.action,
+                    answer,
+                    failure.error
+                );
+            }
+        }
+    }
+}
@@ -0,0 +1,323 @@
+use chrono::{DateTime, Datelike, Duration, Local, NaiveDate, NaiveDateTime, TimeZone, Timelike};
+use color_eyre::{eyre::eyre, Result};
+use std::fmt::Display;
+
+/// The names cron takes for months, January first.
+const MONTHS: [&str; 12] = [
+    "jan", "feb", "mar", "apr", "may", "jun", "jul", "aug", "sep", "oct", "nov", "dec",
+];
+
+/// The names cron takes for days of the week, Sunday first.
+const WEEKDAYS: [&str; 7] = ["sun", "mon", "tue", "wed", "thu", "fri", "sat"];
+
+/// How far ahead a run is looked for, so a schedule like February 30th doesn't loop forever.
+const MAX_YEARS_AHEAD: i32 = 5;
+
+/// When the daemon runs, from --schedule or --every.
+#[derive(Debug, Clone)]
+pub enum Schedule {
+    /// A cron expression, in local time.
+    Cron(Cron),
+    /// A fixed time between the start of one run and the next.
+    Every(Duration),
+}
+
+impl Schedule {
+    /// The first run after `after`, `None` if the schedule never runs again.
+    pub fn next_after(&self, after: DateTime<Local>) -> Option<DateTime<Local>> {
+        match self {
+            Self::Cron(cron) => cron.next_after(after),
+            Self::Every(interval) => Some(after + *interval),
+        }
+    }
+}
+
+impl Display for Schedule {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Cron(cron) => write!(f, "on the schedule \"{}\"", cron.expression),
+            Self::Every(interval) => {
+                let minutes = interval.num_minutes();
+                let (amount, unit) = match minutes {
+                    _ if minutes % (24 * 60) == 0 => (minutes / (24 * 60), "day"),
+                    _ if minutes % 60 == 0 => (minutes / 60, "hour"),
+                    _ => (minutes, "minute"),
+                };
+                let plural = if amount == 1 { "" } else { "s" };
+                write!(f, "every {} {}{}", amount, unit, plural)
+            }
+        }
+    }
+}
+
+/// A cron expression with five fields: minute, hour, day of the month, month and day of the week.
+/// Each field takes `*`, numbers, ranges like `1-5`, steps like `*/15` and lists of those,
+/// and months and days of the week can be given by their English name, like `Sun` or `jan-mar`.
+#[derive(Debug, Clone)]
+pub struct Cron {
+    expression: String,
+    /// The allowed values of each field, as bits.
+    minutes: u64,
+    hours: u64,
+    days: u64,
+    months: u64,
+    weekdays: u64,
+    /// Whether the day of the month and the day of the week are `*`. When both are restricted,
+    /// a day matching either is a match, like in cron.
+    any_day: bool,
+    any_weekday: bool,
+}
+
+impl Cron {
+    pub fn from_str(expression: &str) -> Result<Self> {
+        let expanded = match expression.trim() {
+            "@hourly" => "0 * * * *",
+            "@daily" | "@midnight" => "0 0 * * *",
+            "@weekly" => "0 0 * * 0",
+            "@monthly" => "0 0 1 * *",
+            "@yearly" | "@annually" => "0 0 1 1 *",
+            expression => expression,
+        };
+        let fields: Vec<&str> = expanded.split_whitespace().collect();
+        if fields.len() != 5 {
+            return Err(eyre!(
+                "\"{}\" has to have five fields (minute, hour, day of the month, month and day of the week), like \"0 3 * * Sun\".",
+                expression
+            ));
+        }
+
+        let field = |index: usize, name: &str, min: u32, max: u32, names: &[&str]| {
+            parse_field(fields[index], min, max, names)
+                .map_err(|err| eyre!("The {} of \"{}\" is not valid: {}", name, expression, err))
+        };
+        let mut weekdays = field(4, "day of the week", 0, 7, &WEEKDAYS)?;
+        // Sunday is both 0 and 7.
+        if weekdays & (1 << 7) != 0 {
+            weekdays |= 1;
+        }
+
+        Ok(Self {
+            expression: expression.trim().to_string(),
+            minutes: field(0, "minute", 0, 59, &[])?,
+            hours: field(1, "hour", 0, 23, &[])?,
+            days: field(2, "day of the month", 1, 31, &[])?,
+            months: field(3, "month", 1, 12, &MONTHS)?,
+            weekdays,
+            any_day: fields[2] == "*",
+            any_weekday: fields[4] == "*",
+        })
+    }
+
+    /// The first minute after `after` the expression matches, in the timezone of `after`.
+    fn next_after<Tz: TimeZone>(&self, after: DateTime<Tz>) -> Option<DateTime<Tz>> {
+        let start = after.naive_local().with_second(0)?.with_nanosecond(0)? + Duration::minutes(1);
+        let mut time = start;
+
+        while time.year() <= start.year() + MAX_YEARS_AHEAD {
+            if !has(self.months, time.month()) {
+                time = start_of_day(first_of_next_month(time.date())?);
+            } else if !self.matches_day(time.date()) {
+                time = start_of_day(time.date().succ_opt()?);
+            } else if !has(self.hours, time.hour()) {
+                time = time.with_minute(0)? + Duration::hours(1);
+            } else if !has(self.minutes, time.minute()) {
+                time += Duration::minutes(1);
+            } else {
+                // A time skipped by a clock change doesn't exist, so it runs right after
+                // the change instead. A time that happens twice only runs the first time.
+                let timezone = after.timezone();
+                return (0..24 * 60).find_map(|minutes| {
+                    timezone
+                        .from_local_datetime(&(time + Duration::minutes(minutes)))
+                        .earliest()
+                });
+            }
+        }
+
+        None
+    }
+
+    fn matches_day(&self, date: NaiveDate) -> bool {
+        let day = has(self.days, date.day());
+        let weekday = has(self.weekdays, date.weekday().num_days_from_sunday());
+        match (self.any_day, self.any_weekday) {
+            (false, false) => day || weekday,
+            _ => day && weekday,
+        }
+    }
+}
+
+fn has(bits: u64, value: u32) -> bool {
+    bits & (1 << value) != 0
+}
+
+fn start_of_day(date: NaiveDate) -> NaiveDateTime {
+    date.and_hms_opt(0, 0, 0).unwrap_or_default()
+}
+
+fn first_of_next_month(date: NaiveDate) -> Option<NaiveDate> {
+    match date.month() {
+        12 => NaiveDate::from_ymd_opt(date.year() + 1, 1, 1),
+        month => NaiveDate::from_ymd_opt(date.year(), month + 1, 1),
+    }
+}
+
+/// Reads one field into the bits of the values it allows.
+fn parse_field(field: &str, min: u32, max: u32, names: &[&str]) -> Result<u64> {
+    let mut bits = 0;
+    for part in field.split(',') {
+        let (range, step) = match part.split_once('/') {
+            Some((range, step)) => (
+                range,
+                step.parse::<u32>()
+                    .ok()
+                    .filter(|step| *step > 0)
+                    .ok_or_else(|| {
+                        eyre!("{} is not a step, it has to be a number above 0.", step)
+                    })?,
+            ),
+            None => (part, 1),
+        };
+
+        let (start, end) = match range {
+            "*" => (min, max),
+            range => match range.split_once('-') {
+                Some((start, end)) => (
+                    parse_value(start, min, max, names)?,
+                    parse_value(end, min, max, names)?,
+                ),
+                // A single value with a step runs to the end, like `5/15`.
+                None if step > 1 => (parse_value(range, min, max, names)?, max),
+                None => {
+                    let value = parse_value(range, min, max, names)?;
+                    (value, value)
+                }
+            },
+        };
+        if start > end {
+            return Err(eyre!("{} runs backwards.", range));
+        }
+
+        for value in (start..=end).step_by(step as usize) {
+            bits |= 1 << value;
+        }
+    }
+
+    Ok(bits)
+}
+
+fn parse_value(value: &str, min: u32, max: u32, names: &[&str]) -> Result<u32> {
+    // Names count from the lowest value, January is 1 and Sunday 0.
+    if let Some(position) = names
+        .iter()
+        .position(|name| name.eq_ignore_ascii_case(value))
+    {
+        return Ok(min + position as u32);
+    }
+
+    match value.parse::<u32>() {
+        Ok(number) if (min..=max).contains(&number) => Ok(number),
+        _ => Err(eyre!(
+            "{} has to be a number from {} to {}{}.",
+            value,
+            min,
+            max,
+            if names.is_empty() { "" } else { " or a name" }
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono_tz::{Europe::Amsterdam, Tz};
+
+    /// A time in Amsterdam, like "2024-03-31 02:30".
+    fn amsterdam(time: &str) -> DateTime<Tz> {
+        let time = NaiveDateTime::parse_from_str(time, "%Y-%m-%d %H:%M").unwrap();
+        Amsterdam.from_local_datetime(&time).earliest().unwrap()
+    }
+
+    fn next(expression: &str, after: &str) -> Option<String> {
+        Cron::from_str(expression)
+            .unwrap()
+            .next_after(amsterdam(after))
+            .map(|time| time.format("%Y-%m-%d %H:%M %Z").to_string())
+    }
+
+    #[test]
+    fn finds_the_next_matching_minute() {
+        let cases = [
+            ("*/15 * * * *", "2024-05-01 10:07", "2024-05-01 10:15 CEST"),
+            ("*/15 * * * *", "2024-05-01 10:15", "2024-05-01 10:30 CEST"),
+            ("0 3 * * Sun", "2024-05-01 10:07", "2024-05-05 03:00 CEST"),
+            ("0 3 * * 7", "2024-05-05 03:00", "2024-05-12 03:00 CEST"),
+            ("@monthly", "2024-12-15 00:00", "2025-01-01 00:00 CET"),
+            ("0 0 29 feb *", "2025-01-01 00:00", "2028-02-29 00:00 CET"),
+            // With both days restricted, either one matching is enough.
+            ("0 12 13 * Fri", "2024-09-01 00:00", "2024-09-06 12:00 CEST"),
+            ("0 12 13 * Fri", "2024-09-06 12:00", "2024-09-13 12:00 CEST"),
+        ];
+        for (expression, after, expected) in cases {
+            assert_eq!(
+                next(expression, after).as_deref(),
+                Some(expected),
+                "{} after {}",
+                expression,
+                after
+            );
+        }
+    }
+
+    #[test]
+    fn a_date_that_never_comes_is_not_scheduled() {
+        assert_eq!(next("0 0 30 feb *", "2024-01-01 00:00"), None);
+    }
+
+    #[test]
+    fn runs_skipped_by_the_clocks_happen_right_after_the_change() {
+        // In 2024 the clocks skipped from 02:00 to 03:00 on March 31st.
+        assert_eq!(
+            next("30 2 * * *", "2024-03-30 12:00").as_deref(),
+            Some("2024-03-31 03:00 CEST")
+        );
+        assert_eq!(
+            next("30 2 * * *", "2024-03-31 03:00").as_deref(),
+            Some("2024-04-01 02:30 CEST")
+        );
+    }
+
+    #[test]
+    fn runs_at_a_time_that_happens_twice_only_run_once() {
+        // In 2024 the clocks went back from 03:00 to 02:00 on October 27th.
+        let first = Cron::from_str("30 2 * * *")
+            .unwrap()
+            .next_after(amsterdam("2024-10-26 12:00"))
+            .unwrap();
+        assert_eq!(first.to_rfc3339(), "2024-10-27T02:30:00+02:00");
+
+        let second = Cron::from_str("30 2 * * *")
+            .unwrap()
+            .next_after(first)
+            .unwrap();
+        assert_eq!(second.to_rfc3339(), "2024-10-28T02:30:00+01:00");
+
+        // The hour that happens twice is only gone through once.
+        let quarter = Cron::from_str("*/15 * * * *").unwrap();
+        let after_first = quarter.next_after(amsterdam("2024-10-27 02:45")).unwrap();
+        assert_eq!(after_first.to_rfc3339(), "2024-10-27T03:00:00+01:00");
+    }
+
+    #[test]
+    fn rejects_invalid_expressions() {
+        let err = |expression| Cron::from_str(expression).unwrap_err().to_string();
+
+        assert!(err("0 3 * *").contains("has to have five fields"));
+        assert_eq!(
+            err("61 * * * *"),
+            "The minute of \"61 * * * *\" is not valid: 61 has to be a number from 0 to 59."
+        );
+        assert!(err("0 3 * * fri-mon").ends_with("fri-mon runs backwards."));
+        assert!(err("*/0 * * * *").contains("0 is not a step"));
+    }
+}
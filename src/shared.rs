@@ -1,12 +1,19 @@
 use color_eyre::{eyre::eyre, Result};
-use serde::Deserialize;
-use std::fmt::Display;
+use serde::{Deserialize, Serialize};
+use std::{
+    cmp::{Ordering, Reverse},
+    fmt::Display,
+};
 
-#[derive(Debug, Deserialize, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+use crate::{arguments::Arguments, config::Config, media_item::CompleteMediaItem};
+
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
 #[serde(rename_all = "camelCase")]
 pub enum MediaType {
     Movie,
     Tv,
+    /// An album in a music library.
+    Music,
 }
 
 impl Display for MediaType {
@@ -14,24 +21,141 @@ impl Display for MediaType {
         match self {
             Self::Movie => write!(f, "Movie"),
             Self::Tv => write!(f, "TV"),
+            Self::Music => write!(f, "Music"),
+        }
+    }
+}
+
+impl MediaType {
+    /// Reads a media type without regard to case, in the singular or plural, like "Movies" or "show".
+    pub fn from_str(s: &str) -> Result<Self> {
+        match s.trim().to_lowercase().as_str() {
+            "movie" | "movies" | "film" | "films" => Ok(Self::Movie),
+            "tv" | "show" | "shows" | "series" => Ok(Self::Tv),
+            "music" | "album" | "albums" => Ok(Self::Music),
+            _ => Err(eyre!(
+                "{} is not a media type, it has to be movies, tv, music or all.",
+                s
+            )),
         }
     }
 }
 
-#[derive(Debug, Clone, Copy)]
+/// Which media a run is about, with --media-type or `rules.media_type`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub enum MediaScope {
+    #[default]
+    All,
+    Only(MediaType),
+}
+
+impl MediaScope {
+    pub fn from_str(s: &str) -> Result<Self> {
+        match s.trim().to_lowercase().as_str() {
+            "all" | "both" => Ok(Self::All),
+            media_type => MediaType::from_str(media_type).map(Self::Only),
+        }
+    }
+
+    /// The scope from --media-type, or else the config.
+    pub fn of_run() -> Self {
+        Arguments::get_args()
+            .media_type
+            .unwrap_or(Config::global().rules.media_type)
+    }
+
+    pub fn includes(&self, media_type: MediaType) -> bool {
+        match self {
+            Self::All => true,
+            Self::Only(only) => *only == media_type,
+        }
+    }
+
+    /// The media types in scope, like they are named in the JSON output.
+    pub fn media_types(&self) -> Vec<MediaType> {
+        [MediaType::Movie, MediaType::Tv, MediaType::Music]
+            .into_iter()
+            .filter(|media_type| self.includes(*media_type))
+            .collect()
+    }
+}
+
+impl TryFrom<String> for MediaScope {
+    type Error = color_eyre::Report;
+
+    fn try_from(s: String) -> Result<Self> {
+        Self::from_str(&s)
+    }
+}
+
+impl From<MediaScope> for String {
+    fn from(scope: MediaScope) -> Self {
+        match scope {
+            MediaScope::All => "all".to_string(),
+            MediaScope::Only(MediaType::Movie) => "movies".to_string(),
+            MediaScope::Only(MediaType::Tv) => "tv".to_string(),
+            MediaScope::Only(MediaType::Music) => "music".to_string(),
+        }
+    }
+}
+
+impl Display for MediaScope {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::All => write!(f, "all media"),
+            Self::Only(MediaType::Movie) => write!(f, "movies only"),
+            Self::Only(MediaType::Tv) => write!(f, "TV only"),
+            Self::Only(MediaType::Music) => write!(f, "music only"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Order {
     Desc,
     Asc,
 }
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum SortingValue {
     Name,
     Size,
     Type,
+    LastWatched,
+    /// How much of their Overseerr request quota the requester has used.
+    QuotaPressure,
+    /// How long ago the item was requested, or added if it wasn't requested.
+    RequestAge,
+    /// The name of the requester.
+    Requester,
 }
 
-#[derive(Debug, Clone)]
+impl SortingValue {
+    /// The name used with --sort, like "last-watched".
+    fn name(&self) -> &'static str {
+        match self {
+            Self::Name => "title",
+            Self::Size => "size",
+            Self::Type => "type",
+            Self::LastWatched => "last-watched",
+            Self::QuotaPressure => "quota-pressure",
+            Self::RequestAge => "request-age",
+            Self::Requester => "requester",
+        }
+    }
+
+    /// The direction used when --sort doesn't say: the largest, oldest or most pressed first,
+    /// and otherwise alphabetically.
+    fn default_direction(&self) -> Order {
+        match self {
+            Self::Size | Self::Type | Self::QuotaPressure | Self::RequestAge => Order::Desc,
+            Self::Name | Self::LastWatched | Self::Requester => Order::Asc,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct SortingOption {
     pub sorting_value: SortingValue,
     pub sorting_direction: Order,
@@ -47,39 +171,193 @@ impl Default for SortingOption {
 }
 
 impl SortingOption {
+    /// The sorts the list can be switched between while choosing, in order.
+    pub const CYCLE: [SortingOption; 10] = [
+        Self::new(SortingValue::Name, Order::Asc),
+        Self::new(SortingValue::Name, Order::Desc),
+        Self::new(SortingValue::Size, Order::Desc),
+        Self::new(SortingValue::Size, Order::Asc),
+        Self::new(SortingValue::LastWatched, Order::Asc),
+        Self::new(SortingValue::LastWatched, Order::Desc),
+        Self::new(SortingValue::RequestAge, Order::Desc),
+        Self::new(SortingValue::RequestAge, Order::Asc),
+        Self::new(SortingValue::Requester, Order::Asc),
+        Self::new(SortingValue::Requester, Order::Desc),
+    ];
+
+    const fn new(sorting_value: SortingValue, sorting_direction: Order) -> Self {
+        Self {
+            sorting_value,
+            sorting_direction,
+        }
+    }
+
+    /// Reads a short sort like "sa", or a name like "size" with an optional ":asc" or ":desc".
     pub fn from_str(s: &str) -> Result<Self> {
-        match s {
-            "nd" => {
-                return Ok(SortingOption {
-                    sorting_value: SortingValue::Name,
-                    sorting_direction: Order::Desc,
-                })
-            }
-            "n" => {
-                return Ok(SortingOption {
-                    sorting_value: SortingValue::Name,
-                    sorting_direction: Order::Asc,
-                })
-            }
-            "sa" => {
-                return Ok(SortingOption {
-                    sorting_value: SortingValue::Size,
-                    sorting_direction: Order::Asc,
-                })
-            }
-            "s" => {
-                return Ok(SortingOption {
-                    sorting_value: SortingValue::Size,
-                    sorting_direction: Order::Desc,
-                })
+        let short = match s {
+            "nd" => Some(Self::new(SortingValue::Name, Order::Desc)),
+            "n" => Some(Self::new(SortingValue::Name, Order::Asc)),
+            "sa" => Some(Self::new(SortingValue::Size, Order::Asc)),
+            "s" => Some(Self::new(SortingValue::Size, Order::Desc)),
+            "t" => Some(Self::new(SortingValue::Type, Order::Desc)),
+            "w" => Some(Self::new(SortingValue::LastWatched, Order::Asc)),
+            "wd" => Some(Self::new(SortingValue::LastWatched, Order::Desc)),
+            "q" => Some(Self::new(SortingValue::QuotaPressure, Order::Desc)),
+            _ => None,
+        };
+        if let Some(sort) = short {
+            return Ok(sort);
+        }
+
+        let (name, direction) = match s.split_once(':') {
+            Some((name, direction)) => (name, Some(direction)),
+            None => (s, None),
+        };
+        let sorting_value = match name {
+            "title" | "name" => SortingValue::Name,
+            "size" => SortingValue::Size,
+            "type" => SortingValue::Type,
+            "last-watched" => SortingValue::LastWatched,
+            "quota-pressure" => SortingValue::QuotaPressure,
+            "request-age" => SortingValue::RequestAge,
+            "requester" => SortingValue::Requester,
+            _ => return Err(eyre!("Not a valid Sorting Option")),
+        };
+        let sorting_direction = match direction {
+            None => sorting_value.default_direction(),
+            Some("asc") => Order::Asc,
+            Some("desc") => Order::Desc,
+            Some(_) => return Err(eyre!("Not a valid Sorting Option")),
+        };
+
+        Ok(Self::new(sorting_value, sorting_direction))
+    }
+
+    /// Compares two items by this sort, or by their title if they are the same. Items without
+    /// what is sorted on have the same place either way: never watched first, while unknown
+    /// sizes, request dates, requesters and quotas come last.
+    pub fn compare(&self, a: &CompleteMediaItem, b: &CompleteMediaItem) -> Ordering {
+        let by_title = || a.title.to_lowercase().cmp(&b.title.to_lowercase());
+        let ordering = match self.sorting_value {
+            SortingValue::Name => self.compare_known(
+                Some(a.title.to_lowercase()),
+                Some(b.title.to_lowercase()),
+                false,
+            ),
+            SortingValue::Size => self.compare_known(a.disk_size(), b.disk_size(), false),
+            SortingValue::Type => self.compare_known(Some(a.media_type), Some(b.media_type), false),
+            SortingValue::LastWatched => {
+                self.compare_known(a.last_activity(), b.last_activity(), true)
             }
-            "t" => {
-                return Ok(SortingOption {
-                    sorting_value: SortingValue::Type,
-                    sorting_direction: Order::Desc,
-                })
+            SortingValue::QuotaPressure => {
+                self.compare_known(a.quota_pressure(), b.quota_pressure(), false)
             }
-            _ => Err(eyre!("Not a valid Sorting Option")),
+            // The oldest request has the largest age.
+            SortingValue::RequestAge => self.compare_known(
+                a.requested_at().map(Reverse),
+                b.requested_at().map(Reverse),
+                false,
+            ),
+            SortingValue::Requester => self.compare_known(
+                a.requester_name().map(str::to_lowercase),
+                b.requester_name().map(str::to_lowercase),
+                false,
+            ),
+        };
+
+        ordering.then_with(by_title)
+    }
+
+    /// Compares in the direction of the sort, with missing values first or last either way.
+    fn compare_known<T: Ord>(&self, a: Option<T>, b: Option<T>, missing_first: bool) -> Ordering {
+        match (a, b) {
+            (Some(a), Some(b)) => match self.sorting_direction {
+                Order::Asc => a.cmp(&b),
+                Order::Desc => b.cmp(&a),
+            },
+            (None, None) => Ordering::Equal,
+            (None, Some(_)) if missing_first => Ordering::Less,
+            (None, Some(_)) => Ordering::Greater,
+            (Some(_), None) if missing_first => Ordering::Greater,
+            (Some(_), None) => Ordering::Less,
+        }
+    }
+}
+
+impl Display for SortingOption {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let direction = match self.sorting_direction {
+            Order::Asc => "asc",
+            Order::Desc => "desc",
+        };
+        write!(f, "{}:{}", self.sorting_value.name(), direction)
+    }
+}
+
+/// What to do with a chosen item.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Action {
+    /// Removes the item and its files, and cleans up its request.
+    Delete,
+    /// Removes the item like `Delete`, and always adds an import list exclusion,
+    /// so Radarr or Sonarr doesn't add it again.
+    DeleteAndBlock,
+    /// Deletes the files and unmonitors the item, but keeps it in Radarr or Sonarr.
+    DeleteFiles,
+    /// Removes only some seasons of a show, unmonitoring them and deleting their episode files.
+    DeleteSeasons,
+    /// Stops Radarr or Sonarr from monitoring the item, so it isn't upgraded or grabbed again.
+    Unmonitor,
+    /// Leaves the item alone this time.
+    Skip,
+    /// Leaves the item alone and adds it to the ignore list, so it isn't shown again.
+    Ignore,
+}
+
+impl Action {
+    pub const ALL: [Action; 7] = [
+        Self::Delete,
+        Self::DeleteAndBlock,
+        Self::DeleteFiles,
+        Self::DeleteSeasons,
+        Self::Unmonitor,
+        Self::Skip,
+        Self::Ignore,
+    ];
+
+    /// Whether the action removes files.
+    pub fn deletes(&self) -> bool {
+        matches!(
+            self,
+            Self::Delete | Self::DeleteAndBlock | Self::DeleteFiles | Self::DeleteSeasons
+        )
+    }
+
+    pub fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "delete" => Ok(Self::Delete),
+            "delete-and-block" => Ok(Self::DeleteAndBlock),
+            "delete-files" => Ok(Self::DeleteFiles),
+            "unmonitor" => Ok(Self::Unmonitor),
+            _ => Err(eyre!(
+                "{} is not an action, it has to be delete, delete-and-block, delete-files or unmonitor.",
+                s
+            )),
+        }
+    }
+}
+
+impl Display for Action {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Delete => write!(f, "Delete"),
+            Self::DeleteAndBlock => write!(f, "Delete & block"),
+            Self::DeleteFiles => write!(f, "Delete files, keep entry"),
+            Self::DeleteSeasons => write!(f, "Delete seasons"),
+            Self::Unmonitor => write!(f, "Unmonitor"),
+            Self::Skip => write!(f, "Skip"),
+            Self::Ignore => write!(f, "Ignore"),
         }
     }
 }
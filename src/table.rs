@@ -0,0 +1,90 @@
+use dialoguer::console::Term;
+use itertools::Itertools;
+
+/// The columns every item is shown with, see `CompleteMediaItem::table_row`.
+pub const ITEM_COLUMNS: [&str; 6] = [
+    "Title",
+    "Type",
+    "Size",
+    "Last watched",
+    "Requester",
+    "Watched",
+];
+
+/// Space between the columns.
+const GAP: &str = "  ";
+
+/// Rows of plain text shown as aligned columns, the first of which is shortened to fit the
+/// terminal. When stdout isn't a terminal, the columns are only separated by a `|`.
+pub struct Table {
+    header: Vec<String>,
+    rows: Vec<Vec<String>>,
+}
+
+impl Table {
+    pub fn new(header: &[&str]) -> Self {
+        Self {
+            header: header.iter().map(|column| column.to_string()).collect(),
+            rows: Vec::new(),
+        }
+    }
+
+    pub fn add_row(&mut self, row: Vec<String>) {
+        self.rows.push(row);
+    }
+
+    /// The header line followed by a line for each row.
+    /// `indent` columns are left free, for what is shown before each line.
+    pub fn lines(&self, indent: usize) -> Vec<String> {
+        let term = Term::stdout();
+        if !term.is_term() {
+            return [&self.header]
+                .into_iter()
+                .chain(&self.rows)
+                .map(|row| row.join(" | "))
+                .collect();
+        }
+
+        let mut widths = self
+            .header
+            .iter()
+            .map(|column| column.chars().count())
+            .collect_vec();
+        for row in &self.rows {
+            for (width, cell) in widths.iter_mut().zip(row) {
+                *width = (*width).max(cell.chars().count());
+            }
+        }
+
+        // The columns after the first keep their width, the first gets what is left.
+        if let Some((_, columns)) = term.size_checked() {
+            let others: usize = widths.iter().skip(1).map(|width| width + GAP.len()).sum();
+            let available = usize::from(columns).saturating_sub(indent + others);
+            widths[0] = widths[0].min(available.max(self.header[0].len()));
+        }
+
+        [&self.header]
+            .into_iter()
+            .chain(&self.rows)
+            .map(|row| {
+                row.iter()
+                    .zip(&widths)
+                    .map(|(cell, width)| format!("{:<width$}", truncate(cell, *width)))
+                    .join(GAP)
+                    .trim_end()
+                    .to_string()
+            })
+            .collect()
+    }
+}
+
+/// Shortens text to the given number of characters, ending it with … if anything was cut off.
+fn truncate(text: &str, width: usize) -> String {
+    if text.chars().count() <= width {
+        return text.to_string();
+    }
+
+    let mut truncated: String = text.chars().take(width.saturating_sub(1)).collect();
+    truncated.push('…');
+    truncated
+}
@@ -1,38 +1,471 @@
 use color_eyre::{eyre::eyre, Result};
+use once_cell::sync::{Lazy, OnceCell};
 use serde::de::DeserializeOwned;
+use std::{
+    error::Error,
+    time::{Duration, Instant},
+};
+use tokio::{
+    sync::Mutex,
+    time::{self, sleep_until},
+};
 
-use super::responses::ResponseObj;
+use super::responses::{History, ResponseObj, ResultType, TautulliInfo};
 use crate::{
-    config::Config,
-    utils::{create_api_error_message, create_param_string},
+    config::{Config, Tautulli},
+    http::{self, RetryPolicy, SendLogged},
+    utils::{create_api_error_message, create_client, create_param_string, join_url},
 };
 
-trait Response<TypeParam> {
-    type TypeParam;
-}
+static CLIENT: OnceCell<reqwest::Client> = OnceCell::new();
+static RATE_LIMITER: Lazy<RateLimiter> = Lazy::new(RateLimiter::default);
 
-pub async fn get_obj<T>(command: &str, params: Option<Vec<(&str, &str)>>) -> Result<ResponseObj<T>>
+const PAGE_LENGTH: usize = 1000;
+
+pub async fn get_obj<T>(command: &str, params: Option<Vec<(&str, &str)>>) -> Result<T>
 where
     T: DeserializeOwned,
 {
-    let config = &Config::global().tautulli;
-    let client = reqwest::Client::new();
+    let config = config()?;
+    let client = CLIENT.get_or_try_init(|| {
+        create_client(
+            "Tautulli",
+            Duration::from_secs(config.timeout_seconds),
+            config.verify_tls,
+            &config.ca_cert_path,
+        )
+    })?;
+    let timeout_message = format!(
+        "no response within the configured timeout of {} seconds",
+        config.timeout_seconds
+    );
 
     let cmd = command.to_string() + "&" + &create_param_string(params);
 
+    let api_url = join_url(&config.url, "api/v2");
+    let url = format!("{}?apikey={}&cmd={}", api_url, config.api_key, cmd);
+    // Used in errors, so the API key isn't printed.
+    let redacted_url = format!("{}?cmd={}", api_url, cmd);
+
+    let policy = RetryPolicy {
+        attempts: config.retry_attempts,
+        base_delay_ms: config.retry_delay_ms,
+        timeout: Some(Duration::from_secs(config.timeout_seconds)),
+    };
+    let response = http::send_with_retry("Tautulli", &redacted_url, &policy, || async {
+        wait_for_rate_limit().await;
+        client.get(&url).send_logged("Tautulli").await
+    })
+    .await?;
+
+    let response: ResponseObj = response.json().await.map_err(|err| {
+        // The URL has the API key in it.
+        let err = err.without_url();
+        if err.is_timeout() {
+            eyre!("Tautulli command {} got {}.", command, timeout_message)
+        } else {
+            eyre!(
+                "Tautulli command {} returned a response that could not be read: {}",
+                command,
+                err
+            )
+        }
+    })?;
+
+    if let ResultType::Error = response.response.result {
+        return Err(eyre!(create_tautulli_error_message(
+            command,
+            response.response.message
+        )));
+    }
+
+    let data = serde_json::from_value(response.response.data).map_err(|err| {
+        eyre!(
+            "Tautulli command {} returned data in an unexpected format: {}",
+            command,
+            err
+        )
+    })?;
+
+    Ok(data)
+}
+
+/// Spaces requests out evenly, so no more than the configured amount of requests per second
+/// are sent to Tautulli, however many are made concurrently.
+async fn wait_for_rate_limit() {
+    let requests_per_second = Config::global()
+        .tautulli
+        .as_ref()
+        .map_or(0.0, |tautulli| tautulli.requests_per_second);
+    if requests_per_second <= 0.0 {
+        return;
+    }
+
+    RATE_LIMITER
+        .wait(Duration::from_secs_f64(1.0 / requests_per_second))
+        .await;
+}
+
+#[derive(Default)]
+struct RateLimiter {
+    /// The earliest time the next request may be sent.
+    next_request_at: Mutex<Option<time::Instant>>,
+}
+
+impl RateLimiter {
+    /// Waits until it is the turn of the caller, with at least `interval` between the turns.
+    async fn wait(&self, interval: Duration) {
+        let send_at = {
+            let mut next_request_at = self.next_request_at.lock().await;
+            let now = time::Instant::now();
+            let send_at = match *next_request_at {
+                Some(next) if next > now => next,
+                _ => now,
+            };
+            *next_request_at = Some(send_at + interval);
+
+            send_at
+        };
+
+        sleep_until(send_at).await;
+    }
+}
+
+pub fn config() -> Result<&'static Tautulli> {
+    Config::global().tautulli.as_ref().ok_or_else(|| {
+        eyre!("Tautulli is not configured, it is needed for this. Set watch_provider to tautulli and fill in the tautulli settings.")
+    })
+}
+
+fn create_tautulli_error_message(command: &str, message: Option<String>) -> String {
+    let message = message.unwrap_or_else(|| "No message given".to_string());
+    let lowercase_message = message.to_lowercase();
+
+    if lowercase_message.contains("apikey") || lowercase_message.contains("api key") {
+        format!(
+            "Tautulli rejected the API key ({}). Please check the Tautulli API key in the config.",
+            message
+        )
+    } else if lowercase_message.contains("unknown command") {
+        format!(
+            "Tautulli does not know the command {} ({}). Your Tautulli version may be too old.",
+            command, message
+        )
+    } else if lowercase_message.contains("parameter") {
+        format!(
+            "Tautulli did not accept the parameters for command {} ({}). Please report this on Github.",
+            command, message
+        )
+    } else {
+        format!(
+            "Tautulli returned an error for command {}: {}",
+            command, message
+        )
+    }
+}
+
+/// The result of a successful health check.
+#[derive(Debug)]
+pub struct ServerStatus {
+    pub version: String,
+    pub latency: Duration,
+}
+
+/// Makes a single request to Tautulli, without retries, telling apart the common setup mistakes
+/// (unreachable host, TLS failure, bad API key and wrong base path) in the error.
+pub async fn check_health() -> Result<ServerStatus> {
+    let config = config()?;
+    let client = CLIENT.get_or_try_init(|| {
+        create_client(
+            "Tautulli",
+            Duration::from_secs(config.timeout_seconds),
+            config.verify_tls,
+            &config.ca_cert_path,
+        )
+    })?;
+
+    let api_url = join_url(&config.url, "api/v2");
     let url = format!(
-        "{}/api/v2?apikey={}&cmd={}",
-        config.url, config.api_key, cmd
+        "{}?apikey={}&cmd=get_tautulli_info",
+        api_url, config.api_key
     );
 
-    let response = client.get(&url).send().await?;
+    wait_for_rate_limit().await;
+    let start = Instant::now();
+    let response = client.get(&url).send_logged("Tautulli").await.map_err(|err| {
+        // The URL has the API key in it.
+        let err = err.without_url();
+        if is_tls_error(&err) {
+            eyre!("TLS failure: could not establish a secure connection to {} ({}). If Tautulli uses a self-signed certificate, set ca_cert_path in the config.", api_url, error_chain(&err))
+        } else if err.is_timeout() {
+            eyre!("Unreachable host: {} did not respond within {} seconds.", api_url, config.timeout_seconds)
+        } else {
+            eyre!("Unreachable host: could not connect to {} ({}). Please check the Tautulli URL in the config.", api_url, error_chain(&err))
+        }
+    })?;
+    let latency = start.elapsed();
+
+    let code = response.status().as_u16();
+    match code {
+        401 | 403 => {
+            return Err(eyre!(
+                "Bad API key: Tautulli answered with status code {}. Please check the Tautulli API key in the config.",
+                code
+            ))
+        }
+        404 => {
+            return Err(eyre!(
+                "Wrong base path: nothing was found at {}. Please check the Tautulli URL in the config, including any base path.",
+                api_url
+            ))
+        }
+        code if !(200..300).contains(&code) => {
+            return Err(eyre!(create_api_error_message(code, &api_url, "Tautulli")))
+        }
+        _ => (),
+    }
+
+    let response: ResponseObj = response.json().await.map_err(|_| {
+        eyre!(
+            "Wrong base path: {} did not answer like the Tautulli API. Please check the Tautulli URL in the config, including any base path.",
+            api_url
+        )
+    })?;
+
+    if let ResultType::Error = response.response.result {
+        return Err(eyre!(
+            "Bad API key: {}",
+            create_tautulli_error_message("get_tautulli_info", response.response.message)
+        ));
+    }
+
+    let info: TautulliInfo = serde_json::from_value(response.response.data).map_err(|err| {
+        eyre!(
+            "Tautulli at {} returned its info in an unexpected format: {}",
+            api_url,
+            err
+        )
+    })?;
+
+    Ok(ServerStatus {
+        version: info.tautulli_version,
+        latency,
+    })
+}
+
+fn is_tls_error(err: &reqwest::Error) -> bool {
+    let chain = error_chain(err).to_lowercase();
+    ["certificate", "tls", "ssl", "handshake"]
+        .iter()
+        .any(|keyword| chain.contains(keyword))
+}
+
+/// Joins an error with all of its sources, as reqwest keeps the useful part of connection errors in them.
+fn error_chain(err: &reqwest::Error) -> String {
+    let mut chain = err.to_string();
+    let mut source = err.source();
+    while let Some(err) = source {
+        chain.push_str(": ");
+        chain.push_str(&err.to_string());
+        source = err.source();
+    }
+    chain
+}
+
+/// Gets all history rows matching the given params.
+pub async fn get_history<T>(params: Vec<(&str, &str)>) -> Result<Vec<T>>
+where
+    T: DeserializeOwned,
+{
+    get_pages("get_history", params).await
+}
+
+/// Gets all rows of a paged command, requesting page after page until as many
+/// rows as Tautulli reports in `records_filtered` have been received.
+pub async fn get_pages<T>(command: &str, params: Vec<(&str, &str)>) -> Result<Vec<T>>
+where
+    T: DeserializeOwned,
+{
+    let length_string = PAGE_LENGTH.to_string();
+    let mut rows: Vec<T> = Vec::new();
+
+    loop {
+        let start_string = rows.len().to_string();
+        let mut page_params = params.clone();
+        page_params.push(("length", &length_string));
+        page_params.push(("start", &start_string));
+
+        let mut page: History<T> = get_obj(command, Some(page_params)).await?;
+
+        let records_filtered = page.records_filtered as usize;
+        let page_is_empty = page.data.is_empty();
+        rows.append(&mut page.data);
+
+        if page_is_empty || rows.len() >= records_filtered {
+            break;
+        }
+    }
+
+    Ok(rows)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        tautulli::HistoryItem,
+        test_support::{self, mock, tautulli_response},
+    };
+
+    /// A page of the history of an item, with a watch on each of the dates.
+    fn history_page(records_filtered: u32, dates: &[i64]) -> String {
+        let rows = dates
+            .iter()
+            .map(|date| {
+                format!(
+                    r#"{{"user": "alice", "user_id": 1, "date": {}, "percent_complete": 100}}"#,
+                    date
+                )
+            })
+            .collect::<Vec<_>>()
+            .join(", ");
+
+        tautulli_response(&format!(
+            r#"{{"recordsFiltered": {}, "recordsTotal": {}, "data": [{}]}}"#,
+            records_filtered, records_filtered, rows
+        ))
+    }
+
+    #[tokio::test]
+    async fn gets_every_page_of_the_history() {
+        test_support::init();
+        let pages = [
+            mock(
+                "rating_key=1001&length=1000&start=0",
+                &[(200, &history_page(5, &[1, 2]))],
+            ),
+            mock(
+                "rating_key=1001&length=1000&start=2",
+                &[(200, &history_page(5, &[3, 4]))],
+            ),
+            mock(
+                "rating_key=1001&length=1000&start=4",
+                &[(200, &history_page(5, &[5]))],
+            ),
+        ];
+
+        let history: Vec<HistoryItem> = get_history(vec![("rating_key", "1001")]).await.unwrap();
 
-    if !(response.status().as_u16() >= 200 && response.status().as_u16() < 300) {
-        let code = response.status().as_u16();
-        return Err(eyre!(create_api_error_message(code, &url, "Tautulli")));
+        assert_eq!(
+            history.iter().map(|row| row.date).collect::<Vec<_>>(),
+            [1, 2, 3, 4, 5]
+        );
+        assert!(pages.iter().all(|page| page.hits() == 1));
     }
 
-    let response = response.json().await?;
+    #[tokio::test]
+    async fn stops_at_an_empty_page() {
+        test_support::init();
+        // Rows can disappear while paging, like when history is deleted.
+        let first = mock(
+            "rating_key=1002&length=1000&start=0",
+            &[(200, &history_page(3, &[1]))],
+        );
+        let empty = mock(
+            "rating_key=1002&length=1000&start=1",
+            &[(200, &history_page(3, &[]))],
+        );
+
+        let history: Vec<HistoryItem> = get_history(vec![("rating_key", "1002")]).await.unwrap();
+
+        assert_eq!(history.len(), 1);
+        assert_eq!((first.hits(), empty.hits()), (1, 1));
+    }
 
-    Ok(response)
+    #[tokio::test]
+    async fn retries_server_errors_until_one_succeeds() {
+        test_support::init();
+        let server = mock(
+            "test=1004",
+            &[
+                (502, ""),
+                (503, ""),
+                (200, &tautulli_response(r#"{"ok": true}"#)),
+            ],
+        );
+
+        let data: serde_json::Value = get_obj("get_server_info", Some(vec![("test", "1004")]))
+            .await
+            .unwrap();
+
+        assert_eq!(data["ok"], true);
+        assert_eq!(server.hits(), 3);
+    }
+
+    #[tokio::test]
+    async fn gives_up_after_the_configured_attempts() {
+        test_support::init();
+        let server = mock("test=1005", &[(502, "")]);
+
+        let err = get_obj::<serde_json::Value>("get_server_info", Some(vec![("test", "1005")]))
+            .await
+            .unwrap_err()
+            .to_string();
+
+        assert_eq!(server.hits(), 3);
+        assert!(err.contains("failed after 3 attempts"), "{}", err);
+        assert!(err.contains("502"), "{}", err);
+        assert!(!err.contains("TAUTULLI_KEY"), "{}", err);
+    }
+
+    #[tokio::test]
+    async fn does_not_retry_client_errors() {
+        test_support::init();
+        let server = mock("test=1006", &[(400, "")]);
+
+        let result =
+            get_obj::<serde_json::Value>("get_server_info", Some(vec![("test", "1006")])).await;
+
+        assert!(result.is_err());
+        assert_eq!(server.hits(), 1);
+    }
+
+    #[tokio::test]
+    async fn the_health_check_names_the_url_it_tried() {
+        test_support::init();
+        mock("cmd=get_tautulli_info", &[(404, "")]);
+
+        let err = check_health().await.unwrap_err().to_string();
+
+        assert!(err.contains("Wrong base path"), "{}", err);
+        assert!(err.contains("/tautulli/api/v2."), "{}", err);
+        assert!(!err.contains("TAUTULLI_KEY"), "{}", err);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn spreads_queued_requests_over_the_interval() {
+        let limiter = RateLimiter::default();
+        let start = time::Instant::now();
+
+        let sent_at = futures::future::join_all((0..5).map(|_| async {
+            limiter.wait(Duration::from_millis(200)).await;
+            start.elapsed().as_millis()
+        }))
+        .await;
+
+        assert_eq!(sent_at, [0, 200, 400, 600, 800]);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn does_not_hold_back_requests_after_a_pause() {
+        let limiter = RateLimiter::default();
+        limiter.wait(Duration::from_millis(200)).await;
+        time::sleep(Duration::from_secs(1)).await;
+
+        let before = time::Instant::now();
+        limiter.wait(Duration::from_millis(200)).await;
+        limiter.wait(Duration::from_millis(200)).await;
+
+        assert_eq!(before.elapsed(), Duration::from_millis(200));
+    }
 }
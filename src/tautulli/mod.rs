@@ -2,201 +2,1432 @@ mod api;
 mod responses;
 
 use chrono::prelude::*;
-use color_eyre::{owo_colors::OwoColorize, Result};
-use serde::de::DeserializeOwned;
-use std::{collections::BTreeMap, fmt::Display};
+use color_eyre::{
+    eyre::{eyre, WrapErr},
+    owo_colors::OwoColorize,
+    Result,
+};
+use futures::future::BoxFuture;
+use itertools::Itertools;
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap, HashSet},
+    fmt::Display,
+    sync::Mutex,
+    time::Duration,
+};
 
-use self::responses::{History, HistoryItem, HistoryMovieItem};
-use crate::{shared::MediaType, tautulli::responses::ResponseObj};
+use once_cell::sync::Lazy;
+use serde::Serialize;
+use tokio::sync::OnceCell;
 
-#[derive(Debug)]
+pub use self::responses::HistoryItem;
+use self::responses::{
+    Activity, HistoryMovieItem, Library, LibraryMediaItem, Metadata, SearchResults, UserResponse,
+};
+use crate::{
+    arguments::Arguments,
+    config::Config,
+    exit_code::ExitCode,
+    output::WatchEntry,
+    plex::PlexData,
+    shared::MediaType,
+    table::Table,
+    utils::{encode_param, human_date_time, human_duration, human_file_size},
+    watch_provider::{self, WatchProvider},
+};
+
+static USERS: Lazy<OnceCell<Vec<User>>> = Lazy::new(OnceCell::new);
+static HISTORY_CACHE: Lazy<Mutex<HistoryCache>> = Lazy::new(|| Mutex::new(HashMap::new()));
+
+type HistoryCache = HashMap<(String, MediaType, Option<DateTime<Utc>>), Vec<HistoryItem>>;
+
+#[derive(Debug, Serialize)]
 pub enum WatchHistory {
     Movie(ItemWatches<UserMovieWatch>),
     TvShow(ItemWatches<UserEpisodeWatch>),
+    Music(ItemWatches<UserTrackWatch>),
+    /// The watch provider does not know the item at all, so it is unknown whether anyone watched it.
+    NoData,
 }
 
+/// All watches of an item grouped by user id, so renamed users are still counted once.
+type UserHistories<'a> = BTreeMap<u32, Vec<&'a HistoryItem>>;
+
 impl WatchHistory {
+    /// Puts together the history of an item from the rows of its watches.
+    pub fn from_rows<'a>(
+        rows: impl IntoIterator<Item = &'a HistoryItem>,
+        users: &[User],
+        media_type: &MediaType,
+        rating_key: &str,
+    ) -> Result<Self> {
+        let user_history = rows.into_iter().fold(
+            BTreeMap::new(),
+            |mut user_watches: UserHistories<'a>, current_watch| {
+                user_watches
+                    .entry(current_watch.user_id)
+                    .or_default()
+                    .push(current_watch);
+
+                user_watches
+            },
+        );
+
+        WatchHistory::from_user_watches(user_history, users, media_type, rating_key)
+    }
+
     fn from_user_watches(
-        user_watches: BTreeMap<&String, &HistoryItem>,
+        user_watches: UserHistories,
+        users: &[User],
         media_type: &MediaType,
         rating_key: &str,
-    ) -> Self {
+    ) -> Result<Self> {
         match media_type {
-            MediaType::Movie => WatchHistory::create_movie_history(user_watches, rating_key),
-            MediaType::Tv => WatchHistory::create_tv_history(user_watches, rating_key),
+            MediaType::Movie => WatchHistory::create_movie_history(user_watches, users, rating_key),
+            MediaType::Tv => WatchHistory::create_tv_history(user_watches, users, rating_key),
+            MediaType::Music => WatchHistory::create_music_history(user_watches, users, rating_key),
         }
     }
 
     fn create_movie_history(
-        user_watches: BTreeMap<&String, &HistoryItem>,
+        user_watches: UserHistories,
+        users: &[User],
+        rating_key: &str,
+    ) -> Result<Self> {
+        let watches = user_watches
+            .iter()
+            .map(|(user_id, movie_watches)| {
+                let movie_watch = latest_watch(movie_watches, *user_id, rating_key)?;
+
+                Ok(UserMovieWatch {
+                    user_id: *user_id,
+                    display_name: display_name(users, movie_watch),
+                    last_watched: watch_date(movie_watch, rating_key)?,
+                    progress: movie_watch.percent_complete,
+                    in_progress: in_progress(movie_watch),
+                    resume_position: resume_position(movie_watch),
+                    last_platform: platform(movie_watch),
+                    play_count: movie_watches.iter().map(|watch| watch.plays()).sum(),
+                })
+            })
+            .collect::<Result<_>>()?;
+
+        Ok(WatchHistory::Movie(watches))
+    }
+
+    fn create_tv_history(
+        user_watches: UserHistories,
+        users: &[User],
         rating_key: &str,
-    ) -> Self {
+    ) -> Result<Self> {
         let watches = user_watches
             .iter()
-            .map(|(user, movie_watch)| UserMovieWatch {
-                display_name: user.to_string(),
-                last_watched: unix_seconds_to_date(movie_watch.date).expect(&format!(
-                    "Failed to parse unix time for rating key {}",
-                    rating_key
-                )),
-                progress: movie_watch.percent_complete,
+            .map(|(user_id, tv_watches)| {
+                let tv_watch = latest_watch(tv_watches, *user_id, rating_key)?;
+
+                Ok(UserEpisodeWatch {
+                    user_id: *user_id,
+                    display_name: display_name(users, tv_watch),
+                    last_watched: watch_date(tv_watch, rating_key)?,
+                    progress: tv_watch.percent_complete,
+                    in_progress: in_progress(tv_watch),
+                    resume_position: resume_position(tv_watch),
+                    last_platform: platform(tv_watch),
+                    season: tv_watch.parent_media_index,
+                    episode: tv_watch.media_index,
+                    play_count: tv_watches.iter().map(|watch| watch.plays()).sum(),
+                    watched_episodes: watched_episodes(tv_watches),
+                })
             })
-            .collect();
+            .collect::<Result<_>>()?;
 
-        WatchHistory::Movie(watches)
+        Ok(WatchHistory::TvShow(watches))
     }
 
-    fn create_tv_history(user_watches: BTreeMap<&String, &HistoryItem>, rating_key: &str) -> Self {
+    fn create_music_history(
+        user_watches: UserHistories,
+        users: &[User],
+        rating_key: &str,
+    ) -> Result<Self> {
         let watches = user_watches
             .iter()
-            .map(|(user, tv_watch)| UserEpisodeWatch {
-                display_name: user.to_string(),
-                last_watched: unix_seconds_to_date(tv_watch.date).expect(&format!(
-                    "Failed to parse unix time for rating key {}",
-                    rating_key
-                )),
-                progress: tv_watch.percent_complete,
-                season: tv_watch.parent_media_index.unwrap(),
-                episode: tv_watch.media_index.unwrap(),
+            .map(|(user_id, track_watches)| {
+                let track_watch = latest_watch(track_watches, *user_id, rating_key)?;
+
+                Ok(UserTrackWatch {
+                    user_id: *user_id,
+                    display_name: display_name(users, track_watch),
+                    last_watched: watch_date(track_watch, rating_key)?,
+                    progress: track_watch.percent_complete,
+                    in_progress: in_progress(track_watch),
+                    last_platform: platform(track_watch),
+                    disc: track_watch.parent_media_index,
+                    track: track_watch.media_index,
+                    play_count: track_watches.iter().map(|watch| watch.plays()).sum(),
+                    listened_tracks: listened_tracks(track_watches),
+                })
             })
-            .collect();
+            .collect::<Result<_>>()?;
 
-        WatchHistory::TvShow(watches)
+        Ok(WatchHistory::Music(watches))
     }
 }
 
-impl Display for WatchHistory {
+/// The history with how many users finished the item, see `WatchHistory::display`.
+pub struct HistoryDisplay<'a> {
+    history: &'a WatchHistory,
+    seasons: &'a [(u32, Option<i32>)],
+}
+
+impl Display for HistoryDisplay<'_> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let history = self.history;
+        if let WatchHistory::NoData = history {
+            return write!(
+                f,
+                "{}",
+                format!(
+                    "No {} data (item may have been re-added).",
+                    watch_provider::provider().name()
+                )
+                .red()
+            );
+        }
+
+        if history.user_count() == 0 {
+            return write!(f, "Never watched by anyone.");
+        }
+
+        let threshold = Config::global().rules.watched_threshold;
+        write!(
+            f,
+            "Watch history, finished by {}/{} users:",
+            history.watched_by(threshold, self.seasons).len().green(),
+            history.user_count().green()
+        )?;
+
+        if history.is_watched_by_all(threshold, self.seasons) {
+            write!(f, " {}", "Watched by everyone.".green())?;
+        }
+
+        let users_in_progress = history.users_in_progress();
+        if users_in_progress > 0 {
+            write!(
+                f,
+                " {}",
+                format!("{} users mid-watch.", users_in_progress).red()
+            )?;
+        }
+
+        match history {
+            WatchHistory::Movie(watches) => write_watches(f, watches),
+            WatchHistory::TvShow(watches) => write_watches(f, watches),
+            WatchHistory::Music(watches) => write_watches(f, watches),
+            WatchHistory::NoData => Ok(()),
+        }
+    }
+}
+
+impl WatchHistory {
+    /// Displays the history, for a show judging who finished it by the seasons, which are given
+    /// with their episode count like for `watched_by`.
+    pub fn display<'a>(&'a self, seasons: &'a [(u32, Option<i32>)]) -> HistoryDisplay<'a> {
+        HistoryDisplay {
+            history: self,
+            seasons,
+        }
+    }
+
+    pub fn user_count(&self) -> usize {
+        match self {
+            Self::Movie(watches) => watches.len(),
+            Self::TvShow(watches) => watches.len(),
+            Self::Music(watches) => watches.len(),
+            Self::NoData => 0,
+        }
+    }
+
+    pub fn users_in_progress(&self) -> usize {
+        match self {
+            Self::Movie(watches) => watches.iter().filter(|watch| watch.in_progress).count(),
+            Self::TvShow(watches) => watches.iter().filter(|watch| watch.in_progress).count(),
+            Self::Music(watches) => watches.iter().filter(|watch| watch.in_progress).count(),
+            Self::NoData => 0,
+        }
+    }
+
+    /// The users that have finished the item. For movies the movie itself has to have reached the
+    /// threshold, for albums it is the latest track the user played. For shows the user has to have
+    /// watched every episode of the seasons, which are given with their episode count in Sonarr.
+    /// Episodes count as watched by the configured threshold, and a season whose episode count
+    /// isn't known can't be finished, so without any seasons nobody has finished a show.
+    pub fn watched_by(&self, threshold: u8, seasons: &[(u32, Option<i32>)]) -> Vec<&str> {
+        match self {
+            Self::Movie(watches) => watches
+                .iter()
+                .filter(|watch| watch.progress >= threshold)
+                .map(|watch| watch.display_name.as_str())
+                .collect(),
+            Self::TvShow(watches) => watches
+                .iter()
+                .filter(|watch| watch.finished_seasons(seasons))
+                .map(|watch| watch.display_name.as_str())
+                .collect(),
+            Self::Music(watches) => watches
+                .iter()
+                .filter(|watch| watch.progress >= threshold)
+                .map(|watch| watch.display_name.as_str())
+                .collect(),
+            Self::NoData => Vec::new(),
+        }
+    }
+
+    /// The most recent watch of the item by any user, `None` if nobody has watched it.
+    pub fn last_activity(&self) -> Option<DateTime<Utc>> {
+        match self {
+            Self::Movie(watches) => watches.iter().map(|watch| watch.last_watched).max(),
+            Self::TvShow(watches) => watches.iter().map(|watch| watch.last_watched).max(),
+            Self::Music(watches) => watches.iter().map(|watch| watch.last_watched).max(),
+            Self::NoData => None,
+        }
+    }
+
+    /// The ids and names of the users that watched the item, only counting watches after `since` if it is given.
+    pub fn watchers_since(&self, since: Option<DateTime<Utc>>) -> Vec<(u32, &str)> {
+        let recent = |last_watched: DateTime<Utc>| since.is_none_or(|since| last_watched >= since);
+        match self {
+            Self::Movie(watches) => watches
+                .iter()
+                .filter(|watch| recent(watch.last_watched))
+                .map(|watch| (watch.user_id, watch.display_name.as_str()))
+                .collect(),
+            Self::TvShow(watches) => watches
+                .iter()
+                .filter(|watch| recent(watch.last_watched))
+                .map(|watch| (watch.user_id, watch.display_name.as_str()))
+                .collect(),
+            Self::Music(watches) => watches
+                .iter()
+                .filter(|watch| recent(watch.last_watched))
+                .map(|watch| (watch.user_id, watch.display_name.as_str()))
+                .collect(),
+            Self::NoData => Vec::new(),
+        }
+    }
+
+    /// The latest watch of each user, as written with --output json.
+    pub fn entries(&self) -> Vec<WatchEntry> {
+        match self {
+            Self::Movie(watches) => watches
+                .iter()
+                .map(|watch| WatchEntry {
+                    name: watch.display_name.clone(),
+                    progress: watch.progress,
+                    last_watched: watch.last_watched,
+                })
+                .collect(),
+            Self::TvShow(watches) => watches
+                .iter()
+                .map(|watch| WatchEntry {
+                    name: watch.display_name.clone(),
+                    progress: watch.progress,
+                    last_watched: watch.last_watched,
+                })
+                .collect(),
+            Self::Music(watches) => watches
+                .iter()
+                .map(|watch| WatchEntry {
+                    name: watch.display_name.clone(),
+                    progress: watch.progress,
+                    last_watched: watch.last_watched,
+                })
+                .collect(),
+            Self::NoData => Vec::new(),
+        }
+    }
+
+    /// How far the given user got in their latest watch, `None` if they have not watched it.
+    pub fn progress_of(&self, user_id: u32) -> Option<u8> {
+        match self {
+            Self::Movie(watches) => watches
+                .iter()
+                .find(|watch| watch.user_id == user_id)
+                .map(|watch| watch.progress),
+            Self::TvShow(watches) => watches
+                .iter()
+                .find(|watch| watch.user_id == user_id)
+                .map(|watch| watch.progress),
+            Self::Music(watches) => watches
+                .iter()
+                .find(|watch| watch.user_id == user_id)
+                .map(|watch| watch.progress),
+            Self::NoData => None,
+        }
+    }
+
+    /// Who has watched episodes of a season of a show, and how many.
+    pub fn season_summary(&self, season: u32, episode_count: Option<i32>) -> String {
+        let watches = match self {
+            Self::TvShow(watches) => watches,
+            _ => return "No episode history.".to_string(),
+        };
+
+        let watchers = watches
+            .iter()
+            .filter_map(|watch| {
+                let episodes = watch.watched_episodes.get(&season)?.len();
+                let episodes = match episode_count {
+                    Some(count) => format!("{}/{}", episodes, count),
+                    None => episodes.to_string(),
+                };
+
+                Some(format!(
+                    "{} watched {} episodes",
+                    watch.display_name.yellow(),
+                    episodes.blue()
+                ))
+            })
+            .collect_vec();
+
+        if watchers.is_empty() {
+            "Unwatched.".red().to_string()
+        } else {
+            format!("{}.", watchers.join(", "))
+        }
+    }
+
+    /// Whether every user with history for the show has watched all episodes of a season.
+    /// A season nobody has watched, or whose episode count isn't known, is not finished by all.
+    pub fn season_finished_by_all(&self, season: u32, episode_count: Option<i32>) -> bool {
+        match self {
+            Self::TvShow(watches) => {
+                !watches.is_empty()
+                    && watches
+                        .iter()
+                        .all(|watch| watch.finished_seasons(&[(season, episode_count)]))
+            }
+            _ => false,
+        }
+    }
+
+    /// The users who are in the middle of an episode of a season.
+    pub fn watching_season(&self, season: u32) -> Vec<&str> {
         match self {
-            Self::Movie(watches) => write_watches(f, watches),
-            Self::TvShow(watches) => write_watches(f, watches),
+            Self::TvShow(watches) => watches
+                .iter()
+                .filter(|watch| watch.in_progress && watch.season == Some(season))
+                .map(|watch| watch.display_name.as_str())
+                .collect(),
+            _ => Vec::new(),
         }
     }
+
+    /// How many of the users finished the item, like "3/5 users finished", as shown in tables.
+    /// The seasons are the ones of `watched_by`.
+    pub fn finished_summary(&self, seasons: &[(u32, Option<i32>)]) -> String {
+        match self {
+            Self::NoData => "no history".to_string(),
+            _ if self.user_count() == 0 => "never watched".to_string(),
+            _ => format!(
+                "{}/{} users finished",
+                self.watched_by(Config::global().rules.watched_threshold, seasons)
+                    .len(),
+                self.user_count()
+            ),
+        }
+    }
+
+    /// Whether every user with history for the item has finished it, see `watched_by`.
+    /// An item nobody has watched is not considered watched by all.
+    pub fn is_watched_by_all(&self, threshold: u8, seasons: &[(u32, Option<i32>)]) -> bool {
+        self.user_count() > 0 && self.watched_by(threshold, seasons).len() == self.user_count()
+    }
+
+    /// A row for the latest watch of each user, the most recent first, for the detail view.
+    /// `None` if nobody has watched the item.
+    pub fn watch_table(&self) -> Option<Table> {
+        if self.user_count() == 0 {
+            return None;
+        }
+
+        let mut table = match self {
+            Self::Movie(_) => {
+                Table::new(&["User", "Progress", "Last watched", "Platform", "Plays"])
+            }
+            Self::TvShow(_) => Table::new(&[
+                "User",
+                "Latest episode",
+                "Progress",
+                "Last watched",
+                "Platform",
+                "Episodes",
+                "Plays",
+            ]),
+            Self::Music(_) => Table::new(&[
+                "User",
+                "Latest track",
+                "Progress",
+                "Last watched",
+                "Platform",
+                "Tracks",
+                "Plays",
+            ]),
+            Self::NoData => return None,
+        };
+        let rows = match self {
+            Self::Movie(watches) => watches
+                .iter()
+                .map(|watch| (watch.last_watched, watch.detail_row()))
+                .collect_vec(),
+            Self::TvShow(watches) => watches
+                .iter()
+                .map(|watch| (watch.last_watched, watch.detail_row()))
+                .collect_vec(),
+            Self::Music(watches) => watches
+                .iter()
+                .map(|watch| (watch.last_watched, watch.detail_row()))
+                .collect_vec(),
+            Self::NoData => Vec::new(),
+        };
+        rows.into_iter()
+            .sorted_by_key(|(last_watched, _)| std::cmp::Reverse(*last_watched))
+            .for_each(|(_, row)| table.add_row(row));
+
+        Some(table)
+    }
+
+    /// How many episodes of each season every user finished, like "4/10", for the detail view.
+    /// The seasons are the ones given with their episode count if it is known, along with any
+    /// other season someone watched. `None` for anything but a show someone watched.
+    pub fn season_grid(&self, seasons: &[(u32, Option<i32>)]) -> Option<Table> {
+        let watches = match self {
+            Self::TvShow(watches) if !watches.is_empty() => watches,
+            _ => return None,
+        };
+
+        let mut episode_counts: BTreeMap<u32, Option<i32>> = seasons.iter().copied().collect();
+        for season in watches
+            .iter()
+            .flat_map(|watch| watch.watched_episodes.keys())
+        {
+            episode_counts.entry(*season).or_insert(None);
+        }
+
+        let header = ["User".to_string()]
+            .into_iter()
+            .chain(episode_counts.keys().map(|season| format!("S{}", season)))
+            .collect_vec();
+        let mut table = Table::new(&header.iter().map(String::as_str).collect_vec());
+        for watch in watches {
+            let cells = episode_counts.iter().map(|(season, count)| {
+                let watched = watch.watched_episodes.get(season).map_or(0, BTreeSet::len);
+                match count {
+                    Some(count) if watched == 0 && *count > 0 => format!("-/{}", count),
+                    Some(count) => format!("{}/{}", watched, count),
+                    None if watched == 0 => "-".to_string(),
+                    None => watched.to_string(),
+                }
+            });
+            table.add_row(
+                [watch.display_name.clone()]
+                    .into_iter()
+                    .chain(cells)
+                    .collect(),
+            );
+        }
+
+        Some(table)
+    }
 }
 
 fn write_watches<T>(f: &mut std::fmt::Formatter, watches: &ItemWatches<T>) -> std::fmt::Result
 where
     T: Display,
 {
-    if watches.len() > 0 {
-        write!(f, "Watch history:")?;
-        for watch in watches.iter() {
-            write!(f, "\n      * {}", watch)?;
-        }
-        Ok(())
-    } else {
-        write!(f, "No watch history.")
+    for watch in watches.iter() {
+        write!(f, "\n      * {}", watch)?;
     }
+
+    Ok(())
 }
 
 pub type ItemWatches<T> = Vec<T>;
 
-#[derive(Debug)]
+#[derive(Debug, Serialize)]
 pub struct UserEpisodeWatch {
+    user_id: u32,
     display_name: String,
     last_watched: DateTime<Utc>,
     progress: u8,
-    season: u32,
-    episode: u32,
+    in_progress: bool,
+    resume_position: Option<Duration>,
+    last_platform: Option<String>,
+    /// Tautulli leaves these out for some specials, extras and manually imported history.
+    season: Option<u32>,
+    episode: Option<u32>,
+    play_count: u32,
+    watched_episodes: WatchedEpisodes,
+}
+
+/// The episodes (by index) a user has finished, grouped by season number.
+pub type WatchedEpisodes = BTreeMap<u32, BTreeSet<u32>>;
+
+impl UserEpisodeWatch {
+    pub fn episodes_watched(&self) -> usize {
+        self.watched_episodes.values().map(BTreeSet::len).sum()
+    }
+
+    /// Whether the user watched every episode of the seasons that have any. A season whose episode
+    /// count isn't known isn't finished, and neither are no seasons at all.
+    fn finished_seasons(&self, seasons: &[(u32, Option<i32>)]) -> bool {
+        let mut seasons = seasons
+            .iter()
+            .filter(|(_, count)| count.is_none_or(|count| count > 0))
+            .peekable();
+
+        seasons.peek().is_some()
+            && seasons.all(|(season, count)| {
+                count.is_some_and(|count| {
+                    self.watched_episodes
+                        .get(season)
+                        .is_some_and(|episodes| episodes.len() >= count as usize)
+                })
+            })
+    }
+}
+
+impl UserEpisodeWatch {
+    fn detail_row(&self) -> Vec<String> {
+        let episode = match (self.season, self.episode) {
+            (Some(season), Some(episode)) => format!("S{}E{}", season, episode),
+            _ => "special/unknown".to_string(),
+        };
+
+        vec![
+            self.display_name.clone(),
+            episode,
+            progress_cell(self.progress, self.in_progress, self.resume_position),
+            human_date_time(self.last_watched),
+            platform_cell(&self.last_platform),
+            self.episodes_watched().to_string(),
+            self.play_count.to_string(),
+        ]
+    }
 }
 
 impl Display for UserEpisodeWatch {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let episode = match (self.season, self.episode) {
+            (Some(season), Some(episode)) => format!("Season {} Episode {}", season, episode),
+            _ => "Special/Unknown episode".to_string(),
+        };
+
         write!(
             f,
-            "Last watch by {}, was at {}. Season {} Episode {}, with {} complete.",
+            "Last watch by {}, was at {}. {}, with {} complete{}. {} episodes watched, {} plays in total.",
             self.display_name.yellow(),
-            self.last_watched.format("%d-%m-%Y").blue(),
-            self.season.yellow(),
-            self.episode.yellow(),
-            format!("{}%", self.progress).blue()
-        )
+            human_date_time(self.last_watched).blue(),
+            episode.yellow(),
+            format!("{}%", self.progress).blue(),
+            platform_suffix(&self.last_platform),
+            self.episodes_watched().yellow(),
+            self.play_count.yellow()
+        )?;
+
+        write_resume_position(f, self.in_progress, self.resume_position)
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Serialize)]
 pub struct UserMovieWatch {
+    user_id: u32,
     display_name: String,
     last_watched: DateTime<Utc>,
     progress: u8,
+    in_progress: bool,
+    resume_position: Option<Duration>,
+    last_platform: Option<String>,
+    play_count: u32,
+}
+
+impl UserMovieWatch {
+    fn detail_row(&self) -> Vec<String> {
+        vec![
+            self.display_name.clone(),
+            progress_cell(self.progress, self.in_progress, self.resume_position),
+            human_date_time(self.last_watched),
+            platform_cell(&self.last_platform),
+            self.play_count.to_string(),
+        ]
+    }
 }
 
 impl Display for UserMovieWatch {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(
             f,
-            "Last watch by {} at {}, with {} progress.",
+            "Last watch by {} at {}, with {} progress{}. Watched {} times.",
+            self.display_name.yellow(),
+            human_date_time(self.last_watched).blue(),
+            format!("{}%", self.progress).blue(),
+            platform_suffix(&self.last_platform),
+            self.play_count.yellow()
+        )?;
+
+        write_resume_position(f, self.in_progress, self.resume_position)
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct UserTrackWatch {
+    user_id: u32,
+    display_name: String,
+    last_watched: DateTime<Utc>,
+    progress: u8,
+    in_progress: bool,
+    last_platform: Option<String>,
+    disc: Option<u32>,
+    track: Option<u32>,
+    play_count: u32,
+    /// The tracks the user has finished, as (disc, track) indices.
+    listened_tracks: BTreeSet<(u32, u32)>,
+}
+
+impl UserTrackWatch {
+    fn detail_row(&self) -> Vec<String> {
+        let track = match (self.disc, self.track) {
+            (Some(disc), Some(track)) => format!("Disc {} Track {}", disc, track),
+            (None, Some(track)) => format!("Track {}", track),
+            _ => "unknown".to_string(),
+        };
+
+        vec![
+            self.display_name.clone(),
+            track,
+            progress_cell(self.progress, self.in_progress, None),
+            human_date_time(self.last_watched),
+            platform_cell(&self.last_platform),
+            self.listened_tracks.len().to_string(),
+            self.play_count.to_string(),
+        ]
+    }
+}
+
+impl Display for UserTrackWatch {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let track = match (self.disc, self.track) {
+            (Some(disc), Some(track)) => format!("Disc {} Track {}", disc, track),
+            (None, Some(track)) => format!("Track {}", track),
+            _ => "Unknown track".to_string(),
+        };
+
+        write!(
+            f,
+            "Last listen by {}, was at {}. {}, with {} complete{}. {} tracks listened to, {} plays in total.",
             self.display_name.yellow(),
-            self.last_watched.format("%d-%m-%Y").blue(),
-            format!("{}%", self.progress).blue()
+            human_date_time(self.last_watched).blue(),
+            track.yellow(),
+            format!("{}%", self.progress).blue(),
+            platform_suffix(&self.last_platform),
+            self.listened_tracks.len().yellow(),
+            self.play_count.yellow()
         )
     }
 }
 
-pub async fn get_item_watches(rating_key: &str, media_type: &MediaType) -> Result<WatchHistory> {
-    let history = get_item_history(rating_key, media_type).await?;
+fn platform_suffix(platform: &Option<String>) -> String {
+    match platform {
+        Some(platform) => format!(" on {}", platform.blue()),
+        None => String::new(),
+    }
+}
+
+/// The progress of a watch in the detail view, saying whether it counts as finished or is
+/// still going on.
+fn progress_cell(progress: u8, in_progress: bool, resume_position: Option<Duration>) -> String {
+    let state = match (in_progress, resume_position) {
+        (true, Some(position)) => format!(", mid-watch at {}", human_duration(position)),
+        (true, None) => ", mid-watch".to_string(),
+        _ if progress >= Config::global().rules.watched_threshold => ", finished".to_string(),
+        _ => String::new(),
+    };
+
+    format!("{}%{}", progress, state)
+}
+
+fn platform_cell(platform: &Option<String>) -> String {
+    platform.clone().unwrap_or_else(|| "unknown".to_string())
+}
+
+fn write_resume_position(
+    f: &mut std::fmt::Formatter,
+    in_progress: bool,
+    resume_position: Option<Duration>,
+) -> std::fmt::Result {
+    match (in_progress, resume_position) {
+        (true, Some(position)) => write!(
+            f,
+            " {} at {}.",
+            "Mid-watch".red(),
+            human_duration(position).blue()
+        ),
+        (true, None) => write!(f, " {}.", "Mid-watch".red()),
+        (false, _) => Ok(()),
+    }
+}
+
+#[derive(Debug)]
+pub struct User {
+    pub user_id: u32,
+    pub username: String,
+    pub friendly_name: Option<String>,
+    pub email: Option<String>,
+}
+
+impl User {
+    /// Whether the user goes by a name, as their username, friendly name or email, without regard to case.
+    pub fn is_named(&self, name: &str) -> bool {
+        [
+            Some(&self.username),
+            self.friendly_name.as_ref(),
+            self.email.as_ref(),
+        ]
+        .into_iter()
+        .flatten()
+        .any(|own_name| own_name.eq_ignore_ascii_case(name))
+    }
+
+    fn from_response(response: UserResponse) -> Self {
+        Self {
+            user_id: response.user_id,
+            username: response.username,
+            friendly_name: response.friendly_name.filter(|name| !name.is_empty()),
+            email: response.email.filter(|email| !email.is_empty()),
+        }
+    }
+}
+
+/// Gets all users known to Tautulli. The list is only requested once per run.
+pub async fn get_users() -> Result<&'static [User]> {
+    let users = USERS
+        .get_or_try_init(|| async {
+            let users: Vec<UserResponse> = api::get_obj("get_users", None).await?;
 
-    let latest_user_history =
+            Ok::<Vec<User>, color_eyre::Report>(
+                users.into_iter().map(User::from_response).collect(),
+            )
+        })
+        .await?;
+
+    Ok(users)
+}
+
+/// Whether the user that requested an item has watched it.
+#[derive(Debug, Clone, Copy, Serialize)]
+pub enum RequesterWatch {
+    /// The requester watched it, with the progress of their latest watch.
+    Watched(u8),
+    NeverWatched,
+    /// The requester could not be matched to a single Tautulli user, or Tautulli has no data for the item.
+    Unknown,
+}
+
+impl RequesterWatch {
+    /// Whether the requester finished the item, `None` if that is unknown.
+    pub fn is_finished(&self) -> Option<bool> {
+        match self {
+            Self::Watched(progress) => Some(*progress >= Config::global().rules.watched_threshold),
+            Self::NeverWatched => Some(false),
+            Self::Unknown => None,
+        }
+    }
+}
+
+impl Display for RequesterWatch {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match (self, self.is_finished()) {
+            (Self::Watched(progress), Some(true)) => {
+                write!(f, "{}", format!("watched {}%", progress).green())
+            }
+            (Self::Watched(progress), _) => {
+                write!(f, "{}", format!("watched {}%", progress).yellow())
+            }
+            (Self::NeverWatched, _) => write!(f, "{}", "never watched".red()),
+            (Self::Unknown, _) => write!(f, "{}", "unknown".red()),
+        }
+    }
+}
+
+/// Finds out if the requester of an item has watched it. The requester is matched to a user of
+/// the watch provider by their Plex id, or otherwise by their username or email if only one user matches.
+pub async fn requester_watch(
+    history: &WatchHistory,
+    plex_id: Option<u32>,
+    username: Option<&str>,
+    email: Option<&str>,
+) -> Result<RequesterWatch> {
+    if let WatchHistory::NoData = history {
+        return Ok(RequesterWatch::Unknown);
+    }
+
+    Ok(match find_requester(plex_id, username, email).await? {
+        Some(user) => match history.progress_of(user.user_id) {
+            Some(progress) => RequesterWatch::Watched(progress),
+            None => RequesterWatch::NeverWatched,
+        },
+        None => RequesterWatch::Unknown,
+    })
+}
+
+/// Finds the user of the watch provider that made a request, by their Plex id or otherwise
+/// by their username or email if only one user matches.
+pub async fn find_requester(
+    plex_id: Option<u32>,
+    username: Option<&str>,
+    email: Option<&str>,
+) -> Result<Option<&'static User>> {
+    let provider = watch_provider::provider();
+    let users = provider.users().await?;
+    let plex_id = plex_id.filter(|_| provider.has_plex_ids());
+    Ok(
+        match plex_id.and_then(|id| users.iter().find(|user| user.user_id == id)) {
+            Some(user) => Some(user),
+            None => users
+                .iter()
+                .filter(|user| {
+                    let username_matches =
+                        username.is_some_and(|name| user.username.eq_ignore_ascii_case(name));
+                    let email_matches = match (email, &user.email) {
+                        (Some(email), Some(user_email)) => user_email.eq_ignore_ascii_case(email),
+                        _ => false,
+                    };
+
+                    username_matches || email_matches
+                })
+                .exactly_one()
+                .ok(),
+        },
+    )
+}
+
+/// Gets the watch history of an item, only counting watches after `since` if it is given.
+pub async fn get_item_watches(
+    rating_key: &str,
+    media_type: &MediaType,
+    since: Option<DateTime<Utc>>,
+    force_refresh: bool,
+) -> Result<WatchHistory> {
+    let history = get_item_history(rating_key, media_type, since, force_refresh).await?;
+    if history.is_empty() && !rating_key_known(rating_key).await? {
+        return Ok(WatchHistory::NoData);
+    }
+
+    let users = get_users().await?;
+    let config = api::config()?;
+
+    WatchHistory::from_rows(
         history
             .iter()
-            .fold(BTreeMap::new(), |mut user_latest_watch, current_watch| {
-                user_latest_watch
-                    .entry(&current_watch.user)
-                    .and_modify(|entry: &mut &HistoryItem| {
-                        if entry.date < current_watch.date {
-                            *entry = current_watch;
-                        }
-                    })
-                    .or_insert(current_watch);
-
-                user_latest_watch
-            });
-
-    Ok(WatchHistory::from_user_watches(
-        latest_user_history,
+            .filter(|watch| user_counted(watch, &config.exclude_users, &config.include_users)),
+        users,
         media_type,
         rating_key,
-    ))
+    )
 }
 
-async fn get_item_history(rating_key: &str, media_type: &MediaType) -> Result<Vec<HistoryItem>> {
-    if let MediaType::Movie = media_type {
-        let history: Vec<HistoryMovieItem> = get_full_history(rating_key, "rating_key").await?;
-        Ok(movie_item_to_history_item(history))
-    } else {
-        let history: Vec<HistoryItem> =
-            get_full_history(rating_key, "grandparent_rating_key").await?;
-        Ok(history)
+/// Checks that Tautulli can be reached and the API key works, returning its version and the round trip time.
+pub async fn check_health() -> Result<(String, Duration)> {
+    let status = api::check_health().await?;
+
+    Ok((status.version, status.latency))
+}
+
+/// An item found by searching Tautulli, which may be the same as an item whose rating key changed.
+#[derive(Debug)]
+pub struct RatingKeyCandidate {
+    pub rating_key: String,
+    pub title: String,
+    pub year: Option<i32>,
+}
+
+impl RatingKeyCandidate {
+    pub fn is_exact_match(&self, title: &str, year: Option<i32>) -> bool {
+        self.title.eq_ignore_ascii_case(title) && self.year == year
     }
 }
 
-async fn get_full_history<T>(rating_key: &str, rating_key_kind: &str) -> Result<Vec<T>>
-where
-    T: DeserializeOwned,
-{
-    let length = 1000;
-    let length_string = length.to_string();
-    let mut history: Vec<T> = Vec::new();
-    let mut page = 0;
-    loop {
-        let page_string = page.to_string();
-        let params = vec![
-            (rating_key_kind, rating_key),
-            ("length", &length_string),
-            ("start", &page_string),
-        ];
-        let mut history_page: ResponseObj<History<T>> =
-            api::get_obj("get_history", Some(params)).await?;
+impl Display for RatingKeyCandidate {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.year {
+            Some(year) => write!(
+                f,
+                "{} ({}), rating key {}",
+                self.title, year, self.rating_key
+            ),
+            None => write!(f, "{}, rating key {}", self.title, self.rating_key),
+        }
+    }
+}
 
-        if history_page.response.data.data.len() < length {
-            history.append(&mut history_page.response.data.data);
-            break;
-        } else {
-            history.append(&mut history_page.response.data.data);
-            page += 1;
+/// Searches Tautulli for items of the given type with a matching title, for when the rating key
+/// of an item is no longer known, like after a Plex library rebuild. Exact matches come first.
+pub async fn search_rating_keys(
+    title: &str,
+    year: Option<i32>,
+    media_type: MediaType,
+) -> Result<Vec<RatingKeyCandidate>> {
+    let result_type = match media_type {
+        MediaType::Movie => "movie",
+        MediaType::Tv => "show",
+        MediaType::Music => "album",
+    };
+
+    let query = encode_param(title);
+    let mut results: SearchResults =
+        api::get_obj("search", Some(vec![("query", &query), ("limit", "10")])).await?;
+
+    let candidates = results
+        .results_list
+        .remove(result_type)
+        .unwrap_or_default()
+        .into_iter()
+        .map(|result| RatingKeyCandidate {
+            rating_key: result.rating_key.to_string(),
+            title: result.title,
+            year: result.year,
+        })
+        .sorted_by_key(|candidate| !candidate.is_exact_match(title, year))
+        .collect();
+
+    Ok(candidates)
+}
+
+/// Tautulli as the watch provider, for Plex.
+pub struct Tautulli;
+
+impl WatchProvider for Tautulli {
+    fn name(&self) -> &'static str {
+        "Tautulli"
+    }
+
+    fn item_watches<'a>(
+        &'a self,
+        id: &'a str,
+        media_type: MediaType,
+        since: Option<DateTime<Utc>>,
+    ) -> BoxFuture<'a, Result<WatchHistory>> {
+        Box::pin(async move { get_item_watches(id, &media_type, since, false).await })
+    }
+
+    fn users(&self) -> BoxFuture<'_, Result<&'static [User]>> {
+        Box::pin(get_users())
+    }
+
+    fn active_item_ids(&self) -> BoxFuture<'_, Result<HashSet<String>>> {
+        Box::pin(get_active_rating_keys())
+    }
+
+    fn item_data<'a>(
+        &'a self,
+        id: &'a str,
+        media_type: MediaType,
+    ) -> BoxFuture<'a, Result<PlexData>> {
+        Box::pin(PlexData::get_data(id, media_type))
+    }
+
+    fn version(&self) -> BoxFuture<'_, Result<String>> {
+        Box::pin(async { check_health().await.map(|(version, _)| version) })
+    }
+
+    fn concurrency(&self) -> usize {
+        Config::global()
+            .tautulli
+            .as_ref()
+            .map_or(1, |tautulli| tautulli.concurrency)
+    }
+
+    fn has_plex_ids(&self) -> bool {
+        true
+    }
+}
+
+/// An item in a Plex library, with how much it has been played according to Tautulli.
+#[derive(Debug)]
+pub struct LibraryItem {
+    pub rating_key: String,
+    pub title: String,
+    pub media_type: MediaType,
+    pub play_count: u32,
+    pub last_played: Option<DateTime<Utc>>,
+}
+
+impl Display for LibraryItem {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let last_played = match self.last_played {
+            Some(last_played) => human_date_time(last_played),
+            None => "never".to_string(),
+        };
+
+        write!(
+            f,
+            "{} ({}, rating key {}) - {} plays, last played {}.",
+            self.title.bold(),
+            self.media_type.blue(),
+            self.rating_key,
+            self.play_count.yellow(),
+            last_played.blue()
+        )
+    }
+}
+
+/// Gets every movie and show in the Plex libraries that has not been played for at least
+/// `days` days, least recently played first. Music libraries are left out, as Tautulli lists
+/// them by artist rather than by album.
+pub async fn get_unplayed_library_items(days: i64) -> Result<Vec<LibraryItem>> {
+    let cutoff = chrono::Duration::try_days(days)
+        .and_then(|age| Utc::now().checked_sub_signed(age))
+        .ok_or_else(|| {
+            eyre!(
+                "{} days is too long for --min-days-since-watch or min_days_since_watch.",
+                days
+            )
+        })
+        .wrap_err(ExitCode::Config)?;
+    let libraries: Vec<Library> = api::get_obj("get_libraries", None).await?;
+
+    let mut items = Vec::new();
+    for library in libraries {
+        let media_type = match library.section_type.as_str() {
+            "movie" => MediaType::Movie,
+            "show" => MediaType::Tv,
+            _ => continue,
+        };
+
+        let section_id = library.section_id.to_string();
+        let library_items: Vec<LibraryMediaItem> = api::get_pages(
+            "get_library_media_info",
+            vec![
+                ("section_id", &section_id),
+                ("order_column", "last_played"),
+                ("order_dir", "asc"),
+            ],
+        )
+        .await?;
+
+        for item in library_items {
+            let last_played = match item.last_played {
+                Some(last_played) if last_played > 0 => Some(unix_seconds_to_date(last_played)?),
+                _ => None,
+            };
+
+            if last_played.is_none_or(|last_played| last_played < cutoff) {
+                items.push(LibraryItem {
+                    rating_key: item.rating_key.to_string(),
+                    title: item.title,
+                    media_type,
+                    play_count: item.play_count.unwrap_or(0),
+                    last_played,
+                });
+            }
+        }
+    }
+
+    items.sort_by_key(|item| item.last_played);
+
+    Ok(items)
+}
+
+/// Which Plex library sections may be cleaned up, from `libraries` in the config.
+pub struct LibraryFilter {
+    /// The names of the sections that may not be cleaned up, by section id.
+    excluded_sections: HashMap<String, String>,
+    /// The rating keys of the movies and shows in the excluded sections, so they can be left
+    /// out before anything else is fetched for them.
+    excluded_rating_keys: HashSet<String>,
+}
+
+impl LibraryFilter {
+    pub fn allows_rating_key(&self, rating_key: &str) -> bool {
+        !self.excluded_rating_keys.contains(rating_key)
+    }
+
+    /// The name of the section if it may not be cleaned up. Items in unknown sections are
+    /// allowed, as are items that aren't in Plex.
+    pub fn excluded_section(&self, section_id: Option<&str>) -> Option<&str> {
+        self.excluded_sections.get(section_id?).map(String::as_str)
+    }
+}
+
+/// Gets which library sections may be cleaned up, `None` if the config doesn't restrict them.
+/// Sections are matched by name, without regard to case, or by id.
+pub async fn get_library_filter() -> Result<Option<LibraryFilter>> {
+    let config = &Config::global().libraries;
+    if config.include.is_empty() && config.exclude.is_empty() {
+        return Ok(None);
+    }
+    api::config()
+        .map_err(|err| err.wrap_err("libraries in the config only work with Tautulli."))?;
+
+    let libraries: Vec<Library> = api::get_obj("get_libraries", None).await?;
+    let matches = |entry: &String, library: &Library| {
+        entry.eq_ignore_ascii_case(&library.section_name)
+            || *entry == library.section_id.to_string()
+    };
+
+    for entry in config.include.iter().chain(&config.exclude) {
+        if !libraries.iter().any(|library| matches(entry, library)) {
+            message!(
+                "{}",
+                format!(
+                    "Warning: There is no Plex library named {} or with that id, check libraries in the config.",
+                    entry
+                )
+                .yellow()
+            );
+        }
+    }
+
+    let mut filter = LibraryFilter {
+        excluded_sections: HashMap::new(),
+        excluded_rating_keys: HashSet::new(),
+    };
+    for library in libraries {
+        let included = config.include.is_empty()
+            || config.include.iter().any(|entry| matches(entry, &library));
+        let excluded = config.exclude.iter().any(|entry| matches(entry, &library));
+        if included && !excluded {
+            continue;
+        }
+
+        let section_id = library.section_id.to_string();
+        // Music sections are listed by artist, so their albums are only left out once
+        // their section is known.
+        if let "movie" | "show" = library.section_type.as_str() {
+            let library_items: Vec<LibraryMediaItem> =
+                api::get_pages("get_library_media_info", vec![("section_id", &section_id)]).await?;
+            filter.excluded_rating_keys.extend(
+                library_items
+                    .into_iter()
+                    .map(|item| item.rating_key.to_string()),
+            );
+        }
+        filter
+            .excluded_sections
+            .insert(section_id, library.section_name);
+    }
+
+    Ok(Some(filter))
+}
+
+/// The watch history of an item, both as shown in the list and the rows it was made from.
+#[derive(Serialize)]
+struct HistoryExport {
+    rating_key: String,
+    media_type: MediaType,
+    history: WatchHistory,
+    rows: Vec<HistoryItem>,
+}
+
+/// Gets the watch history of an item as pretty printed JSON, for checking how it was put together.
+pub async fn export_history(rating_key: &str) -> Result<String> {
+    let metadata: Metadata =
+        api::get_obj("get_metadata", Some(vec![("rating_key", rating_key)])).await?;
+
+    let media_type = match metadata.media_type.as_deref() {
+        Some("movie") => MediaType::Movie,
+        Some("show") => MediaType::Tv,
+        Some("album") => MediaType::Music,
+        Some(media_type) => {
+            return Err(eyre!(
+                "Rating key {} is a {}, only movies, shows and albums have a history to export.",
+                rating_key,
+                media_type
+            ))
+        }
+        None => return Err(eyre!("Tautulli does not know rating key {}.", rating_key)),
+    };
+
+    let since = Arguments::get_args().watched_since;
+    let rows = get_item_history(rating_key, &media_type, since, false).await?;
+    let history = get_item_watches(rating_key, &media_type, since, false).await?;
+
+    let export = HistoryExport {
+        rating_key: rating_key.to_string(),
+        media_type,
+        history,
+        rows,
+    };
+
+    Ok(serde_json::to_string_pretty(&export)?)
+}
+
+/// The TMDB and TVDB ids Plex has matched an item to.
+#[derive(Debug, Default)]
+pub struct ExternalIds {
+    pub tmdb_id: Option<u32>,
+    pub tvdb_id: Option<u32>,
+}
+
+/// Gets the TMDB and TVDB ids of an item from the guids in its metadata.
+pub async fn get_external_ids(rating_key: &str) -> Result<ExternalIds> {
+    let metadata: Metadata =
+        api::get_obj("get_metadata", Some(vec![("rating_key", rating_key)])).await?;
+
+    let mut ids = ExternalIds::default();
+    for guid in metadata.guids {
+        if let Some(id) = guid.strip_prefix("tmdb://") {
+            ids.tmdb_id = id.parse().ok();
+        } else if let Some(id) = guid.strip_prefix("tvdb://") {
+            ids.tvdb_id = id.parse().ok();
+        }
+    }
+
+    Ok(ids)
+}
+
+/// Gets the Plex guid and external ids of an item, as watchlists use them.
+pub async fn get_guids(rating_key: &str) -> Result<Vec<String>> {
+    let metadata: Metadata =
+        api::get_obj("get_metadata", Some(vec![("rating_key", rating_key)])).await?;
+
+    Ok(metadata.guid.into_iter().chain(metadata.guids).collect())
+}
+
+/// A version of a movie in Plex, as movies can have several, like a 1080p and a 4K one.
+#[derive(Debug, Clone)]
+pub struct MediaVersion {
+    pub resolution: Option<String>,
+    /// In kbps.
+    pub bitrate: Option<u64>,
+    pub container: Option<String>,
+    /// The size of all files of the version.
+    pub size: i64,
+}
+
+impl Display for MediaVersion {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let resolution = match self.resolution {
+            Some(ref resolution) if resolution.chars().all(|c| c.is_ascii_digit()) => {
+                format!("{}p", resolution)
+            }
+            Some(ref resolution) => resolution.to_uppercase(),
+            None => "Unknown resolution".to_string(),
+        };
+        write!(f, "{}", resolution)?;
+        if let Some(ref container) = self.container {
+            write!(f, " {}", container)?;
+        }
+        if let Some(bitrate) = self.bitrate {
+            write!(f, " at {:.1} Mbps", bitrate as f64 / 1000.0)?;
+        }
+
+        write!(f, ", {}", human_file_size(self.size))
+    }
+}
+
+/// Gets the versions Plex has of an item, with their file details. Shows and albums have none,
+/// as their files belong to their episodes and tracks.
+pub async fn get_media_versions(rating_key: &str) -> Result<Vec<MediaVersion>> {
+    let metadata: Metadata =
+        api::get_obj("get_metadata", Some(vec![("rating_key", rating_key)])).await?;
+
+    Ok(metadata
+        .media_info
+        .into_iter()
+        .map(|media_info| MediaVersion {
+            resolution: media_info.video_resolution,
+            bitrate: media_info.bitrate,
+            container: media_info.container,
+            size: media_info
+                .parts
+                .iter()
+                .filter_map(|part| part.file_size)
+                .sum(),
+        })
+        .collect())
+}
+
+/// Gets the names of the Plex collections an item is in, and the Plex labels it has.
+pub async fn get_collections_and_labels(rating_key: &str) -> Result<(Vec<String>, Vec<String>)> {
+    let metadata: Metadata =
+        api::get_obj("get_metadata", Some(vec![("rating_key", rating_key)])).await?;
+
+    Ok((metadata.collections, metadata.labels))
+}
+
+/// Checks whether Tautulli has metadata for the rating key. Tautulli returns
+/// empty metadata for keys it doesn't know, e.g. when an item was re-added to Plex.
+async fn rating_key_known(rating_key: &str) -> Result<bool> {
+    let metadata: Metadata =
+        api::get_obj("get_metadata", Some(vec![("rating_key", rating_key)])).await?;
+
+    Ok(metadata.rating_key.is_some())
+}
+
+/// Gets the rating keys of everything currently being streamed, including the
+/// season and show keys of episodes, so both movies and shows can be matched.
+pub async fn get_active_rating_keys() -> Result<HashSet<String>> {
+    let activity: Activity = api::get_obj("get_activity", None).await?;
+
+    let rating_keys = activity
+        .sessions
+        .into_iter()
+        .flat_map(|session| {
+            [
+                session.rating_key,
+                session.parent_rating_key,
+                session.grandparent_rating_key,
+            ]
+        })
+        .filter(|rating_key| !rating_key.is_empty())
+        .collect();
+
+    Ok(rating_keys)
+}
+
+/// Gets the history of an item, reusing the history fetched earlier in the run
+/// for the same item unless `force_refresh` is set or caching is turned off.
+async fn get_item_history(
+    rating_key: &str,
+    media_type: &MediaType,
+    since: Option<DateTime<Utc>>,
+    force_refresh: bool,
+) -> Result<Vec<HistoryItem>> {
+    let use_cache = !force_refresh && !Arguments::get_args().no_cache;
+    let cache_key = (rating_key.to_string(), *media_type, since);
+
+    if use_cache {
+        if let Some(history) = HISTORY_CACHE.lock().unwrap().get(&cache_key) {
+            return Ok(history.clone());
         }
     }
 
+    // Music is handled at the album level, so the tracks' parent is the item.
+    let rating_key_kind = match media_type {
+        MediaType::Movie => "rating_key",
+        MediaType::Tv => "grandparent_rating_key",
+        MediaType::Music => "parent_rating_key",
+    };
+    let after = since.map(|since| since.format("%Y-%m-%d").to_string());
+
+    let mut params = vec![(rating_key_kind, rating_key), ("grouping", "0")];
+    if let Some(ref after) = after {
+        params.push(("after", after));
+    }
+
+    let history = match media_type {
+        MediaType::Movie => {
+            let history: Vec<HistoryMovieItem> = api::get_history(params).await?;
+            movie_item_to_history_item(history)
+        }
+        MediaType::Tv | MediaType::Music => api::get_history(params).await?,
+    };
+
+    HISTORY_CACHE
+        .lock()
+        .unwrap()
+        .insert(cache_key, history.clone());
+
     Ok(history)
 }
 
@@ -205,16 +1436,512 @@ fn movie_item_to_history_item(history: Vec<HistoryMovieItem>) -> Vec<HistoryItem
         .into_iter()
         .map(|item| HistoryItem {
             user: item.user,
+            user_id: item.user_id,
             date: item.date,
-            duration: item.duration,
             percent_complete: item.percent_complete,
             media_index: None,
             parent_media_index: None,
+            group_count: item.group_count,
+            group_ids: item.group_ids,
+            state: item.state,
+            view_offset: item.view_offset,
+            platform: item.platform,
+            player: item.player,
+            product: item.product,
+        })
+        .collect()
+}
+
+/// Checks the user of a watch against the include and exclude lists of the watch provider,
+/// matching either the username (case-insensitively) or the user id.
+pub fn user_counted(
+    watch: &HistoryItem,
+    exclude_users: &Option<Vec<String>>,
+    include_users: &Option<Vec<String>>,
+) -> bool {
+    let matches_user = |users: &Vec<String>| {
+        users.iter().any(|user| {
+            user.eq_ignore_ascii_case(&watch.user) || user == &watch.user_id.to_string()
+        })
+    };
+
+    if let Some(exclude_users) = exclude_users {
+        if matches_user(exclude_users) {
+            return false;
+        }
+    }
+
+    match include_users {
+        Some(include_users) => matches_user(include_users),
+        None => true,
+    }
+}
+
+fn display_name(users: &[User], watch: &HistoryItem) -> String {
+    users
+        .iter()
+        .find(|user| user.user_id == watch.user_id)
+        .map(|user| {
+            user.friendly_name
+                .clone()
+                .unwrap_or_else(|| user.username.clone())
+        })
+        .unwrap_or_else(|| watch.user.clone())
+}
+
+/// A watch is in progress if it is still playing or was stopped before being finished.
+/// Older Tautulli versions without these fields are treated as not in progress.
+fn in_progress(watch: &HistoryItem) -> bool {
+    match watch.state {
+        Some(_) => true,
+        None => {
+            watch.percent_complete < Config::global().rules.watched_threshold
+                && watch.view_offset.unwrap_or(0) > 0
+        }
+    }
+}
+
+/// Describes what the watch was played on, like "Android (Plex for Android, Pixel 7)".
+fn platform(watch: &HistoryItem) -> Option<String> {
+    let platform = watch.platform.clone()?;
+    let details = [&watch.product, &watch.player]
+        .into_iter()
+        .flatten()
+        .filter(|detail| !detail.is_empty())
+        .join(", ");
+
+    if details.is_empty() {
+        Some(platform)
+    } else {
+        Some(format!("{} ({})", platform, details))
+    }
+}
+
+fn resume_position(watch: &HistoryItem) -> Option<Duration> {
+    match watch.view_offset {
+        Some(view_offset) if view_offset > 0 => Some(Duration::from_millis(view_offset)),
+        _ => None,
+    }
+}
+
+fn latest_watch<'a>(
+    watches: &[&'a HistoryItem],
+    user_id: u32,
+    rating_key: &str,
+) -> Result<&'a HistoryItem> {
+    watches
+        .iter()
+        .copied()
+        .max_by_key(|watch| watch.date)
+        .ok_or_else(|| {
+            eyre!(
+                "No watches found for user id {} on rating key {}",
+                user_id,
+                rating_key
+            )
+        })
+}
+
+fn watched_episodes(watches: &[&HistoryItem]) -> WatchedEpisodes {
+    watches
+        .iter()
+        .filter(|watch| watch.percent_complete >= Config::global().rules.watched_threshold)
+        .filter_map(|watch| Some((watch.parent_media_index?, watch.media_index?)))
+        .fold(BTreeMap::new(), |mut seasons, (season, episode)| {
+            seasons
+                .entry(season)
+                .or_insert_with(BTreeSet::new)
+                .insert(episode);
+
+            seasons
         })
+}
+
+fn listened_tracks(watches: &[&HistoryItem]) -> BTreeSet<(u32, u32)> {
+    watches
+        .iter()
+        .filter(|watch| watch.percent_complete >= Config::global().rules.watched_threshold)
+        .filter_map(|watch| Some((watch.parent_media_index.unwrap_or(1), watch.media_index?)))
         .collect()
 }
 
-fn unix_seconds_to_date(unix_seconds: i64) -> Option<DateTime<Utc>> {
-    let naive_date = NaiveDateTime::from_timestamp_millis(unix_seconds * 1000).unwrap();
-    Some(DateTime::from_utc(naive_date, Utc))
+fn watch_date(watch: &HistoryItem, rating_key: &str) -> Result<DateTime<Utc>> {
+    unix_seconds_to_date(watch.date).wrap_err_with(|| {
+        format!(
+            "Tautulli returned an invalid watch date for user {} on rating key {}",
+            watch.user, rating_key
+        )
+    })
+}
+
+fn unix_seconds_to_date(unix_seconds: i64) -> Result<DateTime<Utc>> {
+    if unix_seconds <= 0 {
+        return Err(eyre!("{} is not a valid unix timestamp", unix_seconds));
+    }
+
+    match DateTime::from_timestamp(unix_seconds, 0) {
+        Some(date) => Ok(date),
+        None => Err(eyre!("{} is out of range for a date", unix_seconds)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tautulli::responses::{History, ResponseObj};
+    use crate::test_support::{self, mock, tautulli_history};
+
+    /// A row of history of a finished watch, on the day given by `date`.
+    fn watch(user_id: u32, user: &str, date: i64) -> HistoryItem {
+        HistoryItem {
+            user: user.to_string(),
+            user_id,
+            date: 1_700_000_000 + date * 86_400,
+            percent_complete: 100,
+            media_index: None,
+            parent_media_index: None,
+            group_count: None,
+            group_ids: None,
+            state: None,
+            view_offset: None,
+            platform: None,
+            player: None,
+            product: None,
+        }
+    }
+
+    /// An episode watched by alice (1) or bob (2).
+    fn episode(user_id: u32, date: i64, season: u32, episode: u32) -> HistoryItem {
+        let user = if user_id == 1 { "alice" } else { "bob" };
+        HistoryItem {
+            parent_media_index: Some(season),
+            media_index: Some(episode),
+            ..watch(user_id, user, date)
+        }
+    }
+
+    fn history(rows: &[HistoryItem], media_type: MediaType) -> WatchHistory {
+        test_support::init();
+        WatchHistory::from_rows(rows, &[], &media_type, "1").unwrap()
+    }
+
+    /// The rows of a `get_history` response.
+    fn fixture_rows(fixture: &str) -> Vec<HistoryItem> {
+        let response: ResponseObj = serde_json::from_str(fixture).unwrap();
+        serde_json::from_value::<History<HistoryItem>>(response.response.data)
+            .unwrap()
+            .data
+    }
+
+    fn tv_watches(history: &WatchHistory) -> &[UserEpisodeWatch] {
+        match history {
+            WatchHistory::TvShow(watches) => watches,
+            _ => panic!("Expected the history of a show, got {:?}", history),
+        }
+    }
+
+    #[test]
+    fn counts_the_plays_of_each_user() {
+        let history = history(
+            &[
+                watch(1, "alice", 1),
+                watch(2, "bob", 2),
+                watch(1, "alice", 3),
+            ],
+            MediaType::Movie,
+        );
+
+        let WatchHistory::Movie(watches) = history else {
+            panic!("Expected the history of a movie");
+        };
+        let play_counts = watches
+            .iter()
+            .map(|watch| (watch.user_id, watch.play_count))
+            .collect_vec();
+        assert_eq!(play_counts, [(1, 2), (2, 1)]);
+    }
+
+    #[test]
+    fn counts_duplicate_plays_of_an_episode() {
+        let history = history(
+            &[
+                episode(1, 1, 1, 1),
+                episode(1, 2, 1, 1),
+                episode(1, 3, 1, 2),
+            ],
+            MediaType::Tv,
+        );
+
+        let watches = tv_watches(&history);
+        assert_eq!(watches.len(), 1);
+        assert_eq!(watches[0].play_count, 3);
+        assert_eq!(watches[0].episodes_watched(), 2);
+    }
+
+    #[test]
+    fn keeps_the_listened_tracks_of_each_disc() {
+        let rows = fixture_rows(include_str!("../../fixtures/tautulli/history_music.json"));
+        let history = history(&rows, MediaType::Music);
+
+        let WatchHistory::Music(watches) = history else {
+            panic!("Expected the history of an album");
+        };
+        let alice = &watches[0];
+        assert_eq!((alice.disc, alice.track), (Some(2), Some(1)));
+        assert_eq!(alice.play_count, 4);
+        // The track without a disc counts as on the first one, and the unfinished one not at all.
+        assert_eq!(
+            alice.listened_tracks.iter().copied().collect_vec(),
+            [(1, 1), (1, 2), (2, 1)]
+        );
+        assert_eq!(watches[1].listened_tracks.len(), 1);
+    }
+
+    #[test]
+    fn keeps_specials_without_a_season() {
+        let rows = fixture_rows(include_str!("../../fixtures/tautulli/history_special.json"));
+        let history = history(&rows, MediaType::Tv);
+
+        let watch = &tv_watches(&history)[0];
+        assert_eq!((watch.season, watch.episode), (None, None));
+        assert_eq!(watch.play_count, 3);
+        // Only the episode with both indices can be placed in a season.
+        assert_eq!(watch.episodes_watched(), 1);
+        assert!(watch.to_string().contains("Special/Unknown episode"));
+    }
+
+    #[test]
+    fn nobody_has_finished_an_item_without_history() {
+        for history in [history(&[], MediaType::Movie), WatchHistory::NoData] {
+            assert!(history.watched_by(85, &[]).is_empty());
+            assert!(!history.is_watched_by_all(85, &[]));
+        }
+    }
+
+    #[test]
+    fn a_watch_exactly_at_the_threshold_is_finished() {
+        let at = |percent_complete| HistoryItem {
+            percent_complete,
+            ..watch(1, "alice", 1)
+        };
+        let at_threshold = history(&[at(85), watch(2, "bob", 1)], MediaType::Movie);
+        assert_eq!(at_threshold.watched_by(85, &[]), ["alice", "bob"]);
+        assert!(at_threshold.is_watched_by_all(85, &[]));
+
+        let below_threshold = history(&[at(84), watch(2, "bob", 1)], MediaType::Movie);
+        assert_eq!(below_threshold.watched_by(85, &[]), ["bob"]);
+        assert!(!below_threshold.is_watched_by_all(85, &[]));
+    }
+
+    #[test]
+    fn one_finished_episode_does_not_finish_a_show() {
+        // Both finished the one episode they watched, of a show with 50.
+        let rows = [episode(1, 1, 1, 1), episode(2, 1, 2, 1)];
+        let show = history(&rows, MediaType::Tv);
+        let seasons = [(1, Some(25)), (2, Some(25))];
+        assert!(show.watched_by(85, &seasons).is_empty());
+        assert!(!show.is_watched_by_all(85, &seasons));
+
+        // For a movie every row is a watch of the movie itself, so the latest one counts.
+        assert!(history(&rows, MediaType::Movie).is_watched_by_all(85, &[]));
+    }
+
+    #[test]
+    fn shows_are_finished_by_watching_every_season() {
+        let started_next = HistoryItem {
+            percent_complete: 20,
+            ..episode(2, 4, 2, 2)
+        };
+        let rows = [
+            episode(1, 1, 1, 1),
+            episode(1, 2, 1, 2),
+            episode(1, 3, 2, 1),
+            episode(1, 4, 2, 2),
+            episode(2, 1, 1, 1),
+            episode(2, 2, 1, 2),
+            episode(2, 3, 2, 1),
+            started_next,
+        ];
+        let show = history(&rows, MediaType::Tv);
+        // Bob has only started the last episode of the second season.
+        let seasons = [(1, Some(2)), (2, Some(2))];
+        assert_eq!(show.watched_by(85, &seasons), ["alice"]);
+        assert!(!show.is_watched_by_all(85, &seasons));
+        assert!(show.is_watched_by_all(85, &seasons[..1]));
+
+        // Seasons without episodes don't need watching, ones with an unknown count can't be finished.
+        assert!(show.is_watched_by_all(85, &[(1, Some(2)), (3, Some(0))]));
+        assert!(show.watched_by(85, &[(1, Some(2)), (3, None)]).is_empty());
+        assert!(show.watched_by(85, &[]).is_empty());
+    }
+
+    #[test]
+    fn a_renamed_user_is_one_watcher() {
+        let rows = [
+            watch(3, "carol", 1),
+            watch(1, "alice", 2),
+            watch(3, "carol_renamed", 3),
+        ];
+        let history = history(&rows, MediaType::Movie);
+
+        let WatchHistory::Movie(watches) = &history else {
+            panic!("Expected the history of a movie");
+        };
+        let watchers = watches
+            .iter()
+            .map(|watch| (watch.user_id, watch.display_name.as_str(), watch.play_count))
+            .collect_vec();
+        // The name of the latest watch is shown, unless Tautulli knows the user by another one.
+        assert_eq!(watchers, [(1, "alice", 1), (3, "carol_renamed", 2)]);
+        assert_eq!(history.user_count(), 2);
+
+        let users = [User {
+            user_id: 3,
+            username: "carol_renamed".to_string(),
+            friendly_name: Some("Carol".to_string()),
+            email: None,
+        }];
+        let history = WatchHistory::from_rows(&rows, &users, &MediaType::Movie, "1").unwrap();
+        assert!(history.watched_by(85, &[]).contains(&"Carol"));
+    }
+
+    #[test]
+    fn counts_every_play_of_a_grouped_row() {
+        let grouped = HistoryItem {
+            group_count: Some(3),
+            group_ids: Some("11,12,13".to_string()),
+            ..watch(1, "alice", 2)
+        };
+        let history = history(&[watch(1, "alice", 1), grouped], MediaType::Movie);
+
+        let WatchHistory::Movie(watches) = history else {
+            panic!("Expected the history of a movie");
+        };
+        assert_eq!(watches[0].play_count, 4);
+    }
+
+    #[test]
+    fn keeps_the_watched_episodes_of_every_season() {
+        let unfinished = HistoryItem {
+            percent_complete: 40,
+            ..episode(1, 6, 1, 3)
+        };
+        let history = history(
+            &[
+                episode(1, 1, 2, 1),
+                episode(1, 2, 1, 1),
+                episode(1, 3, 2, 2),
+                // A rewatch of an episode that was already watched.
+                episode(1, 4, 1, 1),
+                episode(1, 5, 1, 2),
+                unfinished,
+            ],
+            MediaType::Tv,
+        );
+
+        let watch = &tv_watches(&history)[0];
+        assert_eq!(
+            watch.watched_episodes,
+            BTreeMap::from([(1, BTreeSet::from([1, 2])), (2, BTreeSet::from([1, 2]))])
+        );
+        assert_eq!(watch.episodes_watched(), 4);
+        // The latest episode is the one in progress, even though it isn't counted as watched.
+        assert_eq!((watch.season, watch.episode), (Some(1), Some(3)));
+    }
+
+    #[test]
+    fn a_season_is_finished_when_every_user_watched_all_of_it() {
+        let history = history(
+            &[
+                episode(1, 1, 1, 1),
+                episode(1, 2, 1, 2),
+                episode(2, 3, 1, 2),
+                episode(2, 4, 2, 1),
+                episode(2, 5, 1, 1),
+            ],
+            MediaType::Tv,
+        );
+
+        assert!(history.season_finished_by_all(1, Some(2)));
+        assert!(!history.season_finished_by_all(1, Some(3)));
+        assert!(!history.season_finished_by_all(2, Some(1)));
+        // Without an episode count it isn't known whether a season is finished.
+        assert!(!history.season_finished_by_all(1, None));
+    }
+
+    #[tokio::test]
+    async fn rejects_more_days_than_a_date_can_go_back() {
+        for days in [i64::MAX, 1_000_000_000] {
+            let err = get_unplayed_library_items(days).await.unwrap_err();
+
+            assert_eq!(ExitCode::of(&err), ExitCode::Config);
+            assert!(format!("{:?}", err).contains("is too long for --min-days-since-watch"));
+        }
+    }
+
+    #[tokio::test]
+    async fn leaves_out_the_excluded_users() {
+        test_support::init();
+        // The config excludes "Dashboards" by name and the user with id 99.
+        let rows = r#"[
+            {"user": "alice", "user_id": 1, "date": 1700000000, "percent_complete": 100, "parent_media_index": 1, "media_index": 1},
+            {"user": "dashboards", "user_id": 5, "date": 1700000100, "percent_complete": 100, "parent_media_index": 1, "media_index": 1},
+            {"user": "tester", "user_id": 99, "date": 1700000200, "percent_complete": 30, "parent_media_index": 1, "media_index": 2}
+        ]"#;
+        mock(
+            "get_history&rating_key=1003&",
+            &[(200, &tautulli_history(rows))],
+        );
+        mock(
+            "get_history&grandparent_rating_key=1003&",
+            &[(200, &tautulli_history(rows))],
+        );
+
+        for media_type in [MediaType::Movie, MediaType::Tv] {
+            let history = get_item_watches("1003", &media_type, None, false)
+                .await
+                .unwrap();
+
+            assert_eq!(history.watchers_since(None), [(1, "Alice")]);
+            assert!(history.is_watched_by_all(85, &[(1, Some(1))]));
+        }
+    }
+
+    #[tokio::test]
+    async fn gets_the_history_of_an_item_once() {
+        test_support::init();
+        let rows = r#"[{"user": "bob", "user_id": 2, "date": 1700000000, "percent_complete": 90}]"#;
+        let server = mock(
+            "get_history&rating_key=1007&",
+            &[(200, &tautulli_history(rows))],
+        );
+
+        for _ in 0..3 {
+            let history = get_item_watches("1007", &MediaType::Movie, None, false)
+                .await
+                .unwrap();
+            assert_eq!(history.watchers_since(None), [(2, "bob")]);
+        }
+        assert_eq!(server.hits(), 1);
+
+        get_item_watches("1007", &MediaType::Movie, None, true)
+            .await
+            .unwrap();
+        assert_eq!(server.hits(), 2);
+    }
+
+    #[test]
+    fn matches_users_by_name_or_id() {
+        let watch = watch(7, "Alice", 1);
+        let users = |users: &[&str]| Some(users.iter().map(|user| user.to_string()).collect());
+
+        assert!(user_counted(&watch, &None, &None));
+        assert!(!user_counted(&watch, &users(&["alice"]), &None));
+        assert!(!user_counted(&watch, &users(&["7"]), &None));
+        assert!(user_counted(&watch, &users(&["bob", "70"]), &None));
+        assert!(user_counted(&watch, &None, &users(&["ALICE"])));
+        assert!(!user_counted(&watch, &None, &users(&["bob"])));
+        // Being excluded wins over being included.
+        assert!(!user_counted(&watch, &users(&["7"]), &users(&["alice"])));
+    }
 }
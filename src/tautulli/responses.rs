@@ -1,31 +1,21 @@
-use serde::Deserialize;
+use serde::{de, Deserialize, Deserializer, Serialize};
+use std::{collections::HashMap, fmt::Display, str::FromStr};
 
+/// The envelope every Tautulli response is wrapped in. The data is kept untyped,
+/// as its shape depends on the command and on whether the command succeeded.
 #[derive(Debug, Deserialize)]
 #[serde(rename_all = "snake_case")]
-pub struct ResponseArr<T> {
-    pub response: ResponseInternalArr<T>,
+pub struct ResponseObj {
+    pub response: ResponseInternalObj,
 }
 
 #[derive(Debug, Deserialize)]
 #[serde(rename_all = "snake_case")]
-pub struct ResponseInternalArr<T> {
+pub struct ResponseInternalObj {
     pub message: Option<String>,
     pub result: ResultType,
-    pub data: Vec<T>,
-}
-
-#[derive(Debug, Deserialize)]
-#[serde(rename_all = "snake_case")]
-pub struct ResponseObj<T> {
-    pub response: ResponseInternalObj<T>,
-}
-
-#[derive(Debug, Deserialize)]
-#[serde(rename_all = "snake_case")]
-pub struct ResponseInternalObj<T> {
-    pub message: Option<String>,
-    pub result: ResultType,
-    pub data: T,
+    #[serde(default)]
+    pub data: serde_json::Value,
 }
 
 #[derive(Debug, Deserialize, Clone, Copy)]
@@ -38,28 +28,327 @@ pub enum ResultType {
 #[derive(Debug, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct History<T> {
-    pub draw: u32,
-    pub records_total: u32,
     pub records_filtered: u32,
     pub data: Vec<T>,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, Serialize, Clone)]
 #[serde(rename_all = "snake_case")]
 pub struct HistoryItem {
     pub user: String,
+    #[serde(deserialize_with = "string_or_number")]
+    pub user_id: u32,
+    #[serde(deserialize_with = "string_or_number")]
     pub date: i64,
-    pub duration: u64,
+    #[serde(deserialize_with = "string_or_number")]
     pub percent_complete: u8,
+    #[serde(default, deserialize_with = "optional_string_or_number")]
     pub media_index: Option<u32>,
+    #[serde(default, deserialize_with = "optional_string_or_number")]
     pub parent_media_index: Option<u32>,
+    #[serde(default, deserialize_with = "optional_string_or_number")]
+    pub group_count: Option<u32>,
+    pub group_ids: Option<String>,
+    pub state: Option<String>,
+    #[serde(default, deserialize_with = "optional_string_or_number")]
+    pub view_offset: Option<u64>,
+    pub platform: Option<String>,
+    pub player: Option<String>,
+    pub product: Option<String>,
+}
+
+impl HistoryItem {
+    /// The number of plays this row stands for, as Tautulli may group consecutive plays into one row.
+    pub fn plays(&self) -> u32 {
+        match (&self.group_ids, self.group_count) {
+            (Some(group_ids), _) if !group_ids.is_empty() => group_ids.split(',').count() as u32,
+            (_, Some(group_count)) if group_count > 0 => group_count,
+            _ => 1,
+        }
+    }
 }
 
 #[derive(Debug, Deserialize)]
 #[serde(rename_all = "snake_case")]
 pub struct HistoryMovieItem {
+    #[serde(deserialize_with = "string_or_number")]
     pub date: i64,
-    pub duration: u64,
+    #[serde(deserialize_with = "string_or_number")]
     pub percent_complete: u8,
     pub user: String,
+    #[serde(deserialize_with = "string_or_number")]
+    pub user_id: u32,
+    #[serde(default, deserialize_with = "optional_string_or_number")]
+    pub group_count: Option<u32>,
+    pub group_ids: Option<String>,
+    pub state: Option<String>,
+    #[serde(default, deserialize_with = "optional_string_or_number")]
+    pub view_offset: Option<u64>,
+    pub platform: Option<String>,
+    pub player: Option<String>,
+    pub product: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub struct Library {
+    #[serde(deserialize_with = "string_or_number")]
+    pub section_id: u32,
+    pub section_name: String,
+    pub section_type: String,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub struct LibraryMediaItem {
+    #[serde(deserialize_with = "string_or_number")]
+    pub rating_key: u64,
+    pub title: String,
+    #[serde(default, deserialize_with = "optional_string_or_number")]
+    pub play_count: Option<u32>,
+    #[serde(default, deserialize_with = "optional_string_or_number")]
+    pub last_played: Option<i64>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub struct Activity {
+    pub sessions: Vec<ActivitySession>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub struct ActivitySession {
+    pub rating_key: String,
+    pub parent_rating_key: String,
+    pub grandparent_rating_key: String,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub struct Metadata {
+    pub rating_key: Option<String>,
+    pub media_type: Option<String>,
+    /// The Plex guid of the item, like "plex://movie/5d776b59ad5437001f79c6f8".
+    pub guid: Option<String>,
+    /// The external ids of the item, like "tmdb://603" or "tvdb://81189".
+    #[serde(default)]
+    pub guids: Vec<String>,
+    /// The names of the Plex collections the item is in.
+    #[serde(default)]
+    pub collections: Vec<String>,
+    /// The labels admins gave the item in Plex.
+    #[serde(default)]
+    pub labels: Vec<String>,
+    /// The versions of the item, only movies and episodes have them.
+    #[serde(default)]
+    pub media_info: Vec<MediaInfo>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub struct MediaInfo {
+    /// In kbps.
+    #[serde(default, deserialize_with = "optional_string_or_number")]
+    pub bitrate: Option<u64>,
+    /// Like "1080", "4k" or "sd".
+    pub video_resolution: Option<String>,
+    pub container: Option<String>,
+    #[serde(default)]
+    pub parts: Vec<MediaPart>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub struct MediaPart {
+    #[serde(default, deserialize_with = "optional_string_or_number")]
+    pub file_size: Option<i64>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub struct SearchResults {
+    /// The results grouped by type, like "movie", "show" or "album".
+    #[serde(default)]
+    pub results_list: HashMap<String, Vec<SearchResult>>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub struct SearchResult {
+    #[serde(deserialize_with = "string_or_number")]
+    pub rating_key: u64,
+    pub title: String,
+    #[serde(default, deserialize_with = "optional_string_or_number")]
+    pub year: Option<i32>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub struct TautulliInfo {
+    pub tautulli_version: String,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub struct UserResponse {
+    #[serde(deserialize_with = "string_or_number")]
+    pub user_id: u32,
+    pub username: String,
+    pub friendly_name: Option<String>,
+    pub email: Option<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum StringOrNumber<T> {
+    Number(T),
+    String(String),
+}
+
+/// Depending on the version, Tautulli sends some numbers as strings, so accept both.
+fn string_or_number<'de, D, T>(deserializer: D) -> Result<T, D::Error>
+where
+    D: Deserializer<'de>,
+    T: Deserialize<'de> + FromStr,
+    T::Err: Display,
+{
+    match StringOrNumber::deserialize(deserializer)? {
+        StringOrNumber::Number(number) => Ok(number),
+        StringOrNumber::String(string) => string.trim().parse().map_err(de::Error::custom),
+    }
+}
+
+/// Like `string_or_number`, but treats null and empty strings as `None`.
+fn optional_string_or_number<'de, D, T>(deserializer: D) -> Result<Option<T>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: Deserialize<'de> + FromStr,
+    T::Err: Display,
+{
+    match Option::<StringOrNumber<T>>::deserialize(deserializer)? {
+        None => Ok(None),
+        Some(StringOrNumber::Number(number)) => Ok(Some(number)),
+        Some(StringOrNumber::String(string)) if string.trim().is_empty() => Ok(None),
+        Some(StringOrNumber::String(string)) => {
+            string.trim().parse().map(Some).map_err(de::Error::custom)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde::de::DeserializeOwned;
+
+    /// The history in a `get_history` response.
+    fn history<T: DeserializeOwned>(fixture: &str) -> History<T> {
+        let response: ResponseObj = serde_json::from_str(fixture).unwrap();
+        serde_json::from_value(response.response.data).unwrap()
+    }
+
+    /// Serializes the rows and reads them back, which has to give the same rows.
+    fn round_trip(rows: &[HistoryItem]) {
+        let json = serde_json::to_value(rows).unwrap();
+        let read_back: Vec<HistoryItem> = serde_json::from_value(json.clone()).unwrap();
+        assert_eq!(serde_json::to_value(read_back).unwrap(), json);
+    }
+
+    #[test]
+    fn reads_grouped_history_rows() {
+        let fixture = include_str!("../../fixtures/tautulli/history_grouped.json");
+        let rows = history::<HistoryItem>(fixture).data;
+
+        let plays = rows.iter().map(HistoryItem::plays).collect::<Vec<_>>();
+        assert_eq!(plays, [3, 1]);
+        assert_eq!(rows[0].group_count, Some(3));
+        assert_eq!(history::<HistoryMovieItem>(fixture).data.len(), 2);
+    }
+
+    #[test]
+    fn reads_ungrouped_history_rows() {
+        let fixture = include_str!("../../fixtures/tautulli/history_ungrouped.json");
+        let rows = history::<HistoryItem>(fixture).data;
+
+        assert!(rows.iter().all(|row| row.plays() == 1));
+        assert!(rows.iter().all(|row| row.group_count.is_none()));
+        assert_eq!(history::<HistoryMovieItem>(fixture).data.len(), 3);
+    }
+
+    #[test]
+    fn counts_the_plays_by_the_group_ids_first() {
+        let rows =
+            history::<HistoryItem>(include_str!("../../fixtures/tautulli/history_grouped.json"))
+                .data;
+        let row = |group_count, group_ids: Option<&str>| HistoryItem {
+            group_count,
+            group_ids: group_ids.map(str::to_string),
+            ..rows[0].clone()
+        };
+
+        assert_eq!(row(Some(2), Some("1,2,3")).plays(), 3);
+        assert_eq!(row(Some(2), Some("")).plays(), 2);
+        assert_eq!(row(Some(0), None).plays(), 1);
+        assert_eq!(row(None, None).plays(), 1);
+    }
+
+    #[test]
+    fn reads_the_numbers_tautulli_2_10_sends_as_strings() {
+        let rows =
+            history::<HistoryItem>(include_str!("../../fixtures/tautulli/history_2.10.json"));
+        assert_eq!(rows.records_filtered, 2);
+
+        let rows = rows.data;
+        assert_eq!(rows[0].user_id, 4821937);
+        assert_eq!(rows[0].date, 1699999200);
+        assert_eq!(rows[0].percent_complete, 98);
+        assert_eq!(rows[0].parent_media_index, Some(1));
+        assert_eq!(rows[0].media_index, Some(1));
+        assert_eq!(rows[0].group_count, Some(1));
+        assert_eq!(rows[0].view_offset, None);
+        assert_eq!(rows[1].view_offset, Some(1520000));
+        round_trip(&rows);
+    }
+
+    #[test]
+    fn reads_the_mixed_numbers_of_tautulli_2_13() {
+        let rows =
+            history::<HistoryItem>(include_str!("../../fixtures/tautulli/history_2.13.json")).data;
+
+        assert_eq!(rows[0].date, 1700085600);
+        assert_eq!(rows[0].percent_complete, 100);
+        assert_eq!(rows[0].media_index, Some(3));
+        assert_eq!(rows[1].media_index, None);
+        assert_eq!(rows[1].parent_media_index, Some(1));
+        round_trip(&rows);
+    }
+
+    #[test]
+    fn reads_music_history_rows() {
+        let rows =
+            history::<HistoryItem>(include_str!("../../fixtures/tautulli/history_music.json")).data;
+
+        // For tracks, the index is the track number and the parent index the disc.
+        let indices = rows
+            .iter()
+            .map(|row| (row.parent_media_index, row.media_index))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            indices,
+            [
+                (Some(2), Some(1)),
+                (Some(1), Some(3)),
+                (Some(1), Some(2)),
+                (None, Some(1)),
+                (Some(1), Some(1)),
+            ]
+        );
+    }
+
+    #[test]
+    fn rejects_strings_that_are_not_numbers() {
+        let row = r#"{"user": "alice", "user_id": 1, "date": 1, "percent_complete": "most"}"#;
+        let err = serde_json::from_str::<HistoryItem>(row).unwrap_err();
+        assert!(err.to_string().contains("invalid digit"), "{}", err);
+    }
 }
@@ -0,0 +1,201 @@
+//! What the tests share: a config for every service, pointing at a local server that answers
+//! with canned responses. The config and arguments are globals, so every test uses the same ones,
+//! and tells its requests apart from the others' by what is in the URL.
+
+use once_cell::sync::Lazy;
+use std::{
+    collections::VecDeque,
+    io::{BufRead, BufReader, Read, Write},
+    net::{TcpListener, TcpStream},
+    sync::{Arc, Mutex, Once},
+    thread,
+};
+
+use crate::{arguments::Arguments, config::Config};
+
+static SERVER: Lazy<MockServer> = Lazy::new(MockServer::start);
+static INIT: Once = Once::new();
+
+/// Sets up the arguments (as if none were passed) and the config, once for all tests.
+pub fn init() {
+    INIT.call_once(|| {
+        let url = &SERVER.url;
+        Arguments::init_for_tests();
+        Config::init_for_tests(&format!(
+            r#"
+            version = 2
+
+            [plex]
+            url = "{url}/plex"
+            token = "PLEX_TOKEN"
+
+            [tautulli]
+            url = "{url}/tautulli"
+            api_key = "TAUTULLI_KEY"
+            retry_attempts = 3
+            retry_delay_ms = 1
+            requests_per_second = 0
+            exclude_users = ["Dashboards", "99"]
+
+            [overseerr]
+            url = "{url}/overseerr"
+            api_key = "OVERSEERR_KEY"
+            retry_attempts = 3
+            retry_delay_ms = 1
+
+            [radarr]
+            url = "{url}/radarr"
+            api_key = "RADARR_KEY"
+
+            [sonarr]
+            url = "{url}/sonarr"
+            api_key = "SONARR_KEY"
+            "#
+        ));
+
+        mock(
+            "cmd=get_users",
+            &[(
+                200,
+                &tautulli_response(
+                    r#"[
+                        {"user_id": 1, "username": "alice", "friendly_name": "Alice"},
+                        {"user_id": 2, "username": "bob", "friendly_name": ""}
+                    ]"#,
+                ),
+            )],
+        );
+    });
+}
+
+/// A successful answer from Tautulli with the data.
+pub fn tautulli_response(data: &str) -> String {
+    format!(
+        r#"{{"response": {{"result": "success", "message": null, "data": {}}}}}"#,
+        data
+    )
+}
+
+/// A page of Tautulli history with all of the rows.
+pub fn tautulli_history(rows: &str) -> String {
+    let count = serde_json::from_str::<Vec<serde_json::Value>>(rows)
+        .unwrap()
+        .len();
+    tautulli_response(&format!(
+        r#"{{"recordsFiltered": {}, "recordsTotal": {}, "data": {}}}"#,
+        count, count, rows
+    ))
+}
+
+/// Answers the requests whose URL (path and query) contains `pattern` with the responses in order,
+/// repeating the last one once the others are used up.
+pub fn mock(pattern: &str, responses: &[(u16, &str)]) -> Mock {
+    let mock = Mock::default();
+    SERVER.routes.lock().unwrap().push(Route {
+        pattern: pattern.to_string(),
+        responses: responses
+            .iter()
+            .map(|(status, body)| (*status, body.to_string()))
+            .collect(),
+        requests: mock.requests.clone(),
+    });
+
+    mock
+}
+
+/// What a mocked route was asked.
+#[derive(Default)]
+pub struct Mock {
+    requests: Arc<Mutex<Vec<String>>>,
+}
+
+impl Mock {
+    /// How many requests the route answered.
+    pub fn hits(&self) -> usize {
+        self.requests.lock().unwrap().len()
+    }
+}
+
+struct Route {
+    pattern: String,
+    responses: VecDeque<(u16, String)>,
+    requests: Arc<Mutex<Vec<String>>>,
+}
+
+struct MockServer {
+    url: String,
+    routes: Arc<Mutex<Vec<Route>>>,
+}
+
+impl MockServer {
+    fn start() -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0").expect("The mock server could not start");
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let routes: Arc<Mutex<Vec<Route>>> = Arc::default();
+
+        let server_routes = routes.clone();
+        thread::spawn(move || {
+            for stream in listener.incoming().flatten() {
+                let routes = server_routes.clone();
+                thread::spawn(move || answer(stream, &routes));
+            }
+        });
+
+        Self { url, routes }
+    }
+}
+
+/// Answers a single request, and closes the connection.
+fn answer(mut stream: TcpStream, routes: &Mutex<Vec<Route>>) {
+    let mut reader = BufReader::new(stream.try_clone().unwrap());
+    let mut request_line = String::new();
+    if reader.read_line(&mut request_line).is_err() {
+        return;
+    }
+    let target = request_line
+        .split_whitespace()
+        .nth(1)
+        .unwrap_or_default()
+        .to_string();
+
+    let mut content_length = 0;
+    loop {
+        let mut header = String::new();
+        if reader.read_line(&mut header).is_err() || header.trim().is_empty() {
+            break;
+        }
+        if let Some((name, value)) = header.split_once(':') {
+            if name.eq_ignore_ascii_case("content-length") {
+                content_length = value.trim().parse().unwrap_or(0);
+            }
+        }
+    }
+    let mut body = vec![0; content_length];
+    let _ = reader.read_exact(&mut body);
+
+    let (status, body) = {
+        let mut routes = routes.lock().unwrap();
+        match routes
+            .iter_mut()
+            .find(|route| target.contains(&route.pattern))
+        {
+            Some(route) => {
+                route.requests.lock().unwrap().push(target.clone());
+                match route.responses.len() {
+                    0 => (404, String::new()),
+                    1 => route.responses[0].clone(),
+                    _ => route.responses.pop_front().unwrap(),
+                }
+            }
+            None => (404, format!("Nothing is mocked for {}", target)),
+        }
+    };
+
+    let _ = write!(
+        stream,
+        "HTTP/1.1 {} Mocked\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        body.len(),
+        body
+    );
+}
@@ -0,0 +1,58 @@
+use regex::{Regex, RegexBuilder};
+
+use crate::{arguments::Arguments, config::Config, exclusion::ExclusionReason};
+
+/// The titles a run leaves out, from --exclude and `rules.exclude_titles`, and the ones it is
+/// restricted to with --include. They are matched against titles like "Title (Year)".
+pub struct TitleFilter {
+    exclude: Vec<Regex>,
+    include: Vec<Regex>,
+}
+
+impl TitleFilter {
+    pub fn of_run() -> Self {
+        let args = Arguments::get_args();
+        let mut exclude = args.exclude.clone();
+        // The config was checked to only have valid patterns when it was read.
+        exclude.extend(
+            Config::global()
+                .rules
+                .exclude_titles
+                .iter()
+                .filter_map(|pattern| compile(pattern).ok()),
+        );
+
+        Self {
+            exclude,
+            include: args.include.clone(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.exclude.is_empty() && self.include.is_empty()
+    }
+
+    /// Why an item with the title is left out, `None` if it is kept. An item without a known
+    /// title can't match anything, so it is only left out when the run is restricted with --include.
+    pub fn exclusion(&self, title: Option<&str>) -> Option<ExclusionReason> {
+        let title = match title {
+            Some(title) => title,
+            None if self.include.is_empty() => return None,
+            None => return Some(ExclusionReason::NotIncluded),
+        };
+
+        if let Some(pattern) = self.exclude.iter().find(|pattern| pattern.is_match(title)) {
+            return Some(ExclusionReason::ExcludedTitle(pattern.to_string()));
+        }
+        if !self.include.is_empty() && !self.include.iter().any(|pattern| pattern.is_match(title)) {
+            return Some(ExclusionReason::NotIncluded);
+        }
+
+        None
+    }
+}
+
+/// Compiles a title pattern, which never cares about case.
+pub fn compile(pattern: &str) -> Result<Regex, regex::Error> {
+    RegexBuilder::new(pattern).case_insensitive(true).build()
+}
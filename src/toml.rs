@@ -0,0 +1,812 @@
+//! Reading and writing TOML configs. They are turned into the same values as a YAML config,
+//! so the rest of reading the config (migrating, profiles, environment variables) works on both.
+
+use itertools::Itertools;
+use serde_yaml::{Mapping, Number, Value};
+use std::fmt::Write;
+
+/// Why a TOML file can't be read, and where.
+#[derive(Debug, PartialEq)]
+pub struct TomlError {
+    pub line: usize,
+    pub column: usize,
+    pub reason: String,
+}
+
+pub fn from_str(text: &str) -> Result<Value, TomlError> {
+    let mut parser = Parser {
+        chars: text.chars().collect(),
+        position: 0,
+        root: Mapping::new(),
+        table: Vec::new(),
+    };
+    parser.parse()?;
+    Ok(Value::Mapping(parser.root))
+}
+
+/// Writes a mapping as TOML. Settings that aren't set (null) are left out, as TOML has no null.
+pub fn to_string(value: &Value) -> Result<String, String> {
+    let map = value
+        .as_mapping()
+        .ok_or("Only a mapping can be written as TOML")?;
+    let mut out = String::new();
+    write_table(&mut out, &[], map, false)?;
+    Ok(out.trim_start().to_string())
+}
+
+struct Parser {
+    chars: Vec<char>,
+    position: usize,
+    root: Mapping,
+    /// The keys of the table the key/values are currently put in, from the last `[header]`.
+    table: Vec<String>,
+}
+
+impl Parser {
+    fn parse(&mut self) -> Result<(), TomlError> {
+        loop {
+            self.skip_whitespace_and_comments();
+            match self.peek() {
+                None => return Ok(()),
+                Some('[') => self.header()?,
+                Some(_) => {
+                    let keys = self.key()?;
+                    self.expect('=')?;
+                    let value = self.value()?;
+                    insert(&mut self.root, &self.table, &keys, value)
+                        .map_err(|reason| self.error(reason))?;
+                }
+            }
+            self.end_of_line()?;
+        }
+    }
+
+    /// A `[table]` or `[[array of tables]]` header.
+    fn header(&mut self) -> Result<(), TomlError> {
+        self.position += 1;
+        let is_array = self.peek() == Some('[');
+        if is_array {
+            self.position += 1;
+        }
+        let keys = self.key()?;
+        self.expect(']')?;
+        if is_array && self.chars.get(self.position) != Some(&']') {
+            return Err(self.error("expected `]]` to end the header"));
+        }
+        if is_array {
+            self.position += 1;
+        }
+
+        let (last, parents) = keys.split_last().expect("A key has at least one part");
+        let parent = match table_at(&mut self.root, parents) {
+            Ok(parent) => parent,
+            Err(reason) => return Err(self.error(reason)),
+        };
+        match parent.get_mut(last.as_str()) {
+            None if is_array => {
+                parent.insert(
+                    last.as_str().into(),
+                    vec![Value::Mapping(Mapping::new())].into(),
+                );
+            }
+            None => {
+                parent.insert(last.as_str().into(), Mapping::new().into());
+            }
+            Some(Value::Sequence(tables)) if is_array => {
+                tables.push(Mapping::new().into());
+            }
+            Some(Value::Mapping(_)) if !is_array => {}
+            Some(_) => {
+                let reason = format!("`{}` is already defined", keys.join("."));
+                return Err(self.error(reason));
+            }
+        }
+
+        self.table = keys;
+        Ok(())
+    }
+
+    /// A key, with its parts if it is dotted like `rules.watched_threshold`.
+    fn key(&mut self) -> Result<Vec<String>, TomlError> {
+        let mut keys = Vec::new();
+        loop {
+            self.skip_spaces();
+            let key = match self.peek() {
+                Some('"') => self.basic_string()?,
+                Some('\'') => self.literal_string()?,
+                _ => {
+                    let start = self.position;
+                    while self
+                        .peek()
+                        .is_some_and(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+                    {
+                        self.position += 1;
+                    }
+                    if start == self.position {
+                        return Err(self.error("expected a key"));
+                    }
+                    self.chars[start..self.position].iter().collect()
+                }
+            };
+            keys.push(key);
+
+            self.skip_spaces();
+            if self.peek() != Some('.') {
+                return Ok(keys);
+            }
+            self.position += 1;
+        }
+    }
+
+    fn value(&mut self) -> Result<Value, TomlError> {
+        self.skip_spaces();
+        match self.peek() {
+            Some('"') if self.starts_with("\"\"\"") => {
+                self.multiline_basic_string().map(Value::from)
+            }
+            Some('"') => self.basic_string().map(Value::from),
+            Some('\'') if self.starts_with("'''") => {
+                self.multiline_literal_string().map(Value::from)
+            }
+            Some('\'') => self.literal_string().map(Value::from),
+            Some('[') => self.array(),
+            Some('{') => self.inline_table(),
+            Some(_) => self.bare_value(),
+            None => Err(self.error("expected a value")),
+        }
+    }
+
+    fn array(&mut self) -> Result<Value, TomlError> {
+        self.position += 1;
+        let mut values = Vec::new();
+        loop {
+            self.skip_whitespace_and_comments();
+            if self.peek() == Some(']') {
+                self.position += 1;
+                return Ok(Value::Sequence(values));
+            }
+            values.push(self.value()?);
+            self.skip_whitespace_and_comments();
+            match self.peek() {
+                Some(',') => self.position += 1,
+                Some(']') => {}
+                _ => return Err(self.error("expected `,` or `]` in the array")),
+            }
+        }
+    }
+
+    fn inline_table(&mut self) -> Result<Value, TomlError> {
+        self.position += 1;
+        let mut table = Mapping::new();
+        self.skip_spaces();
+        if self.peek() == Some('}') {
+            self.position += 1;
+            return Ok(Value::Mapping(table));
+        }
+
+        loop {
+            let keys = self.key()?;
+            self.expect('=')?;
+            let value = self.value()?;
+            insert(&mut table, &[], &keys, value).map_err(|reason| self.error(reason))?;
+
+            self.skip_spaces();
+            match self.peek() {
+                Some(',') => self.position += 1,
+                Some('}') => {
+                    self.position += 1;
+                    return Ok(Value::Mapping(table));
+                }
+                _ => return Err(self.error("expected `,` or `}` in the inline table")),
+            }
+        }
+    }
+
+    /// A boolean, number or date, which aren't quoted. Dates are kept as text.
+    fn bare_value(&mut self) -> Result<Value, TomlError> {
+        let start = self.position;
+        let skip_word = |parser: &mut Self| {
+            while parser
+                .peek()
+                .is_some_and(|c| !c.is_whitespace() && !matches!(c, ',' | ']' | '}' | '#'))
+            {
+                parser.position += 1;
+            }
+        };
+        skip_word(self);
+        // A date and time can be separated by a space, like 2023-01-01 10:00:00.
+        if self.position - start == 10
+            && is_date(&self.chars[start..self.position].iter().collect::<String>())
+            && self.peek() == Some(' ')
+            && self
+                .chars
+                .get(self.position + 1)
+                .is_some_and(char::is_ascii_digit)
+        {
+            self.position += 1;
+            skip_word(self);
+        }
+
+        let text: String = self.chars[start..self.position].iter().collect();
+        bare_value(&text).map_err(|reason| {
+            self.position = start;
+            self.error(reason)
+        })
+    }
+
+    fn basic_string(&mut self) -> Result<String, TomlError> {
+        self.position += 1;
+        let mut string = String::new();
+        loop {
+            match self.next() {
+                Some('"') => return Ok(string),
+                Some('\\') => string.push(self.escape()?),
+                Some('\n') | None => return Err(self.error("the text is missing its closing `\"`")),
+                Some(c) => string.push(c),
+            }
+        }
+    }
+
+    fn multiline_basic_string(&mut self) -> Result<String, TomlError> {
+        self.position += 3;
+        self.skip_newline();
+        let mut string = String::new();
+        loop {
+            if self.starts_with("\"\"\"") && !self.starts_with("\"\"\"\"") {
+                self.position += 3;
+                return Ok(string);
+            }
+            match self.next() {
+                Some('\\') if self.peek().is_some_and(char::is_whitespace) => {
+                    // A backslash at the end of a line leaves out the whitespace that follows it.
+                    while self.peek().is_some_and(char::is_whitespace) {
+                        self.position += 1;
+                    }
+                }
+                Some('\\') => string.push(self.escape()?),
+                Some(c) => string.push(c),
+                None => return Err(self.error("the text is missing its closing `\"\"\"`")),
+            }
+        }
+    }
+
+    fn literal_string(&mut self) -> Result<String, TomlError> {
+        self.position += 1;
+        let mut string = String::new();
+        loop {
+            match self.next() {
+                Some('\'') => return Ok(string),
+                Some('\n') | None => return Err(self.error("the text is missing its closing `'`")),
+                Some(c) => string.push(c),
+            }
+        }
+    }
+
+    fn multiline_literal_string(&mut self) -> Result<String, TomlError> {
+        self.position += 3;
+        self.skip_newline();
+        let mut string = String::new();
+        loop {
+            if self.starts_with("'''") && !self.starts_with("''''") {
+                self.position += 3;
+                return Ok(string);
+            }
+            match self.next() {
+                Some(c) => string.push(c),
+                None => return Err(self.error("the text is missing its closing `'''`")),
+            }
+        }
+    }
+
+    /// The character after a `\` in a basic string.
+    fn escape(&mut self) -> Result<char, TomlError> {
+        let unicode = |parser: &mut Self, length: usize| {
+            let code: String = parser
+                .chars
+                .iter()
+                .skip(parser.position)
+                .take(length)
+                .collect();
+            parser.position += length;
+            u32::from_str_radix(&code, 16)
+                .ok()
+                .filter(|_| code.len() == length)
+                .and_then(char::from_u32)
+                .ok_or_else(|| parser.error(format!("`{}` is not a valid unicode escape", code)))
+        };
+
+        match self.next() {
+            Some('b') => Ok('\u{8}'),
+            Some('t') => Ok('\t'),
+            Some('n') => Ok('\n'),
+            Some('f') => Ok('\u{c}'),
+            Some('r') => Ok('\r'),
+            Some('"') => Ok('"'),
+            Some('\\') => Ok('\\'),
+            Some('u') => unicode(self, 4),
+            Some('U') => unicode(self, 8),
+            _ => Err(self.error("unknown escape, write `\\\\` for a backslash")),
+        }
+    }
+
+    fn expect(&mut self, expected: char) -> Result<(), TomlError> {
+        self.skip_spaces();
+        if self.peek() == Some(expected) {
+            self.position += 1;
+            Ok(())
+        } else {
+            Err(self.error(format!("expected `{}`", expected)))
+        }
+    }
+
+    /// Only a comment can follow a key/value or a header on its line.
+    fn end_of_line(&mut self) -> Result<(), TomlError> {
+        self.skip_spaces();
+        self.skip_comment();
+        match self.peek() {
+            None | Some('\n') => Ok(()),
+            Some('\r') if self.chars.get(self.position + 1) == Some(&'\n') => Ok(()),
+            Some(_) => Err(self.error("expected the end of the line")),
+        }
+    }
+
+    fn skip_spaces(&mut self) {
+        while matches!(self.peek(), Some(' ' | '\t')) {
+            self.position += 1;
+        }
+    }
+
+    fn skip_comment(&mut self) {
+        if self.peek() == Some('#') {
+            while !matches!(self.peek(), None | Some('\n')) {
+                self.position += 1;
+            }
+        }
+    }
+
+    fn skip_newline(&mut self) {
+        if self.starts_with("\r\n") {
+            self.position += 2;
+        } else if self.peek() == Some('\n') {
+            self.position += 1;
+        }
+    }
+
+    fn skip_whitespace_and_comments(&mut self) {
+        loop {
+            self.skip_spaces();
+            self.skip_comment();
+            match self.peek() {
+                Some('\n' | '\r') => self.position += 1,
+                _ => return,
+            }
+        }
+    }
+
+    fn starts_with(&self, text: &str) -> bool {
+        text.chars()
+            .enumerate()
+            .all(|(index, c)| self.chars.get(self.position + index) == Some(&c))
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.position).copied()
+    }
+
+    fn next(&mut self) -> Option<char> {
+        let c = self.peek();
+        self.position += 1;
+        c
+    }
+
+    fn error(&self, reason: impl Into<String>) -> TomlError {
+        let before = &self.chars[..self.position.min(self.chars.len())];
+        let line_start = before
+            .iter()
+            .rposition(|c| *c == '\n')
+            .map_or(0, |index| index + 1);
+        TomlError {
+            line: before.iter().filter(|c| **c == '\n').count() + 1,
+            column: before.len() - line_start + 1,
+            reason: reason.into(),
+        }
+    }
+}
+
+/// Puts a value in the table at the path of keys, with the parts of a dotted key as tables of their own.
+fn insert(
+    root: &mut Mapping,
+    path: &[String],
+    keys: &[String],
+    value: Value,
+) -> Result<(), String> {
+    let (last, parents) = keys.split_last().expect("A key has at least one part");
+    let path = path.iter().chain(parents).cloned().collect_vec();
+    let table = table_at(root, &path)?;
+    if table.contains_key(last.as_str()) {
+        return Err(format!("`{}` is defined twice", keys.join(".")));
+    }
+    table.insert(last.as_str().into(), value);
+    Ok(())
+}
+
+/// The table at the path of keys, creating the ones that aren't there yet.
+/// For an array of tables it is the last table added to it.
+fn table_at<'a>(root: &'a mut Mapping, keys: &[String]) -> Result<&'a mut Mapping, String> {
+    let mut table = root;
+    for key in keys {
+        let value = table
+            .entry(key.as_str().into())
+            .or_insert_with(|| Mapping::new().into());
+        table = match value {
+            Value::Mapping(map) => map,
+            Value::Sequence(tables) => match tables.last_mut() {
+                Some(Value::Mapping(map)) => map,
+                _ => return Err(format!("`{}` is not a table", key)),
+            },
+            _ => return Err(format!("`{}` is not a table", key)),
+        };
+    }
+    Ok(table)
+}
+
+fn bare_value(text: &str) -> Result<Value, String> {
+    let number = text.replace('_', "");
+    let radix = match number.get(..2) {
+        Some("0x") => Some(16),
+        Some("0o") => Some(8),
+        Some("0b") => Some(2),
+        _ => None,
+    };
+
+    Ok(match text {
+        "true" => Value::Bool(true),
+        "false" => Value::Bool(false),
+        "inf" | "+inf" => Value::from(f64::INFINITY),
+        "-inf" => Value::from(f64::NEG_INFINITY),
+        "nan" | "+nan" | "-nan" => Value::from(f64::NAN),
+        "" => return Err("expected a value".to_string()),
+        _ if is_date(text) || is_time(text) => Value::from(text),
+        _ => match radix {
+            Some(radix) => i64::from_str_radix(&number[2..], radix)
+                .map(Value::from)
+                .map_err(|_| format!("`{}` is not a valid number", text))?,
+            None => match number.parse::<i64>() {
+                Ok(integer) => Value::from(integer),
+                Err(_) => number
+                    .parse::<f64>()
+                    .ok()
+                    .filter(|_| number.chars().any(|c| c.is_ascii_digit()))
+                    .map(Value::from)
+                    .ok_or_else(|| {
+                        format!("`{}` is not a valid value, text has to be in quotes", text)
+                    })?,
+            },
+        },
+    })
+}
+
+/// A date like 2023-01-01, which may have a time after it. TOML has dates, YAML reads them as text.
+fn is_date(text: &str) -> bool {
+    let bytes = text.as_bytes();
+    bytes.len() >= 10
+        && bytes[4] == b'-'
+        && bytes[7] == b'-'
+        && [0, 1, 2, 3, 5, 6, 8, 9]
+            .iter()
+            .all(|index| bytes[*index].is_ascii_digit())
+}
+
+/// A time like 10:00:00.
+fn is_time(text: &str) -> bool {
+    let bytes = text.as_bytes();
+    bytes.len() >= 8
+        && bytes[2] == b':'
+        && bytes[5] == b':'
+        && [0, 1, 3, 4, 6, 7]
+            .iter()
+            .all(|index| bytes[*index].is_ascii_digit())
+}
+
+/// Writes the settings of a table, then its tables, each under its own `[header]`.
+fn write_table(
+    out: &mut String,
+    path: &[String],
+    map: &Mapping,
+    in_array: bool,
+) -> Result<(), String> {
+    let entries = map
+        .iter()
+        .filter(|(_, value)| !value.is_null())
+        .map(|(key, value)| Ok((key_text(key)?, value)))
+        .collect::<Result<Vec<_>, String>>()?;
+    let (tables, settings): (Vec<_>, Vec<_>) = entries
+        .into_iter()
+        .partition(|(_, value)| value.is_mapping() || is_array_of_tables(value));
+
+    if !path.is_empty() && (in_array || !settings.is_empty() || tables.is_empty()) {
+        let header = path.join(".");
+        match in_array {
+            true => write!(out, "\n[[{}]]\n", header),
+            false => write!(out, "\n[{}]\n", header),
+        }
+        .expect("Writing to a string can't fail");
+    }
+    for (key, value) in settings {
+        writeln!(out, "{} = {}", key, inline_value(value)?)
+            .expect("Writing to a string can't fail");
+    }
+
+    for (key, value) in tables {
+        let path = path.iter().cloned().chain([key]).collect_vec();
+        match value {
+            Value::Mapping(map) => write_table(out, &path, map, false)?,
+            Value::Sequence(tables) => {
+                for table in tables {
+                    let table = table.as_mapping().expect("Checked to be a table");
+                    write_table(out, &path, table, true)?;
+                }
+            }
+            _ => unreachable!("Only tables are left"),
+        }
+    }
+    Ok(())
+}
+
+/// A list where every item is a table, which is written as `[[name]]` tables.
+fn is_array_of_tables(value: &Value) -> bool {
+    match value {
+        Value::Sequence(values) => !values.is_empty() && values.iter().all(Value::is_mapping),
+        _ => false,
+    }
+}
+
+fn key_text(key: &Value) -> Result<String, String> {
+    let key = match key {
+        Value::String(key) => key.clone(),
+        Value::Number(number) => number.to_string(),
+        Value::Bool(bool) => bool.to_string(),
+        _ => return Err(format!("{:?} can't be a key in TOML", key)),
+    };
+
+    let is_bare = !key.is_empty()
+        && key
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-');
+    Ok(match is_bare {
+        true => key,
+        false => string_text(&key),
+    })
+}
+
+fn inline_value(value: &Value) -> Result<String, String> {
+    Ok(match value {
+        Value::Null => return Err("TOML has no empty value".to_string()),
+        Value::Bool(bool) => bool.to_string(),
+        Value::Number(number) => number_text(number),
+        Value::String(string) => string_text(string),
+        Value::Sequence(values) => format!(
+            "[{}]",
+            values
+                .iter()
+                .map(inline_value)
+                .collect::<Result<Vec<_>, _>>()?
+                .join(", ")
+        ),
+        Value::Mapping(map) if map.is_empty() => "{}".to_string(),
+        Value::Mapping(map) => format!(
+            "{{ {} }}",
+            map.iter()
+                .filter(|(_, value)| !value.is_null())
+                .map(|(key, value)| Ok(format!("{} = {}", key_text(key)?, inline_value(value)?)))
+                .collect::<Result<Vec<_>, String>>()?
+                .join(", ")
+        ),
+        Value::Tagged(tagged) => inline_value(&tagged.value)?,
+    })
+}
+
+fn number_text(number: &Number) -> String {
+    match number.as_f64() {
+        Some(float) if number.is_f64() && float.is_nan() => "nan".to_string(),
+        Some(float) if number.is_f64() && float.is_infinite() => match float > 0.0 {
+            true => "inf".to_string(),
+            false => "-inf".to_string(),
+        },
+        Some(float) if number.is_f64() && float.fract() == 0.0 => format!("{:.1}", float),
+        _ => number.to_string(),
+    }
+}
+
+fn string_text(string: &str) -> String {
+    let mut text = String::from('"');
+    for c in string.chars() {
+        match c {
+            '"' => text.push_str("\\\""),
+            '\\' => text.push_str("\\\\"),
+            '\n' => text.push_str("\\n"),
+            '\t' => text.push_str("\\t"),
+            '\r' => text.push_str("\\r"),
+            c if c.is_control() => {
+                write!(text, "\\u{:04X}", c as u32).expect("Writing to a string can't fail")
+            }
+            c => text.push(c),
+        }
+    }
+    text.push('"');
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn yaml(text: &str) -> Value {
+        serde_yaml::from_str(text).unwrap()
+    }
+
+    #[test]
+    fn reads_tables_and_arrays_of_tables() {
+        let value = from_str(
+            r#"
+            # The version
+            version = 2
+            ignored_users = ["alice", 'bob'] # Never shown
+
+            [tautulli]
+            url = "http://localhost:8181"
+            retry_attempts = 3
+
+            [[radarr]]
+            name = "Radarr"
+
+            [[radarr]]
+            name = "Radarr 4K"
+            is_4k = true
+
+            [profiles.aggressive]
+            rules.watched_threshold = 60
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            value,
+            yaml(
+                r#"
+                version: 2
+                ignored_users: [alice, bob]
+                tautulli:
+                    url: http://localhost:8181
+                    retry_attempts: 3
+                radarr:
+                    - name: Radarr
+                    - name: Radarr 4K
+                      is_4k: true
+                profiles:
+                    aggressive:
+                        rules:
+                            watched_threshold: 60
+                "#
+            )
+        );
+    }
+
+    #[test]
+    fn reads_values() {
+        let value = from_str(
+            r#"
+            escaped = "a\t\"b\"\u00e9"
+            literal = 'C:\media'
+            multiline = """
+one \
+two"""
+            integer = 1_000
+            hex = 0xff
+            float = 0.5
+            negative = -3
+            boolean = false
+            date = 2023-01-01
+            inline = { a = 1, b.c = "d" }
+            nested = [
+                [1, 2], # A comment
+                [],
+            ]
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            value,
+            yaml(
+                r#"
+                escaped: "a\t\"b\"é"
+                literal: 'C:\media'
+                multiline: one two
+                integer: 1000
+                hex: 255
+                float: 0.5
+                negative: -3
+                boolean: false
+                date: "2023-01-01"
+                inline: { a: 1, b: { c: d } }
+                nested: [[1, 2], []]
+                "#
+            )
+        );
+    }
+
+    #[test]
+    fn says_where_the_file_is_wrong() {
+        let error = |text| from_str(text).unwrap_err();
+
+        assert_eq!(
+            error("version = 2\nurl = http://x\n"),
+            TomlError {
+                line: 2,
+                column: 7,
+                reason: "`http://x` is not a valid value, text has to be in quotes".to_string()
+            }
+        );
+        assert_eq!(error("[plex\nurl = 'x'").reason, "expected `]`");
+        assert_eq!(error("a = 1\na = 2").reason, "`a` is defined twice");
+        assert_eq!(error("a = 1\n[a]").reason, "`a` is already defined");
+        assert_eq!(
+            error("a = 'b").reason,
+            "the text is missing its closing `'`"
+        );
+        assert_eq!(error("a = 1 b = 2").reason, "expected the end of the line");
+    }
+
+    #[test]
+    fn writes_what_it_reads() {
+        let value = yaml(
+            r#"
+            version: 2
+            unset: null
+            ignored_users: [alice, "with \"quotes\""]
+            threshold: 1.0
+            tautulli:
+                url: http://localhost:8181
+            radarr:
+                - name: Radarr
+                  exclude_tags: []
+                - name: Radarr 4K
+            profiles:
+                aggressive:
+                    rules:
+                        watched_threshold: 60
+            "weird key": { a: 1 }
+            "#,
+        );
+
+        let text = to_string(&value).unwrap();
+        assert_eq!(
+            text,
+            r#"version = 2
+ignored_users = ["alice", "with \"quotes\""]
+threshold = 1.0
+
+[tautulli]
+url = "http://localhost:8181"
+
+[[radarr]]
+name = "Radarr"
+exclude_tags = []
+
+[[radarr]]
+name = "Radarr 4K"
+
+[profiles.aggressive.rules]
+watched_threshold = 60
+
+["weird key"]
+a = 1
+"#
+        );
+
+        let mut expected = value;
+        expected.as_mapping_mut().unwrap().remove("unset");
+        assert_eq!(from_str(&text).unwrap(), expected);
+    }
+}
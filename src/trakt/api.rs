@@ -0,0 +1,108 @@
+use color_eyre::{eyre::eyre, Result};
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+
+use super::responses::TokenResponse;
+use crate::{
+    config::{Config, Trakt},
+    http::{self, RetryPolicy, SendLogged},
+};
+
+const BASE_URL: &str = "https://api.trakt.tv";
+
+/// What checking for the token of a device code gave.
+pub enum DevicePoll {
+    Token(TokenResponse),
+    /// The code hasn't been entered yet.
+    Pending,
+    /// Checking too often, the interval has to go up.
+    SlowDown,
+}
+
+/// Gets something from the account the access token belongs to.
+pub async fn get<T>(path: &str, access_token: &str) -> Result<T>
+where
+    T: DeserializeOwned,
+{
+    let config = config()?;
+    let client = reqwest::Client::new();
+    let url = format!("{}{}", BASE_URL, path);
+
+    let response = http::send_with_retry("Trakt", path, &retry_policy(), || {
+        client
+            .get(&url)
+            .header("trakt-api-version", "2")
+            .header("trakt-api-key", &config.client_id)
+            .bearer_auth(access_token)
+            .send_logged("Trakt")
+    })
+    .await?;
+
+    read_response(response, path).await
+}
+
+pub async fn post<T>(path: &str, body: &Value) -> Result<T>
+where
+    T: DeserializeOwned,
+{
+    let client = reqwest::Client::new();
+    let url = format!("{}{}", BASE_URL, path);
+
+    let response = http::send_with_retry("Trakt", path, &retry_policy(), || {
+        client.post(&url).json(body).send_logged("Trakt")
+    })
+    .await?;
+
+    read_response(response, path).await
+}
+
+/// Checks whether the device code was entered yet. Trakt tells how it's going with the status code.
+pub async fn poll_device_token(body: &Value) -> Result<DevicePoll> {
+    let client = reqwest::Client::new();
+    let response = client
+        .post(format!("{}/oauth/device/token", BASE_URL))
+        .json(body)
+        .send_logged("Trakt")
+        .await?;
+
+    match response.status().as_u16() {
+        200 => Ok(DevicePoll::Token(
+            read_response(response, "/oauth/device/token").await?,
+        )),
+        400 => Ok(DevicePoll::Pending),
+        429 => Ok(DevicePoll::SlowDown),
+        404 => Err(eyre!("Trakt does not know the device code.")),
+        409 => Err(eyre!("The code was already used.")),
+        410 => Err(eyre!("The code expired before it was entered.")),
+        418 => Err(eyre!("Connecting the account was denied.")),
+        code => Err(http::ApiError::from_status("Trakt", "/oauth/device/token", code).into()),
+    }
+}
+
+pub fn config() -> Result<&'static Trakt> {
+    Config::global()
+        .trakt
+        .as_ref()
+        .ok_or_else(|| eyre!("Trakt is not configured, fill in the trakt settings."))
+}
+
+async fn read_response<T>(response: reqwest::Response, path: &str) -> Result<T>
+where
+    T: DeserializeOwned,
+{
+    response.json().await.map_err(|err| {
+        eyre!(
+            "Trakt returned a response for {} that could not be read: {}",
+            path,
+            err
+        )
+    })
+}
+
+fn retry_policy() -> RetryPolicy {
+    RetryPolicy {
+        attempts: 3,
+        base_delay_ms: 1000,
+        timeout: None,
+    }
+}
@@ -0,0 +1,318 @@
+mod api;
+mod responses;
+
+use chrono::Utc;
+use color_eyre::{eyre::eyre, owo_colors::OwoColorize, Result};
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    fmt::Display,
+    fs,
+    time::{Duration, Instant},
+};
+use tokio::{sync::OnceCell, time::sleep};
+
+use self::{
+    api::DevicePoll,
+    responses::{DeviceCodeResponse, TokenResponse, WatchedMovie, WatchedShow},
+};
+use crate::{config::Config, shared::MediaType};
+
+/// Where the tokens of the connected accounts are kept, next to the config.
+const TOKEN_FILE: &str = "trakt_tokens.json";
+/// Tokens are refreshed when they expire within this many seconds.
+const REFRESH_MARGIN_SECONDS: i64 = 24 * 60 * 60;
+
+/// What each account has watched, fetched once per run.
+static WATCHED: Lazy<OnceCell<Vec<AccountWatched>>> = Lazy::new(OnceCell::new);
+
+#[derive(Debug, Serialize, Deserialize)]
+struct StoredToken {
+    access_token: String,
+    refresh_token: String,
+    /// A unix timestamp.
+    expires_at: i64,
+}
+
+impl From<TokenResponse> for StoredToken {
+    fn from(token: TokenResponse) -> Self {
+        Self {
+            access_token: token.access_token,
+            refresh_token: token.refresh_token,
+            expires_at: token.created_at + token.expires_in,
+        }
+    }
+}
+
+/// The tokens by account name.
+type Tokens = HashMap<String, StoredToken>;
+
+/// What an account has watched on Trakt.
+struct AccountWatched {
+    account: String,
+    /// The TMDB ids of the watched movies.
+    movies: HashSet<u32>,
+    /// How many episodes were watched of each show, by TVDB id.
+    shows: HashMap<u32, usize>,
+}
+
+/// An account that watched an item on Trakt.
+#[derive(Debug, Clone)]
+pub struct TraktWatch {
+    pub account: String,
+    /// How many episodes were watched, `None` for movies.
+    pub episodes: Option<usize>,
+}
+
+impl Display for TraktWatch {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.episodes {
+            Some(episodes) => write!(f, "{} ({} episodes)", self.account, episodes),
+            None => write!(f, "{}", self.account),
+        }
+    }
+}
+
+/// Connects every account in the config with Trakt's device code flow, asking to enter
+/// a code on trakt.tv for each, and keeps the tokens for later runs.
+pub async fn login() -> Result<()> {
+    let config = api::config()?;
+    let mut tokens = read_tokens()?;
+
+    for account in &config.accounts {
+        let code: DeviceCodeResponse = api::post(
+            "/oauth/device/code",
+            &json!({ "client_id": config.client_id }),
+        )
+        .await?;
+        message!(
+            "To connect the Trakt account of {}, sign in to it on {} and enter the code {}",
+            account.yellow(),
+            code.verification_url.blue(),
+            code.user_code.bold()
+        );
+
+        let token = wait_for_token(&code).await.map_err(|err| {
+            eyre!(
+                "Failed to connect the Trakt account of {}: {}",
+                account,
+                err
+            )
+        })?;
+        tokens.insert(account.clone(), token.into());
+        write_tokens(&tokens)?;
+
+        message!("Connected the Trakt account of {}.", account);
+    }
+
+    Ok(())
+}
+
+async fn wait_for_token(code: &DeviceCodeResponse) -> Result<TokenResponse> {
+    let config = api::config()?;
+    let deadline = Instant::now() + Duration::from_secs(code.expires_in);
+    let mut interval = Duration::from_secs(code.interval.max(1));
+    let body = json!({
+        "code": code.device_code,
+        "client_id": config.client_id,
+        "client_secret": config.client_secret,
+    });
+
+    loop {
+        sleep(interval).await;
+        if Instant::now() > deadline {
+            return Err(eyre!("The code expired before it was entered."));
+        }
+
+        match api::poll_device_token(&body).await? {
+            DevicePoll::Token(token) => return Ok(token),
+            DevicePoll::Pending => (),
+            DevicePoll::SlowDown => interval += Duration::from_secs(1),
+        }
+    }
+}
+
+/// The accounts that watched the item on Trakt, matched by TMDB id for movies and TVDB id
+/// for shows. Music isn't on Trakt.
+pub async fn watched_by(
+    media_type: MediaType,
+    tmdb_id: Option<u32>,
+    tvdb_id: Option<u32>,
+) -> Vec<TraktWatch> {
+    let watched = WATCHED.get_or_init(fetch_watched).await;
+
+    watched
+        .iter()
+        .filter_map(|account| {
+            let episodes = match media_type {
+                MediaType::Movie => {
+                    return account.movies.contains(&tmdb_id?).then(|| TraktWatch {
+                        account: account.account.clone(),
+                        episodes: None,
+                    })
+                }
+                MediaType::Tv => *account.shows.get(&tvdb_id?)?,
+                MediaType::Music => return None,
+            };
+
+            Some(TraktWatch {
+                account: account.account.clone(),
+                episodes: Some(episodes),
+            })
+        })
+        .collect()
+}
+
+/// Gets what every connected account watched. An account that can't be used is left out
+/// with a warning, as Trakt only adds to the watch history.
+async fn fetch_watched() -> Vec<AccountWatched> {
+    let accounts = match api::config() {
+        Ok(config) => &config.accounts,
+        Err(_) => return Vec::new(),
+    };
+    let mut tokens = match read_tokens() {
+        Ok(tokens) => tokens,
+        Err(err) => {
+            warn(&err.to_string());
+            return Vec::new();
+        }
+    };
+
+    let mut watched = Vec::new();
+    for account in accounts {
+        match fetch_account_watched(account, &mut tokens).await {
+            Ok(Some(account_watched)) => watched.push(account_watched),
+            Ok(None) => warn(&format!(
+                "The Trakt account of {} isn't connected, run with --trakt-login to connect it.",
+                account
+            )),
+            Err(err) => warn(&format!(
+                "Failed to get the Trakt history of {}, so it isn't shown: {}",
+                account, err
+            )),
+        }
+    }
+
+    watched
+}
+
+/// Gets what an account watched, `None` if it isn't connected.
+async fn fetch_account_watched(
+    account: &str,
+    tokens: &mut Tokens,
+) -> Result<Option<AccountWatched>> {
+    let access_token = match access_token(account, tokens).await? {
+        Some(access_token) => access_token,
+        None => return Ok(None),
+    };
+
+    let movies: Vec<WatchedMovie> = api::get("/sync/watched/movies", &access_token).await?;
+    let shows: Vec<WatchedShow> = api::get("/sync/watched/shows", &access_token).await?;
+
+    Ok(Some(AccountWatched {
+        account: account.to_string(),
+        movies: movies
+            .into_iter()
+            .filter_map(|movie| movie.movie.ids.tmdb)
+            .collect(),
+        shows: shows
+            .into_iter()
+            .filter_map(|show| {
+                let episodes = show
+                    .seasons
+                    .iter()
+                    .map(|season| season.episodes.len())
+                    .sum();
+                Some((show.show.ids.tvdb?, episodes))
+            })
+            .collect(),
+    }))
+}
+
+/// Checks that every account in the config is connected and its token works, for the health
+/// check. Returns how many accounts there are.
+pub async fn check_accounts() -> Result<usize> {
+    let accounts = &api::config()?.accounts;
+    let mut tokens = read_tokens()?;
+
+    for account in accounts {
+        let access_token = access_token(account, &mut tokens).await?.ok_or_else(|| {
+            eyre!(
+                "The account of {} isn't connected, run with --trakt-login to connect it.",
+                account
+            )
+        })?;
+        let _: serde_json::Value = api::get("/users/settings", &access_token)
+            .await
+            .map_err(|err| err.wrap_err(format!("The token of {} doesn't work.", account)))?;
+    }
+
+    Ok(accounts.len())
+}
+
+/// The access token of an account, refreshed first if it's about to expire.
+async fn access_token(account: &str, tokens: &mut Tokens) -> Result<Option<String>> {
+    let token = match tokens.get(account) {
+        Some(token) => token,
+        None => return Ok(None),
+    };
+    if token.expires_at - Utc::now().timestamp() > REFRESH_MARGIN_SECONDS {
+        return Ok(Some(token.access_token.clone()));
+    }
+
+    let config = api::config()?;
+    let refreshed: TokenResponse = api::post(
+        "/oauth/token",
+        &json!({
+            "refresh_token": token.refresh_token,
+            "client_id": config.client_id,
+            "client_secret": config.client_secret,
+            "redirect_uri": "urn:ietf:wg:oauth:2.0:oob",
+            "grant_type": "refresh_token",
+        }),
+    )
+    .await
+    .map_err(|err| err.wrap_err("The token could not be refreshed, run with --trakt-login to connect the account again."))?;
+
+    let access_token = refreshed.access_token.clone();
+    tokens.insert(account.to_string(), refreshed.into());
+    write_tokens(tokens)?;
+
+    Ok(Some(access_token))
+}
+
+fn read_tokens() -> Result<Tokens> {
+    let path = Config::file_next_to_config(TOKEN_FILE);
+    if !path.exists() {
+        return Ok(HashMap::new());
+    }
+
+    let contents = fs::read_to_string(&path)?;
+    serde_json::from_str(&contents).map_err(|err| {
+        eyre!(
+            "The Trakt tokens in {} could not be read: {}",
+            path.display(),
+            err
+        )
+    })
+}
+
+fn write_tokens(tokens: &Tokens) -> Result<()> {
+    // Sorted, so the file doesn't change order every time it's written.
+    let sorted: BTreeMap<_, _> = tokens.iter().collect();
+    let contents = serde_json::to_string_pretty(&sorted)?;
+    let path = Config::file_next_to_config(TOKEN_FILE);
+    fs::write(&path, contents).map_err(|err| {
+        eyre!(
+            "Failed to save the Trakt tokens to {}: {}",
+            path.display(),
+            err
+        )
+    })
+}
+
+fn warn(message: &str) {
+    message!("{}", format!("Warning: {}", message).yellow());
+}
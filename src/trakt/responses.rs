@@ -0,0 +1,52 @@
+use serde::Deserialize;
+
+#[derive(Debug, Deserialize)]
+pub struct DeviceCodeResponse {
+    pub device_code: String,
+    pub user_code: String,
+    pub verification_url: String,
+    /// In seconds.
+    pub expires_in: u64,
+    /// How many seconds to wait between checking whether the code was entered.
+    pub interval: u64,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct TokenResponse {
+    pub access_token: String,
+    pub refresh_token: String,
+    /// In seconds from `created_at`.
+    pub expires_in: i64,
+    /// A unix timestamp.
+    pub created_at: i64,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct WatchedMovie {
+    pub movie: MediaObject,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct WatchedShow {
+    pub show: MediaObject,
+    #[serde(default)]
+    pub seasons: Vec<WatchedSeason>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct WatchedSeason {
+    /// Only the number of the episodes is used.
+    #[serde(default)]
+    pub episodes: Vec<serde_json::Value>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct MediaObject {
+    pub ids: Ids,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Ids {
+    pub tmdb: Option<u32>,
+    pub tvdb: Option<u32>,
+}
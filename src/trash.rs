@@ -0,0 +1,380 @@
+use chrono::{DateTime, Duration, Utc};
+use color_eyre::{eyre::eyre, owo_colors::OwoColorize, Result};
+use itertools::Itertools;
+use serde::{Deserialize, Serialize};
+use std::{
+    fs,
+    io::{self, ErrorKind},
+    path::{Component, Path, PathBuf},
+};
+
+use crate::{
+    config::{Config, Trash},
+    http,
+    shared::{Action, MediaType},
+    utils::human_file_size,
+};
+
+/// The file in the trash folder that records where everything in it came from.
+const MANIFEST_FILE: &str = "manifest.json";
+
+/// Media that was moved to the trash.
+#[derive(Debug, Deserialize, Serialize)]
+pub struct TrashedItem {
+    pub title: String,
+    pub media_type: MediaType,
+    /// What was chosen for it, which says whether Radarr or Sonarr still has it.
+    pub action: Action,
+    /// The Radarr or Sonarr instance it was in.
+    pub instance: String,
+    pub trashed_at: DateTime<Utc>,
+    /// Where the files were on this machine, and are restored to.
+    pub original_path: PathBuf,
+    pub trash_path: PathBuf,
+    pub size_bytes: i64,
+}
+
+/// The trash settings, `None` if removed files are deleted.
+pub fn config() -> Option<&'static Trash> {
+    Config::global().trash.as_ref()
+}
+
+/// Moves the folder of an item into the trash, at the same path below the trash folder,
+/// and records where it came from. A folder that isn't there is fine when there are no files
+/// to move. With --dry-run, the move is only shown.
+pub fn move_to_trash(
+    title: &str,
+    media_type: MediaType,
+    action: Action,
+    instance: String,
+    path: &str,
+    size_bytes: i64,
+) -> Result<()> {
+    let trash = required_config()?;
+    let original_path = local_path(trash, path);
+    if !original_path.exists() {
+        if size_bytes == 0 {
+            return Ok(());
+        }
+        return Err(eyre!(
+            "{} is not on this machine, set trash.path_mappings if the media is mounted at another path.",
+            original_path.display()
+        ));
+    }
+    if trash.path.starts_with(&original_path) {
+        return Err(eyre!(
+            "{} holds the trash folder, so it can't be moved into it.",
+            original_path.display()
+        ));
+    }
+
+    let trash_path = trash.path.join(
+        original_path
+            .components()
+            .filter(|component| matches!(component, Component::Normal(_)))
+            .collect::<PathBuf>(),
+    );
+    if trash_path.exists() {
+        return Err(eyre!(
+            "{} is already in the trash, restore or purge it first.",
+            trash_path.display()
+        ));
+    }
+
+    if http::is_dry_run() {
+        message!(
+            "{} Move {} to {}",
+            http::DRY_RUN_PREFIX.yellow(),
+            original_path.display(),
+            trash_path.display()
+        );
+        return Ok(());
+    }
+
+    move_path(&original_path, &trash_path)?;
+    tracing::info!(item = title, from = %original_path.display(), to = %trash_path.display(), "Moved to the trash");
+
+    let mut manifest = read_manifest(trash)?;
+    manifest.push(TrashedItem {
+        title: title.to_string(),
+        media_type,
+        action,
+        instance,
+        trashed_at: Utc::now(),
+        original_path,
+        trash_path,
+        size_bytes,
+    });
+    write_manifest(trash, &manifest)
+}
+
+/// Moves the media with this title back to where it was, also matching part of the title
+/// if nothing has the whole title. Radarr or Sonarr is told nothing, so what to do there is shown.
+pub fn restore(query: &str) -> Result<()> {
+    let trash = required_config()?;
+    let mut manifest = read_manifest(trash)?;
+
+    let mut matches = manifest
+        .iter()
+        .positions(|item| item.title.eq_ignore_ascii_case(query))
+        .collect_vec();
+    if matches.is_empty() {
+        let query = query.to_lowercase();
+        matches = manifest
+            .iter()
+            .positions(|item| item.title.to_lowercase().contains(&query))
+            .collect();
+    }
+
+    let titles = matches
+        .iter()
+        .map(|&position| manifest[position].title.to_lowercase())
+        .unique()
+        .collect_vec();
+    match titles.len() {
+        0 => {
+            return Err(eyre!(
+                "Nothing called {} is in the trash at {}.",
+                query,
+                trash.path.display()
+            ))
+        }
+        1 => (),
+        _ => {
+            return Err(eyre!(
+            "{} matches more than one title in the trash, pass the whole title of one of them: {}.",
+            query,
+            matches
+                .iter()
+                .map(|&position| &manifest[position].title)
+                .unique()
+                .join(", ")
+        ))
+        }
+    }
+
+    // Restored items are taken out of the manifest from the back, so the positions stay right.
+    for position in matches.into_iter().rev() {
+        let item = &manifest[position];
+        if item.original_path.exists() {
+            return Err(eyre!(
+                "{} exists again, move it out of the way to restore {}.",
+                item.original_path.display(),
+                item.title
+            ));
+        }
+
+        if http::is_dry_run() {
+            message!(
+                "{} Move {} to {}",
+                http::DRY_RUN_PREFIX.yellow(),
+                item.trash_path.display(),
+                item.original_path.display()
+            );
+            continue;
+        }
+
+        move_path(&item.trash_path, &item.original_path)?;
+        remove_empty_parents(trash, &item.trash_path);
+        let item = manifest.remove(position);
+        write_manifest(trash, &manifest)?;
+
+        message!(
+            "Restored {} to {}.",
+            item.title.green(),
+            item.original_path.display()
+        );
+        match item.action {
+            Action::DeleteFiles => message!(
+                "It is still in {}, rescan it there and monitor it again.",
+                item.instance
+            ),
+            _ => message!(
+                "It was removed from {}, add it there again and its files are picked up.",
+                item.instance
+            ),
+        }
+    }
+
+    Ok(())
+}
+
+/// Deletes everything that was moved to the trash longer ago than `older_than`.
+pub fn purge(older_than: Duration) -> Result<()> {
+    let trash = required_config()?;
+    let cutoff = Utc::now() - older_than;
+    let (expired, mut kept): (Vec<TrashedItem>, Vec<TrashedItem>) = read_manifest(trash)?
+        .into_iter()
+        .partition(|item| item.trashed_at < cutoff);
+
+    if expired.is_empty() {
+        message!("Nothing in the trash is old enough to be purged.");
+        return Ok(());
+    }
+
+    if http::is_dry_run() {
+        for item in &expired {
+            message!(
+                "{} Delete {} ({})",
+                http::DRY_RUN_PREFIX.yellow(),
+                item.trash_path.display(),
+                item.title
+            );
+        }
+        return Ok(());
+    }
+
+    let mut purged = 0;
+    let mut freed_size = 0;
+    let mut errors = Vec::new();
+    for item in expired {
+        let removal = if item.trash_path.exists() {
+            remove_all(&item.trash_path)
+        } else {
+            Ok(())
+        };
+
+        match removal {
+            Ok(()) => {
+                remove_empty_parents(trash, &item.trash_path);
+                purged += 1;
+                freed_size += item.size_bytes;
+            }
+            Err(err) => {
+                errors.push(format!("{}: {}", item.trash_path.display(), err));
+                kept.push(item);
+            }
+        }
+    }
+    write_manifest(trash, &kept)?;
+
+    message!(
+        "Purged {} items from the trash, freeing {}.",
+        purged,
+        human_file_size(freed_size)
+    );
+    if !errors.is_empty() {
+        return Err(eyre!(
+            "Failed to delete {} items from the trash, they are kept in it:\n{}",
+            errors.len(),
+            errors.join("\n")
+        ));
+    }
+
+    Ok(())
+}
+
+fn required_config() -> Result<&'static Trash> {
+    config().ok_or_else(|| eyre!("The trash is not set up, fill in trash.path in the config."))
+}
+
+/// Where a path Radarr or Sonarr knows is on this machine, from the first mapping it starts with.
+fn local_path(trash: &Trash, path: &str) -> PathBuf {
+    trash
+        .path_mappings
+        .iter()
+        .find_map(|mapping| {
+            let rest = path.strip_prefix(mapping.from.trim_end_matches('/'))?;
+            (rest.is_empty() || rest.starts_with('/'))
+                .then(|| PathBuf::from(format!("{}{}", mapping.to.trim_end_matches('/'), rest)))
+        })
+        .unwrap_or_else(|| PathBuf::from(path))
+}
+
+/// Moves a file or folder, creating the folders it goes in. A rename can't cross filesystems,
+/// so then it is copied and the original is removed once the copy is complete.
+fn move_path(from: &Path, to: &Path) -> Result<()> {
+    if let Some(parent) = to.parent() {
+        fs::create_dir_all(parent)
+            .map_err(|err| eyre!("Failed to create the folder {}: {}", parent.display(), err))?;
+    }
+
+    match fs::rename(from, to) {
+        Ok(()) => Ok(()),
+        Err(err) if err.kind() == ErrorKind::CrossesDevices => {
+            if let Err(err) = copy_all(from, to) {
+                let _ = remove_all(to);
+                return Err(eyre!(
+                    "Failed to copy {} to {}, nothing was moved: {}",
+                    from.display(),
+                    to.display(),
+                    err
+                ));
+            }
+            remove_all(from).map_err(|err| {
+                eyre!(
+                    "Copied {} to {}, but failed to remove the original: {}",
+                    from.display(),
+                    to.display(),
+                    err
+                )
+            })
+        }
+        Err(err) => Err(eyre!(
+            "Failed to move {} to {}: {}",
+            from.display(),
+            to.display(),
+            err
+        )),
+    }
+}
+
+fn copy_all(from: &Path, to: &Path) -> io::Result<()> {
+    if from.is_dir() {
+        fs::create_dir_all(to)?;
+        for entry in fs::read_dir(from)? {
+            let entry = entry?;
+            copy_all(&entry.path(), &to.join(entry.file_name()))?;
+        }
+        Ok(())
+    } else {
+        fs::copy(from, to).map(|_| ())
+    }
+}
+
+fn remove_all(path: &Path) -> io::Result<()> {
+    if path.is_dir() {
+        fs::remove_dir_all(path)
+    } else {
+        fs::remove_file(path)
+    }
+}
+
+/// Removes the folders that were only there to keep the path of what was taken out of the trash.
+fn remove_empty_parents(trash: &Trash, path: &Path) {
+    for parent in path.ancestors().skip(1) {
+        if parent == trash.path
+            || !parent.starts_with(&trash.path)
+            || fs::remove_dir(parent).is_err()
+        {
+            break;
+        }
+    }
+}
+
+fn read_manifest(trash: &Trash) -> Result<Vec<TrashedItem>> {
+    let path = trash.path.join(MANIFEST_FILE);
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+
+    let reader = fs::File::open(&path)?;
+    serde_json::from_reader(reader).map_err(|err| {
+        eyre!(
+            "Failed to read the trash manifest {}: {}",
+            path.display(),
+            err
+        )
+    })
+}
+
+fn write_manifest(trash: &Trash, manifest: &[TrashedItem]) -> Result<()> {
+    let path = trash.path.join(MANIFEST_FILE);
+    fs::write(&path, serde_json::to_string_pretty(manifest)?).map_err(|err| {
+        eyre!(
+            "Failed to write the trash manifest {}: {}",
+            path.display(),
+            err
+        )
+    })
+}
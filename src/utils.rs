@@ -1,4 +1,17 @@
+use chrono::{DateTime, Local, TimeZone, Utc};
+use color_eyre::{eyre::eyre, owo_colors::OwoColorize, Result};
 use itertools::Itertools;
+use std::{
+    fmt::Display,
+    fs,
+    path::PathBuf,
+    sync::Once,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use crate::config::Config;
+
+static INSECURE_TLS_WARNING: Once = Once::new();
 
 pub fn create_param_string(params: Option<Vec<(&str, &str)>>) -> String {
     params
@@ -9,6 +22,74 @@ pub fn create_param_string(params: Option<Vec<(&str, &str)>>) -> String {
         .join("&")
 }
 
+/// Percent encodes a value, so it can be safely used as a query parameter.
+pub fn encode_param(value: &str) -> String {
+    value
+        .bytes()
+        .map(|byte| match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => {
+                (byte as char).to_string()
+            }
+            byte => format!("%{:02X}", byte),
+        })
+        .collect()
+}
+
+/// Creates a HTTP client for a service, trusting the given CA certificate
+/// in addition to the system ones, or any certificate if `verify_tls` is off.
+pub fn create_client(
+    service: &str,
+    timeout: Duration,
+    verify_tls: bool,
+    ca_cert_path: &Option<PathBuf>,
+) -> Result<reqwest::Client> {
+    let mut builder = reqwest::Client::builder()
+        .connect_timeout(timeout)
+        .timeout(timeout);
+
+    if let Some(ref path) = ca_cert_path {
+        let pem = fs::read(path).map_err(|err| {
+            eyre!(
+                "Failed to read the CA certificate for {} at {}: {}",
+                service,
+                path.display(),
+                err
+            )
+        })?;
+        let certificate = reqwest::Certificate::from_pem(&pem).map_err(|err| {
+            eyre!(
+                "The CA certificate for {} at {} is not a valid PEM certificate: {}",
+                service,
+                path.display(),
+                err
+            )
+        })?;
+        builder = builder.add_root_certificate(certificate);
+    }
+
+    if !verify_tls {
+        INSECURE_TLS_WARNING.call_once(|| {
+            message!(
+                "{}",
+                "Warning: TLS certificate verification is turned off, connections are not secure."
+                    .yellow()
+            )
+        });
+        builder = builder.danger_accept_invalid_certs(true);
+    }
+
+    Ok(builder.build()?)
+}
+
+/// Joins a base URL, which may or may not end with a slash, with a path.
+pub fn join_url(base: &str, path: &str) -> String {
+    format!(
+        "{}/{}",
+        base.trim_end_matches('/'),
+        path.trim_start_matches('/')
+    )
+}
+
 pub fn create_api_error_message(code: u16, path: &str, service: &str) -> String {
     match code {
         400 => format!("Got 400 Bad Request from {} at {}. The api may have changed, please report this on Github.", service, path),
@@ -25,8 +106,236 @@ pub fn create_api_error_message(code: u16, path: &str, service: &str) -> String
     }
 }
 
+pub fn human_duration(duration: Duration) -> String {
+    let seconds = duration.as_secs();
+    format!(
+        "{}:{:02}:{:02}",
+        seconds / 3600,
+        seconds % 3600 / 60,
+        seconds % 60
+    )
+}
+
+/// Formats a date and time in the timezone from the config, followed by how long ago it was.
+pub fn human_date_time(date: DateTime<Utc>) -> String {
+    format!(
+        "{} ({})",
+        format_in_timezone(date, "%d-%m-%Y %H:%M"),
+        relative_time(date)
+    )
+}
+
+/// Formats the day of a date in the timezone from the config.
+pub fn human_date(date: DateTime<Utc>) -> String {
+    format_in_timezone(date, "%d-%m-%Y")
+}
+
+fn format_in_timezone(date: DateTime<Utc>, format: &str) -> String {
+    match Config::global().display.timezone {
+        Some(ref timezone) => format_with(date, timezone, format),
+        None => format_with(date, &Local, format),
+    }
+}
+
+fn format_with<T: TimeZone>(date: DateTime<Utc>, timezone: &T, format: &str) -> String
+where
+    T::Offset: Display,
+{
+    date.with_timezone(timezone).format(format).to_string()
+}
+
+/// Describes how long ago a date was (or how far in the future it is), like "3 weeks ago".
+pub fn relative_time(date: DateTime<Utc>) -> String {
+    relative_to(date, Utc::now())
+}
+
+fn relative_to(date: DateTime<Utc>, now: DateTime<Utc>) -> String {
+    let seconds = now.signed_duration_since(date).num_seconds();
+    let is_future = seconds < 0;
+    let seconds = seconds.unsigned_abs();
+
+    let (amount, unit) = match seconds {
+        0..=59 => return "just now".to_string(),
+        60..=3599 => (seconds / 60, "minute"),
+        3600..=86_399 => (seconds / 3600, "hour"),
+        86_400..=604_799 => (seconds / 86_400, "day"),
+        604_800..=2_629_799 => (seconds / 604_800, "week"),
+        2_629_800..=31_557_599 => (seconds / 2_629_800, "month"),
+        _ => (seconds / 31_557_600, "year"),
+    };
+    let plural = if amount == 1 { "" } else { "s" };
+
+    if is_future {
+        format!("in {} {}{}", amount, unit, plural)
+    } else {
+        format!("{} {}{} ago", amount, unit, plural)
+    }
+}
+
+/// Formats a size in bytes with binary units, like "8.00 GiB".
 pub fn human_file_size(size: i64) -> String {
-    let gig_size = 1000000000.0;
-    let gigs: f64 = size as f64 / gig_size;
-    format!("{:.2}GB", gigs)
+    const UNITS: [&str; 5] = ["B", "KiB", "MiB", "GiB", "TiB"];
+
+    let mut size = size as f64;
+    let mut unit = 0;
+    while size.abs() >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+
+    if unit == 0 {
+        format!("{} {}", size, UNITS[unit])
+    } else {
+        format!("{:.2} {}", size, UNITS[unit])
+    }
+}
+
+/// Reads a size like "500GB", "1.5 TiB" or "2048", which is in bytes without a unit.
+/// KB, MB, GB and TB are powers of 1000, KiB, MiB, GiB and TiB powers of 1024.
+pub fn parse_file_size(size: &str) -> Option<i64> {
+    let size = size.trim();
+    let unit_start = size
+        .find(|c: char| !c.is_ascii_digit() && c != '.')
+        .unwrap_or(size.len());
+    let (amount, unit) = size.split_at(unit_start);
+    let amount: f64 = amount.parse().ok()?;
+
+    let multiplier: f64 = match unit.trim().to_lowercase().as_str() {
+        "" | "b" => 1.0,
+        "kb" => 1e3,
+        "mb" => 1e6,
+        "gb" => 1e9,
+        "tb" => 1e12,
+        "kib" => 1024.0,
+        "mib" => 1024.0_f64.powi(2),
+        "gib" => 1024.0_f64.powi(3),
+        "tib" => 1024.0_f64.powi(4),
+        _ => return None,
+    };
+
+    Some((amount * multiplier) as i64)
+}
+
+/// Reads an age like "30d", "12h" or "2w", which is in days without a unit.
+pub fn parse_age(age: &str) -> Option<chrono::Duration> {
+    let age = age.trim();
+    let unit_start = age.find(|c: char| !c.is_ascii_digit()).unwrap_or(age.len());
+    let (amount, unit) = age.split_at(unit_start);
+    let amount: i64 = amount.parse().ok()?;
+
+    match unit.trim().to_lowercase().as_str() {
+        "" | "d" => chrono::Duration::try_days(amount),
+        "h" => chrono::Duration::try_hours(amount),
+        "w" => chrono::Duration::try_weeks(amount),
+        _ => None,
+    }
+}
+
+/// The major version of a version like "4.0.1" or "v2.13.4".
+pub fn major_version(version: &str) -> Option<u32> {
+    version
+        .trim_start_matches('v')
+        .split('.')
+        .next()?
+        .parse()
+        .ok()
+}
+
+/// Calculates how long to wait before retrying a request, doubling the base delay
+/// for every failed attempt and adding up to half the base delay as jitter.
+pub fn retry_delay(base_delay_ms: u64, attempt: u32) -> Duration {
+    let backoff = base_delay_ms.saturating_mul(2u64.saturating_pow(attempt.saturating_sub(1)));
+    let jitter = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|time| time.subsec_nanos() as u64 % (base_delay_ms / 2 + 1))
+        .unwrap_or(0);
+
+    Duration::from_millis(backoff.saturating_add(jitter))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn doubles_the_retry_delay_with_up_to_half_of_the_base_as_jitter() {
+        for (attempt, backoff) in [(1, 500), (2, 1000), (3, 2000), (4, 4000)] {
+            let delay = retry_delay(500, attempt).as_millis() as u64;
+            assert!(
+                (backoff..=backoff + 250).contains(&delay),
+                "attempt {} waited {}ms",
+                attempt,
+                delay
+            );
+        }
+    }
+
+    #[test]
+    fn joins_urls_with_and_without_a_base_path() {
+        let cases = [
+            ("http://host:8181", "http://host:8181/api/v2"),
+            ("http://host:8181/", "http://host:8181/api/v2"),
+            ("https://host/tautulli", "https://host/tautulli/api/v2"),
+            ("https://host/tautulli/", "https://host/tautulli/api/v2"),
+        ];
+        for (base, url) in cases {
+            assert_eq!(join_url(base, "api/v2"), url);
+            assert_eq!(join_url(base, "/api/v2"), url);
+        }
+    }
+
+    fn utc(date: &str) -> DateTime<Utc> {
+        date.parse().unwrap()
+    }
+
+    #[test]
+    fn formats_dates_across_the_dst_changes() {
+        let amsterdam = chrono_tz::Europe::Amsterdam;
+        let format = |date| format_with(utc(date), &amsterdam, "%d-%m-%Y %H:%M %Z");
+
+        // The clocks skip from 02:00 to 03:00, and fall back from 03:00 to 02:00.
+        assert_eq!(format("2023-03-26T00:59:00Z"), "26-03-2023 01:59 CET");
+        assert_eq!(format("2023-03-26T01:00:00Z"), "26-03-2023 03:00 CEST");
+        assert_eq!(format("2023-10-29T00:30:00Z"), "29-10-2023 02:30 CEST");
+        assert_eq!(format("2023-10-29T01:30:00Z"), "29-10-2023 02:30 CET");
+
+        let new_york = chrono_tz::America::New_York;
+        let date = utc("2024-03-10T07:00:00Z");
+        assert_eq!(
+            format_with(date, &new_york, "%d-%m-%Y %H:%M"),
+            "10-03-2024 03:00"
+        );
+        assert_eq!(
+            format_with(
+                date - chrono::Duration::minutes(1),
+                &new_york,
+                "%d-%m-%Y %H:%M"
+            ),
+            "10-03-2024 01:59"
+        );
+    }
+
+    #[test]
+    fn describes_how_long_ago_a_date_was() {
+        let now = utc("2024-06-01T12:00:00Z");
+        let cases = [
+            ("2024-06-01T11:59:30Z", "just now"),
+            ("2024-06-01T11:59:00Z", "1 minute ago"),
+            ("2024-06-01T09:00:00Z", "3 hours ago"),
+            ("2024-05-31T12:00:00Z", "1 day ago"),
+            ("2024-05-11T12:00:00Z", "3 weeks ago"),
+            ("2024-02-01T12:00:00Z", "3 months ago"),
+            ("2021-06-01T12:00:00Z", "3 years ago"),
+            ("2024-06-03T12:00:00Z", "in 2 days"),
+        ];
+        for (date, relative) in cases {
+            assert_eq!(relative_to(utc(date), now), relative, "{}", date);
+        }
+    }
+
+    #[test]
+    fn retry_delay_does_not_overflow() {
+        assert_eq!(retry_delay(0, 1), Duration::ZERO);
+        assert!(retry_delay(u64::MAX, 100) >= Duration::from_millis(u64::MAX));
+    }
 }
@@ -0,0 +1,95 @@
+use chrono::{DateTime, Utc};
+use color_eyre::Result;
+use futures::{future::BoxFuture, stream, StreamExt};
+use std::collections::HashSet;
+
+use crate::{
+    arguments::Arguments,
+    config::{Config, WatchProviderType},
+    emby::Emby,
+    jellyfin::Jellyfin,
+    plex::PlexData,
+    progress::Progress,
+    shared::MediaType,
+    tautulli::{Tautulli, User, WatchHistory},
+};
+
+/// A service the watch history comes from. Items are known by their id on the media
+/// server, which is the rating key for Plex.
+pub trait WatchProvider: Sync {
+    /// The name of the service, like "Tautulli".
+    fn name(&self) -> &'static str;
+
+    /// Gets the watch history of an item, only counting watches after `since` if it is given.
+    /// Gives `WatchHistory::NoData` if the service doesn't know the item.
+    fn item_watches<'a>(
+        &'a self,
+        id: &'a str,
+        media_type: MediaType,
+        since: Option<DateTime<Utc>>,
+    ) -> BoxFuture<'a, Result<WatchHistory>>;
+
+    /// Gets all users of the media server.
+    fn users(&self) -> BoxFuture<'_, Result<&'static [User]>>;
+
+    /// Gets the ids of everything currently being played, including those of the shows and
+    /// albums that are played, so whole items can be matched.
+    fn active_item_ids(&self) -> BoxFuture<'_, Result<HashSet<String>>>;
+
+    /// Gets the title of an item and the library it is in.
+    fn item_data<'a>(
+        &'a self,
+        id: &'a str,
+        media_type: MediaType,
+    ) -> BoxFuture<'a, Result<PlexData>>;
+
+    /// Gets the version of the service, to check it is reachable and supported.
+    fn version(&self) -> BoxFuture<'_, Result<String>>;
+
+    /// How many items to get the watch history of at the same time.
+    fn concurrency(&self) -> usize;
+
+    /// Whether the users have Plex ids, so requesters can be matched by them.
+    fn has_plex_ids(&self) -> bool;
+
+    /// Finds the id of an item by its TMDB id (for movies) or TVDB id, for items the request
+    /// manager has no id for. Services that can't search by these ids find nothing.
+    fn find_item_id(
+        &self,
+        _media_type: MediaType,
+        _tmdb_id: Option<u32>,
+        _tvdb_id: Option<u32>,
+    ) -> BoxFuture<'_, Result<Option<String>>> {
+        Box::pin(async { Ok(None) })
+    }
+}
+
+/// The watch provider set in the config.
+pub fn provider() -> &'static dyn WatchProvider {
+    match Config::global().watch_provider {
+        WatchProviderType::Tautulli => &Tautulli,
+        WatchProviderType::Jellyfin => &Jellyfin,
+        WatchProviderType::Emby => &Emby,
+    }
+}
+
+/// Gets the watch history of many items, with at most the configured amount of requests
+/// at a time. Every item gets its own result, so one failing item doesn't stop the others.
+pub async fn get_items_watches(items: &[(String, MediaType)]) -> Vec<Result<WatchHistory>> {
+    let provider = provider();
+    let since = Arguments::get_args().watched_since;
+    let progress = Progress::start("Fetching watch histories", items.len());
+
+    stream::iter(items)
+        .map(|(id, media_type)| {
+            let progress = &progress;
+            async move {
+                let history = provider.item_watches(id, *media_type, since).await;
+                progress.inc(id);
+                history
+            }
+        })
+        .buffered(provider.concurrency().max(1))
+        .collect()
+        .await
+}